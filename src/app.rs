@@ -1,29 +1,129 @@
+use std::fmt;
 use std::str::FromStr;
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 
 pub enum Network {
     Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
     Ethereum
 }
 
+// version bytes and prefixes that differ between the bitcoin networks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub struct BitcoinParams {
+    pub p2pkh_version: u8,
+    pub p2sh_version: u8,
+    pub wif_prefix: u8,
+    pub bech32_hrp: &'static str,
+    // BIP352 silent payment addresses
+    pub silent_payment_hrp: &'static str,
+    pub xprv_version: [u8; 4],
+    pub xpub_version: [u8; 4]
+}
+
+pub const MAINNET_PARAMS: BitcoinParams = BitcoinParams {
+    p2pkh_version: 0x00,
+    p2sh_version: 0x05,
+    wif_prefix: 0x80,
+    bech32_hrp: "bc",
+    silent_payment_hrp: "sp",
+    xprv_version: [0x04, 0x88, 0xAD, 0xE4],
+    xpub_version: [0x04, 0x88, 0xB2, 0x1E]
+};
+
+// signet reuses the testnet parameters
+pub const TESTNET_PARAMS: BitcoinParams = BitcoinParams {
+    p2pkh_version: 0x6F,
+    p2sh_version: 0xC4,
+    wif_prefix: 0xEF,
+    bech32_hrp: "tb",
+    silent_payment_hrp: "tsp",
+    xprv_version: [0x04, 0x35, 0x83, 0x94],
+    xpub_version: [0x04, 0x35, 0x87, 0xCF]
+};
+
+pub const REGTEST_PARAMS: BitcoinParams = BitcoinParams {
+    bech32_hrp: "bcrt",
+    silent_payment_hrp: "sprt",
+    ..TESTNET_PARAMS
+};
+
+impl Network {
+    pub fn bitcoin_params(&self) -> Option<BitcoinParams> {
+        match self {
+            Network::Bitcoin => return Some(MAINNET_PARAMS),
+            Network::Testnet | Network::Signet => return Some(TESTNET_PARAMS),
+            Network::Regtest => return Some(REGTEST_PARAMS),
+            Network::Ethereum => return None,
+        };
+    }
+
+    pub fn is_bitcoin(&self) -> bool {
+        return self.bitcoin_params().is_some();
+    }
+}
+
 impl FromStr for Network {
     type Err = ();
 
     fn from_str(input: &str) -> Result<Network, Self::Err> {
         match input {
             "bitcoin" => Ok(Network::Bitcoin),
+            "testnet" => Ok(Network::Testnet),
+            "signet" => Ok(Network::Signet),
+            "regtest" => Ok(Network::Regtest),
             "ethereum" => Ok(Network::Ethereum),
             _ => Err(()),
         }
     }
 }
 
-impl ToString for Network {
-    fn to_string(&self) -> String {
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Network::Bitcoin => return String::from("Bitcoin"),
-            Network::Ethereum => return String::from("Ethereum"),
+            Network::Bitcoin => return write!(f, "Bitcoin"),
+            Network::Testnet => return write!(f, "Testnet"),
+            Network::Signet => return write!(f, "Signet"),
+            Network::Regtest => return write!(f, "Regtest"),
+            Network::Ethereum => return write!(f, "Ethereum"),
         };
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum AddressType {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+    P2tr
+}
+
+impl FromStr for AddressType {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<AddressType, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "p2pkh" => Ok(AddressType::P2pkh),
+            "p2sh-p2wpkh" | "p2sh_p2wpkh" => Ok(AddressType::P2shP2wpkh),
+            "p2wpkh" => Ok(AddressType::P2wpkh),
+            "p2tr" => Ok(AddressType::P2tr),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for AddressType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressType::P2pkh => return write!(f, "P2PKH"),
+            AddressType::P2shP2wpkh => return write!(f, "P2SH-P2WPKH"),
+            AddressType::P2wpkh => return write!(f, "P2WPKH"),
+            AddressType::P2tr => return write!(f, "P2TR"),
+        };
+    }
+}
@@ -5,5 +5,5 @@ pub fn decode_string(input: &str) -> Vec<u8> {
 }
 
 pub fn encode_bytes(input: &[u8]) -> String {
-    return hex::encode(&input);
+    return hex::encode(input);
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use primitive_types::U512;
+use crate::crypto::hashing;
 
 const BASE58_CHARS: [char; 58] = [
     '1', '2', '3', '4', '5', '6', '7', '8', '9', 
@@ -6,20 +6,117 @@ const BASE58_CHARS: [char; 58] = [
     'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z'
 ];
 
-pub fn convert_hex_to_base58(s: &str) -> String {
-    let b: U512 = U512::from_big_endian(&[58]);
-    let z: U512 = U512::from_big_endian(&[0]);
+pub fn encode_bytes(input: &[u8]) -> String {
+    let zeros: usize = input.iter().take_while(|b: &&u8| **b == 0).count();
 
-    let mut n: U512 = U512::from_str_radix(s, 16).expect("parse-hex");
-    let mut r: Vec<u8> = vec![];
-
-    while n > z {
-        let rem: u8 = n.checked_rem(b).expect("remainder").byte(0);
-        r.push(rem);
-        n = n.checked_div(b).expect("division");
+    // little-endian base58 digits, grown by repeated multiply-by-256
+    let mut digits: Vec<u8> = vec![];
+    for b in input.iter() {
+        let mut carry: u32 = *b as u32;
+        for d in digits.iter_mut() {
+            carry += (*d as u32) << 8;
+            *d = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
     }
 
-    let mut rs: String = r.into_iter().map(|b: u8| BASE58_CHARS[b as usize]).collect::<String>();    
-    rs = rs.chars().rev().collect::<String>();
+    let mut rs: String = "1".repeat(zeros);
+    rs.extend(digits.into_iter().rev().map(|b: u8| BASE58_CHARS[b as usize]));
     rs
-}
\ No newline at end of file
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub struct Base58ParseError;
+
+pub fn decode_string(input: &str) -> Result<Vec<u8>, Base58ParseError> {
+    let zeros: usize = input.chars().take_while(|c: &char| *c == '1').count();
+
+    // little-endian bytes, grown by repeated multiply-by-58
+    let mut bytes: Vec<u8> = vec![];
+    for c in input.chars() {
+        let mut carry: u32 = match BASE58_CHARS.iter().position(|b: &char| *b == c) {
+            Some(i) => i as u32,
+            None => return Err(Base58ParseError)
+        };
+        for b in bytes.iter_mut() {
+            carry += (*b as u32) * 58;
+            *b = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut r: Vec<u8> = vec![0; zeros];
+    r.extend(bytes.into_iter().rev());
+    return Ok(r);
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum Base58CheckError {
+    InvalidEncoding,
+    InvalidLength(usize),
+    InvalidChecksum
+}
+
+// payload | first 4 bytes of sha256d(payload)
+pub fn encode_check(payload: &[u8]) -> String {
+    let mut data: Vec<u8> = payload.to_vec();
+    data.extend_from_slice(&hashing::sha256d(payload)[..4]);
+    return encode_bytes(&data);
+}
+
+// the payload of a Base58Check string, its checksum verified and removed
+pub fn decode_check(input: &str) -> Result<Vec<u8>, Base58CheckError> {
+    let data: Vec<u8> = decode_string(input).map_err(|_| Base58CheckError::InvalidEncoding)?;
+    if data.len() < 5 {
+        return Err(Base58CheckError::InvalidLength(data.len()));
+    }
+
+    let (payload, checksum) = data.split_at(data.len() - 4);
+    if hashing::sha256d(payload)[..4] != *checksum {
+        return Err(Base58CheckError::InvalidChecksum);
+    }
+    return Ok(payload.to_vec());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base58::*;
+    use crate::base16;
+
+    #[test]
+    fn base58_encode_bytes() {
+        assert_eq!(encode_bytes(&[]), "");
+        assert_eq!(encode_bytes(&[0, 0, 1]), "112");
+        assert_eq!(encode_bytes(b"Hello World!"), "2NEpo7TZRRrLZSi2U");
+        assert_eq!(encode_bytes(&base16::decode_string("00eb15231dfceb60925886b67d065299925915aeb172c06647")), "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L");
+    }
+
+    #[test]
+    fn base58_decode_string() {
+        assert_eq!(decode_string("").unwrap(), vec![]);
+        assert_eq!(decode_string("112").unwrap(), vec![0, 0, 1]);
+        assert_eq!(decode_string("2NEpo7TZRRrLZSi2U").unwrap(), b"Hello World!".to_vec());
+        assert_eq!(base16::encode_bytes(&decode_string("1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L").unwrap()), "00eb15231dfceb60925886b67d065299925915aeb172c06647");
+        assert_eq!(decode_string("1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE0L"), Err(Base58ParseError));
+    }
+
+    #[test]
+    fn base58_check() {
+        let payload = base16::decode_string("00751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(encode_check(&payload), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+        assert_eq!(decode_check("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH").unwrap(), payload);
+        assert_eq!(decode_check("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMh"), Err(Base58CheckError::InvalidChecksum));
+        assert_eq!(decode_check("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAM0"), Err(Base58CheckError::InvalidEncoding));
+        assert_eq!(decode_check("11"), Err(Base58CheckError::InvalidLength(2)));
+    }
+}
@@ -0,0 +1,85 @@
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// RFC 4648 with padding, as used by PSBTs and signed messages
+pub fn encode_bytes(input: &[u8]) -> String {
+    let mut rs: String = String::new();
+    for chunk in input.chunks(3) {
+        let n: u32 = chunk.iter().enumerate().fold(0, |n: u32, (i, b): (usize, &u8)| n | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                rs.push(BASE64_CHARS[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            }
+            else {
+                rs.push('=');
+            }
+        }
+    }
+    return rs;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub struct Base64ParseError;
+
+pub fn decode_string(input: &str) -> Result<Vec<u8>, Base64ParseError> {
+    let input: &[u8] = input.as_bytes();
+    if !input.len().is_multiple_of(4) {
+        return Err(Base64ParseError);
+    }
+
+    let mut r: Vec<u8> = vec![];
+    for (index, chunk) in input.chunks(4).enumerate() {
+        let padding: usize = chunk.iter().rev().take_while(|c: &&u8| **c == b'=').count();
+        // padding is only allowed at the very end
+        if padding > 2 || (padding > 0 && index != input.len() / 4 - 1) {
+            return Err(Base64ParseError);
+        }
+
+        let mut n: u32 = 0;
+        for (i, c) in chunk[..4 - padding].iter().enumerate() {
+            let d: usize = match BASE64_CHARS.iter().position(|b: &u8| b == c) {
+                Some(d) => d,
+                None => return Err(Base64ParseError)
+            };
+            n |= (d as u32) << (18 - 6 * i);
+        }
+        let bytes: [u8; 3] = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        // the bits left over by the padding must be zero
+        if bytes[3 - padding..].iter().any(|b: &u8| *b != 0) {
+            return Err(Base64ParseError);
+        }
+        r.extend_from_slice(&bytes[..3 - padding]);
+    }
+    return Ok(r);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base64::*;
+
+    #[test]
+    fn base64_encode_bytes() {
+        // RFC 4648 vectors
+        assert_eq!(encode_bytes(b""), "");
+        assert_eq!(encode_bytes(b"f"), "Zg==");
+        assert_eq!(encode_bytes(b"fo"), "Zm8=");
+        assert_eq!(encode_bytes(b"foo"), "Zm9v");
+        assert_eq!(encode_bytes(b"foob"), "Zm9vYg==");
+        assert_eq!(encode_bytes(b"fooba"), "Zm9vYmE=");
+        assert_eq!(encode_bytes(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode_bytes(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn base64_decode_string() {
+        assert_eq!(decode_string("").unwrap(), vec![]);
+        assert_eq!(decode_string("Zg==").unwrap(), b"f".to_vec());
+        assert_eq!(decode_string("Zm9vYmE=").unwrap(), b"fooba".to_vec());
+        assert_eq!(decode_string("Zm9vYmFy").unwrap(), b"foobar".to_vec());
+        assert_eq!(decode_string("+/8=").unwrap(), vec![0xfb, 0xff]);
+        assert_eq!(decode_string("Zg="), Err(Base64ParseError));
+        assert_eq!(decode_string("Zh=="), Err(Base64ParseError));
+        assert_eq!(decode_string("Zg==Zg=="), Err(Base64ParseError));
+        assert_eq!(decode_string("Zm9v-mFy"), Err(Base64ParseError));
+    }
+}
@@ -0,0 +1,371 @@
+const CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+const MAX_LENGTH: usize = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum Variant {
+    Bech32,
+    Bech32m
+}
+
+impl Variant {
+    fn constant(&self) -> u32 {
+        match self {
+            Variant::Bech32 => return 1,
+            Variant::Bech32m => return 0x2bc830a3,
+        };
+    }
+
+    // BIP350: witness version 0 keeps bech32, every later version uses bech32m
+    pub fn for_witness_version(version: u8) -> Variant {
+        if version == 0 {
+            return Variant::Bech32;
+        }
+        return Variant::Bech32m;
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum Bech32Error {
+    MixedCase,
+    InvalidLength(usize),
+    MissingSeparator,
+    InvalidHrp,
+    InvalidCharacter(char),
+    InvalidChecksum,
+    InvalidVariant,
+    InvalidPadding,
+    InvalidWitnessVersion(u8),
+    InvalidProgramLength(usize)
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut chk: u32 = 1;
+    for v in values.iter() {
+        let top: u32 = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ *v as u32;
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    return chk;
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut r: Vec<u8> = hrp.bytes().map(|b: u8| b >> 5).collect::<Vec<u8>>();
+    r.push(0);
+    r.extend(hrp.bytes().map(|b: u8| b & 31));
+    return r;
+}
+
+fn create_checksum(hrp: &str, data: &[u8], variant: Variant) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0; 6]);
+
+    let m: u32 = polymod(&values) ^ variant.constant();
+    return (0..6).map(|i: u32| ((m >> (5 * (5 - i))) & 31) as u8).collect::<Vec<u8>>();
+}
+
+fn verify_checksum(hrp: &str, data: &[u8]) -> Option<Variant> {
+    let mut values: Vec<u8> = hrp_expand(hrp);
+    values.extend_from_slice(data);
+
+    let m: u32 = polymod(&values);
+    return [Variant::Bech32, Variant::Bech32m].into_iter().find(|v: &Variant| v.constant() == m);
+}
+
+// data is a sequence of 5-bit groups; the result is always lowercase
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> Result<String, Bech32Error> {
+    return encode_with_limit(hrp, data, variant, MAX_LENGTH);
+}
+
+// for formats that lift the 90 character limit of addresses, e.g. BIP352 allows 1023
+pub fn encode_with_limit(hrp: &str, data: &[u8], variant: Variant, max_length: usize) -> Result<String, Bech32Error> {
+    let hrp: String = hrp.to_lowercase();
+    if hrp.is_empty() || hrp.bytes().any(|b: u8| !(33..=126).contains(&b)) {
+        return Err(Bech32Error::InvalidHrp);
+    }
+    if data.iter().any(|d: &u8| *d > 31) {
+        return Err(Bech32Error::InvalidPadding);
+    }
+    if hrp.len() + data.len() + 7 > max_length {
+        return Err(Bech32Error::InvalidLength(hrp.len() + data.len() + 7));
+    }
+
+    let mut r: String = format!("{}1", hrp);
+    for d in data.iter().chain(create_checksum(&hrp, data, variant).iter()) {
+        r.push(CHARSET.as_bytes()[*d as usize] as char);
+    }
+    return Ok(r);
+}
+
+// returns the lowercase hrp, the 5-bit data groups without the checksum and the
+// checksum variant they matched
+pub fn decode(s: &str) -> Result<(String, Vec<u8>, Variant), Bech32Error> {
+    return decode_with_limit(s, MAX_LENGTH);
+}
+
+pub fn decode_with_limit(s: &str, max_length: usize) -> Result<(String, Vec<u8>, Variant), Bech32Error> {
+    if s.len() > max_length {
+        return Err(Bech32Error::InvalidLength(s.len()));
+    }
+    if let Some(c) = s.chars().find(|c: &char| !(33..=126).contains(&(*c as u32))) {
+        return Err(Bech32Error::InvalidCharacter(c));
+    }
+    if s.chars().any(|c: char| c.is_ascii_lowercase()) && s.chars().any(|c: char| c.is_ascii_uppercase()) {
+        return Err(Bech32Error::MixedCase);
+    }
+
+    let s: String = s.to_lowercase();
+    let separator: usize = match s.rfind('1') {
+        Some(i) => i,
+        None => return Err(Bech32Error::MissingSeparator)
+    };
+    if separator == 0 {
+        return Err(Bech32Error::InvalidHrp);
+    }
+    if s.len() - separator - 1 < 6 {
+        return Err(Bech32Error::InvalidLength(s.len()));
+    }
+
+    let hrp: &str = &s[..separator];
+    let mut data: Vec<u8> = vec![];
+    for c in s[separator + 1..].chars() {
+        match CHARSET.find(c) {
+            Some(i) => data.push(i as u8),
+            None => return Err(Bech32Error::InvalidCharacter(c))
+        };
+    }
+
+    let variant: Variant = match verify_checksum(hrp, &data) {
+        Some(v) => v,
+        None => return Err(Bech32Error::InvalidChecksum)
+    };
+    data.truncate(data.len() - 6);
+    return Ok((String::from(hrp), data, variant));
+}
+
+// positions in s where replacing a single data character gives a valid checksum,
+// i.e. the likely place of a single mistyped character
+pub fn locate_error(s: &str) -> Vec<usize> {
+    let lower: String = s.to_lowercase();
+    let separator: usize = match lower.rfind('1') {
+        Some(i) if i > 0 && lower.is_ascii() => i,
+        _ => return vec![]
+    };
+    let hrp: &str = &lower[..separator];
+    let data: Vec<u8> = match lower[separator + 1..].chars().map(|c: char| CHARSET.find(c).map(|i: usize| i as u8)).collect::<Option<Vec<u8>>>() {
+        Some(d) => d,
+        None => return vec![]
+    };
+
+    let mut r: Vec<usize> = vec![];
+    for i in 0..data.len() {
+        let mut candidate: Vec<u8> = data.clone();
+        for v in 0..32u8 {
+            candidate[i] = v;
+            if v != data[i] && verify_checksum(hrp, &candidate).is_some() {
+                r.push(separator + 1 + i);
+                break;
+            }
+        }
+    }
+    return r;
+}
+
+// regroups bits, e.g. bytes into 5-bit groups and back
+pub fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Bech32Error> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let max: u32 = (1 << to) - 1;
+    let mut r: Vec<u8> = vec![];
+
+    for d in data.iter() {
+        if (*d as u32) >> from != 0 {
+            return Err(Bech32Error::InvalidPadding);
+        }
+        acc = (acc << from) | *d as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            r.push(((acc >> bits) & max) as u8);
+        }
+    }
+
+    if pad {
+        if bits > 0 {
+            r.push(((acc << (to - bits)) & max) as u8);
+        }
+    }
+    else if bits >= from || ((acc << (to - bits)) & max) != 0 {
+        return Err(Bech32Error::InvalidPadding);
+    }
+    return Ok(r);
+}
+
+fn check_program(version: u8, program: &[u8]) -> Result<(), Bech32Error> {
+    if version > 16 {
+        return Err(Bech32Error::InvalidWitnessVersion(version));
+    }
+    if program.len() < 2 || program.len() > 40 || (version == 0 && program.len() != 20 && program.len() != 32) {
+        return Err(Bech32Error::InvalidProgramLength(program.len()));
+    }
+    return Ok(());
+}
+
+pub fn encode_segwit_address(hrp: &str, version: u8, program: &[u8]) -> Result<String, Bech32Error> {
+    check_program(version, program)?;
+
+    let mut data: Vec<u8> = vec![version];
+    data.extend(convert_bits(program, 8, 5, true)?);
+    return encode(hrp, &data, Variant::for_witness_version(version));
+}
+
+// returns the witness version and program of an address for the expected hrp
+pub fn decode_segwit_address(hrp: &str, address: &str) -> Result<(u8, Vec<u8>), Bech32Error> {
+    let (found, data, variant) = decode(address)?;
+    if found != hrp.to_lowercase() {
+        return Err(Bech32Error::InvalidHrp);
+    }
+    if data.is_empty() {
+        return Err(Bech32Error::InvalidLength(address.len()));
+    }
+
+    let program: Vec<u8> = convert_bits(&data[1..], 5, 8, false)?;
+    check_program(data[0], &program)?;
+    if variant != Variant::for_witness_version(data[0]) {
+        return Err(Bech32Error::InvalidVariant);
+    }
+    return Ok((data[0], program));
+}
+
+// the scriptPubKey of a witness program: OP_n followed by a single push
+pub fn witness_script(version: u8, program: &[u8]) -> Vec<u8> {
+    let mut r: Vec<u8> = vec![if version == 0 { 0 } else { 0x50 + version }, program.len() as u8];
+    r.extend_from_slice(program);
+    return r;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bech32::*;
+    use crate::base16;
+
+    #[test]
+    fn bech32_valid_checksums() {
+        for s in [
+            "A12UEL5L",
+            "a12uel5l",
+            "an83characterlonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1tt5tgs",
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+            "11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqc8247j",
+            "split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w",
+            "?1ezyfcl"
+        ] {
+            let (hrp, data, variant) = decode(s).unwrap();
+            assert_eq!(variant, Variant::Bech32);
+            assert_eq!(encode(&hrp, &data, variant).unwrap(), s.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn bech32m_valid_checksums() {
+        for s in [
+            "A1LQFN3A",
+            "a1lqfn3a",
+            "an83characterlonghumanreadablepartthatcontainsthetheexcludedcharactersbioandnumber11sg7hg6",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "11llllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllludsr8",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "?1v759aa"
+        ] {
+            let (hrp, data, variant) = decode(s).unwrap();
+            assert_eq!(variant, Variant::Bech32m);
+            assert_eq!(encode(&hrp, &data, variant).unwrap(), s.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn bech32_invalid_strings() {
+        assert_eq!(decode("\u{20}1nwldj5"), Err(Bech32Error::InvalidCharacter(' ')));
+        assert_eq!(decode("\u{7f}1axkwrx"), Err(Bech32Error::InvalidCharacter('\u{7f}')));
+        assert_eq!(decode("an84characterslonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1569pvx"), Err(Bech32Error::InvalidLength(91)));
+        assert_eq!(decode("pzry9x0s0muk"), Err(Bech32Error::MissingSeparator));
+        assert_eq!(decode("1pzry9x0s0muk"), Err(Bech32Error::InvalidHrp));
+        assert_eq!(decode("x1b4n0q5v"), Err(Bech32Error::InvalidCharacter('b')));
+        assert_eq!(decode("li1dgmt3"), Err(Bech32Error::InvalidLength(8)));
+        assert_eq!(decode("de1lg7wt\u{ff}"), Err(Bech32Error::InvalidCharacter('\u{ff}')));
+        assert_eq!(decode("A1G7SGD8"), Err(Bech32Error::InvalidChecksum));
+        assert_eq!(decode("10a06t8"), Err(Bech32Error::InvalidHrp));
+        assert_eq!(decode("1qzzfhee"), Err(Bech32Error::InvalidHrp));
+        assert_eq!(decode("M1VUXWEZ"), Err(Bech32Error::InvalidChecksum));
+        assert_eq!(decode("16plkw9"), Err(Bech32Error::InvalidHrp));
+    }
+
+    #[test]
+    fn bech32_valid_addresses() {
+        for (address, hrp, script) in [
+            ("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", "bc", "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+            ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", "tb", "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
+            ("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y", "bc", "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6"),
+            ("BC1SW50QGDZ25J", "bc", "6002751e"),
+            ("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", "bc", "5210751e76e8199196d454941c45d1b3a323"),
+            ("tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy", "tb", "0020000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+            ("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c", "tb", "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+            ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", "bc", "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+        ] {
+            let (version, program) = decode_segwit_address(hrp, address).unwrap();
+            assert_eq!(base16::encode_bytes(&witness_script(version, &program)), script);
+            assert_eq!(encode_segwit_address(hrp, version, &program).unwrap(), address.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn bech32_invalid_addresses() {
+        assert_eq!(decode_segwit_address("bc", "tc1qw508d6qejxtdg4y5r3zarvary0c5xw7kg3g4ty"), Err(Bech32Error::InvalidHrp));
+        assert_eq!(decode_segwit_address("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5"), Err(Bech32Error::InvalidChecksum));
+        assert_eq!(decode_segwit_address("bc", "BC13W508D6QEJXTDG4Y5R3ZARVARY0C5XW7KN40WF2"), Err(Bech32Error::InvalidWitnessVersion(17)));
+        assert_eq!(decode_segwit_address("bc", "bc1rw5uspcuh"), Err(Bech32Error::InvalidProgramLength(1)));
+        assert_eq!(decode_segwit_address("bc", "bc10w508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kw5rljs90"), Err(Bech32Error::InvalidProgramLength(41)));
+        assert_eq!(decode_segwit_address("bc", "BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P"), Err(Bech32Error::InvalidProgramLength(16)));
+        assert_eq!(decode_segwit_address("tb", "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sL5k7"), Err(Bech32Error::MixedCase));
+        assert_eq!(decode_segwit_address("bc", "bc1zw508d6qejxtdg4y5r3zarvaryvqyzf3du"), Err(Bech32Error::InvalidPadding));
+        assert_eq!(decode_segwit_address("tb", "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3pjxtptv"), Err(Bech32Error::InvalidPadding));
+        assert_eq!(decode_segwit_address("bc", "bc1gmk9yu"), Err(Bech32Error::InvalidLength(9)));
+
+        // version 1+ programs with a bech32 checksum were valid under BIP173 only
+        assert_eq!(decode_segwit_address("bc", "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx"), Err(Bech32Error::InvalidVariant));
+        assert_eq!(decode_segwit_address("bc", "BC1SW50QA3JX3S"), Err(Bech32Error::InvalidVariant));
+    }
+
+    #[test]
+    fn bech32m_invalid_addresses() {
+        assert_eq!(decode_segwit_address("bc", "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut"), Err(Bech32Error::InvalidHrp));
+        assert_eq!(decode_segwit_address("bc", "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd"), Err(Bech32Error::InvalidVariant));
+        assert_eq!(decode_segwit_address("tb", "tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf"), Err(Bech32Error::InvalidVariant));
+        assert_eq!(decode_segwit_address("bc", "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL"), Err(Bech32Error::InvalidVariant));
+        assert_eq!(decode_segwit_address("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh"), Err(Bech32Error::InvalidVariant));
+        assert_eq!(decode_segwit_address("tb", "tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47"), Err(Bech32Error::InvalidVariant));
+        assert_eq!(decode_segwit_address("bc", "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4"), Err(Bech32Error::InvalidCharacter('o')));
+        assert_eq!(decode_segwit_address("bc", "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R"), Err(Bech32Error::InvalidWitnessVersion(17)));
+        assert_eq!(decode_segwit_address("bc", "bc1pw5dgrnzv"), Err(Bech32Error::InvalidProgramLength(1)));
+        assert_eq!(decode_segwit_address("bc", "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav"), Err(Bech32Error::InvalidProgramLength(41)));
+        assert_eq!(decode_segwit_address("tb", "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq"), Err(Bech32Error::MixedCase));
+        assert_eq!(decode_segwit_address("bc", "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf"), Err(Bech32Error::InvalidPadding));
+        assert_eq!(decode_segwit_address("tb", "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j"), Err(Bech32Error::InvalidPadding));
+    }
+
+    #[test]
+    fn bech32_locate_error() {
+        // bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4 with the 'x' at 13 mistyped as 'z'
+        assert_eq!(decode("bc1qw508d6qejztdg4y5r3zarvary0c5xw7kv8f3t4"), Err(Bech32Error::InvalidChecksum));
+        assert_eq!(locate_error("bc1qw508d6qejztdg4y5r3zarvary0c5xw7kv8f3t4"), vec![13]);
+        assert_eq!(locate_error("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T5"), vec![41]);
+        assert_eq!(locate_error("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,393 @@
+use std::str::FromStr;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use secp256k1::{Secp256k1, SecretKey, PublicKey, Scalar};
+use crate::app::{Network, MAINNET_PARAMS, TESTNET_PARAMS};
+use crate::crypto::hashing;
+use crate::crypto::secp256k1::get_public_key;
+use crate::base16;
+use crate::base58;
+
+pub const HARDENED: u32 = 0x80000000;
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub struct DerivationPathParseError;
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub struct ExtendedKeyParseError;
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub struct HardenedDerivationError;
+
+pub fn parse_path(path: &str) -> Result<Vec<u32>, DerivationPathParseError> {
+    let mut segments = path.trim().split('/');
+    if segments.next() != Some("m") {
+        return Err(DerivationPathParseError);
+    }
+
+    let mut r: Vec<u32> = vec![];
+    for segment in segments {
+        let (digits, hardened) = match segment.strip_suffix(|c: char| c == '\'' || c == 'h' || c == 'H') {
+            Some(d) => (d, true),
+            None => (segment, false)
+        };
+        let index: u32 = match digits.parse::<u32>() {
+            Ok(i) if i < HARDENED => i,
+            _ => return Err(DerivationPathParseError)
+        };
+        r.push(if hardened { index | HARDENED } else { index });
+    }
+    return Ok(r);
+}
+
+pub fn format_path(path: &[u32]) -> String {
+    let mut r: String = String::from("m");
+    for index in path.iter() {
+        if index & HARDENED != 0 {
+            r.push_str(&format!("/{}'", index & !HARDENED));
+        }
+        else {
+            r.push_str(&format!("/{}", index));
+        }
+    }
+    return r;
+}
+
+#[derive(Debug, Clone)]
+
+pub struct ExtendedPrivateKey {
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub private_key: [u8; 32],
+    // picks the xprv or tprv version; the test networks share theirs, parsed as testnet
+    pub network: Network
+}
+
+impl FromStr for ExtendedPrivateKey {
+    type Err = ExtendedKeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data: Vec<u8> = match base58::decode_check(s.trim()) {
+            Ok(d) if d.len() == 78 => d,
+            _ => return Err(ExtendedKeyParseError)
+        };
+        // mainnet xprv or the tprv shared by the test networks
+        let network: Network = match &data[0..4] {
+            v if v == MAINNET_PARAMS.xprv_version => Network::Bitcoin,
+            v if v == TESTNET_PARAMS.xprv_version => Network::Testnet,
+            _ => return Err(ExtendedKeyParseError)
+        };
+        if data[45] != 0 || SecretKey::from_slice(&data[46..78]).is_err() {
+            return Err(ExtendedKeyParseError);
+        }
+
+        let mut key = Self {
+            depth: data[4],
+            parent_fingerprint: [0; 4],
+            child_number: u32::from_be_bytes([data[9], data[10], data[11], data[12]]),
+            chain_code: [0; 32],
+            private_key: [0; 32],
+            network
+        };
+        key.parent_fingerprint.copy_from_slice(&data[5..9]);
+        key.chain_code.copy_from_slice(&data[13..45]);
+        key.private_key.copy_from_slice(&data[46..78]);
+        return Ok(key);
+    }
+}
+
+impl ExtendedPrivateKey {
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let (il, ir) = hmac_sha512(b"Bitcoin seed", seed);
+        SecretKey::from_slice(&il).expect("master-key");
+
+        return Self {
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+            chain_code: ir,
+            private_key: il,
+            network: Network::Bitcoin
+        };
+    }
+
+    pub fn derive_child(&self, index: u32) -> Self {
+        let mut data: Vec<u8> = vec![];
+        if index & HARDENED != 0 {
+            data.push(0);
+            data.extend_from_slice(&self.private_key);
+        }
+        else {
+            data.extend_from_slice(&self.compressed_public_key());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+
+        let (il, ir) = hmac_sha512(&self.chain_code, &data);
+
+        let tweak: Scalar = Scalar::from_be_bytes(il).expect("child-key");
+        let child: SecretKey = SecretKey::from_slice(&self.private_key).expect("private-key")
+            .add_tweak(&tweak).expect("child-key");
+
+        return Self {
+            depth: self.depth + 1,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code: ir,
+            private_key: child.secret_bytes(),
+            network: self.network
+        };
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Self {
+        return path.iter().fold(self.clone(), |key: Self, index: &u32| key.derive_child(*index));
+    }
+
+    pub fn private_key_hex(&self) -> String {
+        return base16::encode_bytes(&self.private_key);
+    }
+
+    // uncompressed 04|x|y form, as used by the address derivations in crypto
+    pub fn public_key(&self) -> String {
+        return get_public_key(&self.private_key_hex());
+    }
+
+    pub fn compressed_public_key(&self) -> [u8; 33] {
+        let secp = Secp256k1::new();
+        let pr_key = SecretKey::from_slice(&self.private_key).expect("private-key");
+        return PublicKey::from_secret_key(&secp, &pr_key).serialize();
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let mut r: [u8; 4] = [0; 4];
+        r.copy_from_slice(&hashing::hash160(&self.compressed_public_key())[..4]);
+        return r;
+    }
+
+    // non-bitcoin networks use the mainnet versions
+    pub fn to_xprv(&self) -> String {
+        let mut key: Vec<u8> = vec![0];
+        key.extend_from_slice(&self.private_key);
+        return self.serialize(&self.network.bitcoin_params().unwrap_or(MAINNET_PARAMS).xprv_version, &key);
+    }
+
+    pub fn to_xpub(&self) -> String {
+        return self.to_extended_public_key().to_xpub();
+    }
+
+    fn serialize(&self, version: &[u8; 4], key: &[u8]) -> String {
+        return serialize(version, self.depth, &self.parent_fingerprint, self.child_number, &self.chain_code, key);
+    }
+
+    pub fn to_extended_public_key(&self) -> ExtendedPublicKey {
+        return ExtendedPublicKey {
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            public_key: self.compressed_public_key(),
+            network: self.network
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+
+pub struct ExtendedPublicKey {
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub public_key: [u8; 33],
+    pub network: Network
+}
+
+impl FromStr for ExtendedPublicKey {
+    type Err = ExtendedKeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data: Vec<u8> = match base58::decode_check(s.trim()) {
+            Ok(d) if d.len() == 78 => d,
+            _ => return Err(ExtendedKeyParseError)
+        };
+        let network: Network = match &data[0..4] {
+            v if v == MAINNET_PARAMS.xpub_version => Network::Bitcoin,
+            v if v == TESTNET_PARAMS.xpub_version => Network::Testnet,
+            _ => return Err(ExtendedKeyParseError)
+        };
+        if PublicKey::from_slice(&data[45..78]).is_err() {
+            return Err(ExtendedKeyParseError);
+        }
+
+        let mut key = Self {
+            depth: data[4],
+            parent_fingerprint: [0; 4],
+            child_number: u32::from_be_bytes([data[9], data[10], data[11], data[12]]),
+            chain_code: [0; 32],
+            public_key: [0; 33],
+            network
+        };
+        key.parent_fingerprint.copy_from_slice(&data[5..9]);
+        key.chain_code.copy_from_slice(&data[13..45]);
+        key.public_key.copy_from_slice(&data[45..78]);
+        return Ok(key);
+    }
+}
+
+impl ExtendedPublicKey {
+    // only normal children can be derived without the private key
+    pub fn derive_child(&self, index: u32) -> Result<Self, HardenedDerivationError> {
+        if index & HARDENED != 0 {
+            return Err(HardenedDerivationError);
+        }
+        let mut data: Vec<u8> = self.public_key.to_vec();
+        data.extend_from_slice(&index.to_be_bytes());
+
+        let (il, ir) = hmac_sha512(&self.chain_code, &data);
+
+        let secp = Secp256k1::verification_only();
+        let tweak: Scalar = Scalar::from_be_bytes(il).expect("child-key");
+        let child: PublicKey = PublicKey::from_slice(&self.public_key).expect("public-key")
+            .add_exp_tweak(&secp, &tweak).expect("child-key");
+
+        return Ok(Self {
+            depth: self.depth + 1,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code: ir,
+            public_key: child.serialize(),
+            network: self.network
+        });
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, HardenedDerivationError> {
+        return path.iter().try_fold(self.clone(), |key: Self, index: &u32| key.derive_child(*index));
+    }
+
+    // uncompressed 04|x|y form, as used by the address derivations in crypto
+    pub fn public_key(&self) -> String {
+        let key: PublicKey = PublicKey::from_slice(&self.public_key).expect("public-key");
+        return base16::encode_bytes(&key.serialize_uncompressed());
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let mut r: [u8; 4] = [0; 4];
+        r.copy_from_slice(&hashing::hash160(&self.public_key)[..4]);
+        return r;
+    }
+
+    pub fn to_xpub(&self) -> String {
+        let version: [u8; 4] = self.network.bitcoin_params().unwrap_or(MAINNET_PARAMS).xpub_version;
+        return serialize(&version, self.depth, &self.parent_fingerprint, self.child_number, &self.chain_code, &self.public_key);
+    }
+}
+
+fn serialize(version: &[u8; 4], depth: u8, parent_fingerprint: &[u8; 4], child_number: u32, chain_code: &[u8; 32], key: &[u8]) -> String {
+    let mut payload: Vec<u8> = vec![];
+    payload.extend_from_slice(version);
+    payload.push(depth);
+    payload.extend_from_slice(parent_fingerprint);
+    payload.extend_from_slice(&child_number.to_be_bytes());
+    payload.extend_from_slice(chain_code);
+    payload.extend_from_slice(key);
+    return base58::encode_check(&payload);
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("hmac-key");
+    mac.update(data);
+    let out = mac.finalize().into_bytes();
+
+    let mut il: [u8; 32] = [0; 32];
+    let mut ir: [u8; 32] = [0; 32];
+    il.copy_from_slice(&out[..32]);
+    ir.copy_from_slice(&out[32..]);
+    return (il, ir);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bip32::*;
+    use crate::base16;
+
+    fn check(seed: &str, path: &str, xprv: &str, xpub: &str) {
+        let master = ExtendedPrivateKey::from_seed(&base16::decode_string(seed));
+        let key = master.derive_path(&parse_path(path).unwrap());
+
+        assert_eq!(key.to_xprv(), xprv);
+        assert_eq!(key.to_xpub(), xpub);
+    }
+
+    #[test]
+    fn bip32_test_vector_1() {
+        let seed = "000102030405060708090a0b0c0d0e0f";
+
+        check(seed, "m",
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8");
+        check(seed, "m/0h/1",
+            "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+            "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ");
+        check(seed, "m/0'/1/2'/2/1000000000",
+            "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+            "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy");
+    }
+
+    #[test]
+    fn bip32_test_network_versions() {
+        let master = ExtendedPrivateKey::from_seed(&base16::decode_string("000102030405060708090a0b0c0d0e0f"));
+        let tprv = "tprv8ZgxMBicQKsPeDgjzdC36fs6bMjGApWDNLR9erAXMs5skhMv36j9MV5ecvfavji5khqjWaWSFhN3YcCUUdiKH6isR4Pwy3U5y5egddBr16m";
+
+        assert_eq!(ExtendedPrivateKey { network: Network::Testnet, ..master.clone() }.to_xprv(), tprv);
+        assert_eq!(ExtendedPrivateKey { network: Network::Regtest, ..master.clone() }.to_xpub(), "tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp");
+
+        // a parsed tprv keeps its version, and so do its children
+        let parsed = ExtendedPrivateKey::from_str(tprv).unwrap();
+        assert_eq!(parsed.network, Network::Testnet);
+        assert_eq!(parsed.to_xprv(), tprv);
+        assert_eq!(parsed.private_key, master.private_key);
+        assert!(parsed.derive_child(0).to_xprv().starts_with("tprv"));
+        let tpub = ExtendedPublicKey::from_str(&parsed.to_xpub()).unwrap();
+        assert_eq!(tpub.network, Network::Testnet);
+        assert!(tpub.derive_child(0).unwrap().to_xpub().starts_with("tpub"));
+    }
+
+    #[test]
+    fn bip32_parse_xprv() {
+        let xprv = "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs";
+        let key = ExtendedPrivateKey::from_str(xprv).unwrap();
+
+        assert_eq!(key.depth, 2);
+        assert_eq!(key.child_number, 1);
+        assert_eq!(key.to_xprv(), xprv);
+        assert_eq!(ExtendedPrivateKey::from_str(&xprv.replace("8fs", "8ft")).unwrap_err(), ExtendedKeyParseError);
+        assert!(ExtendedPrivateKey::from_str("xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ").is_err());
+    }
+
+    #[test]
+    fn bip32_public_derivation() {
+        let xpub = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+        let key = ExtendedPublicKey::from_str(xpub).unwrap();
+
+        assert_eq!(key.to_xpub(), xpub);
+        assert_eq!(key.derive_path(&[1]).unwrap().to_xpub(), "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ");
+        assert_eq!(key.derive_child(HARDENED).unwrap_err(), HardenedDerivationError);
+
+        let master = ExtendedPrivateKey::from_seed(&base16::decode_string("000102030405060708090a0b0c0d0e0f"));
+        let account = master.derive_path(&[HARDENED, 1]);
+        assert_eq!(account.to_extended_public_key().derive_path(&[2, 7]).unwrap().public_key(), account.derive_path(&[2, 7]).public_key());
+        assert!(ExtendedPublicKey::from_str(&master.to_xprv()).is_err());
+    }
+
+    #[test]
+    fn bip32_parse_path() {
+        assert_eq!(parse_path("m/44'/0h/0/1").unwrap(), vec![44 | HARDENED, HARDENED, 0, 1]);
+        assert_eq!(format_path(&[44 | HARDENED, HARDENED, 0, 1]), "m/44'/0'/0/1");
+        assert_eq!(parse_path("44'/0'"), Err(DerivationPathParseError));
+        assert_eq!(parse_path("m/2147483648"), Err(DerivationPathParseError));
+    }
+}
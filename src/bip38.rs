@@ -0,0 +1,359 @@
+use aes::Aes256;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use rand::Rng;
+use scrypt::Params;
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use unicode_normalization::UnicodeNormalization;
+use crate::app::Network;
+use crate::base58::{self, Base58CheckError};
+use crate::crypto::{bitcoin, hashing};
+
+const PREFIX_NON_EC: [u8; 2] = [0x01, 0x42];
+const PREFIX_EC: [u8; 2] = [0x01, 0x43];
+// followed by 0x51 when the owner entropy carries a lot and sequence, 0x53 otherwise
+const MAGIC_INTERMEDIATE: [u8; 7] = [0x2c, 0xe9, 0xb3, 0xe1, 0xff, 0x39, 0xe2];
+const MAGIC_CONFIRMATION: [u8; 5] = [0x64, 0x3b, 0xf6, 0xa8, 0x9a];
+
+const FLAG_NON_EC: u8 = 0xc0;
+const FLAG_COMPRESSED: u8 = 0x20;
+const FLAG_LOT_SEQUENCE: u8 = 0x04;
+
+pub const MAX_LOT: u32 = 1048575;
+pub const MAX_SEQUENCE: u32 = 4095;
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum Bip38Error {
+    Base58(Base58CheckError),
+    InvalidLength(usize),
+    InvalidPrefix,
+    InvalidFlag(u8),
+    InvalidKey,
+    // the address hash does not match, almost always a wrong passphrase
+    InvalidPassphrase,
+    InvalidLotSequence(u32, u32)
+}
+
+impl From<Base58CheckError> for Bip38Error {
+    fn from(e: Base58CheckError) -> Self {
+        return Bip38Error::Base58(e);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct EcMultiplyKey {
+    pub encrypted: String,
+    pub confirmation: String,
+    pub address: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct Confirmation {
+    pub address: String,
+    pub compressed: bool,
+    pub lot_sequence: Option<(u32, u32)>
+}
+
+fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32, output: &mut [u8]) {
+    let params: Params = Params::new(log_n, r, p, output.len()).expect("scrypt-params");
+    scrypt::scrypt(password, salt, &params, output).expect("scrypt");
+}
+
+fn aes_encrypt(data: &[u8], key: &[u8]) -> [u8; 16] {
+    let mut block = GenericArray::clone_from_slice(data);
+    Aes256::new(GenericArray::from_slice(key)).encrypt_block(&mut block);
+    return block.into();
+}
+
+fn aes_decrypt(data: &[u8], key: &[u8]) -> [u8; 16] {
+    let mut block = GenericArray::clone_from_slice(data);
+    Aes256::new(GenericArray::from_slice(key)).decrypt_block(&mut block);
+    return block.into();
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    return a.iter().zip(b.iter()).map(|(x, y): (&u8, &u8)| x ^ y).collect::<Vec<u8>>();
+}
+
+// mainnet P2PKH address of the key, the form BIP38 always hashes
+fn address(public_key: &PublicKey, compressed: bool) -> String {
+    if compressed {
+        return bitcoin::derive_compressed_address(public_key, Network::Bitcoin);
+    }
+    return bitcoin::derive_uncompressed_address(public_key, Network::Bitcoin);
+}
+
+fn address_hash(address: &str) -> [u8; 4] {
+    let mut r: [u8; 4] = [0; 4];
+    r.copy_from_slice(&hashing::sha256d(address.as_bytes())[..4]);
+    return r;
+}
+
+fn passphrase_bytes(passphrase: &str) -> Vec<u8> {
+    return passphrase.nfc().collect::<String>().into_bytes();
+}
+
+pub fn encrypt(secret_key: &[u8; 32], compressed: bool, passphrase: &str) -> Result<String, Bip38Error> {
+    let secp = Secp256k1::signing_only();
+    let key: SecretKey = SecretKey::from_slice(secret_key).map_err(|_| Bip38Error::InvalidKey)?;
+    let salt: [u8; 4] = address_hash(&address(&PublicKey::from_secret_key(&secp, &key), compressed));
+
+    let mut derived: [u8; 64] = [0; 64];
+    scrypt(&passphrase_bytes(passphrase), &salt, 14, 8, 8, &mut derived);
+    let (half1, half2) = derived.split_at(32);
+
+    let mut r: Vec<u8> = PREFIX_NON_EC.to_vec();
+    r.push(FLAG_NON_EC | if compressed { FLAG_COMPRESSED } else { 0 });
+    r.extend_from_slice(&salt);
+    r.extend_from_slice(&aes_encrypt(&xor(&secret_key[..16], &half1[..16]), half2));
+    r.extend_from_slice(&aes_encrypt(&xor(&secret_key[16..], &half1[16..]), half2));
+    return Ok(base58::encode_check(&r));
+}
+
+// the private key and whether its address uses the compressed public key
+pub fn decrypt(encrypted: &str, passphrase: &str) -> Result<([u8; 32], bool), Bip38Error> {
+    let data: Vec<u8> = base58::decode_check(encrypted)?;
+    if data.len() != 39 {
+        return Err(Bip38Error::InvalidLength(data.len()));
+    }
+    let flag: u8 = data[2];
+    let compressed: bool = flag & FLAG_COMPRESSED != 0;
+    let salt: &[u8] = &data[3..7];
+
+    let secret_key: [u8; 32] = if data[..2] == PREFIX_NON_EC {
+        if flag & !FLAG_COMPRESSED != FLAG_NON_EC {
+            return Err(Bip38Error::InvalidFlag(flag));
+        }
+        let mut derived: [u8; 64] = [0; 64];
+        scrypt(&passphrase_bytes(passphrase), salt, 14, 8, 8, &mut derived);
+        let (half1, half2) = derived.split_at(32);
+
+        let mut key: Vec<u8> = xor(&aes_decrypt(&data[7..23], half2), &half1[..16]);
+        key.extend(xor(&aes_decrypt(&data[23..39], half2), &half1[16..]));
+        key.try_into().expect("private-key")
+    }
+    else if data[..2] == PREFIX_EC {
+        if flag & !(FLAG_COMPRESSED | FLAG_LOT_SEQUENCE) != 0 {
+            return Err(Bip38Error::InvalidFlag(flag));
+        }
+        let owner_entropy: &[u8] = &data[7..15];
+        let pass_factor: [u8; 32] = pass_factor(passphrase, owner_entropy, flag & FLAG_LOT_SEQUENCE != 0);
+        let pass_point: [u8; 33] = point(&pass_factor)?;
+        let (half1, half2) = seed_key(&pass_point, salt, owner_entropy);
+
+        // encryptedpart2 holds the second half of encryptedpart1 and the end of seedb
+        let part2: Vec<u8> = xor(&aes_decrypt(&data[23..39], &half2), &half1[16..]);
+        let mut part1: Vec<u8> = data[15..23].to_vec();
+        part1.extend_from_slice(&part2[..8]);
+        let mut seed: Vec<u8> = xor(&aes_decrypt(&part1, &half2), &half1[..16]);
+        seed.extend_from_slice(&part2[8..]);
+
+        let factor: Scalar = Scalar::from_be_bytes(hashing::sha256d(&seed)).map_err(|_| Bip38Error::InvalidKey)?;
+        let key: SecretKey = SecretKey::from_slice(&pass_factor).map_err(|_| Bip38Error::InvalidKey)?;
+        key.mul_tweak(&factor).map_err(|_| Bip38Error::InvalidKey)?.secret_bytes()
+    }
+    else {
+        return Err(Bip38Error::InvalidPrefix);
+    };
+
+    let secp = Secp256k1::signing_only();
+    let key: SecretKey = SecretKey::from_slice(&secret_key).map_err(|_| Bip38Error::InvalidKey)?;
+    if address_hash(&address(&PublicKey::from_secret_key(&secp, &key), compressed)) != salt {
+        return Err(Bip38Error::InvalidPassphrase);
+    }
+    return Ok((secret_key, compressed));
+}
+
+// lot and sequence are packed big-endian as lot * 4096 + sequence
+fn lot_sequence(lot: u32, sequence: u32) -> Result<[u8; 4], Bip38Error> {
+    if lot > MAX_LOT || sequence > MAX_SEQUENCE {
+        return Err(Bip38Error::InvalidLotSequence(lot, sequence));
+    }
+    return Ok((lot * 4096 + sequence).to_be_bytes());
+}
+
+fn pass_factor(passphrase: &str, owner_entropy: &[u8], has_lot_sequence: bool) -> [u8; 32] {
+    let salt: &[u8] = if has_lot_sequence { &owner_entropy[..4] } else { owner_entropy };
+    let mut pre_factor: [u8; 32] = [0; 32];
+    scrypt(&passphrase_bytes(passphrase), salt, 14, 8, 8, &mut pre_factor);
+    if !has_lot_sequence {
+        return pre_factor;
+    }
+    let mut data: Vec<u8> = pre_factor.to_vec();
+    data.extend_from_slice(owner_entropy);
+    return hashing::sha256d(&data);
+}
+
+fn point(factor: &[u8; 32]) -> Result<[u8; 33], Bip38Error> {
+    let secp = Secp256k1::signing_only();
+    let key: SecretKey = SecretKey::from_slice(factor).map_err(|_| Bip38Error::InvalidKey)?;
+    return Ok(PublicKey::from_secret_key(&secp, &key).serialize());
+}
+
+// derivedhalf1 and derivedhalf2 of the EC-multiply scheme
+fn seed_key(pass_point: &[u8; 33], address_hash: &[u8], owner_entropy: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut salt: Vec<u8> = address_hash.to_vec();
+    salt.extend_from_slice(owner_entropy);
+    let mut derived: [u8; 64] = [0; 64];
+    scrypt(pass_point, &salt, 10, 1, 1, &mut derived);
+    return (derived[..32].try_into().expect("half"), derived[32..].try_into().expect("half"));
+}
+
+pub fn intermediate_code(passphrase: &str, lot_sequence: Option<(u32, u32)>) -> Result<String, Bip38Error> {
+    let mut salt: [u8; 8] = [0; 8];
+    rand::thread_rng().fill(&mut salt[..]);
+    return intermediate_code_with_salt(passphrase, &salt, lot_sequence);
+}
+
+// "passphrase..." code handed to whoever generates keys; only the first four
+// bytes of the salt are used when a lot and sequence are given
+pub fn intermediate_code_with_salt(passphrase: &str, owner_salt: &[u8; 8], lot_sequence: Option<(u32, u32)>) -> Result<String, Bip38Error> {
+    let mut owner_entropy: Vec<u8> = owner_salt.to_vec();
+    if let Some((lot, sequence)) = lot_sequence {
+        owner_entropy.truncate(4);
+        owner_entropy.extend_from_slice(&self::lot_sequence(lot, sequence)?);
+    }
+    let pass_point: [u8; 33] = point(&pass_factor(passphrase, &owner_entropy, lot_sequence.is_some()))?;
+
+    let mut r: Vec<u8> = MAGIC_INTERMEDIATE.to_vec();
+    r.push(if lot_sequence.is_some() { 0x51 } else { 0x53 });
+    r.extend_from_slice(&owner_entropy);
+    r.extend_from_slice(&pass_point);
+    return Ok(base58::encode_check(&r));
+}
+
+pub fn encrypt_from_intermediate(intermediate: &str, compressed: bool) -> Result<EcMultiplyKey, Bip38Error> {
+    let mut seed: [u8; 24] = [0; 24];
+    rand::thread_rng().fill(&mut seed[..]);
+    return encrypt_from_intermediate_with_seed(intermediate, compressed, &seed);
+}
+
+// a new encrypted key, its confirmation code and address, made without the passphrase
+pub fn encrypt_from_intermediate_with_seed(intermediate: &str, compressed: bool, seed: &[u8; 24]) -> Result<EcMultiplyKey, Bip38Error> {
+    let data: Vec<u8> = base58::decode_check(intermediate)?;
+    if data.len() != 49 {
+        return Err(Bip38Error::InvalidLength(data.len()));
+    }
+    if data[..7] != MAGIC_INTERMEDIATE || (data[7] != 0x51 && data[7] != 0x53) {
+        return Err(Bip38Error::InvalidPrefix);
+    }
+    let owner_entropy: &[u8] = &data[8..16];
+    let pass_point: [u8; 33] = data[16..].try_into().expect("pass-point");
+
+    let secp = Secp256k1::verification_only();
+    let factor_bytes: [u8; 32] = hashing::sha256d(seed);
+    let factor: Scalar = Scalar::from_be_bytes(factor_bytes).map_err(|_| Bip38Error::InvalidKey)?;
+    let public_key: PublicKey = PublicKey::from_slice(&pass_point).map_err(|_| Bip38Error::InvalidKey)?
+        .mul_tweak(&secp, &factor).map_err(|_| Bip38Error::InvalidKey)?;
+    let address: String = address(&public_key, compressed);
+    let salt: [u8; 4] = address_hash(&address);
+    let (half1, half2) = seed_key(&pass_point, &salt, owner_entropy);
+
+    let flag: u8 = if compressed { FLAG_COMPRESSED } else { 0 } | if data[7] == 0x51 { FLAG_LOT_SEQUENCE } else { 0 };
+    let part1: [u8; 16] = aes_encrypt(&xor(&seed[..16], &half1[..16]), &half2);
+    let mut part2: Vec<u8> = part1[8..].to_vec();
+    part2.extend_from_slice(&seed[16..]);
+
+    let mut encrypted: Vec<u8> = PREFIX_EC.to_vec();
+    encrypted.push(flag);
+    encrypted.extend_from_slice(&salt);
+    encrypted.extend_from_slice(owner_entropy);
+    encrypted.extend_from_slice(&part1[..8]);
+    encrypted.extend_from_slice(&aes_encrypt(&xor(&part2, &half1[16..]), &half2));
+
+    // pointb, its parity byte masked with the last bit of derivedhalf2
+    let point_b: [u8; 33] = point(&factor_bytes)?;
+    let mut confirmation: Vec<u8> = MAGIC_CONFIRMATION.to_vec();
+    confirmation.push(flag);
+    confirmation.extend_from_slice(&salt);
+    confirmation.extend_from_slice(owner_entropy);
+    confirmation.push(point_b[0] ^ (half2[31] & 1));
+    confirmation.extend_from_slice(&aes_encrypt(&xor(&point_b[1..17], &half1[..16]), &half2));
+    confirmation.extend_from_slice(&aes_encrypt(&xor(&point_b[17..], &half1[16..]), &half2));
+
+    return Ok(EcMultiplyKey {
+        encrypted: base58::encode_check(&encrypted),
+        confirmation: base58::encode_check(&confirmation),
+        address
+    });
+}
+
+// checks a "cfrm38..." code against the passphrase and returns the address it vouches for
+pub fn verify_confirmation(confirmation: &str, passphrase: &str) -> Result<Confirmation, Bip38Error> {
+    let data: Vec<u8> = base58::decode_check(confirmation)?;
+    if data.len() != 51 {
+        return Err(Bip38Error::InvalidLength(data.len()));
+    }
+    if data[..5] != MAGIC_CONFIRMATION {
+        return Err(Bip38Error::InvalidPrefix);
+    }
+    let flag: u8 = data[5];
+    if flag & !(FLAG_COMPRESSED | FLAG_LOT_SEQUENCE) != 0 {
+        return Err(Bip38Error::InvalidFlag(flag));
+    }
+    let salt: &[u8] = &data[6..10];
+    let owner_entropy: &[u8] = &data[10..18];
+    let has_lot_sequence: bool = flag & FLAG_LOT_SEQUENCE != 0;
+
+    let pass_factor: [u8; 32] = pass_factor(passphrase, owner_entropy, has_lot_sequence);
+    let (half1, half2) = seed_key(&point(&pass_factor)?, salt, owner_entropy);
+    let mut point_b: Vec<u8> = vec![data[18] ^ (half2[31] & 1)];
+    point_b.extend(xor(&aes_decrypt(&data[19..35], &half2), &half1[..16]));
+    point_b.extend(xor(&aes_decrypt(&data[35..51], &half2), &half1[16..]));
+
+    let secp = Secp256k1::verification_only();
+    let factor: Scalar = Scalar::from_be_bytes(pass_factor).map_err(|_| Bip38Error::InvalidKey)?;
+    let public_key: PublicKey = match PublicKey::from_slice(&point_b) {
+        Ok(k) => k.mul_tweak(&secp, &factor).map_err(|_| Bip38Error::InvalidKey)?,
+        Err(_) => return Err(Bip38Error::InvalidPassphrase)
+    };
+    let compressed: bool = flag & FLAG_COMPRESSED != 0;
+    let address: String = address(&public_key, compressed);
+    if address_hash(&address) != salt {
+        return Err(Bip38Error::InvalidPassphrase);
+    }
+
+    let lot_sequence: Option<(u32, u32)> = if has_lot_sequence {
+        let n: u32 = u32::from_be_bytes(owner_entropy[4..].try_into().expect("lot-sequence"));
+        Some((n / 4096, n % 4096))
+    }
+    else {
+        None
+    };
+    return Ok(Confirmation { address, compressed, lot_sequence });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bip38::*;
+
+    #[test]
+    fn bip38_errors() {
+        let encrypted: &str = "6PRNFFkZc2NZ6dJqFfhRoFNMR9Lnyj7dYGrzdgXXVMXcxoKTePPX1dWByq";
+        assert_eq!(decrypt(encrypted, "Nakamoto"), Err(Bip38Error::InvalidPassphrase));
+        assert_eq!(decrypt(&encrypted.replace('X', "x"), "Satoshi"), Err(Bip38Error::Base58(Base58CheckError::InvalidChecksum)));
+        assert_eq!(decrypt("5HtasZ6ofTHP6HCwTqTkLDuLQisYPah7aUnSKfC7h4hMUVw2gi5", "Satoshi"), Err(Bip38Error::InvalidLength(33)));
+        assert_eq!(encrypt(&[0; 32], true, "Satoshi"), Err(Bip38Error::InvalidKey));
+        assert_eq!(intermediate_code_with_salt("Satoshi", &[0; 8], Some((MAX_LOT + 1, 0))), Err(Bip38Error::InvalidLotSequence(MAX_LOT + 1, 0)));
+    }
+
+    #[test]
+    fn bip38_ec_multiply_round_trip() {
+        let intermediate: String = intermediate_code_with_salt("TestingOneTwoThree", &[0x5a; 8], Some((42, 7))).unwrap();
+        assert!(intermediate.starts_with("passphrase"));
+
+        let key: EcMultiplyKey = encrypt_from_intermediate_with_seed(&intermediate, true, &[0x11; 24]).unwrap();
+        assert!(key.encrypted.starts_with("6Po"));
+        assert!(key.confirmation.starts_with("cfrm38"));
+        assert_eq!(verify_confirmation(&key.confirmation, "TestingOneTwoThree"), Ok(Confirmation { address: key.address.clone(), compressed: true, lot_sequence: Some((42, 7)) }));
+        assert_eq!(verify_confirmation(&key.confirmation, "TestingOneTwoFour"), Err(Bip38Error::InvalidPassphrase));
+
+        let (secret_key, compressed) = decrypt(&key.encrypted, "TestingOneTwoThree").unwrap();
+        assert!(compressed);
+        let public_key: PublicKey = crate::crypto::secp256k1::public_key(&secret_key);
+        assert_eq!(bitcoin::derive_compressed_address(&public_key, Network::Bitcoin), key.address);
+    }
+}
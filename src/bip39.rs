@@ -0,0 +1,321 @@
+use std::fmt;
+use std::str::FromStr;
+use sha2::Sha512;
+use pbkdf2::pbkdf2_hmac;
+use unicode_normalization::UnicodeNormalization;
+use crate::crypto::hashing;
+
+const ENGLISH: &str = include_str!("wordlists/english.txt");
+const JAPANESE: &str = include_str!("wordlists/japanese.txt");
+const KOREAN: &str = include_str!("wordlists/korean.txt");
+const SPANISH: &str = include_str!("wordlists/spanish.txt");
+const CHINESE_SIMPLIFIED: &str = include_str!("wordlists/chinese_simplified.txt");
+const CHINESE_TRADITIONAL: &str = include_str!("wordlists/chinese_traditional.txt");
+const FRENCH: &str = include_str!("wordlists/french.txt");
+const ITALIAN: &str = include_str!("wordlists/italian.txt");
+const CZECH: &str = include_str!("wordlists/czech.txt");
+const PORTUGUESE: &str = include_str!("wordlists/portuguese.txt");
+
+const PBKDF2_ROUNDS: u32 = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum Language {
+    English,
+    Japanese,
+    Korean,
+    Spanish,
+    ChineseSimplified,
+    ChineseTraditional,
+    French,
+    Italian,
+    Czech,
+    Portuguese
+}
+
+impl Language {
+    pub fn all() -> Vec<Language> {
+        return vec![
+            Language::English, Language::Japanese, Language::Korean, Language::Spanish, Language::ChineseSimplified,
+            Language::ChineseTraditional, Language::French, Language::Italian, Language::Czech, Language::Portuguese
+        ];
+    }
+
+    pub fn wordlist(&self) -> Vec<&'static str> {
+        let words: &str = match self {
+            Language::English => ENGLISH,
+            Language::Japanese => JAPANESE,
+            Language::Korean => KOREAN,
+            Language::Spanish => SPANISH,
+            Language::ChineseSimplified => CHINESE_SIMPLIFIED,
+            Language::ChineseTraditional => CHINESE_TRADITIONAL,
+            Language::French => FRENCH,
+            Language::Italian => ITALIAN,
+            Language::Czech => CZECH,
+            Language::Portuguese => PORTUGUESE,
+        };
+        return words.lines().collect::<Vec<&str>>();
+    }
+
+    // japanese phrases are joined with the ideographic space
+    pub fn separator(&self) -> &'static str {
+        match self {
+            Language::Japanese => return "\u{3000}",
+            _ => return " ",
+        };
+    }
+}
+
+impl FromStr for Language {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Language, Self::Err> {
+        match input.to_lowercase().replace(['_', ' '], "-").as_str() {
+            "english" => Ok(Language::English),
+            "japanese" => Ok(Language::Japanese),
+            "korean" => Ok(Language::Korean),
+            "spanish" => Ok(Language::Spanish),
+            "chinese-simplified" => Ok(Language::ChineseSimplified),
+            "chinese-traditional" => Ok(Language::ChineseTraditional),
+            "french" => Ok(Language::French),
+            "italian" => Ok(Language::Italian),
+            "czech" => Ok(Language::Czech),
+            "portuguese" => Ok(Language::Portuguese),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Language::English => return write!(f, "English"),
+            Language::Japanese => return write!(f, "Japanese"),
+            Language::Korean => return write!(f, "Korean"),
+            Language::Spanish => return write!(f, "Spanish"),
+            Language::ChineseSimplified => return write!(f, "Chinese (Simplified)"),
+            Language::ChineseTraditional => return write!(f, "Chinese (Traditional)"),
+            Language::French => return write!(f, "French"),
+            Language::Italian => return write!(f, "Italian"),
+            Language::Czech => return write!(f, "Czech"),
+            Language::Portuguese => return write!(f, "Portuguese"),
+        };
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum Bip39Error {
+    EntropyLength(usize),
+    WordCount(usize),
+    UnknownWord(String),
+    Checksum
+}
+
+pub fn entropy_to_mnemonic(entropy: &[u8]) -> Result<String, Bip39Error> {
+    return entropy_to_mnemonic_in(entropy, Language::English);
+}
+
+pub fn entropy_to_mnemonic_in(entropy: &[u8], language: Language) -> Result<String, Bip39Error> {
+    if entropy.len() < 16 || entropy.len() > 32 || !entropy.len().is_multiple_of(4) {
+        return Err(Bip39Error::EntropyLength(entropy.len()));
+    }
+
+    let words: Vec<&str> = language.wordlist();
+    let check_sum: u8 = hashing::sha256(entropy)[0];
+    let check_sum_bits: usize = entropy.len() / 4;
+
+    // entropy bits followed by the first ENT/32 bits of its sha256
+    let mut bits: Vec<bool> = vec![];
+    for b in entropy.iter() {
+        (0..8).rev().for_each(|i| bits.push((b >> i) & 1 == 1));
+    }
+    (0..check_sum_bits).for_each(|i| bits.push((check_sum >> (7 - i)) & 1 == 1));
+
+    let mnemonic: Vec<&str> = bits.chunks(11)
+        .map(|chunk| chunk.iter().fold(0usize, |acc, bit| (acc << 1) | *bit as usize))
+        .map(|index| words[index])
+        .collect::<Vec<&str>>();
+
+    return Ok(join_words(&mnemonic, language));
+}
+
+// the wordlists are stored in NFKD, so phrases and passphrases are compared and
+// hashed in the same form; NFKD also maps the ideographic space to a plain one
+pub fn normalize(input: &str) -> String {
+    return input.nfkd().collect::<String>();
+}
+
+pub fn split_words(mnemonic: &str) -> Vec<String> {
+    return normalize(mnemonic).split_whitespace().map(String::from).collect::<Vec<String>>();
+}
+
+pub fn join_words(words: &[&str], language: Language) -> String {
+    return words.join(language.separator());
+}
+
+// picks the language whose wordlist holds every word, preferring one where the
+// checksum also holds (the chinese lists and english/french share some words)
+pub fn detect_language(mnemonic: &str) -> Result<Language, Bip39Error> {
+    let phrase: Vec<String> = split_words(mnemonic);
+    let mut candidates: Vec<Language> = vec![];
+    let mut best: Option<(usize, String)> = None;
+
+    for language in Language::all() {
+        let words: Vec<&str> = language.wordlist();
+        let unknown: Vec<&String> = phrase.iter().filter(|w: &&String| !words.contains(&w.as_str())).collect::<Vec<&String>>();
+
+        if unknown.is_empty() {
+            candidates.push(language);
+        }
+        else if best.as_ref().is_none_or(|(n, _)| unknown.len() < *n) {
+            best = Some((unknown.len(), unknown[0].clone()));
+        }
+    }
+
+    if let Some(l) = candidates.iter().find(|l: &&Language| mnemonic_to_entropy_in(mnemonic, **l).is_ok()) {
+        return Ok(*l);
+    }
+    match (candidates.first(), best) {
+        (Some(l), _) => return Ok(*l),
+        (None, Some((_, word))) => return Err(Bip39Error::UnknownWord(word)),
+        (None, None) => return Err(Bip39Error::WordCount(0)),
+    };
+}
+
+pub fn mnemonic_to_entropy(mnemonic: &str) -> Result<Vec<u8>, Bip39Error> {
+    let count: usize = split_words(mnemonic).len();
+    if !(12..=24).contains(&count) || !count.is_multiple_of(3) {
+        return Err(Bip39Error::WordCount(count));
+    }
+    return mnemonic_to_entropy_in(mnemonic, detect_language(mnemonic)?);
+}
+
+pub fn mnemonic_to_entropy_in(mnemonic: &str, language: Language) -> Result<Vec<u8>, Bip39Error> {
+    let words: Vec<&str> = language.wordlist();
+    let phrase: Vec<String> = split_words(mnemonic);
+
+    if phrase.len() < 12 || phrase.len() > 24 || !phrase.len().is_multiple_of(3) {
+        return Err(Bip39Error::WordCount(phrase.len()));
+    }
+
+    let mut indexes: Vec<usize> = vec![];
+    for word in phrase.iter() {
+        match words.iter().position(|w: &&str| w == word) {
+            Some(i) => indexes.push(i),
+            None => return Err(Bip39Error::UnknownWord(word.to_string()))
+        };
+    }
+    return indexes_to_entropy(&indexes);
+}
+
+// the 11-bit wordlist indexes of a phrase, checked against the trailing checksum bits
+pub fn indexes_to_entropy(indexes: &[usize]) -> Result<Vec<u8>, Bip39Error> {
+    if indexes.len() < 12 || indexes.len() > 24 || !indexes.len().is_multiple_of(3) {
+        return Err(Bip39Error::WordCount(indexes.len()));
+    }
+
+    let mut bits: Vec<bool> = vec![];
+    for index in indexes.iter() {
+        (0..11).rev().for_each(|i| bits.push((index >> i) & 1 == 1));
+    }
+
+    let check_sum_bits: usize = bits.len() / 33;
+    let entropy: Vec<u8> = bits[..bits.len() - check_sum_bits].chunks(8)
+        .map(|chunk| chunk.iter().fold(0u8, |acc, bit| (acc << 1) | *bit as u8))
+        .collect::<Vec<u8>>();
+
+    let check_sum: u8 = hashing::sha256(&entropy)[0];
+    let expected: bool = (0..check_sum_bits).all(|i| bits[bits.len() - check_sum_bits + i] == ((check_sum >> (7 - i)) & 1 == 1));
+
+    if !expected {
+        return Err(Bip39Error::Checksum);
+    }
+    return Ok(entropy);
+}
+
+pub fn validate_mnemonic(mnemonic: &str) -> bool {
+    return mnemonic_to_entropy(mnemonic).is_ok();
+}
+
+// words are rejoined with a single ascii space after NFKD, which is also what the
+// ideographic space of a japanese phrase normalises to
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> [u8; 64] {
+    let phrase: String = split_words(mnemonic).join(" ");
+    let salt: String = normalize(&format!("mnemonic{}", passphrase));
+
+    let mut seed: [u8; 64] = [0; 64];
+    pbkdf2_hmac::<Sha512>(phrase.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS, &mut seed);
+    return seed;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bip39::*;
+    use crate::base16;
+
+    const VECTORS: [(&str, &str, &str); 4] = [
+        (
+            "00000000000000000000000000000000",
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        ),
+        (
+            "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+            "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+            "f2b94508732bcbacbcc020faefecfc89feafa6649a5491b8c952cede496c214a0c7b3c392d168748f2d4a612bada0753b52a1c7ac53c1e93abd5c6320b9e95dd"
+        ),
+        (
+            "8080808080808080808080808080808080808080808080808080808080808080",
+            "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+            "c0c519bd0e91a2ed54357d9d1ebef6f5af218a153624cf4f2da911a0ed8f7a09e2ef61af0aca007096df430022f7a2b6fb91661a9589097069720d015e4e982f"
+        ),
+        (
+            "15da872c95a13dd738fbf50e427583ad61f18fd99f628c417a61cf8343c90419",
+            "beyond stage sleep clip because twist token leaf atom beauty genius food business side grid unable middle armed observe pair crouch tonight away coconut",
+            "b15509eaa2d09d3efd3e006ef42151b30367dc6e3aa5e44caba3fe4d3e352e65101fbdb86a96776b91946ff06f8eac594dc6ee1d3e82a42dfe1b40fef6bcc3fd"
+        )
+    ];
+
+    #[test]
+    fn bip39_entropy_to_mnemonic() {
+        for (entropy, mnemonic, _) in VECTORS.iter() {
+            assert_eq!(entropy_to_mnemonic(&base16::decode_string(entropy)).unwrap(), *mnemonic);
+        }
+    }
+
+    #[test]
+    fn bip39_mnemonic_to_entropy() {
+        for (entropy, mnemonic, _) in VECTORS.iter() {
+            assert_eq!(base16::encode_bytes(&mnemonic_to_entropy(mnemonic).unwrap()), *entropy);
+        }
+    }
+
+    #[test]
+    fn bip39_mnemonic_to_seed() {
+        for (_, mnemonic, seed) in VECTORS.iter() {
+            assert_eq!(base16::encode_bytes(&mnemonic_to_seed(mnemonic, "TREZOR")), *seed);
+        }
+    }
+
+    #[test]
+    fn bip39_invalid_mnemonics() {
+        assert_eq!(mnemonic_to_entropy("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon"), Err(Bip39Error::Checksum));
+        assert_eq!(mnemonic_to_entropy("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"), Err(Bip39Error::WordCount(11)));
+        assert_eq!(mnemonic_to_entropy("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abcdef"), Err(Bip39Error::UnknownWord(String::from("abcdef"))));
+    }
+
+    #[test]
+    fn bip39_normalisation() {
+        // NFC input against the NFKD french wordlist, the first word carries an accent
+        let entropy: Vec<u8> = base16::decode_string("02400000000000000000000000000000");
+        let mnemonic = entropy_to_mnemonic_in(&entropy, Language::French).unwrap();
+        let composed: String = mnemonic.nfc().collect::<String>();
+        assert_ne!(composed, mnemonic);
+        assert_eq!(mnemonic_to_entropy(&composed).unwrap(), entropy);
+        assert_eq!(mnemonic_to_seed(&composed, "caf\u{e9}"), mnemonic_to_seed(&mnemonic, "cafe\u{301}"));
+
+        assert_eq!(detect_language("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon qwerty"), Err(Bip39Error::UnknownWord(String::from("qwerty"))));
+        assert_eq!(join_words(&["a", "b"], Language::Japanese), "a\u{3000}b");
+    }
+}
@@ -0,0 +1,173 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use secp256k1::SecretKey;
+use crate::bip32::{ExtendedPrivateKey, HARDENED};
+use crate::bip39::{self, Language};
+use crate::app::Network;
+use crate::crypto::bitcoin;
+use crate::base16;
+
+pub const PURPOSE: u32 = 83696968;
+
+const APP_BIP39: u32 = 39;
+const APP_HD_SEED_WIF: u32 = 2;
+const APP_XPRV: u32 = 32;
+const APP_HEX: u32 = 128169;
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum Bip85Error {
+    InvalidIndex(u32),
+    InvalidWordCount(u32),
+    InvalidLength(u32),
+    InvalidKey,
+    // a BIP39 wordlist without a BIP85 language code
+    UnsupportedLanguage(Language)
+}
+
+pub fn language_code(language: Language) -> Result<u32, Bip85Error> {
+    match language {
+        Language::English => return Ok(0),
+        Language::Japanese => return Ok(1),
+        Language::Korean => return Ok(2),
+        Language::Spanish => return Ok(3),
+        Language::ChineseSimplified => return Ok(4),
+        Language::ChineseTraditional => return Ok(5),
+        Language::French => return Ok(6),
+        Language::Italian => return Ok(7),
+        Language::Czech => return Ok(8),
+        Language::Portuguese => return Err(Bip85Error::UnsupportedLanguage(language)),
+    };
+}
+
+// every application path is m/83696968'/{app}'/.../{index}', all hardened
+fn hardened_path(indexes: &[u32]) -> Result<Vec<u32>, Bip85Error> {
+    let mut path: Vec<u32> = vec![PURPOSE | HARDENED];
+    for i in indexes.iter() {
+        if i & HARDENED != 0 {
+            return Err(Bip85Error::InvalidIndex(*i));
+        }
+        path.push(i | HARDENED);
+    }
+    return Ok(path);
+}
+
+pub fn derive_entropy(master: &ExtendedPrivateKey, path: &[u32]) -> [u8; 64] {
+    let key: ExtendedPrivateKey = master.derive_path(path);
+
+    let mut mac = Hmac::<Sha512>::new_from_slice(b"bip-entropy-from-k").expect("hmac-key");
+    mac.update(&key.private_key);
+
+    let mut r: [u8; 64] = [0; 64];
+    r.copy_from_slice(&mac.finalize().into_bytes());
+    return r;
+}
+
+pub fn to_mnemonic(master: &ExtendedPrivateKey, language: Language, words: u32, index: u32) -> Result<String, Bip85Error> {
+    let length: usize = match words {
+        12 => 16,
+        18 => 24,
+        24 => 32,
+        _ => return Err(Bip85Error::InvalidWordCount(words))
+    };
+    let path: Vec<u32> = hardened_path(&[APP_BIP39, language_code(language)?, words, index])?;
+    let entropy: [u8; 64] = derive_entropy(master, &path);
+
+    return Ok(bip39::entropy_to_mnemonic_in(&entropy[..length], language).expect("entropy"));
+}
+
+pub fn to_hex(master: &ExtendedPrivateKey, num_bytes: u32, index: u32) -> Result<String, Bip85Error> {
+    if !(16..=64).contains(&num_bytes) {
+        return Err(Bip85Error::InvalidLength(num_bytes));
+    }
+    let path: Vec<u32> = hardened_path(&[APP_HEX, num_bytes, index])?;
+    let entropy: [u8; 64] = derive_entropy(master, &path);
+
+    return Ok(base16::encode_bytes(&entropy[..num_bytes as usize]));
+}
+
+pub fn to_wif(master: &ExtendedPrivateKey, index: u32) -> Result<String, Bip85Error> {
+    let path: Vec<u32> = hardened_path(&[APP_HD_SEED_WIF, index])?;
+    let entropy: [u8; 64] = derive_entropy(master, &path);
+
+    if SecretKey::from_slice(&entropy[..32]).is_err() {
+        return Err(Bip85Error::InvalidKey);
+    }
+    return Ok(bitcoin::encode_compressed_pr_key(&entropy[..32].try_into().expect("private-key"), Network::Bitcoin));
+}
+
+// the first half of the entropy is the chain code, the second half the key
+pub fn to_xprv(master: &ExtendedPrivateKey, index: u32) -> Result<ExtendedPrivateKey, Bip85Error> {
+    let path: Vec<u32> = hardened_path(&[APP_XPRV, index])?;
+    let entropy: [u8; 64] = derive_entropy(master, &path);
+
+    if SecretKey::from_slice(&entropy[32..]).is_err() {
+        return Err(Bip85Error::InvalidKey);
+    }
+
+    let mut key = ExtendedPrivateKey {
+        depth: 0,
+        parent_fingerprint: [0; 4],
+        child_number: 0,
+        chain_code: [0; 32],
+        private_key: [0; 32],
+        network: master.network
+    };
+    key.chain_code.copy_from_slice(&entropy[..32]);
+    key.private_key.copy_from_slice(&entropy[32..]);
+    return Ok(key);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::bip85::*;
+    use crate::bip32::{ExtendedPrivateKey, parse_path};
+    use crate::base16;
+
+    fn master() -> ExtendedPrivateKey {
+        return ExtendedPrivateKey::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+    }
+
+    #[test]
+    fn bip85_derive_entropy() {
+        let entropy = derive_entropy(&master(), &parse_path("m/83696968'/0'/0'").unwrap());
+        assert_eq!(base16::encode_bytes(&entropy), "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f00b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7");
+
+        let entropy = derive_entropy(&master(), &parse_path("m/83696968'/0'/1'").unwrap());
+        assert_eq!(base16::encode_bytes(&entropy), "70c6e3e8ebee8dc4c0dbba66076819bb8c09672527c4277ca8729532ad711872218f826919f6b67218adde99018a6df9095ab2b58d803b5b93ec9802085a690e");
+    }
+
+    #[test]
+    fn bip85_mnemonic() {
+        assert_eq!(to_mnemonic(&master(), Language::English, 12, 0).unwrap(), "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose");
+        assert_eq!(to_mnemonic(&master(), Language::English, 18, 0).unwrap(), "near account window bike charge season chef number sketch tomorrow excuse sniff circle vital hockey outdoor supply token");
+        assert_eq!(to_mnemonic(&master(), Language::English, 24, 0).unwrap(), "puppy ocean match cereal symbol another shed magic wrap hammer bulb intact gadget divorce twin tonight reason outdoor destroy simple truth cigar social volcano");
+        assert_eq!(to_mnemonic(&master(), Language::English, 15, 0), Err(Bip85Error::InvalidWordCount(15)));
+
+        let japanese = to_mnemonic(&master(), Language::Japanese, 12, 0).unwrap();
+        assert_eq!(japanese.split('\u{3000}').count(), 12);
+
+        assert_eq!(language_code(Language::Czech), Ok(8));
+        assert_eq!(to_mnemonic(&master(), Language::Portuguese, 12, 0), Err(Bip85Error::UnsupportedLanguage(Language::Portuguese)));
+    }
+
+    #[test]
+    fn bip85_hex() {
+        assert_eq!(to_hex(&master(), 64, 0).unwrap(), "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f878555d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c");
+        assert_eq!(to_hex(&master(), 35, 0).unwrap().len(), 70);
+        assert_eq!(to_hex(&master(), 15, 0), Err(Bip85Error::InvalidLength(15)));
+        assert_eq!(to_hex(&master(), 65, 0), Err(Bip85Error::InvalidLength(65)));
+    }
+
+    #[test]
+    fn bip85_wif() {
+        assert_eq!(to_wif(&master(), 0).unwrap(), "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp");
+        assert_eq!(to_wif(&master(), 0x80000001), Err(Bip85Error::InvalidIndex(0x80000001)));
+    }
+
+    #[test]
+    fn bip85_xprv() {
+        assert_eq!(to_xprv(&master(), 0).unwrap().to_xprv(), "xprv9s21ZrQH143K2srSbCSg4m4kLvPMzcWydgmKEnMmoZUurYuBuYG46c6P71UGXMzmriLzCCBvKQWBUv3vPB3m1SATMhp3uEjXHJ42jFg7myX");
+    }
+}
@@ -0,0 +1,132 @@
+use crate::base16;
+use crate::bytes::{self, Reader, ReadError};
+use crate::crypto::hashing;
+use crate::transaction::{Transaction, TransactionError};
+
+pub const HEADER_SIZE: usize = 80;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum BlockError {
+    InvalidHex,
+    Read(ReadError),
+    Transaction(TransactionError),
+    TrailingBytes(usize)
+}
+
+impl From<ReadError> for BlockError {
+    fn from(e: ReadError) -> Self {
+        return BlockError::Read(e);
+    }
+}
+
+impl From<TransactionError> for BlockError {
+    fn from(e: TransactionError) -> Self {
+        return BlockError::Transaction(e);
+    }
+}
+
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, BlockError> {
+    let s: String = s.trim().to_lowercase();
+    if !s.len().is_multiple_of(2) || !s.chars().all(|c: char| c.is_ascii_hexdigit()) {
+        return Err(BlockError::InvalidHex);
+    }
+    return Ok(base16::decode_string(&s));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub struct BlockHeader {
+    pub version: i32,
+    // hashes in internal byte order
+    pub prev_blockhash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    // the compact encoding of the proof of work target
+    pub bits: u32,
+    pub nonce: u32
+}
+
+impl BlockHeader {
+    pub fn read(reader: &mut Reader) -> Result<Self, ReadError> {
+        return Ok(Self {
+            version: reader.read_u32()? as i32,
+            prev_blockhash: reader.read_array::<32>()?,
+            merkle_root: reader.read_array::<32>()?,
+            time: reader.read_u32()?,
+            bits: reader.read_u32()?,
+            nonce: reader.read_u32()?
+        });
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut r: Vec<u8> = self.version.to_le_bytes().to_vec();
+        r.extend_from_slice(&self.prev_blockhash);
+        r.extend_from_slice(&self.merkle_root);
+        r.extend_from_slice(&self.time.to_le_bytes());
+        r.extend_from_slice(&self.bits.to_le_bytes());
+        r.extend_from_slice(&self.nonce.to_le_bytes());
+        return r;
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, BlockError> {
+        let mut reader: Reader = Reader::new(data);
+        let header: BlockHeader = BlockHeader::read(&mut reader)?;
+        if reader.remaining() > 0 {
+            return Err(BlockError::TrailingBytes(reader.remaining()));
+        }
+        return Ok(header);
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, BlockError> {
+        return BlockHeader::deserialize(&decode_hex(s)?);
+    }
+
+    // internal byte order; see transaction::hash_to_hex for the usual display
+    pub fn block_hash(&self) -> [u8; 32] {
+        return hashing::sha256d(&self.serialize());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct Block {
+    pub header: BlockHeader,
+    pub transactions: Vec<Transaction>
+}
+
+impl Block {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut r: Vec<u8> = self.header.serialize();
+        r.extend(bytes::compact_size(self.transactions.len() as u64));
+        for tx in self.transactions.iter() {
+            r.extend(tx.serialize());
+        }
+        return r;
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, BlockError> {
+        let mut reader: Reader = Reader::new(data);
+        let header: BlockHeader = BlockHeader::read(&mut reader)?;
+        let mut transactions: Vec<Transaction> = vec![];
+        for _ in 0..reader.read_compact_size()? {
+            transactions.push(Transaction::read(&mut reader)?);
+        }
+        if reader.remaining() > 0 {
+            return Err(BlockError::TrailingBytes(reader.remaining()));
+        }
+        return Ok(Self { header, transactions });
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, BlockError> {
+        return Block::deserialize(&decode_hex(s)?);
+    }
+
+    pub fn block_hash(&self) -> [u8; 32] {
+        return self.header.block_hash();
+    }
+
+    pub fn txids(&self) -> Vec<[u8; 32]> {
+        return self.transactions.iter().map(|tx: &Transaction| tx.txid()).collect();
+    }
+}
@@ -2,4 +2,105 @@ pub fn bytes_to_binary(i: &[u8; 32], r: &mut Vec<u8>) {
     for m in i.iter() {
         format!("{:8b}", m).chars().for_each(|b| if b == '1' { r.push(1); } else { r.push(0); } );
     }
-}
\ No newline at end of file
+}
+// bitcoin's variable length integer prefix
+pub fn compact_size(n: u64) -> Vec<u8> {
+    if n < 0xfd {
+        return vec![n as u8];
+    }
+    if n <= 0xffff {
+        let mut r: Vec<u8> = vec![0xfd];
+        r.extend_from_slice(&(n as u16).to_le_bytes());
+        return r;
+    }
+    if n <= 0xffffffff {
+        let mut r: Vec<u8> = vec![0xfe];
+        r.extend_from_slice(&(n as u32).to_le_bytes());
+        return r;
+    }
+    let mut r: Vec<u8> = vec![0xff];
+    r.extend_from_slice(&n.to_le_bytes());
+    return r;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum ReadError {
+    // offset at which more bytes were needed
+    UnexpectedEnd(usize),
+    NonMinimalCompactSize(usize)
+}
+
+// little-endian cursor over consensus-serialized data
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pub position: usize
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        return Self { data, position: 0 };
+    }
+
+    pub fn remaining(&self) -> usize {
+        return self.data.len() - self.position;
+    }
+
+    pub fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], ReadError> {
+        if self.remaining() < n {
+            return Err(ReadError::UnexpectedEnd(self.position));
+        }
+        let r: &'a [u8] = &self.data[self.position..self.position + n];
+        self.position += n;
+        return Ok(r);
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        let mut r: [u8; N] = [0; N];
+        r.copy_from_slice(self.read_bytes(N)?);
+        return Ok(r);
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, ReadError> {
+        return Ok(self.read_bytes(1)?[0]);
+    }
+
+    pub fn peek_u8(&self) -> Result<u8, ReadError> {
+        return self.data.get(self.position).copied().ok_or(ReadError::UnexpectedEnd(self.position));
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, ReadError> {
+        return Ok(u16::from_le_bytes(self.read_array::<2>()?));
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, ReadError> {
+        return Ok(u32::from_le_bytes(self.read_array::<4>()?));
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, ReadError> {
+        return Ok(u64::from_le_bytes(self.read_array::<8>()?));
+    }
+
+    pub fn read_compact_size(&mut self) -> Result<u64, ReadError> {
+        let start: usize = self.position;
+        let (n, min): (u64, u64) = match self.read_u8()? {
+            0xfd => (self.read_u16()? as u64, 0xfd),
+            0xfe => (self.read_u32()? as u64, 0x10000),
+            0xff => (self.read_u64()?, 0x100000000),
+            b => return Ok(b as u64)
+        };
+        if n < min {
+            return Err(ReadError::NonMinimalCompactSize(start));
+        }
+        return Ok(n);
+    }
+
+    // a compact size length followed by that many bytes
+    pub fn read_var_bytes(&mut self) -> Result<&'a [u8], ReadError> {
+        let n: u64 = self.read_compact_size()?;
+        if n > self.remaining() as u64 {
+            return Err(ReadError::UnexpectedEnd(self.position));
+        }
+        return self.read_bytes(n as usize);
+    }
+}
@@ -0,0 +1,647 @@
+use std::cmp::Reverse;
+use std::fmt;
+use std::str::FromStr;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::bytes;
+use crate::crypto::hashing;
+use crate::psbt::Psbt;
+use crate::script::{Script, ScriptType};
+use crate::transaction::{OutPoint, Transaction, TxIn, TxOut, MAX_MONEY, SEQUENCE_FINAL};
+
+// bitcoin core's limits for the branch and bound search and the knapsack approximation
+const BNB_TRIES: usize = 100000;
+const KNAPSACK_ITERATIONS: usize = 1000;
+
+// bitcoin core's -dustrelayfee, in sat/vB
+const DUST_RELAY_FEE_RATE: u64 = 3;
+
+pub const SEQUENCE_RBF: u32 = 0xfffffffd;
+// final for replacement but still enforcing the locktime
+pub const SEQUENCE_LOCKTIME: u32 = 0xfffffffe;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum Algorithm {
+    BranchAndBound,
+    Knapsack,
+    LargestFirst
+}
+
+impl FromStr for Algorithm {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Algorithm, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "bnb" | "branch-and-bound" => Ok(Algorithm::BranchAndBound),
+            "knapsack" => Ok(Algorithm::Knapsack),
+            "largest-first" => Ok(Algorithm::LargestFirst),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Algorithm::BranchAndBound => return write!(f, "Branch and bound"),
+            Algorithm::Knapsack => return write!(f, "Knapsack"),
+            Algorithm::LargestFirst => return write!(f, "Largest first"),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub txout: TxOut,
+    // the transaction creating a P2PKH or P2PK output, which signers need for its amount
+    pub previous_tx: Option<Transaction>,
+    // the P2WPKH program behind a P2SH output, the only kind of P2SH that can be funded from
+    pub redeem_script: Option<Script>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+
+pub struct FeePolicy {
+    // sat/vB
+    pub fee_rate: f64,
+    // the rate expected when the change is spent later, which weighs the waste of a selection
+    pub long_term_fee_rate: f64,
+    pub rbf: bool,
+    // the chain tip, from which the locktime discourages fee sniping; locktime 0 without it
+    pub height: Option<u32>
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum CoinSelectionError {
+    NoPayments,
+    // the utxo at this index is of a type whose spending size is unknown
+    UnsupportedScript(usize),
+    // a legacy utxo without the transaction that created it, or with another one
+    MissingPreviousTx(usize),
+    PreviousTxMismatch(usize),
+    // not a finite, non-negative number of sat/vB
+    InvalidFeeRate,
+    // the payment at this index is below the dust threshold of its script
+    DustOutput(usize),
+    // the utxo or payment at this index, or all payments together, above 21 million bitcoin
+    UtxoAboveMaxMoney(usize),
+    PaymentAboveMaxMoney(usize),
+    PaymentsAboveMaxMoney,
+    // the effective value of the utxos and the amount needed
+    InsufficientFunds(u64, u64),
+    // branch and bound found no selection that avoids change
+    NoSolution
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct Selection {
+    pub psbt: Psbt,
+    pub selected: Vec<Utxo>,
+    pub fee: u64,
+    pub vsize: usize,
+    // the index of the change output, if one was worth creating
+    pub change: Option<usize>
+}
+
+// a utxo with the fees of spending it now and at the long term rate
+#[derive(Debug, Clone)]
+
+struct Candidate {
+    index: usize,
+    effective_value: i64,
+    fee: i64,
+    long_term_fee: i64
+}
+
+// the scriptSig and witness sizes assume 72-byte signatures and compressed keys, and P2SH is taken to be
+// P2SH-P2WPKH, the only kind fund accepts
+fn spend_sizes(script_pubkey: &Script) -> Option<(usize, usize)> {
+    match script_pubkey.script_type() {
+        ScriptType::P2pk(_) => return Some((1 + 72, 0)),
+        ScriptType::P2pkh(_) => return Some((1 + 72 + 1 + 33, 0)),
+        ScriptType::P2sh(_) => return Some((1 + 22, 1 + 1 + 72 + 1 + 33)),
+        ScriptType::P2wpkh(_) => return Some((0, 1 + 1 + 72 + 1 + 33)),
+        // key path, SIGHASH_DEFAULT
+        ScriptType::P2tr(_) => return Some((0, 1 + 1 + 64)),
+        _ => return None,
+    };
+}
+
+// outpoint, scriptSig, sequence and witness of an input spending this script, in weight units
+pub fn input_weight(script_pubkey: &Script) -> Option<usize> {
+    let (script_sig, witness) = spend_sizes(script_pubkey)?;
+    return Some((36 + bytes::compact_size(script_sig as u64).len() + script_sig + 4) * 4 + witness);
+}
+
+pub fn output_weight(script_pubkey: &Script) -> usize {
+    let len: usize = script_pubkey.bytes.len();
+    return (8 + bytes::compact_size(len as u64).len() + len) * 4;
+}
+
+fn is_segwit(script_pubkey: &Script) -> bool {
+    return spend_sizes(script_pubkey).map(|(_, witness)| witness > 0).unwrap_or(false);
+}
+
+// the weight of a signed transaction spending and creating these scripts
+pub fn estimate_weight(inputs: &[Script], outputs: &[Script]) -> Option<usize> {
+    let mut weight: usize = (8 + bytes::compact_size(inputs.len() as u64).len() + bytes::compact_size(outputs.len() as u64).len()) * 4;
+    for script in inputs.iter() {
+        weight += input_weight(script)?;
+    }
+    weight += outputs.iter().map(output_weight).sum::<usize>();
+    // marker and flag, and an empty witness for every input without one
+    if inputs.iter().any(is_segwit) {
+        weight += 2 + inputs.iter().filter(|s: &&Script| !is_segwit(s)).count();
+    }
+    return Some(weight);
+}
+
+pub fn estimate_vsize(inputs: &[Script], outputs: &[Script]) -> Option<usize> {
+    return estimate_weight(inputs, outputs).map(|w: usize| w.div_ceil(4));
+}
+
+fn fee_for_weight(weight: usize, fee_rate: f64) -> u64 {
+    return (weight as f64 * fee_rate / 4.0).ceil() as u64;
+}
+
+fn fee_for_vsize(vsize: usize, fee_rate: f64) -> u64 {
+    return (vsize as f64 * fee_rate).ceil() as u64;
+}
+
+// the smallest output worth relaying: one that costs a third of its value to create and spend at the dust relay rate
+pub fn dust_threshold(script_pubkey: &Script) -> u64 {
+    if script_pubkey.bytes.first() == Some(&0x6a) {
+        return 0;
+    }
+    let spend: usize = if script_pubkey.witness_program().is_some() { 32 + 4 + 1 + 107 / 4 + 4 } else { 32 + 4 + 1 + 107 + 4 };
+    return (output_weight(script_pubkey) / 4 + spend) as u64 * DUST_RELAY_FEE_RATE;
+}
+
+// bitcoin core's search for a changeless selection with the least waste, over candidates sorted by descending effective value
+fn branch_and_bound(pool: &[Candidate], target: i64, cost_of_change: i64, fee_rate_high: bool) -> Option<Vec<usize>> {
+    let mut available: i64 = pool.iter().map(|c: &Candidate| c.effective_value).sum();
+    if available < target {
+        return None;
+    }
+    let mut value: i64 = 0;
+    let mut waste: i64 = 0;
+    let mut selection: Vec<usize> = vec![];
+    let mut best: Option<Vec<usize>> = None;
+    let mut best_waste: i64 = i64::MAX;
+    let mut index: usize = 0;
+    for _ in 0..BNB_TRIES {
+        let mut backtrack: bool = false;
+        if value + available < target || value > target + cost_of_change || (waste > best_waste && fee_rate_high) {
+            backtrack = true;
+        }
+        else if value >= target {
+            // the excess is lost to fees
+            if waste + value - target <= best_waste {
+                best = Some(selection.clone());
+                best_waste = waste + value - target;
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            let last: usize = match selection.last() {
+                Some(last) => *last,
+                None => break
+            };
+            // put the skipped candidates back and try the branch without the last one included
+            index -= 1;
+            while index > last {
+                available += pool[index].effective_value;
+                index -= 1;
+            }
+            value -= pool[index].effective_value;
+            waste -= pool[index].fee - pool[index].long_term_fee;
+            selection.pop();
+        }
+        else {
+            let candidate: &Candidate = &pool[index];
+            available -= candidate.effective_value;
+            // a candidate equal to an omitted predecessor would only repeat that branch
+            if selection.last().map(|l: &usize| *l + 1 == index).unwrap_or(true)
+                || candidate.effective_value != pool[index - 1].effective_value
+                || candidate.fee != pool[index - 1].fee {
+                selection.push(index);
+                value += candidate.effective_value;
+                waste += candidate.fee - candidate.long_term_fee;
+            }
+        }
+        index += 1;
+    }
+    return best.map(|b: Vec<usize>| b.iter().map(|i: &usize| pool[*i].index).collect());
+}
+
+// random subsets of the values, keeping the smallest sum reaching the target
+fn approximate_best_subset<R: Rng>(values: &[i64], total: i64, target: i64, rng: &mut R) -> (Vec<bool>, i64) {
+    let mut best: Vec<bool> = vec![true; values.len()];
+    let mut best_value: i64 = total;
+    for _ in 0..KNAPSACK_ITERATIONS {
+        if best_value == target {
+            break;
+        }
+        let mut included: Vec<bool> = vec![false; values.len()];
+        let mut sum: i64 = 0;
+        let mut reached: bool = false;
+        // first a random half, then whatever is left
+        for pass in 0..2 {
+            if reached {
+                break;
+            }
+            for i in 0..values.len() {
+                let take: bool = if pass == 0 { rng.gen_bool(0.5) } else { !included[i] };
+                if !take {
+                    continue;
+                }
+                sum += values[i];
+                included[i] = true;
+                if sum >= target {
+                    reached = true;
+                    if sum < best_value {
+                        best_value = sum;
+                        best = included.clone();
+                    }
+                    sum -= values[i];
+                    included[i] = false;
+                }
+            }
+        }
+    }
+    return (best, best_value);
+}
+
+// bitcoin core's knapsack solver, where min_change is the least change worth creating
+fn knapsack<R: Rng>(pool: &[Candidate], target: i64, min_change: i64, rng: &mut R) -> Option<Vec<usize>> {
+    let mut shuffled: Vec<&Candidate> = pool.iter().collect();
+    shuffled.shuffle(rng);
+
+    let mut smaller: Vec<&Candidate> = vec![];
+    let mut lowest_larger: Option<&Candidate> = None;
+    for candidate in shuffled {
+        if candidate.effective_value == target {
+            return Some(vec![candidate.index]);
+        }
+        if candidate.effective_value < target + min_change {
+            smaller.push(candidate);
+        }
+        else if lowest_larger.map(|l: &Candidate| candidate.effective_value < l.effective_value).unwrap_or(true) {
+            lowest_larger = Some(candidate);
+        }
+    }
+
+    let total: i64 = smaller.iter().map(|c: &&Candidate| c.effective_value).sum();
+    if total == target {
+        return Some(smaller.iter().map(|c: &&Candidate| c.index).collect());
+    }
+    if total < target {
+        return lowest_larger.map(|l: &Candidate| vec![l.index]);
+    }
+
+    smaller.sort_by_key(|c: &&Candidate| Reverse(c.effective_value));
+    let values: Vec<i64> = smaller.iter().map(|c: &&Candidate| c.effective_value).collect();
+    let (mut best, mut best_value) = approximate_best_subset(&values, total, target, rng);
+    if best_value != target && total >= target + min_change {
+        (best, best_value) = approximate_best_subset(&values, total, target + min_change, rng);
+    }
+
+    // a single larger coin beats a subset that leaves too little change or more value
+    if let Some(larger) = lowest_larger {
+        if (best_value != target && best_value < target + min_change) || larger.effective_value <= best_value {
+            return Some(vec![larger.index]);
+        }
+    }
+    return Some(smaller.iter().zip(best.iter()).filter(|(_, b)| **b).map(|(c, _)| c.index).collect());
+}
+
+fn largest_first(pool: &[Candidate], target: i64) -> Option<Vec<usize>> {
+    let mut selection: Vec<usize> = vec![];
+    let mut value: i64 = 0;
+    for candidate in pool.iter() {
+        if value >= target {
+            break;
+        }
+        selection.push(candidate.index);
+        value += candidate.effective_value;
+    }
+    if value < target {
+        return None;
+    }
+    return Some(selection);
+}
+
+// bitcoin core's locktime: the tip height, sometimes up to 100 blocks earlier so that delayed broadcasts blend in
+pub fn anti_fee_sniping_locktime<R: Rng>(height: u32, rng: &mut R) -> u32 {
+    if rng.gen_range(0..10) == 0 {
+        return height.saturating_sub(rng.gen_range(0..100));
+    }
+    return height;
+}
+
+// what a signer needs besides the output: the previous transaction of a legacy output, the redeem script of a P2SH one
+fn check_utxo(utxo: &Utxo, index: usize) -> Result<(), CoinSelectionError> {
+    if utxo.txout.value > MAX_MONEY {
+        return Err(CoinSelectionError::UtxoAboveMaxMoney(index));
+    }
+    match utxo.txout.script_pubkey.script_type() {
+        ScriptType::P2pkh(_) | ScriptType::P2pk(_) => {
+            let previous: &Transaction = utxo.previous_tx.as_ref().ok_or(CoinSelectionError::MissingPreviousTx(index))?;
+            if previous.txid() != utxo.outpoint.txid || previous.outputs.get(utxo.outpoint.vout as usize) != Some(&utxo.txout) {
+                return Err(CoinSelectionError::PreviousTxMismatch(index));
+            }
+        },
+        ScriptType::P2sh(hash) => {
+            let redeem_script: &Script = utxo.redeem_script.as_ref().ok_or(CoinSelectionError::UnsupportedScript(index))?;
+            if hashing::hash160(&redeem_script.bytes)[..] != hash[..] || !matches!(redeem_script.script_type(), ScriptType::P2wpkh(_)) {
+                return Err(CoinSelectionError::UnsupportedScript(index));
+            }
+        },
+        _ => {}
+    };
+    return Ok(());
+}
+
+pub fn fund(utxos: &[Utxo], payments: &[TxOut], change_script: &Script, policy: &FeePolicy, algorithm: Algorithm) -> Result<Selection, CoinSelectionError> {
+    return fund_with_rng(utxos, payments, change_script, policy, algorithm, &mut rand::thread_rng());
+}
+
+// selects utxos paying for the outputs and the fee, adds change above the dust threshold and wraps the unsigned transaction in a PSBT
+pub fn fund_with_rng<R: Rng>(utxos: &[Utxo], payments: &[TxOut], change_script: &Script, policy: &FeePolicy, algorithm: Algorithm, rng: &mut R) -> Result<Selection, CoinSelectionError> {
+    if payments.is_empty() {
+        return Err(CoinSelectionError::NoPayments);
+    }
+    if let Some(i) = payments.iter().position(|p: &TxOut| p.value < dust_threshold(&p.script_pubkey)) {
+        return Err(CoinSelectionError::DustOutput(i));
+    }
+    if let Some(i) = payments.iter().position(|p: &TxOut| p.value > MAX_MONEY) {
+        return Err(CoinSelectionError::PaymentAboveMaxMoney(i));
+    }
+    // checked before the effective values and the target are worked out in i64
+    let payment_value: u64 = payments.iter().try_fold(0, |total: u64, p: &TxOut| total.checked_add(p.value)).filter(|v: &u64| *v <= MAX_MONEY).ok_or(CoinSelectionError::PaymentsAboveMaxMoney)?;
+
+    if [policy.fee_rate, policy.long_term_fee_rate].iter().any(|r: &f64| !r.is_finite() || *r < 0.0) {
+        return Err(CoinSelectionError::InvalidFeeRate);
+    }
+
+    let mut pool: Vec<Candidate> = vec![];
+    for (i, utxo) in utxos.iter().enumerate() {
+        check_utxo(utxo, i)?;
+        let mut weight: usize = input_weight(&utxo.txout.script_pubkey).ok_or(CoinSelectionError::UnsupportedScript(i))?;
+        // counted as if the transaction had a witness
+        if !is_segwit(&utxo.txout.script_pubkey) {
+            weight += 1;
+        }
+        let fee: i64 = fee_for_weight(weight, policy.fee_rate) as i64;
+        let candidate: Candidate = Candidate {
+            index: i,
+            effective_value: utxo.txout.value as i64 - fee,
+            fee,
+            long_term_fee: fee_for_weight(weight, policy.long_term_fee_rate) as i64
+        };
+        // coins costing more to spend than they are worth are left out
+        if candidate.effective_value > 0 {
+            pool.push(candidate);
+        }
+    }
+    pool.sort_by_key(|c: &Candidate| Reverse(c.effective_value));
+
+    // the outputs and the fixed part of the transaction, with the marker and flag and room for rounding up to whole vbytes
+    let payment_scripts: Vec<Script> = payments.iter().map(|p: &TxOut| p.script_pubkey.clone()).collect();
+    let fixed_weight: usize = estimate_weight(&[], &payment_scripts).expect("weight") + 2 + 3;
+    let target: i64 = payment_value as i64 + fee_for_weight(fixed_weight, policy.fee_rate) as i64;
+    let available: i64 = pool.iter().map(|c: &Candidate| c.effective_value).sum();
+    if available < target {
+        return Err(CoinSelectionError::InsufficientFunds(available as u64, target as u64));
+    }
+
+    let change_fee: i64 = fee_for_weight(output_weight(change_script), policy.fee_rate) as i64;
+    let cost_of_change: i64 = change_fee + input_weight(change_script).map(|w: usize| fee_for_weight(w, policy.long_term_fee_rate)).unwrap_or(0) as i64;
+    let selected: Vec<usize> = match algorithm {
+        Algorithm::BranchAndBound => branch_and_bound(&pool, target, cost_of_change, policy.fee_rate > policy.long_term_fee_rate),
+        Algorithm::Knapsack => knapsack(&pool, target, cost_of_change + dust_threshold(change_script) as i64, rng),
+        Algorithm::LargestFirst => largest_first(&pool, target)
+    }.ok_or(CoinSelectionError::NoSolution)?;
+    let selected: Vec<Utxo> = selected.iter().map(|i: &usize| utxos[*i].clone()).collect();
+
+    let lock_time: u32 = policy.height.map(|h: u32| anti_fee_sniping_locktime(h, rng)).unwrap_or(0);
+    let sequence: u32 = match (policy.rbf, lock_time) {
+        (true, _) => SEQUENCE_RBF,
+        (false, 0) => SEQUENCE_FINAL,
+        (false, _) => SEQUENCE_LOCKTIME
+    };
+    let mut tx: Transaction = Transaction::new(2, lock_time);
+    for utxo in selected.iter() {
+        let mut input: TxIn = TxIn::new(utxo.outpoint);
+        input.sequence = sequence;
+        tx.inputs.push(input);
+    }
+    tx.outputs = payments.to_vec();
+
+    let input_scripts: Vec<Script> = selected.iter().map(|u: &Utxo| u.txout.script_pubkey.clone()).collect();
+    let input_value: u64 = selected.iter().map(|u: &Utxo| u.txout.value).sum();
+    let mut vsize: usize = estimate_vsize(&input_scripts, &payment_scripts).expect("vsize");
+    let mut fee: u64 = input_value - payment_value;
+
+    // change goes at a random position, and is dropped to the fee when it would be dust
+    let mut change: Option<usize> = None;
+    let mut with_change: Vec<Script> = payment_scripts.clone();
+    with_change.push(change_script.clone());
+    let change_vsize: usize = estimate_vsize(&input_scripts, &with_change).expect("vsize");
+    let change_value: i64 = fee as i64 - fee_for_vsize(change_vsize, policy.fee_rate) as i64;
+    if algorithm != Algorithm::BranchAndBound && change_value >= dust_threshold(change_script) as i64 {
+        let position: usize = rng.gen_range(0..=tx.outputs.len());
+        tx.outputs.insert(position, TxOut { value: change_value as u64, script_pubkey: change_script.clone() });
+        change = Some(position);
+        vsize = change_vsize;
+        fee -= change_value as u64;
+    }
+
+    // spent amounts are only known for segwit inputs, the others need the whole previous transaction
+    let mut psbt: Psbt = Psbt::from_unsigned_tx(tx).expect("unsigned");
+    for (input, utxo) in psbt.inputs.iter_mut().zip(selected.iter()) {
+        if is_segwit(&utxo.txout.script_pubkey) {
+            input.witness_utxo = Some(utxo.txout.clone());
+        }
+        else {
+            input.non_witness_utxo = utxo.previous_tx.clone();
+        }
+        input.redeem_script = utxo.redeem_script.clone();
+    }
+    return Ok(Selection { psbt, selected, fee, vsize, change });
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::base16;
+    use crate::coinselect::*;
+
+    fn p2wpkh(tag: u8) -> Script {
+        let mut bytes: Vec<u8> = vec![0x00, 0x14];
+        bytes.extend_from_slice(&[tag; 20]);
+        return Script::from_bytes(&bytes);
+    }
+
+    fn utxos(values: &[u64]) -> Vec<Utxo> {
+        return values.iter().enumerate().map(|(i, v): (usize, &u64)| Utxo {
+            outpoint: OutPoint::new([i as u8 + 1; 32], 0),
+            txout: TxOut { value: *v, script_pubkey: p2wpkh(1) },
+            previous_tx: None,
+            redeem_script: None
+        }).collect();
+    }
+
+    fn policy(fee_rate: f64) -> FeePolicy {
+        return FeePolicy { fee_rate, long_term_fee_rate: fee_rate, rbf: true, height: None };
+    }
+
+    #[test]
+    fn coinselect_weights() {
+        let p2pkh: Script = Script::from_hex("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").unwrap();
+        let p2sh: Script = Script::from_hex("a914bcfeb728b584253d5f3f70bcb780e9ef218a68f487").unwrap();
+        let p2tr: Script = Script::from_hex("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c").unwrap();
+        assert_eq!((input_weight(&p2pkh), input_weight(&p2sh), input_weight(&p2wpkh(1)), input_weight(&p2tr)), (Some(592), Some(364), Some(272), Some(230)));
+        assert_eq!((output_weight(&p2pkh), output_weight(&p2sh), output_weight(&p2wpkh(1)), output_weight(&p2tr)), (136, 128, 124, 172));
+        assert_eq!(input_weight(&Script::from_hex("6a0101").unwrap()), None);
+
+        // the usual one input, two output transactions
+        assert_eq!(estimate_vsize(&[p2wpkh(1)], &[p2wpkh(2), p2wpkh(3)]), Some(141));
+        assert_eq!(estimate_vsize(std::slice::from_ref(&p2pkh), &[p2pkh.clone(), p2pkh.clone()]), Some(226));
+        assert_eq!(estimate_vsize(std::slice::from_ref(&p2tr), &[p2tr.clone(), p2tr.clone()]), Some(154));
+        // a legacy input among segwit ones gets an empty witness
+        assert_eq!(estimate_weight(&[p2pkh.clone(), p2wpkh(1)], &[p2wpkh(2)]), Some(40 + 592 + 272 + 124 + 2 + 1));
+
+        assert_eq!((dust_threshold(&p2pkh), dust_threshold(&p2sh), dust_threshold(&p2wpkh(1)), dust_threshold(&p2tr)), (546, 540, 294, 330));
+        assert_eq!(dust_threshold(&Script::from_hex("6a0101").unwrap()), 0);
+    }
+
+    #[test]
+    fn coinselect_algorithms() {
+        let mut rng: StdRng = StdRng::seed_from_u64(1);
+        // 1 sat/vB: each P2WPKH input costs 68 sats, the fixed part of a one output transaction 43
+        let pool: Vec<Utxo> = utxos(&[100000, 50000, 30068, 20111, 5000]);
+        let payment: Vec<TxOut> = vec![TxOut { value: 50000, script_pubkey: p2wpkh(9) }];
+
+        // 30000 + 20043 of effective value pays exactly 50000 + 43
+        let selection: Selection = fund_with_rng(&pool, &payment, &p2wpkh(8), &policy(1.0), Algorithm::BranchAndBound, &mut rng).unwrap();
+        let mut spent: Vec<u64> = selection.selected.iter().map(|u: &Utxo| u.txout.value).collect();
+        spent.sort();
+        assert_eq!(spent, vec![20111, 30068]);
+        assert_eq!(selection.change, None);
+        assert_eq!((selection.fee, selection.vsize), (179, 178));
+        assert!(selection.fee >= selection.vsize as u64);
+
+        let selection: Selection = fund_with_rng(&pool, &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng).unwrap();
+        assert_eq!(selection.selected.len(), 1);
+        assert_eq!(selection.selected[0].txout.value, 100000);
+        let tx: Transaction = selection.psbt.unsigned_tx().unwrap();
+        let change: usize = selection.change.unwrap();
+        assert_eq!(tx.outputs[change].script_pubkey, p2wpkh(8));
+        assert_eq!(100000 - tx.output_value().unwrap(), selection.fee);
+        assert_eq!(selection.vsize, 141);
+        assert_eq!(selection.fee, 141);
+        assert_eq!(selection.psbt.fee(), Ok(141));
+
+        for seed in 0..20 {
+            let mut rng: StdRng = StdRng::seed_from_u64(seed);
+            let selection: Selection = fund_with_rng(&pool, &payment, &p2wpkh(8), &policy(2.0), Algorithm::Knapsack, &mut rng).unwrap();
+            let tx: Transaction = selection.psbt.unsigned_tx().unwrap();
+            let input_value: u64 = selection.selected.iter().map(|u: &Utxo| u.txout.value).sum();
+            assert_eq!(input_value - tx.output_value().unwrap(), selection.fee);
+            assert!(selection.fee >= 2 * selection.vsize as u64);
+            assert!(selection.change.map(|c: usize| tx.outputs[c].value >= 294).unwrap_or(true));
+        }
+
+        // change under the dust threshold goes to the fee
+        let selection: Selection = fund_with_rng(&utxos(&[50300]), &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng).unwrap();
+        assert_eq!((selection.change, selection.fee), (None, 300));
+
+        // nothing short of the whole pool, and more than that, is needed
+        let selection: Selection = fund_with_rng(&pool, &[TxOut { value: 204700, script_pubkey: p2wpkh(9) }], &p2wpkh(8), &policy(1.0), Algorithm::Knapsack, &mut rng).unwrap();
+        assert_eq!(selection.selected.len(), 5);
+        assert_eq!(fund_with_rng(&pool, &[TxOut { value: 210000, script_pubkey: p2wpkh(9) }], &p2wpkh(8), &policy(1.0), Algorithm::Knapsack, &mut rng), Err(CoinSelectionError::InsufficientFunds(204839, 210043)));
+        assert_eq!(fund_with_rng(&pool, &[TxOut { value: 60000, script_pubkey: p2wpkh(9) }], &p2wpkh(8), &policy(1.0), Algorithm::BranchAndBound, &mut rng), Err(CoinSelectionError::NoSolution));
+        assert_eq!(fund_with_rng(&pool, &[TxOut { value: 200, script_pubkey: p2wpkh(9) }], &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::DustOutput(0)));
+        assert_eq!(fund_with_rng(&pool, &[], &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::NoPayments));
+        let bare: Vec<Utxo> = vec![Utxo { outpoint: OutPoint::new([1; 32], 0), txout: TxOut { value: 1000, script_pubkey: Script::from_bytes(&base16::decode_string("51")) }, previous_tx: None, redeem_script: None }];
+        assert_eq!(fund_with_rng(&bare, &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::UnsupportedScript(0)));
+    }
+
+    #[test]
+    fn coinselect_locktime_and_sequence() {
+        let pool: Vec<Utxo> = utxos(&[100000]);
+        let payment: Vec<TxOut> = vec![TxOut { value: 50000, script_pubkey: p2wpkh(9) }];
+        let mut rng: StdRng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            let locktime: u32 = anti_fee_sniping_locktime(800000, &mut rng);
+            assert!(locktime <= 800000 && locktime > 800000 - 100);
+        }
+        assert_eq!(anti_fee_sniping_locktime(0, &mut rng), 0);
+
+        let policy: FeePolicy = FeePolicy { fee_rate: 5.0, long_term_fee_rate: 10.0, rbf: false, height: Some(800000) };
+        let tx: Transaction = fund_with_rng(&pool, &payment, &p2wpkh(8), &policy, Algorithm::LargestFirst, &mut rng).unwrap().psbt.unsigned_tx().unwrap();
+        assert!(tx.lock_time > 800000 - 100 && tx.lock_time <= 800000);
+        assert_eq!(tx.inputs[0].sequence, SEQUENCE_LOCKTIME);
+
+        let policy: FeePolicy = FeePolicy { rbf: true, height: None, ..policy };
+        let selection: Selection = fund_with_rng(&pool, &payment, &p2wpkh(8), &policy, Algorithm::LargestFirst, &mut rng).unwrap();
+        let tx: Transaction = selection.psbt.unsigned_tx().unwrap();
+        assert_eq!((tx.lock_time, tx.inputs[0].sequence, tx.version), (0, SEQUENCE_RBF, 2));
+        assert_eq!(selection.psbt.inputs[0].witness_utxo, Some(pool[0].txout.clone()));
+    }
+
+    #[test]
+    fn coinselect_input_types() {
+        let payment: Vec<TxOut> = vec![TxOut { value: 50000, script_pubkey: p2wpkh(9) }];
+        let mut rng: StdRng = StdRng::seed_from_u64(3);
+
+        // a legacy coin needs the transaction that created it
+        let p2pkh: Script = Script::from_hex("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").unwrap();
+        let mut previous: Transaction = Transaction::new(2, 0);
+        previous.inputs.push(TxIn::new(OutPoint::new([5; 32], 0)));
+        previous.outputs.push(TxOut { value: 100000, script_pubkey: p2pkh.clone() });
+        let mut legacy: Utxo = Utxo { outpoint: OutPoint::new(previous.txid(), 0), txout: previous.outputs[0].clone(), previous_tx: None, redeem_script: None };
+        assert_eq!(fund_with_rng(std::slice::from_ref(&legacy), &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::MissingPreviousTx(0)));
+        legacy.previous_tx = Some(Transaction::new(1, 0));
+        assert_eq!(fund_with_rng(std::slice::from_ref(&legacy), &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::PreviousTxMismatch(0)));
+        legacy.previous_tx = Some(previous.clone());
+        let selection: Selection = fund_with_rng(std::slice::from_ref(&legacy), &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng).unwrap();
+        assert_eq!((selection.psbt.inputs[0].witness_utxo.clone(), selection.psbt.inputs[0].non_witness_utxo.clone()), (None, Some(previous)));
+        assert_eq!(selection.psbt.fee(), Ok(selection.fee));
+
+        // P2SH only with a P2WPKH redeem script
+        let redeem_script: Script = p2wpkh(4);
+        let p2sh: Script = Script::from_bytes(&[&[0xa9, 0x14][..], &hashing::hash160(&redeem_script.bytes), &[0x87]].concat());
+        let mut nested: Utxo = Utxo { outpoint: OutPoint::new([6; 32], 1), txout: TxOut { value: 100000, script_pubkey: p2sh }, previous_tx: None, redeem_script: None };
+        assert_eq!(fund_with_rng(std::slice::from_ref(&nested), &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::UnsupportedScript(0)));
+        nested.redeem_script = Some(p2wpkh(5));
+        assert_eq!(fund_with_rng(std::slice::from_ref(&nested), &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::UnsupportedScript(0)));
+        nested.redeem_script = Some(redeem_script.clone());
+        let selection: Selection = fund_with_rng(std::slice::from_ref(&nested), &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng).unwrap();
+        assert_eq!(selection.psbt.inputs[0].witness_utxo, Some(nested.txout.clone()));
+        assert_eq!(selection.psbt.inputs[0].redeem_script, Some(redeem_script));
+
+        // amounts no transaction can hold
+        let mut too_large: Vec<Utxo> = utxos(&[100000]);
+        too_large[0].txout.value = MAX_MONEY + 1;
+        assert_eq!(fund_with_rng(&too_large, &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::UtxoAboveMaxMoney(0)));
+        too_large[0].txout.value = u64::MAX;
+        assert_eq!(fund_with_rng(&too_large, &payment, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::UtxoAboveMaxMoney(0)));
+        let payments: Vec<TxOut> = vec![payment[0].clone(), TxOut { value: MAX_MONEY + 1, script_pubkey: p2wpkh(9) }];
+        assert_eq!(fund_with_rng(&utxos(&[100000]), &payments, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::PaymentAboveMaxMoney(1)));
+        let payments: Vec<TxOut> = vec![TxOut { value: MAX_MONEY, script_pubkey: p2wpkh(9) }, TxOut { value: MAX_MONEY, script_pubkey: p2wpkh(9) }];
+        assert_eq!(fund_with_rng(&utxos(&[100000]), &payments, &p2wpkh(8), &policy(1.0), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::PaymentsAboveMaxMoney));
+
+        for fee_rate in [f64::NAN, f64::INFINITY, -1.0] {
+            assert_eq!(fund_with_rng(&utxos(&[100000]), &payment, &p2wpkh(8), &policy(fee_rate), Algorithm::LargestFirst, &mut rng), Err(CoinSelectionError::InvalidFeeRate));
+        }
+    }
+}
@@ -1,116 +1,296 @@
 pub mod hashing {
-    use sha256::digest;
-    use sha3::{Digest, Keccak256};
-    use ripemd::{Digest as RIPEDigest, Ripemd160};
+    use sha2::{Digest, Sha256};
+    use ripemd::Ripemd160;
+    use sha1::Sha1;
+    // sha3 0.7 still implements the older digest traits
+    use sha3::{Digest as Sha3Digest, Keccak256};
     use crate::base16;
 
-    //bitcoin
-    pub fn hash_sha256(input: &[u8]) -> String {
-        let r = digest(input);
-        return r;
+    #[derive(Clone, Default)]
+
+    pub struct Sha256Hasher {
+        inner: Sha256
     }
-    pub fn hash_ripemd160(input: &[u8]) -> String {
-        let mut hasher = Ripemd160::new();
-        hasher.update(input);
-        let out = hasher.finalize();
-        return base16::encode_bytes(&out);
+
+    impl Sha256Hasher {
+        pub fn new() -> Self {
+            return Self::default();
+        }
+
+        pub fn update(&mut self, input: &[u8]) {
+            self.inner.update(input);
+        }
+
+        pub fn finalize(self) -> [u8; 32] {
+            return self.inner.finalize().into();
+        }
     }
 
-    //ethereum
-    pub fn hash_keccak256(input: &[u8]) -> String {
-        let mut hasher = Keccak256::default();
-        hasher.input(input);
-        let out = hasher.result();
-        let r = base16::encode_bytes(&out).to_uppercase();
-        return r;
-    }
-    pub fn hash_keccak256_str(input: &String) -> String {
-        let mut hasher = Keccak256::default();
-        hasher.input(&input.clone().into_bytes());
-        let out = hasher.result();
-        let r = base16::encode_bytes(&out).to_uppercase();
-        return r;
+    // sha256(sha256(input)), as in txids, block hashes and Base58Check checksums
+    #[derive(Clone, Default)]
+
+    pub struct Sha256dHasher {
+        inner: Sha256Hasher
     }
-}
 
-pub mod bitcoin {
-    use crate::crypto::hashing;
-    use primitive_types::U512;
-    use crate::base58;
-    use crate::base16;
+    impl Sha256dHasher {
+        pub fn new() -> Self {
+            return Self::default();
+        }
+
+        pub fn update(&mut self, input: &[u8]) {
+            self.inner.update(input);
+        }
+
+        pub fn finalize(self) -> [u8; 32] {
+            return sha256(&self.inner.finalize());
+        }
+    }
+
+    #[derive(Clone, Default)]
+
+    pub struct Ripemd160Hasher {
+        inner: Ripemd160
+    }
 
-    pub fn encode_compressed_pr_key(pr_key: &str) -> String {
-        let mut origin: String = format!("80{}01", pr_key);
-        attach_check_sum(&mut origin);
-        return base58::convert_hex_to_base58(&origin);
+    impl Ripemd160Hasher {
+        pub fn new() -> Self {
+            return Self::default();
+        }
+
+        pub fn update(&mut self, input: &[u8]) {
+            self.inner.update(input);
+        }
+
+        pub fn finalize(self) -> [u8; 20] {
+            return self.inner.finalize().into();
+        }
+    }
+
+    // ripemd160(sha256(input)), the key and script hash of addresses
+    #[derive(Clone, Default)]
+
+    pub struct Hash160Hasher {
+        inner: Sha256Hasher
+    }
+
+    impl Hash160Hasher {
+        pub fn new() -> Self {
+            return Self::default();
+        }
+
+        pub fn update(&mut self, input: &[u8]) {
+            self.inner.update(input);
+        }
+
+        pub fn finalize(self) -> [u8; 20] {
+            return ripemd160(&self.inner.finalize());
+        }
     }
-    pub fn attach_check_sum(s: &mut String) {
-        let h1: String = hashing::hash_sha256(&base16::decode_string(&s));
-        let h2: String = hashing::hash_sha256(&base16::decode_string(&h1));
 
-        let check_sum: &String = &h2[0..8].to_uppercase();
-        s.push_str(&check_sum);
+    // the original keccak padding used by ethereum, not NIST sha3-256
+    #[derive(Clone, Default)]
+
+    pub struct Keccak256Hasher {
+        inner: Keccak256
     }
-    pub fn get_compressed_public_key_prefix(y: &str) -> String {
-        let divisor: U512 = U512::from_big_endian(&[2]);
-        let zero: U512 = U512::from_big_endian(&[0]);
 
-        if U512::from_str_radix(y, 16).expect("").checked_rem(divisor).expect("") == zero {
-            return String::from("02");
+    impl Keccak256Hasher {
+        pub fn new() -> Self {
+            return Self::default();
         }
-        else {
-            return String::from("03");
+
+        pub fn update(&mut self, input: &[u8]) {
+            self.inner.input(input);
+        }
+
+        pub fn finalize(self) -> [u8; 32] {
+            let mut r: [u8; 32] = [0; 32];
+            r.copy_from_slice(&self.inner.result());
+            return r;
         }
     }
-    pub fn derive_compressed_address(pub_key: &str) -> String {
-        let pub_key_x: String = String::from(&pub_key[2..66]).to_uppercase();
-        let pub_key_y: String = String::from(&pub_key[66..130]).to_uppercase();
 
-        let origin = format!("{}{}", &get_compressed_public_key_prefix(&pub_key_y), pub_key_x);
+    // BIP340 tagged hash: sha256(sha256(tag) | sha256(tag) | input)
+    #[derive(Clone)]
 
-        let h1: String = hashing::hash_sha256(&base16::decode_string(&origin));
-        let h2: String = hashing::hash_ripemd160(&base16::decode_string(&h1));
+    pub struct TaggedHasher {
+        inner: Sha256Hasher
+    }
 
-        let mut pre_encode: String = format!("00{}", &h2);
-        attach_check_sum(&mut pre_encode);
+    impl TaggedHasher {
+        pub fn new(tag: &str) -> Self {
+            let tag_hash: [u8; 32] = sha256(tag.as_bytes());
+            let mut inner: Sha256Hasher = Sha256Hasher::new();
+            inner.update(&tag_hash);
+            inner.update(&tag_hash);
+            return Self { inner };
+        }
+
+        pub fn update(&mut self, input: &[u8]) {
+            self.inner.update(input);
+        }
 
-        return format!("1{}", &base58::convert_hex_to_base58(&pre_encode));
+        pub fn finalize(self) -> [u8; 32] {
+            return self.inner.finalize();
+        }
+    }
+
+    pub fn sha256(input: &[u8]) -> [u8; 32] {
+        let mut hasher: Sha256Hasher = Sha256Hasher::new();
+        hasher.update(input);
+        return hasher.finalize();
+    }
+    pub fn sha256d(input: &[u8]) -> [u8; 32] {
+        return sha256(&sha256(input));
+    }
+    pub fn ripemd160(input: &[u8]) -> [u8; 20] {
+        let mut hasher: Ripemd160Hasher = Ripemd160Hasher::new();
+        hasher.update(input);
+        return hasher.finalize();
+    }
+    // only for OP_SHA1 in scripts
+    pub fn sha1(input: &[u8]) -> [u8; 20] {
+        return Sha1::digest(input).into();
+    }
+    pub fn hash160(input: &[u8]) -> [u8; 20] {
+        return ripemd160(&sha256(input));
+    }
+    pub fn keccak256(input: &[u8]) -> [u8; 32] {
+        let mut hasher: Keccak256Hasher = Keccak256Hasher::new();
+        hasher.update(input);
+        return hasher.finalize();
+    }
+    pub fn tagged_hash(tag: &str, input: &[u8]) -> [u8; 32] {
+        let mut hasher: TaggedHasher = TaggedHasher::new(tag);
+        hasher.update(input);
+        return hasher.finalize();
+    }
+
+    // lowercase hex forms of the hashes above
+    pub fn hash_sha256(input: &[u8]) -> String {
+        return base16::encode_bytes(&sha256(input));
+    }
+    pub fn hash_ripemd160(input: &[u8]) -> String {
+        return base16::encode_bytes(&ripemd160(input));
+    }
+    pub fn hash_keccak256(input: &[u8]) -> String {
+        return base16::encode_bytes(&keccak256(input));
+    }
+    pub fn hash_keccak256_str(input: &str) -> String {
+        return hash_keccak256(input.as_bytes());
     }
 }
 
-pub mod ethereum {
+pub mod bitcoin {
+    use secp256k1::PublicKey;
     use crate::crypto::hashing;
+    use crate::app::{Network, BitcoinParams};
+    use crate::base58;
     use crate::base16;
-    use std::iter::zip;
-    use std::u8;
-
-    use super::hashing::hash_keccak256;
+    use crate::bech32;
+    use crate::taproot;
 
-    pub fn derive_address(pub_key: &str) -> String {
-        let pub_key_x = String::from(&pub_key[2..66]).to_lowercase();
-        let pub_key_y = String::from(&pub_key[66..130]).to_lowercase();
+    fn params(network: Network) -> BitcoinParams {
+        return network.bitcoin_params().expect("bitcoin-network");
+    }
+    // version byte | payload | checksum, base58 encoded
+    fn encode_check(version: u8, payload: &[u8]) -> String {
+        let mut data: Vec<u8> = vec![version];
+        data.extend_from_slice(payload);
+        return base58::encode_check(&data);
+    }
 
-        let origin = format!("{}{}", pub_key_x, pub_key_y);
-        let uncompressed_pub_hash = hash_keccak256(&base16::decode_string(&origin));
+    pub fn encode_compressed_pr_key(pr_key: &[u8; 32], network: Network) -> String {
+        let mut payload: Vec<u8> = pr_key.to_vec();
+        payload.push(0x01);
+        return encode_check(params(network).wif_prefix, &payload);
+    }
+    // pre-2012 wallets: no 01 suffix marking a compressed public key
+    pub fn encode_uncompressed_pr_key(pr_key: &[u8; 32], network: Network) -> String {
+        return encode_check(params(network).wif_prefix, pr_key);
+    }
+    // 02 or 03 by the parity of y, then x
+    pub fn get_compressed_public_key(pub_key: &PublicKey) -> String {
+        return base16::encode_bytes(&pub_key.serialize());
+    }
+    // hash160 of the compressed public key, shared by p2pkh and p2wpkh
+    pub fn get_public_key_hash(pub_key: &PublicKey) -> [u8; 20] {
+        return hashing::hash160(&pub_key.serialize());
+    }
+    pub fn derive_compressed_address(pub_key: &PublicKey, network: Network) -> String {
+        return encode_check(params(network).p2pkh_version, &get_public_key_hash(pub_key));
+    }
+    // hash160 of the full 04|x|y key, as produced by wallets before compressed keys
+    pub fn derive_uncompressed_address(pub_key: &PublicKey, network: Network) -> String {
+        return encode_check(params(network).p2pkh_version, &hashing::hash160(&pub_key.serialize_uncompressed()));
+    }
+    pub fn encode_public_key_hash_address(key_hash: &[u8], network: Network) -> String {
+        return encode_check(params(network).p2pkh_version, key_hash);
+    }
+    pub fn derive_p2wpkh_address(pub_key: &PublicKey, network: Network) -> String {
+        return bech32::encode_segwit_address(params(network).bech32_hrp, 0, &get_public_key_hash(pub_key)).expect("p2wpkh");
+    }
+    // the witness program 0 <20-byte key hash>, wrapped in p2sh for nested segwit
+    pub fn get_p2wpkh_redeem_script(pub_key: &PublicKey) -> Vec<u8> {
+        return [&[0x00, 0x14][..], &get_public_key_hash(pub_key)].concat();
+    }
+    pub fn get_script_hash(script: &[u8]) -> [u8; 20] {
+        return hashing::hash160(script);
+    }
+    pub fn encode_script_hash_address(script_hash: &[u8], network: Network) -> String {
+        return encode_check(params(network).p2sh_version, script_hash);
+    }
+    pub fn derive_p2sh_p2wpkh_address(pub_key: &PublicKey, network: Network) -> String {
+        return derive_p2sh_address(&get_p2wpkh_redeem_script(pub_key), network);
+    }
+    pub fn derive_p2sh_address(script: &[u8], network: Network) -> String {
+        return encode_script_hash_address(&get_script_hash(script), network);
+    }
+    // the witness program 0 <32-byte sha256 of the witness script>
+    pub fn get_witness_script_hash(script: &[u8]) -> [u8; 32] {
+        return hashing::sha256(script);
+    }
+    pub fn get_p2wsh_redeem_script(script: &[u8]) -> Vec<u8> {
+        return [&[0x00, 0x20][..], &get_witness_script_hash(script)].concat();
+    }
+    pub fn derive_p2wsh_address(script: &[u8], network: Network) -> String {
+        return bech32::encode_segwit_address(params(network).bech32_hrp, 0, &get_witness_script_hash(script)).expect("p2wsh");
+    }
+    pub fn derive_p2sh_p2wsh_address(script: &[u8], network: Network) -> String {
+        return derive_p2sh_address(&get_p2wsh_redeem_script(script), network);
+    }
+    // BIP86 key-path-only output, the internal key tweaked without a script tree
+    pub fn derive_p2tr_address(pub_key: &PublicKey, network: Network) -> String {
+        let (output_key, _) = taproot::tweak_public_key(&pub_key.x_only_public_key().0.serialize(), None).expect("p2tr");
+        return bech32::encode_segwit_address(params(network).bech32_hrp, 1, &output_key).expect("p2tr");
+    }
+}
 
-        let non_checked_sum_address = format!("0x{}", &uncompressed_pub_hash[24..64]).to_lowercase();
+pub mod ethereum {
+    use crate::crypto::hashing;
+    use crate::base16;
 
-        let address = check_sum(&non_checked_sum_address);
+    // last 20 bytes of keccak256(x | y)
+    pub fn derive_address(pub_key: &str) -> String {
+        let key: Vec<u8> = base16::decode_string(pub_key);
+        let h: [u8; 32] = hashing::keccak256(&key[1..65]);
 
-        return address;
+        return check_sum(&format!("0x{}", base16::encode_bytes(&h[12..])));
     }
 
+    // EIP-55: a letter is uppercased when its nibble of keccak256(lowercase address) is 8 or more
     pub fn check_sum(address: &str) -> String {
         assert!(address.len() == 42);
 
         let ad = String::from(&address[2..]).to_lowercase();
-        let h = hashing::hash_keccak256_str(&ad);
-        let h = h.as_str();
+        let h: [u8; 32] = hashing::keccak256(ad.as_bytes());
 
         let mut r = String::from("");
 
-        for (c, flag) in zip(ad.chars().into_iter(), h.chars().into_iter()) {
-            if c.is_alphabetic() && u8::from_str_radix(flag.to_string().as_str(), 16).unwrap() > 8 {
+        for (i, c) in ad.chars().enumerate() {
+            let flag: u8 = if i % 2 == 0 { h[i / 2] >> 4 } else { h[i / 2] & 0x0f };
+            if c.is_alphabetic() && flag >= 8 {
                 r.push(c.to_ascii_uppercase());
             }
             else {
@@ -132,4 +312,10 @@ pub mod secp256k1 {
         let pub_key = PublicKey::from_secret_key(&secp, &pr_key);
         return base16::encode_bytes(&pub_key.serialize_uncompressed());
     }
+
+    pub fn public_key(pr_key: &[u8; 32]) -> PublicKey {
+        let secp = Secp256k1::new();
+        let pr_key = SecretKey::from_slice(pr_key).expect("private-key");
+        return PublicKey::from_secret_key(&secp, &pr_key);
+    }
 }
\ No newline at end of file
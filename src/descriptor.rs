@@ -0,0 +1,627 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use secp256k1::{PublicKey, XOnlyPublicKey};
+use crate::app::Network;
+use crate::base16;
+use crate::bip32::{self, ExtendedPrivateKey, ExtendedPublicKey, HARDENED};
+use crate::crypto::hashing;
+use crate::multisig::{self, MultisigError};
+use crate::script::{Builder, Opcode, Script};
+use crate::taproot::{self, TAPSCRIPT_LEAF_VERSION};
+
+// BIP380 checksum alphabets: every input character maps to a group of 3 and a position in it
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum DescriptorError {
+    InvalidChecksum,
+    InvalidCharacter(char),
+    Syntax(String),
+    UnknownFunction(String),
+    InvalidContext(String),
+    InvalidKey(String),
+    UncompressedKey(String),
+    HardenedDerivation,
+    InvalidIndex(u32),
+    NoAddress,
+    Multisig(MultisigError)
+}
+
+// where an expression appears, which decides the functions and keys allowed in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+enum Context {
+    Top,
+    Sh,
+    Wsh,
+    Tap
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum Wildcard {
+    None,
+    Unhardened,
+    Hardened
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum KeySource {
+    // SEC1, or x-only inside tr()
+    Public(Vec<u8>),
+    Xpub(String),
+    Xprv(String)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct DescriptorKey {
+    pub origin: Option<([u8; 4], Vec<u32>)>,
+    pub source: KeySource,
+    pub path: Vec<u32>,
+    pub wildcard: Wildcard,
+    // hardened steps are printed with ' unless the key was written with h
+    pub apostrophe: bool
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum TapTree {
+    Leaf(Box<Descriptor>),
+    Branch(Box<TapTree>, Box<TapTree>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum Descriptor {
+    Pk(DescriptorKey),
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    Multi(usize, Vec<DescriptorKey>),
+    SortedMulti(usize, Vec<DescriptorKey>),
+    Sh(Box<Descriptor>),
+    Wsh(Box<Descriptor>),
+    Tr(DescriptorKey, Option<TapTree>)
+}
+
+fn polymod(c: u64, value: u64) -> u64 {
+    let top: u64 = c >> 35;
+    let mut c: u64 = ((c & 0x7ffffffff) << 5) ^ value;
+    for (i, g) in GENERATOR.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            c ^= g;
+        }
+    }
+    return c;
+}
+
+pub fn checksum(descriptor: &str) -> Result<String, DescriptorError> {
+    let mut c: u64 = 1;
+    let mut class: u64 = 0;
+    let mut class_count: usize = 0;
+
+    for ch in descriptor.chars() {
+        let position: u64 = match INPUT_CHARSET.find(ch) {
+            Some(p) => p as u64,
+            None => return Err(DescriptorError::InvalidCharacter(ch))
+        };
+        c = polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    return Ok((0..8).map(|j: u64| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char).collect::<String>());
+}
+
+// the descriptor without its #checksum, which is verified when present
+pub fn strip_checksum(descriptor: &str) -> Result<&str, DescriptorError> {
+    match descriptor.trim().split_once('#') {
+        Some((body, sum)) => {
+            if checksum(body)? != sum {
+                return Err(DescriptorError::InvalidChecksum);
+            }
+            return Ok(body);
+        },
+        None => return Ok(descriptor.trim())
+    };
+}
+
+// splits on the commas that are not nested inside (), [] or {}
+fn split_arguments(s: &str) -> Vec<&str> {
+    let mut r: Vec<&str> = vec![];
+    let mut depth: usize = 0;
+    let mut start: usize = 0;
+    for (i, ch) in s.char_indices() {
+        match ch {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                r.push(&s[start..i]);
+                start = i + 1;
+            },
+            _ => {}
+        };
+    }
+    r.push(&s[start..]);
+    return r;
+}
+
+fn format_origin_path(path: &[u32], apostrophe: bool) -> String {
+    let r: String = bip32::format_path(path).trim_start_matches('m').to_string();
+    if apostrophe {
+        return r;
+    }
+    return r.replace('\'', "h");
+}
+
+impl DescriptorKey {
+    fn parse(s: &str, context: Context) -> Result<Self, DescriptorError> {
+        let error = || DescriptorError::InvalidKey(String::from(s));
+
+        let mut origin: Option<([u8; 4], Vec<u32>)> = None;
+        let mut apostrophe: bool = true;
+        let mut rest: &str = s;
+        if let Some(inner) = s.strip_prefix('[') {
+            let (inside, after) = inner.split_once(']').ok_or_else(error)?;
+            let fingerprint: Vec<u8> = match inside.get(0..8).map(hex::decode) {
+                Some(Ok(f)) => f,
+                _ => return Err(error())
+            };
+            let path: Vec<u32> = bip32::parse_path(&format!("m{}", &inside[8..])).map_err(|_| error())?;
+            apostrophe &= !inside[8..].contains(['h', 'H']);
+
+            let mut f: [u8; 4] = [0; 4];
+            f.copy_from_slice(&fingerprint);
+            origin = Some((f, path));
+            rest = after;
+        }
+
+        let mut segments: Vec<&str> = rest.split('/').collect::<Vec<&str>>();
+        let head: &str = segments.remove(0);
+        let wildcard: Wildcard = match segments.last() {
+            Some(&"*") => Wildcard::Unhardened,
+            Some(&"*'") | Some(&"*h") | Some(&"*H") => Wildcard::Hardened,
+            _ => Wildcard::None
+        };
+        if wildcard != Wildcard::None {
+            segments.pop();
+        }
+        apostrophe &= !rest.split('/').skip(1).any(|segment: &str| segment.ends_with(['h', 'H']));
+        let path: Vec<u32> = if segments.is_empty() { vec![] } else {
+            bip32::parse_path(&format!("m/{}", segments.join("/"))).map_err(|_| error())?
+        };
+
+        let source: KeySource = if ExtendedPublicKey::from_str(head).is_ok() {
+            KeySource::Xpub(String::from(head))
+        }
+        else if ExtendedPrivateKey::from_str(head).is_ok() {
+            KeySource::Xprv(String::from(head))
+        }
+        else {
+            // a plain key cannot be derived any further
+            if !path.is_empty() || wildcard != Wildcard::None {
+                return Err(error());
+            }
+            let bytes: Vec<u8> = hex::decode(head).map_err(|_| error())?;
+            let valid: bool = match bytes.len() {
+                32 => context == Context::Tap && XOnlyPublicKey::from_slice(&bytes).is_ok(),
+                _ => PublicKey::from_slice(&bytes).is_ok()
+            };
+            if !valid {
+                return Err(error());
+            }
+            if bytes.len() == 65 && (context == Context::Wsh || context == Context::Tap) {
+                return Err(DescriptorError::UncompressedKey(String::from(head)));
+            }
+            KeySource::Public(bytes)
+        };
+
+        return Ok(Self { origin, source, path, wildcard, apostrophe });
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        return self.wildcard != Wildcard::None;
+    }
+
+    // the SEC1 key at the index, which only matters for ranged keys
+    pub fn derive(&self, index: u32) -> Result<Vec<u8>, DescriptorError> {
+        if index & HARDENED != 0 {
+            return Err(DescriptorError::InvalidIndex(index));
+        }
+        let mut path: Vec<u32> = self.path.clone();
+        match self.wildcard {
+            Wildcard::None => {},
+            Wildcard::Unhardened => path.push(index),
+            Wildcard::Hardened => path.push(index | HARDENED)
+        };
+
+        match &self.source {
+            KeySource::Public(bytes) => return Ok(bytes.clone()),
+            KeySource::Xpub(s) => {
+                let xpub: ExtendedPublicKey = ExtendedPublicKey::from_str(s).expect("xpub");
+                return xpub.derive_path(&path).map(|k: ExtendedPublicKey| k.public_key.to_vec()).map_err(|_| DescriptorError::HardenedDerivation);
+            },
+            KeySource::Xprv(s) => {
+                let xprv: ExtendedPrivateKey = ExtendedPrivateKey::from_str(s).expect("xprv");
+                return Ok(xprv.derive_path(&path).compressed_public_key().to_vec());
+            }
+        };
+    }
+
+    fn derive_x_only(&self, index: u32) -> Result<Vec<u8>, DescriptorError> {
+        let key: Vec<u8> = self.derive(index)?;
+        if key.len() == 32 {
+            return Ok(key);
+        }
+        return Ok(key[1..33].to_vec());
+    }
+
+    // an xprv becomes the xpub after its last hardened step, which moves into the origin
+    pub fn to_public(&self) -> Result<Self, DescriptorError> {
+        let encoded: &String = match &self.source {
+            KeySource::Xprv(s) => s,
+            _ => return Ok(self.clone())
+        };
+        if self.wildcard == Wildcard::Hardened {
+            return Err(DescriptorError::HardenedDerivation);
+        }
+
+        let xprv: ExtendedPrivateKey = ExtendedPrivateKey::from_str(encoded).expect("xprv");
+        let split: usize = self.path.iter().rposition(|i: &u32| i & HARDENED != 0).map(|p: usize| p + 1).unwrap_or(0);
+        let derived: ExtendedPrivateKey = xprv.derive_path(&self.path[..split]);
+
+        let origin: Option<([u8; 4], Vec<u32>)> = match &self.origin {
+            Some((fingerprint, path)) => Some((*fingerprint, [path.as_slice(), &self.path[..split]].concat())),
+            None if split > 0 => Some((xprv.fingerprint(), self.path[..split].to_vec())),
+            None => None
+        };
+        return Ok(Self {
+            origin,
+            source: KeySource::Xpub(derived.to_xpub()),
+            path: self.path[split..].to_vec(),
+            wildcard: self.wildcard,
+            apostrophe: self.apostrophe
+        });
+    }
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((fingerprint, path)) = &self.origin {
+            write!(f, "[{}{}]", base16::encode_bytes(fingerprint), format_origin_path(path, self.apostrophe))?;
+        }
+        match &self.source {
+            KeySource::Public(bytes) => write!(f, "{}", base16::encode_bytes(bytes))?,
+            KeySource::Xpub(s) | KeySource::Xprv(s) => write!(f, "{}", s)?
+        };
+        write!(f, "{}", format_origin_path(&self.path, self.apostrophe))?;
+        match self.wildcard {
+            Wildcard::None => return Ok(()),
+            Wildcard::Unhardened => return write!(f, "/*"),
+            Wildcard::Hardened if self.apostrophe => return write!(f, "/*'"),
+            Wildcard::Hardened => return write!(f, "/*h"),
+        };
+    }
+}
+
+impl TapTree {
+    fn parse(s: &str) -> Result<Self, DescriptorError> {
+        if let Some(inner) = s.strip_prefix('{') {
+            let inner: &str = inner.strip_suffix('}').ok_or_else(|| DescriptorError::Syntax(String::from(s)))?;
+            let children: Vec<&str> = split_arguments(inner);
+            if children.len() != 2 {
+                return Err(DescriptorError::Syntax(String::from(s)));
+            }
+            return Ok(TapTree::Branch(Box::new(TapTree::parse(children[0])?), Box::new(TapTree::parse(children[1])?)));
+        }
+        return Ok(TapTree::Leaf(Box::new(Descriptor::parse(s, Context::Tap)?)));
+    }
+
+    fn is_ranged(&self) -> bool {
+        match self {
+            TapTree::Leaf(d) => return d.is_ranged(),
+            TapTree::Branch(a, b) => return a.is_ranged() || b.is_ranged(),
+        };
+    }
+
+    fn merkle_root(&self, index: u32) -> Result<[u8; 32], DescriptorError> {
+        match self {
+            TapTree::Leaf(d) => return Ok(taproot::tap_leaf_hash(&d.script(index, Context::Tap)?, TAPSCRIPT_LEAF_VERSION)),
+            TapTree::Branch(a, b) => return Ok(taproot::tap_branch_hash(&a.merkle_root(index)?, &b.merkle_root(index)?)),
+        };
+    }
+
+    fn to_public(&self) -> Result<Self, DescriptorError> {
+        match self {
+            TapTree::Leaf(d) => return Ok(TapTree::Leaf(Box::new(d.to_public()?))),
+            TapTree::Branch(a, b) => return Ok(TapTree::Branch(Box::new(a.to_public()?), Box::new(b.to_public()?))),
+        };
+    }
+}
+
+impl fmt::Display for TapTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapTree::Leaf(d) => return write!(f, "{}", d.without_checksum()),
+            TapTree::Branch(a, b) => return write!(f, "{{{},{}}}", a, b),
+        };
+    }
+}
+
+impl FromStr for Descriptor {
+    type Err = DescriptorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return Descriptor::parse(strip_checksum(s)?, Context::Top);
+    }
+}
+
+impl Descriptor {
+    fn parse(s: &str, context: Context) -> Result<Self, DescriptorError> {
+        let (name, inner) = match s.split_once('(') {
+            Some((n, rest)) => (n, rest.strip_suffix(')').ok_or_else(|| DescriptorError::Syntax(String::from(s)))?),
+            None => return Err(DescriptorError::Syntax(String::from(s)))
+        };
+        let arguments: Vec<&str> = split_arguments(inner);
+        let invalid_context = || DescriptorError::InvalidContext(String::from(name));
+        let single = || -> Result<&str, DescriptorError> {
+            if arguments.len() != 1 {
+                return Err(DescriptorError::Syntax(String::from(s)));
+            }
+            return Ok(arguments[0]);
+        };
+
+        match name {
+            "pk" => return Ok(Descriptor::Pk(DescriptorKey::parse(single()?, context)?)),
+            "pkh" if context != Context::Tap => return Ok(Descriptor::Pkh(DescriptorKey::parse(single()?, context)?)),
+            "wpkh" if context == Context::Top || context == Context::Sh => {
+                // segwit v0 only takes compressed keys
+                return Ok(Descriptor::Wpkh(DescriptorKey::parse(single()?, Context::Wsh)?));
+            },
+            "multi" | "sortedmulti" if context != Context::Tap => {
+                let threshold: usize = arguments[0].parse::<usize>().map_err(|_| DescriptorError::Syntax(String::from(s)))?;
+                let keys: Vec<DescriptorKey> = arguments[1..].iter().map(|k: &&str| DescriptorKey::parse(k, context)).collect::<Result<Vec<DescriptorKey>, DescriptorError>>()?;
+                if keys.len() > multisig::MAX_KEYS {
+                    return Err(DescriptorError::Multisig(MultisigError::TooManyKeys(keys.len())));
+                }
+                if threshold == 0 || threshold > keys.len() {
+                    return Err(DescriptorError::Multisig(MultisigError::InvalidThreshold(threshold, keys.len())));
+                }
+                if name == "multi" {
+                    return Ok(Descriptor::Multi(threshold, keys));
+                }
+                return Ok(Descriptor::SortedMulti(threshold, keys));
+            },
+            "sh" if context == Context::Top => return Ok(Descriptor::Sh(Box::new(Descriptor::parse(single()?, Context::Sh)?))),
+            "wsh" if context == Context::Top || context == Context::Sh => return Ok(Descriptor::Wsh(Box::new(Descriptor::parse(single()?, Context::Wsh)?))),
+            "tr" if context == Context::Top => {
+                let key: DescriptorKey = DescriptorKey::parse(arguments[0], Context::Tap)?;
+                match arguments.len() {
+                    1 => return Ok(Descriptor::Tr(key, None)),
+                    2 => return Ok(Descriptor::Tr(key, Some(TapTree::parse(arguments[1])?))),
+                    _ => return Err(DescriptorError::Syntax(String::from(s)))
+                };
+            },
+            "pkh" | "wpkh" | "multi" | "sortedmulti" | "sh" | "wsh" | "tr" => return Err(invalid_context()),
+            _ => return Err(DescriptorError::UnknownFunction(String::from(name)))
+        };
+    }
+
+    pub fn without_checksum(&self) -> String {
+        let keys = |keys: &[DescriptorKey]| keys.iter().map(|k: &DescriptorKey| k.to_string()).collect::<Vec<String>>().join(",");
+        match self {
+            Descriptor::Pk(k) => return format!("pk({})", k),
+            Descriptor::Pkh(k) => return format!("pkh({})", k),
+            Descriptor::Wpkh(k) => return format!("wpkh({})", k),
+            Descriptor::Multi(t, k) => return format!("multi({},{})", t, keys(k)),
+            Descriptor::SortedMulti(t, k) => return format!("sortedmulti({},{})", t, keys(k)),
+            Descriptor::Sh(d) => return format!("sh({})", d.without_checksum()),
+            Descriptor::Wsh(d) => return format!("wsh({})", d.without_checksum()),
+            Descriptor::Tr(k, None) => return format!("tr({})", k),
+            Descriptor::Tr(k, Some(tree)) => return format!("tr({},{})", k, tree),
+        };
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        match self {
+            Descriptor::Pk(k) | Descriptor::Pkh(k) | Descriptor::Wpkh(k) | Descriptor::Tr(k, None) => return k.is_ranged(),
+            Descriptor::Multi(_, k) | Descriptor::SortedMulti(_, k) => return k.iter().any(|k: &DescriptorKey| k.is_ranged()),
+            Descriptor::Sh(d) | Descriptor::Wsh(d) => return d.is_ranged(),
+            Descriptor::Tr(k, Some(tree)) => return k.is_ranged() || tree.is_ranged(),
+        };
+    }
+
+    // the same descriptor with every xprv replaced by its xpub
+    pub fn to_public(&self) -> Result<Self, DescriptorError> {
+        let keys = |keys: &[DescriptorKey]| keys.iter().map(|k: &DescriptorKey| k.to_public()).collect::<Result<Vec<DescriptorKey>, DescriptorError>>();
+        match self {
+            Descriptor::Pk(k) => return Ok(Descriptor::Pk(k.to_public()?)),
+            Descriptor::Pkh(k) => return Ok(Descriptor::Pkh(k.to_public()?)),
+            Descriptor::Wpkh(k) => return Ok(Descriptor::Wpkh(k.to_public()?)),
+            Descriptor::Multi(t, k) => return Ok(Descriptor::Multi(*t, keys(k)?)),
+            Descriptor::SortedMulti(t, k) => return Ok(Descriptor::SortedMulti(*t, keys(k)?)),
+            Descriptor::Sh(d) => return Ok(Descriptor::Sh(Box::new(d.to_public()?))),
+            Descriptor::Wsh(d) => return Ok(Descriptor::Wsh(Box::new(d.to_public()?))),
+            Descriptor::Tr(k, tree) => return Ok(Descriptor::Tr(k.to_public()?, tree.as_ref().map(|t: &TapTree| t.to_public()).transpose()?)),
+        };
+    }
+
+    fn script(&self, index: u32, context: Context) -> Result<Vec<u8>, DescriptorError> {
+        let derive_all = |keys: &[DescriptorKey]| keys.iter().map(|k: &DescriptorKey| k.derive(index)).collect::<Result<Vec<Vec<u8>>, DescriptorError>>();
+        let builder: Builder = Builder::new();
+        match self {
+            Descriptor::Pk(k) => {
+                let key: Vec<u8> = if context == Context::Tap { k.derive_x_only(index)? } else { k.derive(index)? };
+                return Ok(builder.push_slice(&key).push_opcode(Opcode::CheckSig).into_script().bytes);
+            },
+            Descriptor::Pkh(k) => {
+                return Ok(builder
+                    .push_opcode(Opcode::Dup)
+                    .push_opcode(Opcode::Hash160)
+                    .push_slice(&hashing::hash160(&k.derive(index)?))
+                    .push_opcode(Opcode::EqualVerify)
+                    .push_opcode(Opcode::CheckSig)
+                    .into_script().bytes);
+            },
+            Descriptor::Wpkh(k) => return Ok(builder.push_opcode(Opcode::Op0).push_slice(&hashing::hash160(&k.derive(index)?)).into_script().bytes),
+            Descriptor::Multi(t, k) => return multisig::multisig_script(*t, &derive_all(k)?, false).map_err(DescriptorError::Multisig),
+            Descriptor::SortedMulti(t, k) => return multisig::multisig_script(*t, &derive_all(k)?, true).map_err(DescriptorError::Multisig),
+            Descriptor::Sh(d) => {
+                let redeem_script: Vec<u8> = d.script(index, Context::Sh)?;
+                return Ok(builder.push_opcode(Opcode::Hash160).push_slice(&hashing::hash160(&redeem_script)).push_opcode(Opcode::Equal).into_script().bytes);
+            },
+            Descriptor::Wsh(d) => {
+                let witness_script: Vec<u8> = d.script(index, Context::Wsh)?;
+                return Ok(builder.push_opcode(Opcode::Op0).push_slice(&hashing::sha256(&witness_script)).into_script().bytes);
+            },
+            Descriptor::Tr(k, tree) => {
+                let mut internal_key: [u8; 32] = [0; 32];
+                internal_key.copy_from_slice(&k.derive_x_only(index)?);
+                let root: Option<[u8; 32]> = tree.as_ref().map(|t: &TapTree| t.merkle_root(index)).transpose()?;
+
+                let (output_key, _) = taproot::tweak_public_key(&internal_key, root.as_ref()).map_err(|_| DescriptorError::InvalidKey(k.to_string()))?;
+                return Ok(builder.push_opcode(Opcode::Num(1)).push_slice(&output_key).into_script().bytes);
+            }
+        };
+    }
+
+    pub fn script_pubkey_at(&self, index: u32) -> Result<Script, DescriptorError> {
+        return Ok(Script::from_bytes(&self.script(index, Context::Top)?));
+    }
+
+    // bare pk and multi outputs have no address
+    pub fn address_at(&self, index: u32, network: Network) -> Result<String, DescriptorError> {
+        return self.script_pubkey_at(index)?.to_address(network).map_err(|_| DescriptorError::NoAddress);
+    }
+
+    pub fn addresses(&self, range: Range<u32>, network: Network) -> Result<Vec<String>, DescriptorError> {
+        return range.map(|i: u32| self.address_at(i, network)).collect::<Result<Vec<String>, DescriptorError>>();
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let body: String = self.without_checksum();
+        return write!(f, "{}#{}", body, checksum(&body).expect("descriptor-charset"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::descriptor::*;
+
+    fn round_trip(s: &str) {
+        assert_eq!(Descriptor::from_str(s).unwrap().to_string(), s);
+    }
+
+    // BIP380 and the descriptor tests of bitcoin core, as carried by rust-miniscript
+    #[test]
+    fn descriptor_checksum() {
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(strip_checksum("raw(deadbeef)#89f8spxm").unwrap(), "raw(deadbeef)");
+        assert_eq!(strip_checksum("raw(dedbeef)#89f8spxm"), Err(DescriptorError::InvalidChecksum));
+        assert_eq!(strip_checksum("raw(deadbeef)#89f8spxn"), Err(DescriptorError::InvalidChecksum));
+        assert_eq!(checksum("raw(Ü)"), Err(DescriptorError::InvalidCharacter('Ü')));
+
+        round_trip("pk(020000000000000000000000000000000000000000000000000000000000000002)#7yxkn84h");
+        round_trip("pkh(020000000000000000000000000000000000000000000000000000000000000002)#ma7nspkf");
+        round_trip("wpkh(020000000000000000000000000000000000000000000000000000000000000002)#d3xz2xye");
+        round_trip("sh(wpkh(020000000000000000000000000000000000000000000000000000000000000002))#45zpjtet");
+        round_trip("tr(020000000000000000000000000000000000000000000000000000000000000002)#8hc7wq5h");
+        round_trip("wpkh([2cbe2a6d/44'/0'/0']tpubDCvNhURocXGZsLNqWcqD3syHTqPXrMSTwi8feKVwAcpi29oYKsDD3Vex7x2TDneKMVN23RbLprfxB69v94iYqdaYHsVz3kPR37NQXeqouVz/0/*)#nhdxg96s");
+        round_trip("sh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#ggrsrxfy");
+        round_trip("sh(wsh(pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL)))#6c6hwr22");
+    }
+
+    #[test]
+    fn descriptor_addresses() {
+        let key = "020000000000000000000000000000000000000000000000000000000000000002";
+        let address = |s: String| Descriptor::from_str(&s).unwrap().address_at(0, Network::Bitcoin).unwrap();
+
+        assert_eq!(address(format!("pkh({})", key)), "1D7nRvrRgzCg9kYBwhPH3j3Gs6SmsRg3Wq");
+        assert_eq!(address(format!("wpkh({})", key)), "bc1qsn57m9drscflq5nl76z6ny52hck5w4x5wqd9yt");
+        assert_eq!(address(format!("sh(wpkh({}))", key)), "3PjMEzoveVbvajcnDDuxcJhsuqPHgydQXq");
+        assert_eq!(address(format!("sh(pk({}))", key)), "3HDbdvM9CQ6ASnQFUkWw6Z4t3qNwMesJE9");
+        assert_eq!(address(format!("wsh(pk({}))", key)), "bc1qlymeahyfsv2jm3upw3urqp6m65ufde9seedl7umh0lth6yjt5zzsk33tv6");
+        assert_eq!(address(format!("sh(wsh(pk({})))", key)), "38cTksiyPT2b1uGRVbVqHdDhW9vKs84N6Z");
+        assert_eq!(Descriptor::from_str(&format!("pk({})", key)).unwrap().address_at(0, Network::Bitcoin), Err(DescriptorError::NoAddress));
+    }
+
+    // BIP341 wallet vectors, the second one with a single pk() leaf
+    #[test]
+    fn descriptor_taproot() {
+        let key_path = Descriptor::from_str("tr(d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d)").unwrap();
+        assert_eq!(key_path.address_at(0, Network::Bitcoin).unwrap(), "bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5");
+
+        let script_path = Descriptor::from_str("tr(187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27,pk(d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8))").unwrap();
+        assert_eq!(script_path.script_pubkey_at(0).unwrap().to_hex(), "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+        assert_eq!(script_path.address_at(0, Network::Bitcoin).unwrap(), "bc1pz37fc4cn9ah8anwm4xqqhvxygjf9rjf2resrw8h8w4tmvcs0863sa2e586");
+
+        let tree = "tr(187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27,{pk(d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8),{pk(d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d),pk(187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27)}})";
+        assert_eq!(Descriptor::from_str(tree).unwrap().without_checksum(), tree);
+        assert_eq!(Descriptor::from_str("tr(d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d,pkh(d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8))"), Err(DescriptorError::InvalidContext(String::from("pkh"))));
+    }
+
+    #[test]
+    fn descriptor_sortedmulti_ranges() {
+        let sorted = |s: &str| Descriptor::from_str(s).unwrap().address_at(5, Network::Bitcoin).unwrap();
+
+        assert_eq!(sorted("sh(sortedmulti(1,03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556,0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352))#uetvewm2"), "3JZJNxvDKe6Y55ZaF5223XHwfF2eoMNnoV");
+        assert_eq!(sorted("sh(sortedmulti(1,0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352,03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556))#7l8smyg9"), "3JZJNxvDKe6Y55ZaF5223XHwfF2eoMNnoV");
+        assert_eq!(sorted("wsh(sortedmulti(1,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH))#7etm7zk7"), "bc1qpq2cfgz5lktxzr5zqv7nrzz46hsvq3492ump9pz8rzcl8wqtwqcspx5y6a");
+
+        let ranged = Descriptor::from_str("sh(wsh(sortedmulti(1,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/1/0/*,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/0/0/*)))#u60cee0u").unwrap();
+        assert!(ranged.is_ranged());
+        assert_eq!(ranged.addresses(4..6, Network::Bitcoin).unwrap()[1], "325zcVBN5o2eqqqtGwPjmtDd8dJRyYP82s");
+        assert_eq!(ranged.address_at(HARDENED, Network::Bitcoin), Err(DescriptorError::InvalidIndex(HARDENED)));
+    }
+
+    #[test]
+    fn descriptor_private_keys() {
+        let private = Descriptor::from_str("wpkh(xprv9s21ZrQH143K4CTb63EaMxja1YiTnSEWKMbn23uoEnAzxjdUJRQkazCAtzxGm4LSoTSVTptoV9RbchnKPW9HxKtZumdyxyikZFDLhogJ5Uj/44'/0'/0'/0/*)#v20xlvm9").unwrap();
+        let public = private.to_public().unwrap();
+
+        assert_eq!(public.to_string(), "wpkh([a12b02f4/44'/0'/0']xpub6BzhLAQUDcBUfHRQHZxDF2AbcJqp4Kaeq6bzJpXrjrWuK26ymTFwkEFbxPra2bJ7yeZKbDjfDeFwxe93JMqpo5SsPJH6dZdvV9kMzJkAZ69/0/*)#u37l7u8u");
+        assert_eq!(private.addresses(0..3, Network::Bitcoin).unwrap(), public.addresses(0..3, Network::Bitcoin).unwrap());
+
+        // the h marker of the input is kept, so the checksum stays the same
+        let body = "wpkh(xprv9s21ZrQH143K4CTb63EaMxja1YiTnSEWKMbn23uoEnAzxjdUJRQkazCAtzxGm4LSoTSVTptoV9RbchnKPW9HxKtZumdyxyikZFDLhogJ5Uj/44h/0h/0h/0/*)";
+        round_trip(&format!("{}#{}", body, checksum(body).unwrap()));
+        let public_h = Descriptor::from_str(body).unwrap().to_public().unwrap();
+        assert_eq!(public_h.without_checksum(), "wpkh([a12b02f4/44h/0h/0h]xpub6BzhLAQUDcBUfHRQHZxDF2AbcJqp4Kaeq6bzJpXrjrWuK26ymTFwkEFbxPra2bJ7yeZKbDjfDeFwxe93JMqpo5SsPJH6dZdvV9kMzJkAZ69/0/*)");
+        assert_eq!(public_h.addresses(0..3, Network::Bitcoin).unwrap(), public.addresses(0..3, Network::Bitcoin).unwrap());
+
+        let hardened = Descriptor::from_str("wpkh(xpub6BzhLAQUDcBUfHRQHZxDF2AbcJqp4Kaeq6bzJpXrjrWuK26ymTFwkEFbxPra2bJ7yeZKbDjfDeFwxe93JMqpo5SsPJH6dZdvV9kMzJkAZ69/0/*')").unwrap();
+        assert_eq!(hardened.address_at(0, Network::Bitcoin), Err(DescriptorError::HardenedDerivation));
+    }
+
+    #[test]
+    fn descriptor_invalid() {
+        let uncompressed = "04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235";
+
+        assert_eq!(Descriptor::from_str(&format!("wpkh({})", uncompressed)), Err(DescriptorError::UncompressedKey(String::from(uncompressed))));
+        assert!(Descriptor::from_str(&format!("pkh({})", uncompressed)).is_ok());
+        assert_eq!(Descriptor::from_str("wsh(wpkh(020000000000000000000000000000000000000000000000000000000000000002))"), Err(DescriptorError::InvalidContext(String::from("wpkh"))));
+        assert_eq!(Descriptor::from_str("sh(sh(pk(020000000000000000000000000000000000000000000000000000000000000002)))"), Err(DescriptorError::InvalidContext(String::from("sh"))));
+        assert_eq!(Descriptor::from_str("combo(020000000000000000000000000000000000000000000000000000000000000002)"), Err(DescriptorError::UnknownFunction(String::from("combo"))));
+        assert_eq!(Descriptor::from_str("pk(020000000000000000000000000000000000000000000000000000000000000002/0)"), Err(DescriptorError::InvalidKey(String::from("020000000000000000000000000000000000000000000000000000000000000002/0"))));
+        assert_eq!(Descriptor::from_str("multi(3,020000000000000000000000000000000000000000000000000000000000000002)"), Err(DescriptorError::Multisig(MultisigError::InvalidThreshold(3, 1))));
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+use secp256k1::PublicKey;
+use crate::app::Network;
+use crate::bip32::{ExtendedPrivateKey, HARDENED};
+use crate::crypto::{bitcoin, ethereum};
+
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum Purpose {
+    Bip44,
+    Bip49,
+    Bip84,
+    Bip86
+}
+
+impl Purpose {
+    pub fn all() -> Vec<Purpose> {
+        return vec![Purpose::Bip44, Purpose::Bip49, Purpose::Bip84, Purpose::Bip86];
+    }
+    pub fn index(&self) -> u32 {
+        match self {
+            Purpose::Bip44 => return 44,
+            Purpose::Bip49 => return 49,
+            Purpose::Bip84 => return 84,
+            Purpose::Bip86 => return 86,
+        };
+    }
+}
+
+impl FromStr for Purpose {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Purpose, Self::Err> {
+        match input.to_lowercase().trim_start_matches("bip") {
+            "44" => Ok(Purpose::Bip44),
+            "49" => Ok(Purpose::Bip49),
+            "84" => Ok(Purpose::Bip84),
+            "86" => Ok(Purpose::Bip86),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Purpose {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "BIP{}", self.index());
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum DiscoveryError {
+    UnsupportedPurpose(Purpose, Network)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct UsedAddress {
+    pub change: u32,
+    pub index: u32,
+    pub address: String
+}
+
+#[derive(Debug, Clone)]
+
+pub struct AccountReport {
+    pub purpose: Purpose,
+    pub account: u32,
+    pub path: Vec<u32>,
+    pub xpub: String,
+    pub used: Vec<UsedAddress>
+}
+
+impl AccountReport {
+    // first index past the last used address on the given chain
+    pub fn next_index(&self, change: u32) -> u32 {
+        return self.used.iter().filter(|u: &&UsedAddress| u.change == change).map(|u: &UsedAddress| u.index + 1).max().unwrap_or(0);
+    }
+}
+
+pub fn coin_type(network: Network) -> u32 {
+    match network {
+        Network::Bitcoin => return 0,
+        Network::Testnet | Network::Signet | Network::Regtest => return 1,
+        Network::Ethereum => return 60,
+    };
+}
+
+pub fn supported_purposes(network: Network) -> Vec<Purpose> {
+    match network {
+        Network::Ethereum => return vec![Purpose::Bip44],
+        _ => return vec![Purpose::Bip44, Purpose::Bip49, Purpose::Bip84, Purpose::Bip86],
+    };
+}
+
+// base58 is case sensitive, hex and bech32 addresses are not
+pub fn normalize_address(address: &str) -> String {
+    let a: &str = address.trim().trim_matches(|c: char| c == '"' || c == '\'');
+    let lower: String = a.to_lowercase();
+
+    if ["0x", "bc1", "tb1", "bcrt1"].iter().any(|p: &&str| lower.starts_with(p)) {
+        return lower;
+    }
+    return String::from(a);
+}
+
+// accepts newline-delimited dumps as well as csv/tsv exports; every field is
+// treated as a candidate address so header rows and txid columns never match
+pub fn parse_used_addresses(content: &str) -> HashSet<String> {
+    let mut r: HashSet<String> = HashSet::new();
+
+    for line in content.lines() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        line.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .map(normalize_address)
+            .filter(|field: &String| !field.is_empty())
+            .for_each(|field: String| { r.insert(field); });
+    }
+    return r;
+}
+
+pub fn load_used_addresses(path: &str) -> io::Result<HashSet<String>> {
+    let content: String = fs::read_to_string(path)?;
+    return Ok(parse_used_addresses(&content));
+}
+
+pub fn derive_address(purpose: Purpose, network: Network, key: &ExtendedPrivateKey) -> Result<String, DiscoveryError> {
+    let pub_key: PublicKey = crate::crypto::secp256k1::public_key(&key.private_key);
+    match (purpose, network) {
+        (Purpose::Bip44, Network::Ethereum) => return Ok(ethereum::derive_address(&key.public_key())),
+        (_, Network::Ethereum) => return Err(DiscoveryError::UnsupportedPurpose(purpose, network)),
+        (Purpose::Bip44, _) => return Ok(bitcoin::derive_compressed_address(&pub_key, network)),
+        (Purpose::Bip49, _) => return Ok(bitcoin::derive_p2sh_p2wpkh_address(&pub_key, network)),
+        (Purpose::Bip84, _) => return Ok(bitcoin::derive_p2wpkh_address(&pub_key, network)),
+        (Purpose::Bip86, _) => return Ok(bitcoin::derive_p2tr_address(&pub_key, network)),
+    };
+}
+
+fn scan_chain(purpose: Purpose, network: Network, chain: &ExtendedPrivateKey, change: u32, gap_limit: u32, used: &HashSet<String>, found: &mut Vec<UsedAddress>) -> Result<(), DiscoveryError> {
+    let mut gap: u32 = 0;
+    let mut index: u32 = 0;
+
+    while gap < gap_limit && index < HARDENED {
+        let address: String = derive_address(purpose, network, &chain.derive_child(index))?;
+
+        if used.contains(&normalize_address(&address)) {
+            found.push(UsedAddress { change, index, address });
+            gap = 0;
+        }
+        else {
+            gap += 1;
+        }
+        index += 1;
+    }
+    return Ok(());
+}
+
+// BIP44 account discovery: accounts are scanned in order and discovery stops at
+// the first account with no used address on either chain
+pub fn discover_purpose(master: &ExtendedPrivateKey, network: Network, purpose: Purpose, gap_limit: u32, used: &HashSet<String>) -> Result<Vec<AccountReport>, DiscoveryError> {
+    let mut reports: Vec<AccountReport> = vec![];
+    let mut account: u32 = 0;
+
+    while account < HARDENED {
+        let path: Vec<u32> = vec![purpose.index() | HARDENED, coin_type(network) | HARDENED, account | HARDENED];
+        let account_key: ExtendedPrivateKey = master.derive_path(&path);
+
+        let mut found: Vec<UsedAddress> = vec![];
+        for change in [0, 1] {
+            scan_chain(purpose, network, &account_key.derive_child(change), change, gap_limit, used, &mut found)?;
+        }
+
+        if found.is_empty() {
+            break;
+        }
+        reports.push(AccountReport {
+            purpose,
+            account,
+            path,
+            xpub: ExtendedPrivateKey { network, ..account_key }.to_xpub(),
+            used: found
+        });
+        account += 1;
+    }
+    return Ok(reports);
+}
+
+pub fn discover(master: &ExtendedPrivateKey, network: Network, purposes: &[Purpose], gap_limit: u32, used: &HashSet<String>) -> Result<Vec<AccountReport>, DiscoveryError> {
+    let mut reports: Vec<AccountReport> = vec![];
+    for purpose in purposes.iter() {
+        reports.extend(discover_purpose(master, network, *purpose, gap_limit, used)?);
+    }
+    return Ok(reports);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::discovery::*;
+    use crate::bip32::{ExtendedPrivateKey, parse_path};
+    use crate::bip39;
+    use crate::base16;
+
+    fn master() -> ExtendedPrivateKey {
+        let seed = bip39::mnemonic_to_seed("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about", "");
+        return ExtendedPrivateKey::from_seed(&seed);
+    }
+
+    fn address_at(path: &str, network: Network) -> String {
+        return purpose_address_at(Purpose::Bip44, path, network);
+    }
+
+    fn purpose_address_at(purpose: Purpose, path: &str, network: Network) -> String {
+        let key = master().derive_path(&parse_path(path).unwrap());
+        return derive_address(purpose, network, &key).unwrap();
+    }
+
+    #[test]
+    fn discovery_bip44_addresses() {
+        assert_eq!(address_at("m/44'/0'/0'/0/0", Network::Bitcoin), "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA");
+        assert_eq!(address_at("m/44'/60'/0'/0/0", Network::Ethereum), "0x9858EfFD232B4033E47d90003D41EC34EcaEda94");
+    }
+
+    // BIP49 vector, on testnet
+    #[test]
+    fn discovery_bip49_addresses() {
+        let key = master().derive_path(&parse_path("m/49'/1'/0'/0/0").unwrap());
+        assert_eq!(base16::encode_bytes(&key.compressed_public_key()), "03a1af804ac108a8a51782198c2d034b28bf90c8803f5a53f76276fa69a4eae77f");
+        let redeem_script: Vec<u8> = bitcoin::get_p2wpkh_redeem_script(&crate::crypto::secp256k1::public_key(&key.private_key));
+        assert_eq!(base16::encode_bytes(&redeem_script), "001438971f73930f6c141d977ac4fd4a727c854935b3");
+        assert_eq!(base16::encode_bytes(&bitcoin::get_script_hash(&redeem_script)), "336caa13e08b96080a32b5d818d59b4ab3b36742");
+        assert_eq!(purpose_address_at(Purpose::Bip49, "m/49'/1'/0'/0/0", Network::Testnet), "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2");
+
+        assert_eq!(purpose_address_at(Purpose::Bip49, "m/49'/0'/0'/0/0", Network::Bitcoin), "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf");
+    }
+
+    #[test]
+    fn discovery_bip84_addresses() {
+        assert_eq!(purpose_address_at(Purpose::Bip84, "m/84'/0'/0'/0/0", Network::Bitcoin), "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+        assert_eq!(purpose_address_at(Purpose::Bip84, "m/84'/0'/0'/0/1", Network::Bitcoin), "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g");
+        assert_eq!(purpose_address_at(Purpose::Bip84, "m/84'/0'/0'/1/0", Network::Bitcoin), "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el");
+
+        let used: HashSet<String> = [normalize_address("BC1QNJG0JD8228AQ7EGYZACY8CYS3KNF9XVRERKF9G")].into_iter().collect();
+        let reports = discover(&master(), Network::Bitcoin, &supported_purposes(Network::Bitcoin), 20, &used).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].purpose, Purpose::Bip84);
+        assert_eq!(reports[0].xpub, "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V");
+        assert_eq!(reports[0].next_index(0), 2);
+    }
+
+    #[test]
+    fn discovery_bip86_addresses() {
+        assert_eq!(purpose_address_at(Purpose::Bip86, "m/86'/0'/0'/0/0", Network::Bitcoin), "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr");
+        assert_eq!(purpose_address_at(Purpose::Bip86, "m/86'/0'/0'/0/1", Network::Bitcoin), "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh");
+        assert_eq!(purpose_address_at(Purpose::Bip86, "m/86'/0'/0'/1/0", Network::Bitcoin), "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7");
+        assert_eq!(master().derive_path(&parse_path("m/86'/0'/0'").unwrap()).to_xpub(), "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ");
+    }
+
+    #[test]
+    fn discovery_gap_limit() {
+        let used: HashSet<String> = [
+            address_at("m/44'/0'/0'/0/0", Network::Bitcoin),
+            address_at("m/44'/0'/0'/0/5", Network::Bitcoin),
+            address_at("m/44'/0'/0'/1/2", Network::Bitcoin),
+            address_at("m/44'/0'/1'/0/1", Network::Bitcoin),
+            address_at("m/44'/0'/3'/0/0", Network::Bitcoin)
+        ].into_iter().collect();
+
+        let narrow = discover_purpose(&master(), Network::Bitcoin, Purpose::Bip44, 4, &used).unwrap();
+        assert_eq!(narrow.len(), 2);
+        assert_eq!(narrow[0].used.iter().map(|u| (u.change, u.index)).collect::<Vec<(u32, u32)>>(), vec![(0, 0), (1, 2)]);
+        assert_eq!(narrow[1].account, 1);
+
+        let wide = discover_purpose(&master(), Network::Bitcoin, Purpose::Bip44, 5, &used).unwrap();
+        assert_eq!(wide[0].used.iter().map(|u| (u.change, u.index)).collect::<Vec<(u32, u32)>>(), vec![(0, 0), (0, 5), (1, 2)]);
+        assert_eq!(wide[0].next_index(0), 6);
+        assert_eq!(wide.len(), 2);
+    }
+
+    #[test]
+    fn discovery_parse_used_addresses() {
+        let content = "address,txid,amount\n\"1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA\",aa00,0.1\n# comment\n\n0x9858EfFD232B4033E47d90003D41EC34EcaEda94\n";
+        let used = parse_used_addresses(content);
+
+        assert!(used.contains("1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"));
+        assert!(used.contains("0x9858effd232b4033e47d90003d41ec34ecaeda94"));
+        assert!(!used.contains("1lqbgskux5yyuonjxt5qgfpusxkyywweaba"));
+    }
+
+    #[test]
+    fn discovery_unsupported_purpose() {
+        let used: HashSet<String> = HashSet::new();
+        assert_eq!(discover_purpose(&master(), Network::Ethereum, Purpose::Bip84, 20, &used).unwrap_err(), DiscoveryError::UnsupportedPurpose(Purpose::Bip84, Network::Ethereum));
+    }
+}
@@ -0,0 +1,327 @@
+use primitive_types::{U256, U512};
+use crate::app::Network;
+use crate::block::{BlockError, BlockHeader, HEADER_SIZE};
+use crate::transaction;
+
+pub const RETARGET_INTERVAL: u32 = 2016;
+// two weeks, ten minutes a block
+pub const TARGET_TIMESPAN: u32 = 14 * 24 * 60 * 60;
+pub const TARGET_SPACING: u32 = 10 * 60;
+const MEDIAN_TIME_SPAN: usize = 11;
+
+// consensus rules for the proof of work that differ between the bitcoin networks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub struct ChainParams {
+    // the easiest target allowed, in compact form
+    pub pow_limit: u32,
+    // testnet: a block more than twenty minutes after its parent may use the easiest target
+    pub allow_min_difficulty_blocks: bool,
+    // regtest keeps the target of the genesis block
+    pub no_retargeting: bool,
+    // heights and block hashes, as bitcoin core hardcodes them
+    pub checkpoints: &'static [(u32, &'static str)]
+}
+
+pub const MAINNET_CHAIN: ChainParams = ChainParams {
+    pow_limit: 0x1d00ffff,
+    allow_min_difficulty_blocks: false,
+    no_retargeting: false,
+    checkpoints: &[
+        (0, "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"),
+        (11111, "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d"),
+        (33333, "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6"),
+        (74000, "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20"),
+        (105000, "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97"),
+        (134444, "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe"),
+        (168000, "000000000000099e61ea72015e79632f216fe6cb33d7899acb35b75c8303b763"),
+        (193000, "000000000000059f452a5f7340de6682a977387c17010ff6e6c3bd83ca8b1317"),
+        (210000, "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e"),
+        (216116, "00000000000001b4f4b433e81ee46494af945cf96014816a4e2370f11b23df4e"),
+        (225430, "00000000000001c108384350f74090433e7fcf79a606b8e797f065b130575932"),
+        (250000, "000000000000003887df1f29024b06fc2200b55f8af8f35453d7be294df2d214"),
+        (279000, "0000000000000001ae8c72a0b0c301f67e3afca10e819efa9041e458e9bd7e40"),
+        (295000, "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983")
+    ]
+};
+
+pub const TESTNET_CHAIN: ChainParams = ChainParams {
+    pow_limit: 0x1d00ffff,
+    allow_min_difficulty_blocks: true,
+    no_retargeting: false,
+    checkpoints: &[
+        (0, "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"),
+        (546, "000000002a936ca763904c3c35fce2f3556c559c0214345d31b1bcebf76acb70")
+    ]
+};
+
+pub const SIGNET_CHAIN: ChainParams = ChainParams {
+    pow_limit: 0x1e0377ae,
+    allow_min_difficulty_blocks: false,
+    no_retargeting: false,
+    checkpoints: &[(0, "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6")]
+};
+
+pub const REGTEST_CHAIN: ChainParams = ChainParams {
+    pow_limit: 0x207fffff,
+    allow_min_difficulty_blocks: true,
+    no_retargeting: true,
+    checkpoints: &[(0, "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206")]
+};
+
+pub fn chain_params(network: Network) -> Option<ChainParams> {
+    match network {
+        Network::Bitcoin => return Some(MAINNET_CHAIN),
+        Network::Testnet => return Some(TESTNET_CHAIN),
+        Network::Signet => return Some(SIGNET_CHAIN),
+        Network::Regtest => return Some(REGTEST_CHAIN),
+        Network::Ethereum => return None,
+    };
+}
+
+impl ChainParams {
+    pub fn checkpoint(&self, height: u32) -> Option<[u8; 32]> {
+        return self.checkpoints.iter()
+            .find(|(h, _)| *h == height)
+            .map(|(_, hash)| transaction::hash_from_hex(hash).expect("checkpoint"));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum HeaderError {
+    Block(BlockError),
+    // the heights below are those of the offending header
+    InvalidLength(usize),
+    InvalidBits(u32),
+    HighHash(u32),
+    PrevHashMismatch(u32),
+    // the bits a header should have carried
+    BadDifficulty(u32, u32),
+    TimeTooOld(u32),
+    CheckpointMismatch(u32),
+    // HeaderChain::new only starts from a checkpoint
+    NotACheckpoint(u32),
+    // a retarget needs the first header of the period, which lies before the anchor
+    MissingHistory(u32)
+}
+
+impl From<BlockError> for HeaderError {
+    fn from(e: BlockError) -> Self {
+        return HeaderError::Block(e);
+    }
+}
+
+// None for a negative or overflowing encoding
+pub fn target_from_bits(bits: u32) -> Option<U256> {
+    let exponent: u32 = bits >> 24;
+    let mantissa: u32 = bits & 0x007fffff;
+    if mantissa != 0 && (bits & 0x00800000 != 0 || exponent > 34 || (mantissa > 0xff && exponent > 33) || (mantissa > 0xffff && exponent > 32)) {
+        return None;
+    }
+    if exponent <= 3 {
+        return Some(U256::from(mantissa >> (8 * (3 - exponent))));
+    }
+    return Some(U256::from(mantissa) << (8 * (exponent - 3)));
+}
+
+// rounds down to the three significant bytes the encoding keeps
+pub fn bits_from_target(target: U256) -> u32 {
+    let mut size: u32 = target.bits().div_ceil(8) as u32;
+    let mut mantissa: u32 = if size <= 3 { target.low_u32() << (8 * (3 - size)) } else { (target >> (8 * (size - 3))).low_u32() };
+    // the top bit of the mantissa would read as a sign
+    if mantissa & 0x00800000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    return mantissa | (size << 24);
+}
+
+// the expected number of hashes to find a block, 2^256 / (target + 1)
+pub fn work_from_bits(bits: u32) -> U256 {
+    match target_from_bits(bits) {
+        Some(target) if !target.is_zero() => return (!target / (target + 1)) + 1,
+        _ => return U256::zero(),
+    };
+}
+
+pub fn check_proof_of_work(header: &BlockHeader, params: &ChainParams) -> bool {
+    match target_from_bits(header.bits) {
+        Some(target) if !target.is_zero() && target <= target_from_bits(params.pow_limit).expect("pow-limit") => {
+            return U256::from_little_endian(&header.block_hash()) <= target;
+        },
+        _ => return false,
+    };
+}
+
+// the bits after a period that took actual_timespan seconds, which counts for at most a factor of four
+pub fn retarget(bits: u32, actual_timespan: u32, params: &ChainParams) -> u32 {
+    let timespan: u32 = actual_timespan.clamp(TARGET_TIMESPAN / 4, TARGET_TIMESPAN * 4);
+    // wider than the target so that easy regtest-like limits cannot overflow
+    let target: U512 = U512::from(target_from_bits(bits).unwrap_or_default()) * timespan / TARGET_TIMESPAN;
+    let limit: U256 = target_from_bits(params.pow_limit).expect("pow-limit");
+    return bits_from_target(U256::try_from(target).unwrap_or(limit).min(limit));
+}
+
+pub fn read_headers(data: &[u8]) -> Result<Vec<BlockHeader>, HeaderError> {
+    if data.is_empty() || !data.len().is_multiple_of(HEADER_SIZE) {
+        return Err(HeaderError::InvalidLength(data.len()));
+    }
+    let mut headers: Vec<BlockHeader> = vec![];
+    for chunk in data.chunks(HEADER_SIZE) {
+        headers.push(BlockHeader::deserialize(chunk)?);
+    }
+    return Ok(headers);
+}
+
+// headers checked one by one from a trusted anchor, usually a checkpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct HeaderChain {
+    pub params: ChainParams,
+    pub anchor_height: u32,
+    pub headers: Vec<BlockHeader>,
+    // the work of every header after the anchor
+    pub chain_work: U256,
+    tip_hash: [u8; 32]
+}
+
+impl HeaderChain {
+    // anchored to the checkpoint at height
+    pub fn new(anchor: &BlockHeader, height: u32, params: &ChainParams) -> Result<Self, HeaderError> {
+        match params.checkpoint(height) {
+            None => return Err(HeaderError::NotACheckpoint(height)),
+            Some(hash) if hash != anchor.block_hash() => return Err(HeaderError::CheckpointMismatch(height)),
+            Some(_) => return Self::from_trusted(anchor, height, params),
+        };
+    }
+
+    // anchored to a header the caller vouches for, at a height with or without a checkpoint
+    pub fn from_trusted(anchor: &BlockHeader, height: u32, params: &ChainParams) -> Result<Self, HeaderError> {
+        if params.checkpoint(height).is_some_and(|hash: [u8; 32]| hash != anchor.block_hash()) {
+            return Err(HeaderError::CheckpointMismatch(height));
+        }
+        return Ok(Self { params: *params, anchor_height: height, headers: vec![*anchor], chain_work: U256::zero(), tip_hash: anchor.block_hash() });
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.anchor_height + self.headers.len() as u32 - 1;
+    }
+
+    pub fn tip(&self) -> &BlockHeader {
+        return &self.headers[self.headers.len() - 1];
+    }
+
+    pub fn tip_hash(&self) -> [u8; 32] {
+        return self.tip_hash;
+    }
+
+    pub fn header_at(&self, height: u32) -> Option<&BlockHeader> {
+        return self.headers.get(height.checked_sub(self.anchor_height)? as usize);
+    }
+
+    // the median time of the last eleven headers, or of as many as are known
+    pub fn median_time_past(&self) -> u32 {
+        let mut times: Vec<u32> = self.headers.iter().rev().take(MEDIAN_TIME_SPAN).map(|h: &BlockHeader| h.time).collect();
+        times.sort();
+        return times[times.len() / 2];
+    }
+
+    // the bits the next header must carry, following bitcoin core's GetNextWorkRequired
+    pub fn next_bits(&self, time: u32) -> Result<u32, HeaderError> {
+        let height: u32 = self.height() + 1;
+        let last: &BlockHeader = self.tip();
+        if self.params.no_retargeting {
+            return Ok(last.bits);
+        }
+        if !height.is_multiple_of(RETARGET_INTERVAL) {
+            if !self.params.allow_min_difficulty_blocks {
+                return Ok(last.bits);
+            }
+            if time as u64 > last.time as u64 + 2 * TARGET_SPACING as u64 {
+                return Ok(self.params.pow_limit);
+            }
+            // the last header that was not mined at the easiest target, or the start of the period
+            let mut h: u32 = height - 1;
+            while h > self.anchor_height && !h.is_multiple_of(RETARGET_INTERVAL) && self.header_at(h).expect("header").bits == self.params.pow_limit {
+                h -= 1;
+            }
+            return Ok(self.header_at(h).expect("header").bits);
+        }
+        let first: &BlockHeader = self.header_at(height - RETARGET_INTERVAL).ok_or(HeaderError::MissingHistory(height))?;
+        return Ok(retarget(last.bits, last.time.saturating_sub(first.time), &self.params));
+    }
+
+    pub fn push(&mut self, header: &BlockHeader) -> Result<(), HeaderError> {
+        let height: u32 = self.height() + 1;
+        if header.prev_blockhash != self.tip_hash {
+            return Err(HeaderError::PrevHashMismatch(height));
+        }
+        if target_from_bits(header.bits).is_none_or(|t: U256| t.is_zero()) {
+            return Err(HeaderError::InvalidBits(height));
+        }
+        let expected: u32 = self.next_bits(header.time)?;
+        if header.bits != expected {
+            return Err(HeaderError::BadDifficulty(height, expected));
+        }
+        if !check_proof_of_work(header, &self.params) {
+            return Err(HeaderError::HighHash(height));
+        }
+        if header.time <= self.median_time_past() {
+            return Err(HeaderError::TimeTooOld(height));
+        }
+        let hash: [u8; 32] = header.block_hash();
+        if self.params.checkpoint(height).is_some_and(|h: [u8; 32]| h != hash) {
+            return Err(HeaderError::CheckpointMismatch(height));
+        }
+        self.headers.push(*header);
+        self.chain_work += work_from_bits(header.bits);
+        self.tip_hash = hash;
+        return Ok(());
+    }
+
+    pub fn extend(&mut self, headers: &[BlockHeader]) -> Result<(), HeaderError> {
+        for header in headers.iter() {
+            self.push(header)?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headers::*;
+
+    #[test]
+    fn headers_compact_targets() {
+        // from bitcoin core's arith_uint256 tests
+        for (bits, target) in [(0x01003456, 0x00u64), (0x01123456, 0x12), (0x02008000, 0x80), (0x05009234, 0x92340000), (0x04123456, 0x12345600)] {
+            assert_eq!(target_from_bits(bits), Some(U256::from(target)));
+        }
+        assert_eq!(target_from_bits(0x04923456), None);
+        assert_eq!(target_from_bits(0xff123456), None);
+        assert_eq!(target_from_bits(0x1d00ffff), Some(U256::from(0xffff) << 208));
+        for bits in [0x1d00ffff, 0x1b0404cb, 0x1e0377ae, 0x207fffff, 0x05009234] {
+            assert_eq!(bits_from_target(target_from_bits(bits).unwrap()), bits);
+        }
+        assert_eq!(bits_from_target(U256::from(0x80)), 0x02008000);
+        assert_eq!(bits_from_target(U256::zero()), 0);
+
+        // a block at the easiest mainnet target is worth 2^32 + 2^16 + 1 hashes
+        assert_eq!(work_from_bits(0x1d00ffff), U256::from(0x100010001u64));
+        assert_eq!(work_from_bits(0x207fffff), U256::from(2));
+    }
+
+    #[test]
+    fn headers_retarget() {
+        // the first two signet retargets, from rust-bitcoin's tests
+        assert_eq!(retarget(503543726, 1599332177 - 1598918400, &SIGNET_CHAIN), 503394215);
+        assert_eq!(retarget(503394215, 1600591200 - 1599332844, &SIGNET_CHAIN), 503397348);
+        // clamped to a factor of four either way, and never easier than the limit
+        let target: U256 = target_from_bits(0x1b0404cb).unwrap();
+        assert_eq!(retarget(0x1b0404cb, 1, &MAINNET_CHAIN), bits_from_target(target / 4));
+        assert_eq!(retarget(0x1b0404cb, TARGET_TIMESPAN * 10, &MAINNET_CHAIN), bits_from_target(target * 4));
+        assert_eq!(retarget(0x1d00ffff, TARGET_TIMESPAN * 2, &MAINNET_CHAIN), 0x1d00ffff);
+        assert_eq!(retarget(0x1d00ffff, TARGET_TIMESPAN, &MAINNET_CHAIN), 0x1d00ffff);
+    }
+}
@@ -0,0 +1,211 @@
+use primitive_types::U256;
+use secp256k1::SecretKey;
+use crate::app::{Network, MAINNET_PARAMS, TESTNET_PARAMS};
+use crate::base16;
+use crate::crypto::hashing;
+use crate::base58::{self, Base58CheckError};
+use crate::base64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum KeyFormat {
+    Hex,
+    Wif,
+    // Casascius mini private key
+    MiniKey,
+    Base64,
+    Decimal
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct ImportedKey {
+    pub secret_key: [u8; 32],
+    pub format: KeyFormat,
+    // only a WIF says whether its address uses the compressed public key; mini keys never do
+    pub compressed: bool,
+    // testnet, signet and regtest share the WIF prefix, reported as testnet
+    pub network: Option<Network>
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum ImportError {
+    UnknownFormat,
+    InvalidChecksum,
+    InvalidWifVersion(u8),
+    InvalidWifLength(usize),
+    // a mini key whose sha256(key + "?") does not start with a zero byte
+    InvalidMiniKey,
+    InvalidLength(usize),
+    // zero or not below the curve order
+    OutOfRange,
+    // digits that also read as hex or as a WIF; hex needs its 0x prefix then
+    AmbiguousFormat
+}
+
+fn is_base58(s: &str) -> bool {
+    return s.chars().all(|c: char| c.is_ascii_alphanumeric() && !"0OIl".contains(c));
+}
+
+fn checked(secret_key: &[u8], format: KeyFormat, compressed: bool, network: Option<Network>) -> Result<ImportedKey, ImportError> {
+    if secret_key.len() != 32 {
+        return Err(ImportError::InvalidLength(secret_key.len()));
+    }
+    if SecretKey::from_slice(secret_key).is_err() {
+        return Err(ImportError::OutOfRange);
+    }
+    return Ok(ImportedKey { secret_key: secret_key.try_into().expect("private-key"), format, compressed, network });
+}
+
+fn decode_wif(input: &str) -> Result<ImportedKey, ImportError> {
+    let data: Vec<u8> = match base58::decode_check(input) {
+        Ok(d) => d,
+        Err(Base58CheckError::InvalidChecksum) => return Err(ImportError::InvalidChecksum),
+        Err(_) => return Err(ImportError::UnknownFormat)
+    };
+    let network: Network = match data[0] {
+        v if v == MAINNET_PARAMS.wif_prefix => Network::Bitcoin,
+        v if v == TESTNET_PARAMS.wif_prefix => Network::Testnet,
+        v => return Err(ImportError::InvalidWifVersion(v))
+    };
+    // a compressed key carries a trailing 0x01
+    match data.len() {
+        33 => return checked(&data[1..], KeyFormat::Wif, false, Some(network)),
+        34 if data[33] == 0x01 => return checked(&data[1..33], KeyFormat::Wif, true, Some(network)),
+        n => return Err(ImportError::InvalidWifLength(n)),
+    };
+}
+
+// 22, 26 or 30 base58 characters starting with S; the key is sha256 of the string
+fn decode_mini_key(input: &str) -> Result<ImportedKey, ImportError> {
+    if hashing::sha256(format!("{}?", input).as_bytes())[0] != 0x00 {
+        return Err(ImportError::InvalidMiniKey);
+    }
+    return checked(&hashing::sha256(input.as_bytes()), KeyFormat::MiniKey, false, None);
+}
+
+fn decode_decimal(input: &str) -> Result<ImportedKey, ImportError> {
+    let n: U256 = U256::from_dec_str(input).map_err(|_| ImportError::OutOfRange)?;
+    let mut r: [u8; 32] = [0; 32];
+    n.to_big_endian(&mut r);
+    return checked(&r, KeyFormat::Decimal, true, None);
+}
+
+// hex, WIF, mini key, decimal or base64, told apart by length and alphabet
+pub fn parse_private_key(input: &str) -> Result<ImportedKey, ImportError> {
+    let input: &str = input.trim();
+    let digits: bool = !input.is_empty() && input.chars().all(|c: char| c.is_ascii_digit());
+    if let Some(hex) = input.strip_prefix("0x") {
+        if hex.len() == 64 && hex.chars().all(|c: char| c.is_ascii_hexdigit()) {
+            return checked(&base16::decode_string(&hex.to_lowercase()), KeyFormat::Hex, true, None);
+        }
+    }
+    if input.len() == 64 && input.chars().all(|c: char| c.is_ascii_hexdigit()) {
+        if digits {
+            return Err(ImportError::AmbiguousFormat);
+        }
+        return checked(&base16::decode_string(&input.to_lowercase()), KeyFormat::Hex, true, None);
+    }
+    if input.starts_with('S') && [22, 26, 30].contains(&input.len()) && is_base58(input) {
+        return decode_mini_key(input);
+    }
+    if (input.len() == 51 || input.len() == 52) && is_base58(input) {
+        // digits only count as a WIF when the checksum holds, and then they are ambiguous
+        if !digits {
+            return decode_wif(input);
+        }
+        if base58::decode_check(input).is_ok() {
+            return Err(ImportError::AmbiguousFormat);
+        }
+    }
+    if digits && input.len() <= 78 {
+        return decode_decimal(input);
+    }
+    return match base64::decode_string(input) {
+        Ok(data) => checked(&data, KeyFormat::Base64, true, None),
+        Err(_) => Err(ImportError::UnknownFormat)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::*;
+
+    const KEY: &str = "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d";
+
+    fn key(s: &str) -> [u8; 32] {
+        return base16::decode_string(s).try_into().unwrap();
+    }
+
+    #[test]
+    fn import_hex_and_wif() {
+        let imported: ImportedKey = parse_private_key(KEY).unwrap();
+        assert_eq!((imported.secret_key, imported.format, imported.compressed), (key(KEY), KeyFormat::Hex, true));
+        assert_eq!(parse_private_key(&format!("0x{}", KEY.to_uppercase())).unwrap().secret_key, key(KEY));
+
+        let imported: ImportedKey = parse_private_key("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ").unwrap();
+        assert_eq!(imported, ImportedKey { secret_key: key(KEY), format: KeyFormat::Wif, compressed: false, network: Some(Network::Bitcoin) });
+        let imported: ImportedKey = parse_private_key("KwntMbt59tTsj8xqpqYqRRWufyjGunvhSyeMo3NTYpFYzZbXJ5Hp").unwrap();
+        assert_eq!((imported.secret_key, imported.compressed), ([0x11; 32], true));
+
+        for compressed in [false, true] {
+            let mut payload: Vec<u8> = vec![0xef];
+            payload.extend_from_slice(&key(KEY));
+            if compressed {
+                payload.push(0x01);
+            }
+            let imported: ImportedKey = parse_private_key(&base58::encode_check(&payload)).unwrap();
+            assert_eq!((imported.compressed, imported.network), (compressed, Some(Network::Testnet)));
+        }
+
+        assert_eq!(parse_private_key("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTj"), Err(ImportError::InvalidChecksum));
+        let mut payload: Vec<u8> = vec![0x80];
+        payload.extend_from_slice(&key(KEY));
+        payload.push(0x02);
+        assert_eq!(parse_private_key(&base58::encode_check(&payload)), Err(ImportError::InvalidWifLength(34)));
+        payload[0] = 0x1e;
+        payload.pop();
+        assert_eq!(parse_private_key(&base58::encode_check(&payload)), Err(ImportError::InvalidWifVersion(0x1e)));
+    }
+
+    #[test]
+    fn import_other_formats() {
+        // mini key examples from the bitcoin wiki
+        let imported: ImportedKey = parse_private_key("S6c56bnXQiBjk9mqSYE7ykVQ7NzrRy").unwrap();
+        assert_eq!((imported.secret_key, imported.format, imported.compressed), (key("4c7a9640c72dc2099f23715d0c8a0d8a35f8906e3cab61dd3f78b67bf887c9ab"), KeyFormat::MiniKey, false));
+        assert_eq!(parse_private_key("SzavMBLoXU6kDrqtUVmffv").unwrap().format, KeyFormat::MiniKey);
+        assert_eq!(parse_private_key("S6c56bnXQiBjk9mqSYE7ykVQ7NzrRz"), Err(ImportError::InvalidMiniKey));
+
+        let imported: ImportedKey = parse_private_key("DCj8o4bHoidgCy/lC3yuEeyG078fvkcb6Jgn4Z1yqh0=").unwrap();
+        assert_eq!((imported.secret_key, imported.format), (key(KEY), KeyFormat::Base64));
+        assert_eq!(parse_private_key("Zm9vYmFy"), Err(ImportError::InvalidLength(6)));
+
+        let imported: ImportedKey = parse_private_key("5500171714335001507730457227127633683517613019341760098818554179534751705629").unwrap();
+        assert_eq!((imported.secret_key, imported.format), (key(KEY), KeyFormat::Decimal));
+        assert_eq!(parse_private_key("1").unwrap().secret_key[31], 1);
+
+        // the range is [1, n - 1]
+        assert_eq!(parse_private_key("115792089237316195423570985008687907852837564279074904382605163141518161494336").unwrap().secret_key[31], 0x40);
+        assert_eq!(parse_private_key("115792089237316195423570985008687907852837564279074904382605163141518161494337"), Err(ImportError::OutOfRange));
+        assert_eq!(parse_private_key("0"), Err(ImportError::OutOfRange));
+        assert_eq!(parse_private_key(&format!("0x{}", "0".repeat(64))), Err(ImportError::OutOfRange));
+        assert_eq!(parse_private_key("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"), Err(ImportError::OutOfRange));
+        assert_eq!(parse_private_key("not a key"), Err(ImportError::UnknownFormat));
+    }
+
+    #[test]
+    fn import_digits_only() {
+        // 64 digits read as hex and as decimal give different keys
+        let decimal: &str = "1000000000000000000000000000000000000000000000000000000000000001";
+        assert_eq!(parse_private_key(decimal), Err(ImportError::AmbiguousFormat));
+        assert_eq!(parse_private_key(&format!("0x{}", decimal)).unwrap().format, KeyFormat::Hex);
+
+        // 51 digits with no valid WIF checksum are a decimal key
+        let decimal: &str = "123456789012345678901234567890123456789012345678901";
+        let imported: ImportedKey = parse_private_key(decimal).unwrap();
+        assert_eq!(imported.format, KeyFormat::Decimal);
+        assert_eq!(U256::from_big_endian(&imported.secret_key), U256::from_dec_str(decimal).unwrap());
+        assert_eq!(parse_private_key(&format!("{}2", decimal)).unwrap().format, KeyFormat::Decimal);
+    }
+}
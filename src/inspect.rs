@@ -0,0 +1,234 @@
+use std::fmt;
+use crate::app::Network;
+use crate::base16;
+use crate::base58::{self, Base58CheckError};
+use crate::bech32::{self, Bech32Error, Variant};
+use crate::crypto::ethereum;
+use crate::script::{Builder, Opcode, Script};
+
+const BITCOIN_NETWORKS: [Network; 4] = [Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum Encoding {
+    Base58Check,
+    Bech32,
+    Bech32m,
+    Eip55,
+    // an all-lowercase or all-uppercase ethereum address carries no checksum
+    Hex
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encoding::Base58Check => return write!(f, "Base58Check"),
+            Encoding::Bech32 => return write!(f, "Bech32"),
+            Encoding::Bech32m => return write!(f, "Bech32m"),
+            Encoding::Eip55 => return write!(f, "EIP-55"),
+            Encoding::Hex => return write!(f, "Hex (no checksum)"),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct AddressInfo {
+    pub encoding: Encoding,
+    // testnet and signet share their prefixes, so an address can belong to several networks
+    pub networks: Vec<Network>,
+    pub address_type: String,
+    pub witness_version: Option<u8>,
+    // the key hash, script hash or witness program
+    pub payload: Vec<u8>,
+    pub script_pubkey: Option<Script>
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum InspectError {
+    UnknownFormat,
+    Base58(Base58CheckError),
+    UnknownVersion(u8),
+    InvalidPayloadLength(usize),
+    UnknownHrp(String),
+    Bech32(Bech32Error),
+    // positions of the likely mistyped characters
+    Bech32Checksum(Vec<usize>),
+    Eip55Checksum(Vec<usize>)
+}
+
+pub fn inspect(address: &str) -> Result<AddressInfo, InspectError> {
+    let lower: String = address.to_lowercase();
+    if lower.starts_with("0x") {
+        return inspect_ethereum(address);
+    }
+
+    let hrp: Option<&str> = lower.rfind('1').map(|i: usize| &lower[..i]);
+    let known_hrp: bool = BITCOIN_NETWORKS.iter().any(|n: &Network| Some(n.bitcoin_params().expect("bitcoin-network").bech32_hrp) == hrp);
+    if known_hrp {
+        return inspect_bech32(address);
+    }
+
+    return match base58::decode_check(address) {
+        Err(Base58CheckError::InvalidEncoding) => match bech32::decode(address) {
+            Ok((hrp, _, _)) => Err(InspectError::UnknownHrp(hrp)),
+            Err(_) => Err(InspectError::UnknownFormat)
+        },
+        r => inspect_base58(r.map_err(InspectError::Base58)?)
+    };
+}
+
+fn inspect_base58(payload: Vec<u8>) -> Result<AddressInfo, InspectError> {
+    if payload.len() != 21 {
+        return Err(InspectError::InvalidPayloadLength(payload.len()));
+    }
+    let version: u8 = payload[0];
+    let hash: &[u8] = &payload[1..];
+
+    let p2pkh: Vec<Network> = BITCOIN_NETWORKS.iter().copied().filter(|n: &Network| n.bitcoin_params().expect("bitcoin-network").p2pkh_version == version).collect();
+    let p2sh: Vec<Network> = BITCOIN_NETWORKS.iter().copied().filter(|n: &Network| n.bitcoin_params().expect("bitcoin-network").p2sh_version == version).collect();
+
+    let (networks, script): (Vec<Network>, Script) = if !p2pkh.is_empty() {
+        (p2pkh, Builder::new()
+            .push_opcode(Opcode::Dup)
+            .push_opcode(Opcode::Hash160)
+            .push_slice(hash)
+            .push_opcode(Opcode::EqualVerify)
+            .push_opcode(Opcode::CheckSig)
+            .into_script())
+    }
+    else if !p2sh.is_empty() {
+        (p2sh, Builder::new()
+            .push_opcode(Opcode::Hash160)
+            .push_slice(hash)
+            .push_opcode(Opcode::Equal)
+            .into_script())
+    }
+    else {
+        return Err(InspectError::UnknownVersion(version));
+    };
+
+    return Ok(AddressInfo {
+        encoding: Encoding::Base58Check,
+        networks,
+        address_type: script.script_type().to_string(),
+        witness_version: None,
+        payload: hash.to_vec(),
+        script_pubkey: Some(script)
+    });
+}
+
+fn inspect_bech32(address: &str) -> Result<AddressInfo, InspectError> {
+    let (hrp, _, variant) = match bech32::decode(address) {
+        Ok(d) => d,
+        Err(Bech32Error::InvalidChecksum) => return Err(InspectError::Bech32Checksum(bech32::locate_error(address))),
+        Err(e) => return Err(InspectError::Bech32(e))
+    };
+    let (version, program) = bech32::decode_segwit_address(&hrp, address).map_err(InspectError::Bech32)?;
+
+    let networks: Vec<Network> = BITCOIN_NETWORKS.iter().copied().filter(|n: &Network| n.bitcoin_params().expect("bitcoin-network").bech32_hrp == hrp).collect();
+    let script: Script = Script::from_bytes(&bech32::witness_script(version, &program));
+    return Ok(AddressInfo {
+        encoding: if variant == Variant::Bech32 { Encoding::Bech32 } else { Encoding::Bech32m },
+        networks,
+        address_type: script.script_type().to_string(),
+        witness_version: Some(version),
+        payload: program,
+        script_pubkey: Some(script)
+    });
+}
+
+fn inspect_ethereum(address: &str) -> Result<AddressInfo, InspectError> {
+    let digits: &str = &address[2..];
+    if address.len() != 42 || !address.starts_with("0x") || !digits.chars().all(|c: char| c.is_ascii_hexdigit()) {
+        return Err(InspectError::UnknownFormat);
+    }
+
+    let unchecksummed: bool = digits == digits.to_lowercase() || digits == digits.to_uppercase();
+    if !unchecksummed {
+        let expected: String = ethereum::check_sum(address);
+        let wrong: Vec<usize> = address.chars().zip(expected.chars()).enumerate()
+            .filter(|(_, (a, b)): &(usize, (char, char))| a != b)
+            .map(|(i, _): (usize, (char, char))| i)
+            .collect();
+        if !wrong.is_empty() {
+            return Err(InspectError::Eip55Checksum(wrong));
+        }
+    }
+
+    return Ok(AddressInfo {
+        encoding: if unchecksummed { Encoding::Hex } else { Encoding::Eip55 },
+        networks: vec![Network::Ethereum],
+        address_type: String::from("Account"),
+        witness_version: None,
+        payload: base16::decode_string(&digits.to_lowercase()),
+        script_pubkey: None
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inspect::*;
+    use crate::base16;
+
+    #[test]
+    fn inspect_base58_addresses() {
+        let info = inspect("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH").unwrap();
+        assert_eq!(info.encoding, Encoding::Base58Check);
+        assert_eq!(info.networks, vec![Network::Bitcoin]);
+        assert_eq!(info.address_type, "P2PKH");
+        assert_eq!(base16::encode_bytes(&info.payload), "751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(info.script_pubkey.unwrap().to_hex(), "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac");
+
+        let info = inspect("2N2JD6wb56AfK4tfmM6PwdVmoYk2dCKf4Br").unwrap();
+        assert_eq!(info.networks, vec![Network::Testnet, Network::Signet, Network::Regtest]);
+        assert_eq!(info.address_type, "P2SH");
+
+        assert_eq!(inspect("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMh"), Err(InspectError::Base58(Base58CheckError::InvalidChecksum)));
+        // a mainnet WIF is valid Base58Check but not an address
+        assert_eq!(inspect("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617"), Err(InspectError::InvalidPayloadLength(34)));
+    }
+
+    #[test]
+    fn inspect_bech32_addresses() {
+        let info = inspect("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4").unwrap();
+        assert_eq!(info.encoding, Encoding::Bech32);
+        assert_eq!(info.networks, vec![Network::Bitcoin]);
+        assert_eq!(info.address_type, "P2WPKH");
+        assert_eq!(info.witness_version, Some(0));
+        assert_eq!(base16::encode_bytes(&info.payload), "751e76e8199196d454941c45d1b3a323f1433bd6");
+
+        let info = inspect("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c").unwrap();
+        assert_eq!(info.encoding, Encoding::Bech32m);
+        assert_eq!(info.networks, vec![Network::Testnet, Network::Signet]);
+        assert_eq!(info.address_type, "P2TR");
+        assert_eq!(info.witness_version, Some(1));
+
+        assert_eq!(inspect("bc1qw508d6qejztdg4y5r3zarvary0c5xw7kv8f3t4"), Err(InspectError::Bech32Checksum(vec![13])));
+        assert_eq!(inspect("bc1zw508d6qejxtdg4y5r3zarvaryvqyzf3du"), Err(InspectError::Bech32(Bech32Error::InvalidPadding)));
+        assert_eq!(inspect("ltc1qw508d6qejxtdg4y5r3zarvary0c5xw7kgmn4n9"), Err(InspectError::UnknownHrp(String::from("ltc"))));
+    }
+
+    #[test]
+    fn inspect_ethereum_addresses() {
+        // EIP-55 vectors
+        for address in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb"
+        ] {
+            assert_eq!(ethereum::check_sum(&address.to_lowercase()), address);
+            assert_eq!(inspect(address).unwrap().encoding, Encoding::Eip55);
+        }
+
+        let info = inspect("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+        assert_eq!(info.encoding, Encoding::Hex);
+        assert_eq!(info.networks, vec![Network::Ethereum]);
+        assert_eq!(base16::encode_bytes(&info.payload), "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed");
+
+        assert_eq!(inspect("0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"), Err(InspectError::Eip55Checksum(vec![3])));
+        assert_eq!(inspect("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAe"), Err(InspectError::UnknownFormat));
+    }
+}
@@ -0,0 +1,938 @@
+use secp256k1::{ecdsa, Message, PublicKey, Secp256k1};
+use crate::crypto::hashing;
+use crate::bytes;
+use crate::script::{self, Builder, Opcode, Script};
+use crate::sighash::{self, SighashType, ANNEX_TAG};
+use crate::sign;
+use crate::taproot::{self, TAPSCRIPT_LEAF_VERSION};
+use crate::transaction::{Transaction, TxIn, TxOut, SEQUENCE_FINAL};
+
+pub const MAX_SCRIPT_SIZE: usize = 10000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+// both stacks together
+pub const MAX_STACK_SIZE: usize = 1000;
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+
+// BIP342 budget: every signature checked costs 50 of the witness size plus 50
+const VALIDATION_WEIGHT_PER_SIGOP: i64 = 50;
+const VALIDATION_WEIGHT_OFFSET: i64 = 50;
+// lock times below this are heights, above it timestamps
+const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: i64 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: i64 = 0xffff;
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum InterpreterError {
+    InputIndex(usize),
+    // the spent outputs given and the number of inputs
+    PrevoutsCount(usize, usize),
+    ScriptSize,
+    PushSize,
+    OpCount,
+    StackSize,
+    // a push running past the end of the script, or an opcode that may not run
+    BadOpcode,
+    DisabledOpcode,
+    UnbalancedConditional,
+    InvalidStackOperation,
+    InvalidAltstackOperation,
+    // a number operand longer than the opcode takes
+    NumberOverflow,
+    OpReturn,
+    // OP_VERIFY, or the opcode whose VERIFY variant failed
+    Verify(Opcode),
+    // the script left false or nothing on the stack
+    EvalFalse,
+    // a witness script left more than one element
+    CleanStack,
+    SigDer,
+    SigNullDummy,
+    SigPushOnly,
+    PubkeyCount,
+    SigCount,
+    NegativeLockTime,
+    UnsatisfiedLockTime,
+    WitnessProgramWrongLength,
+    WitnessProgramWitnessEmpty,
+    WitnessProgramMismatch,
+    // a native witness program spent with a non-empty scriptSig
+    WitnessMalleated,
+    // a P2SH witness program spent with anything but its redeem script push
+    WitnessMalleatedP2sh,
+    WitnessUnexpected,
+    TaprootWrongControlSize,
+    TapscriptMinimalIf,
+    TapscriptValidationWeight,
+    TapscriptCheckMultisig,
+    TapscriptEmptyPubkey,
+    SchnorrSigSize,
+    SchnorrSigHashType,
+    SchnorrSig
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+enum SigVersion {
+    Base,
+    WitnessV0,
+    Tapscript
+}
+
+// what signature checks commit to besides the script being run
+struct Context<'a> {
+    tx: &'a Transaction,
+    input_index: usize,
+    prevouts: &'a [TxOut],
+    annex: Option<Vec<u8>>,
+    leaf_hash: Option<[u8; 32]>,
+    code_separator_position: u32,
+    validation_weight_left: i64
+}
+
+// the opcode at pc, the data it pushes and where the next one starts; None for a
+// push running past the end
+fn read_op(script: &[u8], pc: usize) -> Option<(Opcode, &[u8], usize)> {
+    let op: Opcode = Opcode::from_byte(script[pc]);
+    let mut i: usize = pc + 1;
+    let length: usize = match op {
+        Opcode::PushBytes(n) => n as usize,
+        Opcode::PushData1 => {
+            i += 1;
+            *script.get(i - 1)? as usize
+        },
+        Opcode::PushData2 => {
+            let b: &[u8] = script.get(i..i + 2)?;
+            i += 2;
+            u16::from_le_bytes([b[0], b[1]]) as usize
+        },
+        Opcode::PushData4 => {
+            let b: &[u8] = script.get(i..i + 4)?;
+            i += 4;
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize
+        },
+        _ => 0
+    };
+    let data: &[u8] = script.get(i..i.checked_add(length)?)?;
+    return Some((op, data, i + length));
+}
+
+// push-only in the consensus sense, which lets OP_RESERVED through
+fn is_push_only(script: &[u8]) -> bool {
+    let mut pc: usize = 0;
+    while pc < script.len() {
+        match read_op(script, pc) {
+            Some((op, _, next)) if op.to_byte() <= Opcode::Num(16).to_byte() => pc = next,
+            _ => return false
+        };
+    }
+    return true;
+}
+
+fn is_p2sh(script: &[u8]) -> bool {
+    return script.len() == 23 && script[0] == Opcode::Hash160.to_byte() && script[1] == 20 && script[22] == Opcode::Equal.to_byte();
+}
+
+// BIP342 OP_SUCCESSx
+fn is_op_success(b: u8) -> bool {
+    return matches!(b, 80 | 98 | 126..=129 | 131..=134 | 137..=138 | 141..=142 | 149..=153 | 187..=254);
+}
+
+fn is_disabled(op: Opcode) -> bool {
+    return matches!(op, Opcode::Cat | Opcode::Substr | Opcode::Left | Opcode::Right | Opcode::Invert | Opcode::And | Opcode::Or | Opcode::Xor
+        | Opcode::Mul2 | Opcode::Div2 | Opcode::Mul | Opcode::Div | Opcode::Mod | Opcode::LShift | Opcode::RShift);
+}
+
+// any non-zero byte, except for negative zero
+fn cast_to_bool(data: &[u8]) -> bool {
+    for (i, b) in data.iter().enumerate() {
+        if *b != 0 {
+            return !(i == data.len() - 1 && *b == 0x80);
+        }
+    }
+    return false;
+}
+
+fn boolean(b: bool) -> Vec<u8> {
+    return if b { vec![1] } else { vec![] };
+}
+
+// the inverse of script::script_number, for operands of at most max_size bytes
+fn read_number(data: &[u8], max_size: usize) -> Result<i64, InterpreterError> {
+    if data.len() > max_size {
+        return Err(InterpreterError::NumberOverflow);
+    }
+    let mut r: i64 = 0;
+    for (i, b) in data.iter().enumerate() {
+        r |= (*b as i64) << (8 * i);
+    }
+    match data.last() {
+        Some(last) if last & 0x80 != 0 => return Ok(-(r & !(0x80 << (8 * (data.len() - 1))))),
+        _ => return Ok(r)
+    };
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, InterpreterError> {
+    return stack.pop().ok_or(InterpreterError::InvalidStackOperation);
+}
+
+// depth 1 is the top of the stack
+fn peek(stack: &[Vec<u8>], depth: usize) -> Result<&Vec<u8>, InterpreterError> {
+    if depth == 0 || depth > stack.len() {
+        return Err(InterpreterError::InvalidStackOperation);
+    }
+    return Ok(&stack[stack.len() - depth]);
+}
+
+fn require(stack: &[Vec<u8>], n: usize) -> Result<(), InterpreterError> {
+    if stack.len() < n {
+        return Err(InterpreterError::InvalidStackOperation);
+    }
+    return Ok(());
+}
+
+// removes every push of the pattern found at an opcode boundary, as legacy
+// signature checks do with the signature itself
+fn find_and_delete(script: &[u8], pattern: &[u8]) -> Vec<u8> {
+    let mut r: Vec<u8> = vec![];
+    let mut found: bool = false;
+    let mut kept: usize = 0;
+    let mut pc: usize = 0;
+    loop {
+        r.extend_from_slice(&script[kept..pc]);
+        while script[pc..].starts_with(pattern) {
+            pc += pattern.len();
+            found = true;
+        }
+        kept = pc;
+        if pc >= script.len() {
+            break;
+        }
+        match read_op(script, pc) {
+            Some((_, _, next)) => pc = next,
+            None => break
+        };
+    }
+    if !found {
+        return script.to_vec();
+    }
+    r.extend_from_slice(&script[kept..]);
+    return r;
+}
+
+// BIP66 strict DER, followed by the sighash type byte
+fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    if sig.len() < 9 || sig.len() > 73 || sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    let r_length: usize = sig[3] as usize;
+    if 5 + r_length >= sig.len() {
+        return false;
+    }
+    let s_length: usize = sig[5 + r_length] as usize;
+    if r_length + s_length + 7 != sig.len() {
+        return false;
+    }
+    // positive integers without excess leading zeros
+    if sig[2] != 0x02 || r_length == 0 || sig[4] & 0x80 != 0 || (r_length > 1 && sig[4] == 0 && sig[5] & 0x80 == 0) {
+        return false;
+    }
+    if sig[r_length + 4] != 0x02 || s_length == 0 || sig[r_length + 6] & 0x80 != 0 || (s_length > 1 && sig[r_length + 6] == 0 && sig[r_length + 7] & 0x80 == 0) {
+        return false;
+    }
+    return true;
+}
+
+// consensus verifies high S signatures too, so they are normalised first
+fn check_ecdsa_signature(context: &Context, sig: &[u8], public_key: &[u8], script_code: &[u8], sig_version: SigVersion) -> bool {
+    let (sighash_type, der) = match sig.split_last() {
+        Some((t, der)) => (*t as u32, der),
+        None => return false
+    };
+    let key: PublicKey = match PublicKey::from_slice(public_key) {
+        Ok(k) => k,
+        Err(_) => return false
+    };
+    let script_code: Script = Script::from_bytes(script_code);
+    let sighash: [u8; 32] = match sig_version {
+        SigVersion::Base => sighash::legacy_sighash(context.tx, context.input_index, &script_code, sighash_type).expect("input"),
+        _ => sighash::segwit_v0_sighash_raw(context.tx, context.input_index, &script_code, context.prevouts[context.input_index].value, sighash_type).expect("input")
+    };
+    let mut signature: ecdsa::Signature = match ecdsa::Signature::from_der_lax(der) {
+        Ok(s) => s,
+        Err(_) => return false
+    };
+    signature.normalize_s();
+    let secp = Secp256k1::verification_only();
+    return secp.verify_ecdsa(&Message::from_slice(&sighash).expect("sighash"), &signature, &key).is_ok();
+}
+
+fn check_schnorr_signature(context: &Context, sig: &[u8], x_only_key: &[u8; 32]) -> Result<(), InterpreterError> {
+    let sighash_type: SighashType = match sig.len() {
+        64 => SighashType::Default,
+        // the default type is only ever implicit
+        65 if sig[64] == 0 => return Err(InterpreterError::SchnorrSigHashType),
+        65 => SighashType::from_u8(sig[64]).ok_or(InterpreterError::SchnorrSigHashType)?,
+        _ => return Err(InterpreterError::SchnorrSigSize)
+    };
+    let sighash: [u8; 32] = sighash::taproot_sighash_with_code_separator(context.tx, context.input_index, context.prevouts, context.annex.as_deref(), context.leaf_hash, context.code_separator_position, sighash_type)
+        .map_err(|_| InterpreterError::SchnorrSigHashType)?;
+    if !sign::verify_schnorr_signature(&sighash, sig, x_only_key) {
+        return Err(InterpreterError::SchnorrSig);
+    }
+    return Ok(());
+}
+
+// OP_CHECKSIG and friends; the script code runs from the last OP_CODESEPARATOR
+fn eval_checksig(context: &mut Context, sig: &[u8], public_key: &[u8], script: &[u8], code_start: usize, sig_version: SigVersion) -> Result<bool, InterpreterError> {
+    if sig_version == SigVersion::Tapscript {
+        let success: bool = !sig.is_empty();
+        if success {
+            context.validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP;
+            if context.validation_weight_left < 0 {
+                return Err(InterpreterError::TapscriptValidationWeight);
+            }
+        }
+        match public_key.len() {
+            0 => return Err(InterpreterError::TapscriptEmptyPubkey),
+            32 if success => check_schnorr_signature(context, sig, public_key.try_into().expect("key"))?,
+            // other key types are left to future soft forks
+            _ => {}
+        };
+        return Ok(success);
+    }
+
+    let mut script_code: Vec<u8> = script[code_start..].to_vec();
+    if sig_version == SigVersion::Base {
+        script_code = find_and_delete(&script_code, &Builder::new().push_slice(sig).into_script().bytes);
+    }
+    if !sig.is_empty() && !is_valid_signature_encoding(sig) {
+        return Err(InterpreterError::SigDer);
+    }
+    return Ok(check_ecdsa_signature(context, sig, public_key, &script_code, sig_version));
+}
+
+// signatures are matched against the keys in order, each key tried once
+fn eval_checkmultisig(context: &mut Context, stack: &mut Vec<Vec<u8>>, script: &[u8], code_start: usize, sig_version: SigVersion, op_count: &mut usize) -> Result<bool, InterpreterError> {
+    let mut i: usize = 1;
+    require(stack, i)?;
+    let mut keys_count: i64 = read_number(peek(stack, i)?, 4)?;
+    if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&keys_count) {
+        return Err(InterpreterError::PubkeyCount);
+    }
+    *op_count += keys_count as usize;
+    if *op_count > MAX_OPS_PER_SCRIPT {
+        return Err(InterpreterError::OpCount);
+    }
+    i += 1;
+    let mut key_index: usize = i;
+    i += keys_count as usize;
+    require(stack, i)?;
+
+    let mut sigs_count: i64 = read_number(peek(stack, i)?, 4)?;
+    if sigs_count < 0 || sigs_count > keys_count {
+        return Err(InterpreterError::SigCount);
+    }
+    i += 1;
+    let mut sig_index: usize = i;
+    i += sigs_count as usize;
+    // one more for the dummy element
+    require(stack, i)?;
+
+    let mut script_code: Vec<u8> = script[code_start..].to_vec();
+    if sig_version == SigVersion::Base {
+        for k in 0..sigs_count as usize {
+            script_code = find_and_delete(&script_code, &Builder::new().push_slice(peek(stack, sig_index + k)?).into_script().bytes);
+        }
+    }
+
+    let mut success: bool = true;
+    while success && sigs_count > 0 {
+        let sig: &Vec<u8> = peek(stack, sig_index)?;
+        if !sig.is_empty() && !is_valid_signature_encoding(sig) {
+            return Err(InterpreterError::SigDer);
+        }
+        if check_ecdsa_signature(context, sig, peek(stack, key_index)?, &script_code, sig_version) {
+            sig_index += 1;
+            sigs_count -= 1;
+        }
+        key_index += 1;
+        keys_count -= 1;
+        // not enough keys left for the remaining signatures
+        if sigs_count > keys_count {
+            success = false;
+        }
+    }
+
+    for _ in 1..i {
+        stack.pop();
+    }
+    // the element consumed by the original off-by-one bug must be empty, per BIP147
+    if !pop(stack)?.is_empty() {
+        return Err(InterpreterError::SigNullDummy);
+    }
+    return Ok(success);
+}
+
+fn check_lock_time(context: &Context, lock_time: i64) -> bool {
+    let tx_lock_time: i64 = context.tx.lock_time as i64;
+    // heights and timestamps do not compare
+    if (tx_lock_time < LOCKTIME_THRESHOLD) != (lock_time < LOCKTIME_THRESHOLD) {
+        return false;
+    }
+    if lock_time > tx_lock_time {
+        return false;
+    }
+    // a final input would skip the lock time check
+    return context.tx.inputs[context.input_index].sequence != SEQUENCE_FINAL;
+}
+
+// BIP112
+fn check_sequence(context: &Context, sequence: i64) -> bool {
+    let tx_sequence: i64 = context.tx.inputs[context.input_index].sequence as i64;
+    if (context.tx.version as u32) < 2 || tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+        return false;
+    }
+    let mask: i64 = SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK;
+    let (tx_sequence, sequence): (i64, i64) = (tx_sequence & mask, sequence & mask);
+    if (tx_sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) != (sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) {
+        return false;
+    }
+    return sequence <= tx_sequence;
+}
+
+fn eval_script(context: &mut Context, stack: &mut Vec<Vec<u8>>, script: &[u8], sig_version: SigVersion) -> Result<(), InterpreterError> {
+    if sig_version != SigVersion::Tapscript && script.len() > MAX_SCRIPT_SIZE {
+        return Err(InterpreterError::ScriptSize);
+    }
+    let mut alt_stack: Vec<Vec<u8>> = vec![];
+    // one entry per open OP_IF, whether its branch runs
+    let mut conditions: Vec<bool> = vec![];
+    let mut op_count: usize = 0;
+    let mut code_start: usize = 0;
+    let mut opcode_position: u32 = 0;
+    let mut pc: usize = 0;
+
+    while pc < script.len() {
+        let (op, data, next) = read_op(script, pc).ok_or(InterpreterError::BadOpcode)?;
+        pc = next;
+        let executing: bool = !conditions.contains(&false);
+
+        if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err(InterpreterError::PushSize);
+        }
+        if sig_version != SigVersion::Tapscript && op.to_byte() > Opcode::Num(16).to_byte() {
+            op_count += 1;
+            if op_count > MAX_OPS_PER_SCRIPT {
+                return Err(InterpreterError::OpCount);
+            }
+        }
+        // even in a branch not taken
+        if is_disabled(op) {
+            return Err(InterpreterError::DisabledOpcode);
+        }
+
+        if executing && op.to_byte() <= Opcode::PushData4.to_byte() {
+            stack.push(data.to_vec());
+        }
+        else if executing || matches!(op, Opcode::If | Opcode::NotIf | Opcode::VerIf | Opcode::VerNotIf | Opcode::Else | Opcode::EndIf) {
+            match op {
+                Opcode::Op1Negate => stack.push(script::script_number(-1)),
+                Opcode::Num(n) => stack.push(script::script_number(n as i64)),
+                Opcode::Nop | Opcode::Nop1 | Opcode::Nop4 | Opcode::Nop5 | Opcode::Nop6 | Opcode::Nop7 | Opcode::Nop8 | Opcode::Nop9 | Opcode::Nop10 => {},
+                Opcode::CheckLockTimeVerify => {
+                    // five bytes, as lock times reach 2^32 - 1
+                    let lock_time: i64 = read_number(peek(stack, 1)?, 5)?;
+                    if lock_time < 0 {
+                        return Err(InterpreterError::NegativeLockTime);
+                    }
+                    if !check_lock_time(context, lock_time) {
+                        return Err(InterpreterError::UnsatisfiedLockTime);
+                    }
+                },
+                Opcode::CheckSequenceVerify => {
+                    let sequence: i64 = read_number(peek(stack, 1)?, 5)?;
+                    if sequence < 0 {
+                        return Err(InterpreterError::NegativeLockTime);
+                    }
+                    if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0 && !check_sequence(context, sequence) {
+                        return Err(InterpreterError::UnsatisfiedLockTime);
+                    }
+                },
+                Opcode::If | Opcode::NotIf => {
+                    let mut value: bool = false;
+                    if executing {
+                        let top: Vec<u8> = pop(stack)?;
+                        // tapscript takes nothing but empty or 1 as a condition
+                        if sig_version == SigVersion::Tapscript && (top.len() > 1 || (top.len() == 1 && top[0] != 1)) {
+                            return Err(InterpreterError::TapscriptMinimalIf);
+                        }
+                        value = cast_to_bool(&top) != (op == Opcode::NotIf);
+                    }
+                    conditions.push(value);
+                },
+                Opcode::Else => {
+                    let last: &mut bool = conditions.last_mut().ok_or(InterpreterError::UnbalancedConditional)?;
+                    *last = !*last;
+                },
+                Opcode::EndIf => {
+                    conditions.pop().ok_or(InterpreterError::UnbalancedConditional)?;
+                },
+                Opcode::Verify => {
+                    if !cast_to_bool(peek(stack, 1)?) {
+                        return Err(InterpreterError::Verify(op));
+                    }
+                    stack.pop();
+                },
+                Opcode::Return => return Err(InterpreterError::OpReturn),
+                Opcode::ToAltStack => alt_stack.push(pop(stack)?),
+                Opcode::FromAltStack => stack.push(alt_stack.pop().ok_or(InterpreterError::InvalidAltstackOperation)?),
+                Opcode::Drop2 => {
+                    require(stack, 2)?;
+                    stack.truncate(stack.len() - 2);
+                },
+                Opcode::Dup2 | Opcode::Dup3 | Opcode::Over2 => {
+                    let (count, depth): (usize, usize) = match op {
+                        Opcode::Dup2 => (2, 2),
+                        Opcode::Dup3 => (3, 3),
+                        _ => (2, 4)
+                    };
+                    require(stack, depth)?;
+                    let start: usize = stack.len() - depth;
+                    stack.extend_from_within(start..start + count);
+                },
+                Opcode::Rot2 => {
+                    require(stack, 6)?;
+                    let start: usize = stack.len() - 6;
+                    let moved: Vec<Vec<u8>> = stack.drain(start..start + 2).collect();
+                    stack.extend(moved);
+                },
+                Opcode::Swap2 => {
+                    require(stack, 4)?;
+                    let n: usize = stack.len();
+                    stack.swap(n - 4, n - 2);
+                    stack.swap(n - 3, n - 1);
+                },
+                Opcode::IfDup => {
+                    let top: Vec<u8> = peek(stack, 1)?.clone();
+                    if cast_to_bool(&top) {
+                        stack.push(top);
+                    }
+                },
+                Opcode::Depth => stack.push(script::script_number(stack.len() as i64)),
+                Opcode::Drop => {
+                    pop(stack)?;
+                },
+                Opcode::Dup => stack.push(peek(stack, 1)?.clone()),
+                Opcode::Nip => {
+                    require(stack, 2)?;
+                    stack.remove(stack.len() - 2);
+                },
+                Opcode::Over => stack.push(peek(stack, 2)?.clone()),
+                Opcode::Pick | Opcode::Roll => {
+                    require(stack, 2)?;
+                    let n: i64 = read_number(peek(stack, 1)?, 4)?;
+                    stack.pop();
+                    if n < 0 || n as usize >= stack.len() {
+                        return Err(InterpreterError::InvalidStackOperation);
+                    }
+                    let index: usize = stack.len() - 1 - n as usize;
+                    let item: Vec<u8> = if op == Opcode::Roll { stack.remove(index) } else { stack[index].clone() };
+                    stack.push(item);
+                },
+                Opcode::Rot => {
+                    require(stack, 3)?;
+                    let item: Vec<u8> = stack.remove(stack.len() - 3);
+                    stack.push(item);
+                },
+                Opcode::Swap => {
+                    require(stack, 2)?;
+                    let n: usize = stack.len();
+                    stack.swap(n - 2, n - 1);
+                },
+                Opcode::Tuck => {
+                    require(stack, 2)?;
+                    let top: Vec<u8> = peek(stack, 1)?.clone();
+                    stack.insert(stack.len() - 2, top);
+                },
+                Opcode::Size => stack.push(script::script_number(peek(stack, 1)?.len() as i64)),
+                Opcode::Equal | Opcode::EqualVerify => {
+                    require(stack, 2)?;
+                    let equal: bool = pop(stack)? == pop(stack)?;
+                    if op == Opcode::EqualVerify {
+                        if !equal {
+                            return Err(InterpreterError::Verify(op));
+                        }
+                    }
+                    else {
+                        stack.push(boolean(equal));
+                    }
+                },
+                Opcode::Add1 | Opcode::Sub1 | Opcode::Negate | Opcode::Abs | Opcode::Not | Opcode::NotEqual0 => {
+                    let n: i64 = read_number(peek(stack, 1)?, 4)?;
+                    stack.pop();
+                    let r: i64 = match op {
+                        Opcode::Add1 => n + 1,
+                        Opcode::Sub1 => n - 1,
+                        Opcode::Negate => -n,
+                        Opcode::Abs => n.abs(),
+                        Opcode::Not => (n == 0) as i64,
+                        _ => (n != 0) as i64
+                    };
+                    stack.push(script::script_number(r));
+                },
+                Opcode::Add | Opcode::Sub | Opcode::BoolAnd | Opcode::BoolOr | Opcode::NumEqual | Opcode::NumEqualVerify | Opcode::NumNotEqual
+                | Opcode::LessThan | Opcode::GreaterThan | Opcode::LessThanOrEqual | Opcode::GreaterThanOrEqual | Opcode::Min | Opcode::Max => {
+                    require(stack, 2)?;
+                    let a: i64 = read_number(peek(stack, 2)?, 4)?;
+                    let b: i64 = read_number(peek(stack, 1)?, 4)?;
+                    stack.truncate(stack.len() - 2);
+                    let r: i64 = match op {
+                        Opcode::Add => a + b,
+                        Opcode::Sub => a - b,
+                        Opcode::BoolAnd => (a != 0 && b != 0) as i64,
+                        Opcode::BoolOr => (a != 0 || b != 0) as i64,
+                        Opcode::NumEqual | Opcode::NumEqualVerify => (a == b) as i64,
+                        Opcode::NumNotEqual => (a != b) as i64,
+                        Opcode::LessThan => (a < b) as i64,
+                        Opcode::GreaterThan => (a > b) as i64,
+                        Opcode::LessThanOrEqual => (a <= b) as i64,
+                        Opcode::GreaterThanOrEqual => (a >= b) as i64,
+                        Opcode::Min => a.min(b),
+                        _ => a.max(b)
+                    };
+                    if op == Opcode::NumEqualVerify {
+                        if r == 0 {
+                            return Err(InterpreterError::Verify(op));
+                        }
+                    }
+                    else {
+                        stack.push(script::script_number(r));
+                    }
+                },
+                Opcode::Within => {
+                    require(stack, 3)?;
+                    let x: i64 = read_number(peek(stack, 3)?, 4)?;
+                    let min: i64 = read_number(peek(stack, 2)?, 4)?;
+                    let max: i64 = read_number(peek(stack, 1)?, 4)?;
+                    stack.truncate(stack.len() - 3);
+                    stack.push(boolean(min <= x && x < max));
+                },
+                Opcode::Ripemd160 | Opcode::Sha1 | Opcode::Sha256 | Opcode::Hash160 | Opcode::Hash256 => {
+                    let data: Vec<u8> = pop(stack)?;
+                    let hash: Vec<u8> = match op {
+                        Opcode::Ripemd160 => hashing::ripemd160(&data).to_vec(),
+                        Opcode::Sha1 => hashing::sha1(&data).to_vec(),
+                        Opcode::Sha256 => hashing::sha256(&data).to_vec(),
+                        Opcode::Hash160 => hashing::hash160(&data).to_vec(),
+                        _ => hashing::sha256d(&data).to_vec()
+                    };
+                    stack.push(hash);
+                },
+                Opcode::CodeSeparator => {
+                    code_start = pc;
+                    context.code_separator_position = opcode_position;
+                },
+                Opcode::CheckSig | Opcode::CheckSigVerify => {
+                    require(stack, 2)?;
+                    let public_key: Vec<u8> = pop(stack)?;
+                    let sig: Vec<u8> = pop(stack)?;
+                    let success: bool = eval_checksig(context, &sig, &public_key, script, code_start, sig_version)?;
+                    if op == Opcode::CheckSigVerify {
+                        if !success {
+                            return Err(InterpreterError::Verify(op));
+                        }
+                    }
+                    else {
+                        stack.push(boolean(success));
+                    }
+                },
+                Opcode::CheckSigAdd if sig_version == SigVersion::Tapscript => {
+                    require(stack, 3)?;
+                    let n: i64 = read_number(peek(stack, 2)?, 4)?;
+                    let public_key: Vec<u8> = pop(stack)?;
+                    stack.pop();
+                    let sig: Vec<u8> = pop(stack)?;
+                    let success: bool = eval_checksig(context, &sig, &public_key, script, code_start, sig_version)?;
+                    stack.push(script::script_number(n + success as i64));
+                },
+                Opcode::CheckMultisig | Opcode::CheckMultisigVerify => {
+                    if sig_version == SigVersion::Tapscript {
+                        return Err(InterpreterError::TapscriptCheckMultisig);
+                    }
+                    let success: bool = eval_checkmultisig(context, stack, script, code_start, sig_version, &mut op_count)?;
+                    if op == Opcode::CheckMultisigVerify {
+                        if !success {
+                            return Err(InterpreterError::Verify(op));
+                        }
+                    }
+                    else {
+                        stack.push(boolean(success));
+                    }
+                },
+                // OP_RESERVED, OP_VER, OP_VERIF, OP_VERNOTIF, OP_RESERVED1, OP_RESERVED2,
+                // OP_CHECKSIGADD outside tapscript and the unassigned opcodes
+                _ => return Err(InterpreterError::BadOpcode)
+            };
+        }
+
+        if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+            return Err(InterpreterError::StackSize);
+        }
+        opcode_position += 1;
+    }
+
+    if !conditions.is_empty() {
+        return Err(InterpreterError::UnbalancedConditional);
+    }
+    return Ok(());
+}
+
+// witness scripts must leave exactly one true element
+fn execute_witness_script(context: &mut Context, mut stack: Vec<Vec<u8>>, script: &[u8], sig_version: SigVersion) -> Result<(), InterpreterError> {
+    if sig_version == SigVersion::Tapscript {
+        // an OP_SUCCESSx anywhere makes the spend valid, even after a push that does not parse
+        let mut pc: usize = 0;
+        while pc < script.len() {
+            let (op, _, next) = read_op(script, pc).ok_or(InterpreterError::BadOpcode)?;
+            if is_op_success(op.to_byte()) {
+                return Ok(());
+            }
+            pc = next;
+        }
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(InterpreterError::StackSize);
+        }
+    }
+    if stack.iter().any(|e: &Vec<u8>| e.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+        return Err(InterpreterError::PushSize);
+    }
+    eval_script(context, &mut stack, script, sig_version)?;
+    if stack.len() != 1 {
+        return Err(InterpreterError::CleanStack);
+    }
+    if !cast_to_bool(&stack[0]) {
+        return Err(InterpreterError::EvalFalse);
+    }
+    return Ok(());
+}
+
+// the control block proves the leaf is committed to by the output key
+fn verify_taproot_commitment(control: &[u8], output_key: &[u8], leaf_hash: &[u8; 32]) -> bool {
+    let internal_key: [u8; 32] = control[1..33].try_into().expect("key");
+    let mut node: [u8; 32] = *leaf_hash;
+    for sibling in control[33..].chunks(32) {
+        node = taproot::tap_branch_hash(&node, sibling.try_into().expect("node"));
+    }
+    match taproot::tweak_public_key(&internal_key, Some(&node)) {
+        Ok((key, odd)) => return key.as_slice() == output_key && odd == (control[0] & 1 == 1),
+        Err(_) => return false
+    };
+}
+
+fn witness_size(witness: &[Vec<u8>]) -> usize {
+    return bytes::compact_size(witness.len() as u64).len() + witness.iter().map(|e: &Vec<u8>| bytes::compact_size(e.len() as u64).len() + e.len()).sum::<usize>();
+}
+
+fn verify_witness_program(context: &mut Context, witness: &[Vec<u8>], version: u8, program: &[u8], p2sh: bool) -> Result<(), InterpreterError> {
+    match (version, program.len()) {
+        (0, 32) => {
+            let (script, stack) = witness.split_last().ok_or(InterpreterError::WitnessProgramWitnessEmpty)?;
+            if hashing::sha256(script) != program {
+                return Err(InterpreterError::WitnessProgramMismatch);
+            }
+            return execute_witness_script(context, stack.to_vec(), script, SigVersion::WitnessV0);
+        },
+        (0, 20) => {
+            if witness.len() != 2 {
+                return Err(InterpreterError::WitnessProgramMismatch);
+            }
+            return execute_witness_script(context, witness.to_vec(), &sighash::p2wpkh_script_code(program).bytes, SigVersion::WitnessV0);
+        },
+        (0, _) => return Err(InterpreterError::WitnessProgramWrongLength),
+        (1, 32) if !p2sh => {
+            if witness.is_empty() {
+                return Err(InterpreterError::WitnessProgramWitnessEmpty);
+            }
+            let mut stack: &[Vec<u8>] = witness;
+            if let [rest @ .., annex] = stack {
+                if !rest.is_empty() && annex.first() == Some(&ANNEX_TAG) {
+                    context.annex = Some(annex.clone());
+                    stack = rest;
+                }
+            }
+            // key path
+            if let [sig] = stack {
+                return check_schnorr_signature(context, sig, program.try_into().expect("program"));
+            }
+
+            let (control, rest) = stack.split_last().expect("control");
+            let (script, stack) = rest.split_last().expect("script");
+            if control.len() < 33 || control.len() > 33 + 32 * 128 || (control.len() - 33) % 32 != 0 {
+                return Err(InterpreterError::TaprootWrongControlSize);
+            }
+            let leaf_version: u8 = control[0] & 0xfe;
+            let leaf_hash: [u8; 32] = taproot::tap_leaf_hash(script, leaf_version);
+            if !verify_taproot_commitment(control, program, &leaf_hash) {
+                return Err(InterpreterError::WitnessProgramMismatch);
+            }
+            if leaf_version != TAPSCRIPT_LEAF_VERSION {
+                // unknown leaf versions are left to future soft forks
+                return Ok(());
+            }
+            context.leaf_hash = Some(leaf_hash);
+            context.validation_weight_left = witness_size(witness) as i64 + VALIDATION_WEIGHT_OFFSET;
+            return execute_witness_script(context, stack.to_vec(), script, SigVersion::Tapscript);
+        },
+        // so are other witness versions and program sizes
+        _ => return Ok(())
+    };
+}
+
+// runs the scriptSig, the spent scriptPubKey and any P2SH redeem script or witness
+// under the consensus rules in force today, BIP16 through BIP342; policy rules such
+// as low S or minimal pushes are not checked. prevouts lists the output spent by
+// every input, as taproot signs them all
+pub fn verify_input(tx: &Transaction, input_index: usize, prevouts: &[TxOut]) -> Result<(), InterpreterError> {
+    if input_index >= tx.inputs.len() {
+        return Err(InterpreterError::InputIndex(input_index));
+    }
+    if prevouts.len() != tx.inputs.len() {
+        return Err(InterpreterError::PrevoutsCount(prevouts.len(), tx.inputs.len()));
+    }
+    let input: &TxIn = &tx.inputs[input_index];
+    let script_sig: &[u8] = &input.script_sig.bytes;
+    let script_pubkey: &Script = &prevouts[input_index].script_pubkey;
+    let mut context: Context = Context {
+        tx,
+        input_index,
+        prevouts,
+        annex: None,
+        leaf_hash: None,
+        code_separator_position: 0xffffffff,
+        validation_weight_left: 0
+    };
+
+    let mut stack: Vec<Vec<u8>> = vec![];
+    eval_script(&mut context, &mut stack, script_sig, SigVersion::Base)?;
+    let p2sh_stack: Vec<Vec<u8>> = stack.clone();
+    eval_script(&mut context, &mut stack, &script_pubkey.bytes, SigVersion::Base)?;
+    if !stack.last().is_some_and(|top: &Vec<u8>| cast_to_bool(top)) {
+        return Err(InterpreterError::EvalFalse);
+    }
+
+    let mut had_witness: bool = false;
+    if let Some((version, program)) = script_pubkey.witness_program() {
+        had_witness = true;
+        if !script_sig.is_empty() {
+            return Err(InterpreterError::WitnessMalleated);
+        }
+        verify_witness_program(&mut context, &input.witness, version, &program, false)?;
+    }
+    else if is_p2sh(&script_pubkey.bytes) {
+        if !is_push_only(script_sig) {
+            return Err(InterpreterError::SigPushOnly);
+        }
+        let mut stack: Vec<Vec<u8>> = p2sh_stack;
+        let redeem_script: Vec<u8> = pop(&mut stack)?;
+        eval_script(&mut context, &mut stack, &redeem_script, SigVersion::Base)?;
+        if !stack.last().is_some_and(|top: &Vec<u8>| cast_to_bool(top)) {
+            return Err(InterpreterError::EvalFalse);
+        }
+        if let Some((version, program)) = Script::from_bytes(&redeem_script).witness_program() {
+            had_witness = true;
+            if script_sig != Builder::new().push_slice(&redeem_script).into_script().bytes {
+                return Err(InterpreterError::WitnessMalleatedP2sh);
+            }
+            verify_witness_program(&mut context, &input.witness, version, &program, true)?;
+        }
+    }
+
+    if !had_witness && !input.witness.is_empty() {
+        return Err(InterpreterError::WitnessUnexpected);
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::*;
+    use crate::base16;
+    use crate::transaction::OutPoint;
+
+    fn output(value: u64, script: &str) -> TxOut {
+        return TxOut { value, script_pubkey: Script::from_hex(script).unwrap() };
+    }
+
+    #[test]
+    fn interpreter_numbers() {
+        for n in [0, 1, -1, 127, 128, -128, 255, -255, 0x7fffffff, -0x7fffffff] {
+            assert_eq!(read_number(&script::script_number(n), 4), Ok(n));
+        }
+        // negative zero and non-minimal encodings still read
+        assert_eq!(read_number(&[0x80], 4), Ok(0));
+        assert_eq!(read_number(&[0x01, 0x00], 4), Ok(1));
+        assert_eq!(read_number(&[0xff, 0xff, 0xff, 0xff, 0x00], 4), Err(InterpreterError::NumberOverflow));
+        assert_eq!(read_number(&[0xff, 0xff, 0xff, 0xff, 0x00], 5), Ok(0xffffffff));
+
+        assert!(!cast_to_bool(&[]));
+        assert!(!cast_to_bool(&[0x00, 0x80]));
+        assert!(cast_to_bool(&[0x80, 0x00]));
+    }
+
+    #[test]
+    fn interpreter_find_and_delete() {
+        let sig: Vec<u8> = Builder::new().push_slice(&[0xab, 0xcd]).into_script().bytes;
+        assert_eq!(base16::encode_bytes(&find_and_delete(&base16::decode_string("02abcd7602abcd"), &sig)), "76");
+        // only at opcode boundaries
+        assert_eq!(base16::encode_bytes(&find_and_delete(&base16::decode_string("0302abcd"), &sig)), "0302abcd");
+        // a non-minimal push of the same bytes stays
+        assert_eq!(base16::encode_bytes(&find_and_delete(&base16::decode_string("4c02abcd02abcd"), &sig)), "4c02abcd");
+    }
+
+    // what the signer produces verifies, and stops verifying once anything signed changes
+    #[test]
+    fn interpreter_signed_inputs() {
+        let key: [u8; 32] = base16::decode_string("0000000000000000000000000000000000000000000000000000000000000001").try_into().unwrap();
+        let (output_key, _) = taproot::tweak_public_key(&base16::decode_string("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").try_into().unwrap(), None).unwrap();
+        let mut tx: Transaction = Transaction::new(2, 0);
+        tx.inputs = (0..4).map(|i: u32| TxIn::new(OutPoint::new([1; 32], i))).collect();
+        tx.outputs = vec![output(190_000, "0014751e76e8199196d454941c45d1b3a323f1433bd6")];
+        let prevouts: Vec<TxOut> = vec![
+            output(50_000, "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"),
+            output(50_000, "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+            output(50_000, &format!("a914{}87", base16::encode_bytes(&hashing::hash160(&base16::decode_string("0014751e76e8199196d454941c45d1b3a323f1433bd6"))))),
+            output(50_000, &format!("5120{}", base16::encode_bytes(&output_key)))
+        ];
+        for i in 0..4 {
+            assert!(verify_input(&tx, i, &prevouts).is_err());
+            sign::sign_input(&mut tx, i, &key, &prevouts, SighashType::All).unwrap();
+        }
+        for i in 0..4 {
+            assert_eq!(verify_input(&tx, i, &prevouts), Ok(()), "input {}", i);
+        }
+        assert_eq!(verify_input(&tx, 4, &prevouts), Err(InterpreterError::InputIndex(4)));
+
+        // segwit signs the amount, legacy does not
+        let mut other: Vec<TxOut> = prevouts.clone();
+        other[1].value += 1;
+        assert_eq!(verify_input(&tx, 0, &other), Ok(()));
+        assert_eq!(verify_input(&tx, 1, &other), Err(InterpreterError::EvalFalse));
+
+        let mut changed: Transaction = tx.clone();
+        changed.outputs[0].value -= 1;
+        for i in 0..3 {
+            assert_eq!(verify_input(&changed, i, &prevouts), Err(InterpreterError::EvalFalse), "input {}", i);
+        }
+        assert_eq!(verify_input(&changed, 3, &prevouts), Err(InterpreterError::SchnorrSig));
+
+        // witness data on a legacy input, and a scriptSig on a native segwit one
+        let mut malleated: Transaction = tx.clone();
+        malleated.inputs[0].witness = vec![vec![]];
+        malleated.inputs[1].script_sig = Builder::new().push_int(1).into_script();
+        assert_eq!(verify_input(&malleated, 0, &prevouts), Err(InterpreterError::WitnessUnexpected));
+        assert_eq!(verify_input(&malleated, 1, &prevouts), Err(InterpreterError::WitnessMalleated));
+    }
+}
@@ -4,4 +4,31 @@ pub mod secp256k1;
 pub mod base16;
 pub mod crypto;
 pub mod base58;
-pub mod app;
\ No newline at end of file
+pub mod base64;
+pub mod bech32;
+pub mod taproot;
+pub mod app;
+pub mod bip39;
+pub mod bip38;
+pub mod import;
+pub mod bip32;
+pub mod discovery;
+pub mod bip85;
+pub mod slip39;
+pub mod repair;
+pub mod multisig;
+pub mod descriptor;
+pub mod script;
+pub mod inspect;
+pub mod transaction;
+pub mod sighash;
+pub mod sign;
+pub mod interpreter;
+pub mod psbt;
+pub mod coinselect;
+pub mod message;
+
+pub mod block;
+pub mod merkle;
+pub mod headers;
+pub mod silentpayments;
@@ -1,45 +1,921 @@
 use std::str::FromStr;
 use std::env;
-use walletcryptography::app::Network;
+use walletcryptography::app::{Network, AddressType};
 use walletcryptography::crypto::*;
-use std::fs::File;
+use walletcryptography::bip32::{ExtendedPrivateKey, format_path, HARDENED};
+use walletcryptography::bip39::{self, Language};
+use walletcryptography::bip85;
+use walletcryptography::slip39;
+use walletcryptography::repair;
+use walletcryptography::multisig::{self, MultisigType};
+use walletcryptography::descriptor::{Descriptor, DescriptorError};
+use walletcryptography::script::{Script, ScriptError, ScriptType};
+use walletcryptography::inspect::{self, AddressInfo, InspectError};
+use walletcryptography::transaction::{self, Transaction, TxIn, TxOut};
+use walletcryptography::sighash::SighashType;
+use walletcryptography::sign::{self, SignError};
+use walletcryptography::psbt::{self, Psbt, PsbtError, KeySource};
+use walletcryptography::coinselect::{self, Algorithm, CoinSelectionError, FeePolicy, Selection, Utxo};
+use walletcryptography::message::{self, SignatureFormat};
+use walletcryptography::bip38::{self, EcMultiplyKey, Confirmation};
+use walletcryptography::import::{self, ImportedKey};
+use walletcryptography::merkle::{self, MerkleBlock, MerkleProof};
+use walletcryptography::headers::{self, ChainParams, HeaderChain, HeaderError};
+use walletcryptography::block::BlockHeader;
+use walletcryptography::silentpayments::{self, FoundOutput, InputKey, Receiver, SilentPaymentAddress};
+use walletcryptography::bech32;
+use ::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use walletcryptography::base16;
+use walletcryptography::discovery::{self, Purpose, AccountReport};
+use std::fs::{self, File};
 use std::io::Write;
 
 fn main () {
     let args: Vec<String> = env::args().collect();
-    let network: Network = Network::from_str(&args[1].clone().as_str()).expect("network");
-    let raw_pr_key: String = args[2].clone().to_lowercase();
 
-    assert!(raw_pr_key.len() == 64, "private-key");
-    assert!(raw_pr_key.chars().into_iter().all(|c| u8::from_str_radix(c.to_string().as_str(), 16).unwrap() < 16), "private-key");
+    match args[1].as_str() {
+        "discover" => discover(&args[2..]),
+        "bip85" => derive_bip85(&args[2..]),
+        "slip39" => shamir_slip39(&args[2..]),
+        "mnemonic" => check_mnemonic(&args[2..]),
+        "repair" => repair_mnemonic(&args[2..]),
+        "multisig" => derive_multisig(&args[2..]),
+        "descriptor" => expand_descriptor(&args[2..]),
+        "script" => decode_script(&args[2..]),
+        "inspect" => inspect_address(&args[2..]),
+        "decode-tx" => decode_transaction(&args[2..]),
+        "sign-tx" => sign_transaction(&args[2..]),
+        "psbt" => process_psbt(&args[2..]),
+        "fund" => fund_transaction(&args[2..]),
+        "signmessage" => sign_message(&args[2..]),
+        "verifymessage" => verify_message(&args[2..]),
+        "bip38" => process_bip38(&args[2..]),
+        "merkle" => process_merkle(&args[2..]),
+        "headers" => validate_headers(&args[2..]),
+        "silentpayments" => silent_payments(&args[2..]),
+        _ => generate(&args[1..])
+    }
+}
+
+fn option_value(args: &[String], name: &str) -> Option<String> {
+    return args.iter().position(|a: &String| a == name).and_then(|i: usize| args.get(i + 1)).cloned();
+}
+
+// <network> <private key> [--type p2pkh|p2sh-p2wpkh|p2wpkh|p2tr]
+// the key may be hex, WIF, a mini private key, base64 or decimal
+fn generate(args: &[String]) {
+    let network: Network = Network::from_str(args[0].as_str()).expect("network");
+    let imported: ImportedKey = read_private_key(&args[1]);
+
+    // a WIF has to belong to the requested network
+    if let Some(wif_network) = imported.network {
+        assert!(network.bitcoin_params().map(|p| p.wif_prefix) == wif_network.bitcoin_params().map(|p| p.wif_prefix), "network-mismatch");
+    }
+    let raw_pr_key: String = base16::encode_bytes(&imported.secret_key);
 
     // derive pub key
-    let pub_key = secp256k1::get_public_key(&raw_pr_key);
+    let pub_key: PublicKey = secp256k1::public_key(&imported.secret_key);
 
     // get address
-    let address: String;
-    match network {
-        Network::Bitcoin => {
-            address = bitcoin::derive_compressed_address(&pub_key);
-        },
-        Network::Ethereum => {
-            address = ethereum::derive_address(&pub_key);
-        }
-    }
+    let address_type: Option<AddressType> = option_value(args, "--type").map(|t: String| AddressType::from_str(&t).expect("address-type"));
+    let address: String = match (network, address_type) {
+        (Network::Ethereum, None) => ethereum::derive_address(&base16::encode_bytes(&pub_key.serialize_uncompressed())),
+        (Network::Ethereum, Some(_)) => panic!("address-type"),
+        (_, None | Some(AddressType::P2pkh)) => bitcoin::derive_compressed_address(&pub_key, network),
+        (_, Some(AddressType::P2shP2wpkh)) => bitcoin::derive_p2sh_p2wpkh_address(&pub_key, network),
+        (_, Some(AddressType::P2wpkh)) => bitcoin::derive_p2wpkh_address(&pub_key, network),
+        (_, Some(AddressType::P2tr)) => bitcoin::derive_p2tr_address(&pub_key, network)
+    };
+
+    // legacy p2pkh also lists the pre-2012 uncompressed-key address and WIF for the same key
+    let uncompressed: String = match (network, address_type) {
+        (Network::Ethereum, _) => String::new(),
+        (_, None | Some(AddressType::P2pkh)) => format!(
+            "\nUncompressed Key Address: {}\nUncompressed Key Private Key[*]: {}",
+            bitcoin::derive_uncompressed_address(&pub_key, network),
+            bitcoin::encode_uncompressed_pr_key(&imported.secret_key, network)
+        ),
+        _ => String::new()
+    };
 
-    let pr_key: String = if network == Network::Bitcoin {
-        bitcoin::encode_compressed_pr_key(&raw_pr_key)
+    let pr_key: String = if network.is_bitcoin() {
+        bitcoin::encode_compressed_pr_key(&imported.secret_key, network)
     }
     else {
         raw_pr_key
     };
 
-    let mut output: File = File::create(format!("./{}_address_{}", network.to_string(), address)).expect("file-creation");
+    let mut output: File = File::create(format!("./{}_address_{}", network, address)).expect("file-creation");
     write!(&mut output,
-        "[Generate New Wallet Private/Public Keys and Address]\n\nNetwork: {}\nAddress: {}\nPrivate Key[*]: {}\nUncompressed Public Key: {}\n\n[*] Keep secret at all times.",
-        network.to_string(),
+        "[Generate New Wallet Private/Public Keys and Address]\n\nNetwork: {}\nAddress: {}\nPrivate Key[*]: {}{}\nUncompressed Public Key: {}\n\n[*] Keep secret at all times.",
+        network,
         address,
         pr_key,
-        pub_key
+        uncompressed,
+        base16::encode_bytes(&pub_key.serialize_uncompressed())
     ).expect("write-file");
-}
\ No newline at end of file
+}
+
+// mnemonic <phrase> [--language <language>]
+fn check_mnemonic(args: &[String]) {
+    let mnemonic: &String = &args[0];
+    let language: Result<Language, bip39::Bip39Error> = match option_value(args, "--language") {
+        Some(l) => Ok(Language::from_str(&l).expect("language")),
+        None => bip39::detect_language(mnemonic)
+    };
+
+    println!("[BIP39 Mnemonic Validation]\n");
+    println!("Words: {}", bip39::split_words(mnemonic).len());
+    match language.and_then(|l: Language| bip39::mnemonic_to_entropy_in(mnemonic, l).map(|e: Vec<u8>| (l, e))) {
+        Ok((l, entropy)) => {
+            println!("Language: {}", l);
+            println!("Entropy: {}", base16::encode_bytes(&entropy));
+            println!("Valid: yes");
+        },
+        Err(e) => println!("Valid: no ({:?})", e)
+    };
+}
+
+// repair <phrase, ? for a missing word> [--language <language>] [--max-distance <n>]
+//        [--network <network> --address <first receive address> [--passphrase <p>] [--purpose 44]]
+fn repair_mnemonic(args: &[String]) {
+    let mnemonic: &String = &args[0];
+    let language: Option<Language> = option_value(args, "--language").map(|l: String| Language::from_str(&l).expect("language"));
+    let max_distance: usize = match option_value(args, "--max-distance") {
+        Some(v) => v.parse::<usize>().expect("max-distance"),
+        None => repair::DEFAULT_MAX_DISTANCE
+    };
+
+    let mut candidates: Vec<String> = repair::repair(mnemonic, language, max_distance).expect("repair");
+    println!("[BIP39 Mnemonic Repair]\n");
+    println!("Checksum-Valid Candidates: {}", candidates.len());
+
+    if let Some(address) = option_value(args, "--address") {
+        let network: Network = Network::from_str(&option_value(args, "--network").expect("network")).expect("network");
+        let passphrase: String = option_value(args, "--passphrase").unwrap_or_default();
+        let purpose: Purpose = match option_value(args, "--purpose") {
+            Some(p) => Purpose::from_str(&p).expect("purpose"),
+            None => Purpose::Bip44
+        };
+
+        candidates = repair::filter_by_address(&candidates, &passphrase, network, purpose, &address).expect("address");
+        println!("Matching {} {}: {}", purpose, address, candidates.len());
+    }
+
+    println!();
+    candidates.iter().for_each(|c: &String| println!("{}", c));
+}
+
+// multisig <threshold> <key>,<key>,... [--type p2sh|p2sh-p2wsh|p2wsh] [--network <network>] [--unsorted]
+// each key is hex SEC1 or an xpub/tpub with a normal path, e.g. xpub.../0/5
+fn derive_multisig(args: &[String]) {
+    let threshold: usize = args[0].parse::<usize>().expect("threshold");
+    let keys: Vec<Vec<u8>> = args[1].split(',').map(|k: &str| multisig::parse_key(k).expect("key")).collect::<Vec<Vec<u8>>>();
+    let multisig_type: MultisigType = match option_value(args, "--type") {
+        Some(t) => MultisigType::from_str(&t).expect("multisig-type"),
+        None => MultisigType::P2wsh
+    };
+    let network: Network = match option_value(args, "--network") {
+        Some(n) => Network::from_str(&n).expect("network"),
+        None => Network::Bitcoin
+    };
+    assert!(network.is_bitcoin(), "network");
+    let sorted: bool = !args.iter().any(|a: &String| a == "--unsorted");
+
+    let script: Vec<u8> = multisig::multisig_script(threshold, &keys, sorted).expect("multisig");
+    let address: String = multisig::derive_address(threshold, &keys, sorted, multisig_type, network).expect("multisig");
+
+    println!("[Multisig Address]\n");
+    println!("Network: {}", network);
+    println!("Type: {} {} of {}{}", multisig_type, threshold, keys.len(), if sorted { " (BIP67 sorted)" } else { "" });
+    keys.iter().for_each(|k: &Vec<u8>| println!("Key: {}", base16::encode_bytes(k)));
+    println!("{}: {}", if multisig_type.is_segwit() { "Witness Script" } else { "Redeem Script" }, base16::encode_bytes(&script));
+    println!("Address: {}", address);
+}
+
+// descriptor <descriptor> [--network <network>] [--start 0] [--count 10]
+fn expand_descriptor(args: &[String]) {
+    let descriptor: Descriptor = Descriptor::from_str(&args[0]).expect("descriptor");
+    let network: Network = match option_value(args, "--network") {
+        Some(n) => Network::from_str(&n).expect("network"),
+        None => Network::Bitcoin
+    };
+    assert!(network.is_bitcoin(), "network");
+    let start: u32 = match option_value(args, "--start") {
+        Some(v) => v.parse::<u32>().expect("start"),
+        None => 0
+    };
+    let count: u32 = match option_value(args, "--count") {
+        Some(v) => v.parse::<u32>().expect("count"),
+        None if descriptor.is_ranged() => 10,
+        None => 1
+    };
+    // wildcards only expand to unhardened indexes, 0 to 2^31 - 1
+    let end: u32 = start.checked_add(count).expect("count");
+    assert!(end <= HARDENED, "--start plus --count goes past the last index {}", HARDENED - 1);
+
+    println!("[Output Descriptor]\n");
+    println!("Descriptor: {}", descriptor);
+    let public: Descriptor = descriptor.to_public().expect("descriptor");
+    if public != descriptor {
+        println!("Public Descriptor: {}", public);
+    }
+    println!("Network: {}", network);
+    println!();
+
+    for index in start..end {
+        match descriptor.address_at(index, network) {
+            Ok(address) => println!("{} {}", index, address),
+            Err(DescriptorError::NoAddress) => println!("{} {}", index, descriptor.script_pubkey_at(index).expect("descriptor").to_hex()),
+            Err(e) => panic!("{:?}", e)
+        };
+    }
+}
+
+// script <hex or assembly, e.g. "OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG"> [--network <network>]
+fn decode_script(args: &[String]) {
+    let script: Script = match Script::from_hex(&args[0]) {
+        Some(s) => s,
+        None => Script::from_asm(&args[0]).expect("script")
+    };
+    let network: Network = match option_value(args, "--network") {
+        Some(n) => Network::from_str(&n).expect("network"),
+        None => Network::Bitcoin
+    };
+
+    println!("[Bitcoin Script]\n");
+    println!("Hex: {}", script.to_hex());
+    println!("Assembly: {}", script.to_asm().expect("script"));
+    println!("Type: {}", script.script_type());
+    match script.to_address(network) {
+        Ok(address) => println!("Address ({}): {}", network, address),
+        Err(ScriptError::NoAddress) => println!("Address ({}): none", network),
+        Err(e) => panic!("{:?}", e)
+    };
+}
+
+// inspect <address>
+fn inspect_address(args: &[String]) {
+    let address: &String = &args[0];
+
+    println!("[Address Inspection]\n");
+    println!("Address: {}", address);
+    match inspect::inspect(address) {
+        Ok(info) => print_address_info(&info),
+        Err(InspectError::Bech32Checksum(positions) | InspectError::Eip55Checksum(positions)) => {
+            // point at the likely mistyped characters under the address
+            let marks: String = (0..address.len()).map(|i: usize| if positions.contains(&i) { '^' } else { ' ' }).collect();
+            println!("         {}", marks.trim_end());
+            let list: Vec<String> = positions.iter().map(|i: &usize| (i + 1).to_string()).collect();
+            println!("Valid: no (checksum error, likely at character {})", if list.is_empty() { String::from("unknown") } else { list.join(", ") });
+        },
+        Err(e) => println!("Valid: no ({:?})", e)
+    };
+}
+
+fn print_address_info(info: &AddressInfo) {
+    let networks: Vec<String> = info.networks.iter().map(|n: &Network| n.to_string()).collect();
+    println!("Encoding: {}", info.encoding);
+    println!("Network: {}", networks.join(", "));
+    println!("Type: {}", info.address_type);
+    if let Some(version) = info.witness_version {
+        println!("Witness Version: {}", version);
+    }
+    println!("Payload: {}", base16::encode_bytes(&info.payload));
+    if let Some(script) = &info.script_pubkey {
+        println!("ScriptPubKey: {}", script.to_hex());
+    }
+    println!("Valid: yes");
+}
+
+// decode-tx <hex> [--network <network>]
+fn decode_transaction(args: &[String]) {
+    let tx: Transaction = Transaction::from_hex(&args[0]).expect("transaction");
+    let network: Network = match option_value(args, "--network") {
+        Some(n) => Network::from_str(&n).expect("network"),
+        None => Network::Bitcoin
+    };
+
+    println!("[Bitcoin Transaction]\n");
+    println!("TXID: {}", transaction::hash_to_hex(&tx.txid()));
+    println!("WTXID: {}", transaction::hash_to_hex(&tx.wtxid()));
+    println!("Version: {}", tx.version);
+    println!("Size: {} bytes", tx.size());
+    println!("Virtual Size: {} vbytes", tx.vsize());
+    println!("Weight: {} WU", tx.weight());
+    println!("Locktime: {}", tx.lock_time);
+
+    println!("\nInputs: {}", tx.inputs.len());
+    for (i, input) in tx.inputs.iter().enumerate() {
+        print_input(i, input, tx.is_coinbase());
+    }
+    match tx.output_value() {
+        Some(value) => println!("\nOutputs: {} (total {} BTC)", tx.outputs.len(), transaction::format_amount(value)),
+        None => println!("\nOutputs: {} (total overflows)", tx.outputs.len())
+    };
+    for (i, output) in tx.outputs.iter().enumerate() {
+        print_output(i, output, network);
+    }
+}
+
+// sign-tx <hex> <private key> --prevouts <amount:script hex,...> [--sighash ALL]
+fn sign_transaction(args: &[String]) {
+    let mut tx: Transaction = Transaction::from_hex(&args[0]).expect("transaction");
+    let key: [u8; 32] = read_private_key(&args[1]).secret_key;
+    // amount in satoshis and scriptPubKey of the output spent by each input, in order
+    let prevouts: Vec<TxOut> = option_value(args, "--prevouts").expect("prevouts").split(',').map(|p: &str| {
+        let (value, script) = p.split_once(':').expect("prevout");
+        return TxOut { value: value.parse::<u64>().expect("amount"), script_pubkey: Script::from_hex(script).expect("script") };
+    }).collect();
+    let sighash_type: Option<SighashType> = option_value(args, "--sighash").map(|t: String| SighashType::from_str(&t).expect("sighash"));
+
+    println!("[Signed Transaction]\n");
+    for i in 0..tx.inputs.len() {
+        // taproot defaults to SIGHASH_DEFAULT, everything else to ALL
+        let t: SighashType = match (sighash_type, prevouts.get(i).map(|p: &TxOut| p.script_pubkey.witness_program())) {
+            (Some(t), _) => t,
+            (None, Some(Some((1, _)))) => SighashType::Default,
+            (None, _) => SighashType::All
+        };
+        match sign::sign_input(&mut tx, i, &key, &prevouts, t) {
+            Ok(()) => println!("Input #{}: signed ({})", i, t),
+            Err(SignError::KeyMismatch(_) | SignError::UnsupportedScript(_)) => println!("Input #{}: not ours, skipped", i),
+            Err(e) => panic!("{:?}", e)
+        };
+    }
+    println!("\nTXID: {}", transaction::hash_to_hex(&tx.txid()));
+    println!("Hex: {}", tx.to_hex());
+}
+
+// a PSBT in base64 or hex, or the path of a binary or text PSBT file
+fn read_psbt(arg: &str) -> Psbt {
+    return match fs::read(arg) {
+        Ok(data) if data.starts_with(&psbt::PSBT_MAGIC) => Psbt::deserialize(&data).expect("psbt"),
+        Ok(data) => Psbt::from_str(&String::from_utf8(data).expect("psbt")).expect("psbt"),
+        Err(_) => Psbt::from_str(arg).expect("psbt")
+    };
+}
+
+fn print_key_source(key: &[u8], source: &KeySource) {
+    println!("     Key: {} [{}{}]", base16::encode_bytes(key), base16::encode_bytes(&source.fingerprint), &format_path(&source.path)[1..]);
+}
+
+// psbt decode <psbt> [--network <network>]
+// psbt sign <psbt> <xprv>
+// psbt combine <psbt> <psbt>...
+// psbt finalize <psbt>
+fn process_psbt(args: &[String]) {
+    let mut psbt: Psbt = read_psbt(&args[1]);
+
+    match args[0].as_str() {
+        "decode" => {
+            let network: Network = match option_value(args, "--network") {
+                Some(n) => Network::from_str(&n).expect("network"),
+                None => Network::Bitcoin
+            };
+            let tx: Transaction = psbt.unsigned_tx().expect("psbt");
+
+            println!("[PSBT]\n");
+            println!("Version: {}", psbt.version);
+            println!("TXID: {}", transaction::hash_to_hex(&tx.txid()));
+            println!("Locktime: {}", tx.lock_time);
+            match psbt.fee() {
+                Ok(fee) => println!("Fee: {} BTC", transaction::format_amount(fee)),
+                Err(e) => println!("Fee: unknown ({:?})", e)
+            };
+            psbt.xpubs.iter().for_each(|(xpub, source): (&Vec<u8>, &KeySource)| print_key_source(xpub, source));
+
+            println!("\nInputs: {}", tx.inputs.len());
+            for (i, input) in psbt.inputs.iter().enumerate() {
+                println!("  #{} {}", i, tx.inputs[i].previous_output);
+                if let Ok(spent) = psbt.spent_output(i) {
+                    let address: String = spent.script_pubkey.to_address(network).map(|a: String| format!(" {}", a)).unwrap_or_default();
+                    println!("     Spends: {} BTC {}{}", transaction::format_amount(spent.value), spent.script_pubkey.script_type(), address);
+                }
+                if input.is_finalized() {
+                    println!("     Finalized: yes");
+                    continue;
+                }
+                println!("     Signatures: {}", input.partial_sigs.len() + input.tap_script_sigs.len() + input.tap_key_sig.iter().count());
+                input.bip32_derivations.iter().for_each(|(k, s): (&Vec<u8>, &KeySource)| print_key_source(k, s));
+                input.tap_bip32_derivations.iter().for_each(|(k, s): (&[u8; 32], &psbt::TapKeySource)| print_key_source(k, &s.source));
+            }
+            println!("\nOutputs: {}", tx.outputs.len());
+            for (i, output) in psbt.outputs.iter().enumerate() {
+                print_output(i, &tx.outputs[i], network);
+                output.bip32_derivations.iter().for_each(|(k, s): (&Vec<u8>, &KeySource)| print_key_source(k, s));
+                output.tap_bip32_derivations.iter().for_each(|(k, s): (&[u8; 32], &psbt::TapKeySource)| print_key_source(k, &s.source));
+            }
+            println!("\nFinalized: {}", if psbt.is_finalized() { "yes" } else { "no" });
+        },
+        "sign" => {
+            let master: ExtendedPrivateKey = ExtendedPrivateKey::from_str(&args[2]).expect("xprv");
+            let signed: Vec<usize> = psbt.sign(&master).expect("sign");
+
+            println!("[Signed PSBT]\n");
+            if signed.is_empty() {
+                println!("Signed Inputs: none");
+            }
+            else {
+                println!("Signed Inputs: {}", signed.iter().map(|i: &usize| format!("#{}", i)).collect::<Vec<String>>().join(", "));
+            }
+            println!("PSBT: {}", psbt);
+        },
+        "combine" => {
+            for arg in args[2..].iter() {
+                psbt.combine(&read_psbt(arg)).expect("combine");
+            }
+            println!("[Combined PSBT]\n");
+            println!("PSBT: {}", psbt);
+        },
+        "finalize" => {
+            let result: Result<(), PsbtError> = psbt.finalize();
+
+            println!("[Finalized PSBT]\n");
+            println!("PSBT: {}", psbt);
+            // the transaction is only extracted once every input is final
+            match result {
+                Ok(()) => {
+                    let tx: Transaction = psbt.extract().expect("extract");
+                    println!("\nTXID: {}", transaction::hash_to_hex(&tx.txid()));
+                    println!("Hex: {}", tx.to_hex());
+                },
+                Err(e) => println!("\nIncomplete: {:?}", e)
+            };
+        },
+        _ => panic!("psbt-command")
+    }
+}
+
+fn address_script(address: &str) -> Script {
+    return inspect::inspect(address).expect("address").script_pubkey.expect("address");
+}
+
+// fund <txid:vout:amount:script hex[:previous tx or redeem script hex],...> <address:amount,...> --change <address> --fee-rate <sat/vB>
+//      [--algorithm bnb|knapsack|largest-first] [--long-term-fee-rate <sat/vB>] [--height <tip height>] [--no-rbf]
+fn fund_transaction(args: &[String]) {
+    let utxos: Vec<Utxo> = args[0].split(',').map(|u: &str| {
+        let parts: Vec<&str> = u.split(':').collect();
+        assert!(parts.len() == 4 || parts.len() == 5, "utxo");
+        let txid: [u8; 32] = transaction::hash_from_hex(parts[0]).expect("txid");
+        let script_pubkey: Script = Script::from_hex(parts[3]).expect("script");
+        // legacy coins carry the transaction that created them, P2SH coins their redeem script
+        let mut previous_tx: Option<Transaction> = None;
+        let mut redeem_script: Option<Script> = None;
+        if let Some(extra) = parts.get(4) {
+            if let ScriptType::P2sh(_) = script_pubkey.script_type() {
+                redeem_script = Some(Script::from_hex(extra).expect("redeem-script"));
+            }
+            else {
+                previous_tx = Some(Transaction::from_hex(extra).expect("previous-tx"));
+            }
+        }
+        return Utxo {
+            outpoint: transaction::OutPoint::new(txid, parts[1].parse::<u32>().expect("vout")),
+            txout: TxOut { value: parts[2].parse::<u64>().expect("amount"), script_pubkey },
+            previous_tx,
+            redeem_script
+        };
+    }).collect();
+    let payments: Vec<TxOut> = args[1].split(',').map(|p: &str| {
+        let (address, value) = p.rsplit_once(':').expect("payment");
+        return TxOut { value: value.parse::<u64>().expect("amount"), script_pubkey: address_script(address) };
+    }).collect();
+    let change: Script = address_script(&option_value(args, "--change").expect("change"));
+    let policy: FeePolicy = FeePolicy {
+        fee_rate: option_value(args, "--fee-rate").expect("fee-rate").parse::<f64>().expect("fee-rate"),
+        // bitcoin core's -consolidatefeerate
+        long_term_fee_rate: option_value(args, "--long-term-fee-rate").map(|r: String| r.parse::<f64>().expect("long-term-fee-rate")).unwrap_or(10.0),
+        rbf: !args.iter().any(|a: &String| a == "--no-rbf"),
+        height: option_value(args, "--height").map(|h: String| h.parse::<u32>().expect("height"))
+    };
+
+    // without an algorithm a changeless branch and bound selection is tried before the knapsack
+    let (algorithm, selection): (Algorithm, Selection) = match option_value(args, "--algorithm") {
+        Some(a) => {
+            let algorithm: Algorithm = Algorithm::from_str(&a).expect("algorithm");
+            (algorithm, coinselect::fund(&utxos, &payments, &change, &policy, algorithm).expect("fund"))
+        },
+        None => match coinselect::fund(&utxos, &payments, &change, &policy, Algorithm::BranchAndBound) {
+            Ok(selection) => (Algorithm::BranchAndBound, selection),
+            Err(CoinSelectionError::NoSolution) => (Algorithm::Knapsack, coinselect::fund(&utxos, &payments, &change, &policy, Algorithm::Knapsack).expect("fund")),
+            Err(e) => panic!("{:?}", e)
+        }
+    };
+    let tx: Transaction = selection.psbt.unsigned_tx().expect("psbt");
+
+    println!("[Funded PSBT]\n");
+    println!("Algorithm: {}", algorithm);
+    println!("Inputs: {}", selection.selected.iter().map(|u: &Utxo| u.outpoint.to_string()).collect::<Vec<String>>().join(", "));
+    match selection.change {
+        Some(i) => println!("Change: {} BTC (output #{})", transaction::format_amount(tx.outputs[i].value), i),
+        None => println!("Change: none")
+    };
+    println!("Fee: {} BTC ({} vB, {:.2} sat/vB)", transaction::format_amount(selection.fee), selection.vsize, selection.fee as f64 / selection.vsize as f64);
+    println!("Locktime: {}", tx.lock_time);
+    println!("RBF: {}", if policy.rbf { "yes" } else { "no" });
+    println!("PSBT: {}", selection.psbt);
+}
+
+// signmessage <private key> <address> <message> [--full]
+// P2PKH addresses get a legacy signature, the others a BIP322 simple one unless --full is given
+fn sign_message(args: &[String]) {
+    let key: [u8; 32] = read_private_key(&args[0]).secret_key;
+    let format: SignatureFormat = if args.iter().any(|a: &String| a == "--full") {
+        SignatureFormat::Full
+    }
+    else {
+        message::default_format(&args[1]).expect("address")
+    };
+    let signature: String = message::sign(&key, &args[1], &args[2], format).expect("sign");
+
+    println!("[Signed Message]\n");
+    println!("Address: {}", args[1]);
+    println!("Format: {}", format_name(format));
+    println!("Signature: {}", signature);
+}
+
+// verifymessage <address> <signature> <message>
+fn verify_message(args: &[String]) {
+    println!("[Verified Message]\n");
+    println!("Address: {}", args[0]);
+    match message::verify(&args[0], &args[1], &args[2]) {
+        Ok((format, valid)) => {
+            println!("Format: {}", format_name(format));
+            println!("Valid: {}", if valid { "yes" } else { "no" });
+        },
+        Err(e) => println!("Valid: no ({:?})", e)
+    };
+}
+
+fn format_name(format: SignatureFormat) -> &'static str {
+    match format {
+        SignatureFormat::Legacy => return "Legacy (signmessage)",
+        SignatureFormat::Simple => return "BIP322 simple",
+        SignatureFormat::Full => return "BIP322 full",
+    };
+}
+
+fn read_private_key(input: &str) -> ImportedKey {
+    match import::parse_private_key(input) {
+        Ok(imported) => return imported,
+        Err(e) => panic!("private-key: {:?}", e),
+    };
+}
+
+// bip38 encrypt <private key> <passphrase>
+// bip38 decrypt <encrypted key> <passphrase>
+// bip38 intermediate <passphrase> [--lot <n> --sequence <n>]
+// bip38 generate <intermediate code> [--uncompressed]
+// bip38 confirm <confirmation code> <passphrase>
+fn process_bip38(args: &[String]) {
+    match args[0].as_str() {
+        "encrypt" => {
+            let imported: ImportedKey = read_private_key(&args[1]);
+            let (key, compressed) = (imported.secret_key, imported.compressed);
+            println!("[BIP38 Encrypted Key]\n");
+            println!("Encrypted Key: {}", bip38::encrypt(&key, compressed, &args[2]).expect("encrypt"));
+        },
+        "decrypt" => {
+            let (key, compressed) = bip38::decrypt(&args[1], &args[2]).expect("decrypt");
+            let pub_key: PublicKey = secp256k1::public_key(&key);
+            let (address, wif): (String, String) = if compressed {
+                (bitcoin::derive_compressed_address(&pub_key, Network::Bitcoin), bitcoin::encode_compressed_pr_key(&key, Network::Bitcoin))
+            }
+            else {
+                (bitcoin::derive_uncompressed_address(&pub_key, Network::Bitcoin), bitcoin::encode_uncompressed_pr_key(&key, Network::Bitcoin))
+            };
+            println!("[BIP38 Decrypted Key]\n");
+            println!("Address: {}", address);
+            println!("Private Key[*]: {}", wif);
+            println!("\n[*] Keep secret at all times.");
+        },
+        "intermediate" => {
+            let lot_sequence: Option<(u32, u32)> = option_value(args, "--lot").map(|lot: String| (
+                lot.parse::<u32>().expect("lot"),
+                option_value(args, "--sequence").expect("sequence").parse::<u32>().expect("sequence")
+            ));
+            println!("[BIP38 Intermediate Code]\n");
+            println!("Intermediate Code: {}", bip38::intermediate_code(&args[1], lot_sequence).expect("intermediate"));
+        },
+        "generate" => {
+            let compressed: bool = !args.iter().any(|a: &String| a == "--uncompressed");
+            let key: EcMultiplyKey = bip38::encrypt_from_intermediate(&args[1], compressed).expect("generate");
+            println!("[BIP38 Generated Key]\n");
+            println!("Address: {}", key.address);
+            println!("Encrypted Key: {}", key.encrypted);
+            println!("Confirmation Code: {}", key.confirmation);
+        },
+        "confirm" => {
+            let confirmation: Confirmation = bip38::verify_confirmation(&args[1], &args[2]).expect("confirm");
+            println!("[BIP38 Confirmation]\n");
+            println!("Address: {}", confirmation.address);
+            println!("Compressed: {}", if confirmation.compressed { "yes" } else { "no" });
+            if let Some((lot, sequence)) = confirmation.lot_sequence {
+                println!("Lot: {}", lot);
+                println!("Sequence: {}", sequence);
+            }
+        },
+        _ => panic!("bip38-command")
+    }
+}
+
+// merkle root <txid>...
+// merkle proof <index> <txid>...
+// merkle verify <merkleblock hex or file>
+fn process_merkle(args: &[String]) {
+    let txids = |args: &[String]| -> Vec<[u8; 32]> {
+        return args.iter().map(|t: &String| transaction::hash_from_hex(t).expect("txid")).collect();
+    };
+    match args[0].as_str() {
+        "root" => {
+            let (root, mutated) = merkle::compute_merkle_root(&txids(&args[1..])).expect("txids");
+            println!("[Merkle Root]\n");
+            println!("Transactions: {}", args.len() - 1);
+            println!("Merkle Root: {}", transaction::hash_to_hex(&root));
+            // the same root as the list without its repeated hashes, see CVE-2012-2459
+            println!("Mutated: {}", if mutated { "yes" } else { "no" });
+        },
+        "proof" => {
+            let index: usize = args[1].parse::<usize>().expect("index");
+            let ids: Vec<[u8; 32]> = txids(&args[2..]);
+            let proof: MerkleProof = merkle::merkle_proof(&ids, index).expect("index");
+            println!("[Merkle Proof]\n");
+            println!("TXID: {}", transaction::hash_to_hex(&ids[index]));
+            println!("Position: {}", proof.index);
+            println!("Merkle Root: {}", transaction::hash_to_hex(&proof.root(&ids[index])));
+            println!("\nSiblings: {}", proof.siblings.len());
+            proof.siblings.iter().for_each(|h: &[u8; 32]| println!("{}", transaction::hash_to_hex(h)));
+        },
+        "verify" => {
+            // gettxoutproof output, or a file holding it
+            let hex: String = fs::read_to_string(&args[1]).unwrap_or(args[1].clone());
+            let merkle_block: MerkleBlock = MerkleBlock::from_hex(&hex).expect("merkleblock");
+            println!("[Merkle Block]\n");
+            println!("Block Hash: {}", transaction::hash_to_hex(&merkle_block.header.block_hash()));
+            println!("Merkle Root: {}", transaction::hash_to_hex(&merkle_block.header.merkle_root));
+            println!("Transactions: {}", merkle_block.tree.total_transactions);
+            match merkle_block.verify() {
+                Ok(matches) => {
+                    println!("Valid: yes");
+                    println!("\nIncluded: {}", matches.len());
+                    matches.iter().for_each(|(i, txid)| println!("{} {}", i, transaction::hash_to_hex(txid)));
+                },
+                Err(e) => println!("Valid: no ({:?})", e)
+            };
+        },
+        _ => panic!("merkle-command")
+    }
+}
+
+// headers <file> [--network bitcoin] [--height <height of the first header>] [--trusted]
+// the file holds raw 80-byte headers back to back, the first of them a checkpoint unless --trusted
+fn validate_headers(args: &[String]) {
+    let network: Network = match option_value(args, "--network") {
+        Some(n) => Network::from_str(&n).expect("network"),
+        None => Network::Bitcoin
+    };
+    let height: u32 = match option_value(args, "--height") {
+        Some(v) => v.parse::<u32>().expect("height"),
+        None => 0
+    };
+    let data: Vec<u8> = fs::read(&args[0]).expect("headers-file");
+    let list: Vec<BlockHeader> = headers::read_headers(&data).expect("headers");
+    let params: ChainParams = headers::chain_params(network).expect("network");
+    let mut chain: HeaderChain = if args.iter().any(|a: &String| a == "--trusted") {
+        HeaderChain::from_trusted(&list[0], height, &params).expect("anchor")
+    }
+    else {
+        HeaderChain::new(&list[0], height, &params).expect("anchor")
+    };
+
+    println!("[Block Headers]\n");
+    println!("Network: {}", network);
+    println!("Anchor: {} {}", height, transaction::hash_to_hex(&list[0].block_hash()));
+    let result: Result<(), HeaderError> = chain.extend(&list[1..]);
+    println!("Tip: {} {}", chain.height(), transaction::hash_to_hex(&chain.tip_hash()));
+    println!("Chain Work: 0x{:x}", chain.chain_work);
+    println!("Median Time Past: {}", chain.median_time_past());
+    match result {
+        Ok(()) => println!("Valid: yes"),
+        Err(e) => println!("Valid: no ({:?})", e)
+    };
+}
+
+// silentpayments address <scan private key> <spend private key> [--network bitcoin] [--labels 1,2]
+// silentpayments send <txid:vout:private key[:p2tr]>,... <address>...
+// silentpayments scan <transaction hex> <spent scriptPubKey hex>,... <scan private key> <spend public key> [--network bitcoin] [--labels 1,2]
+fn silent_payments(args: &[String]) {
+    let labels: Vec<u32> = match option_value(args, "--labels") {
+        Some(l) => l.split(',').map(|m: &str| m.parse::<u32>().expect("label")).collect(),
+        None => vec![]
+    };
+    // the receiver's network, which senders take from the address instead
+    let network: Network = match option_value(args, "--network") {
+        Some(n) => Network::from_str(&n).expect("network"),
+        None => Network::Bitcoin
+    };
+    assert!(network.is_bitcoin(), "network");
+    match args[0].as_str() {
+        "address" => {
+            let spend_key: PublicKey = SecretKey::from_slice(&read_private_key(&args[2]).secret_key).expect("spend-key").public_key(&Secp256k1::signing_only());
+            let receiver: Receiver = Receiver::new(&read_private_key(&args[1]).secret_key, &spend_key, network).expect("scan-key");
+            println!("[Silent Payment Address]\n");
+            println!("Scan Public Key: {}", base16::encode_bytes(&receiver.address().scan_key.serialize()));
+            println!("Spend Public Key: {}", base16::encode_bytes(&spend_key.serialize()));
+            println!("Address: {}", receiver.address());
+            for m in labels.iter() {
+                println!("Label {}: {}", m, receiver.labelled_address(*m).expect("label"));
+            }
+        },
+        "send" => {
+            let mut outpoints: Vec<transaction::OutPoint> = vec![];
+            let mut keys: Vec<InputKey> = vec![];
+            for input in args[1].split(',') {
+                let parts: Vec<&str> = input.split(':').collect();
+                assert!(parts.len() == 3 || (parts.len() == 4 && parts[3] == "p2tr"), "input");
+                outpoints.push(transaction::OutPoint::new(transaction::hash_from_hex(parts[0]).expect("txid"), parts[1].parse::<u32>().expect("vout")));
+                keys.push(InputKey { secret_key: read_private_key(parts[2]).secret_key, taproot: parts.len() == 4 });
+            }
+            let recipients: Vec<SilentPaymentAddress> = args[2..].iter()
+                .filter(|a: &&String| !a.starts_with("--"))
+                .map(|a: &String| SilentPaymentAddress::decode(a).expect("address"))
+                .collect();
+            let outputs: Vec<[u8; 32]> = silentpayments::sender_outputs(&outpoints, &keys, &recipients).expect("silent-payment");
+            println!("[Silent Payment Outputs]\n");
+            for (recipient, output) in recipients.iter().zip(outputs.iter()) {
+                let hrp: &str = recipient.network.bitcoin_params().expect("network").bech32_hrp;
+                println!("{} {}", base16::encode_bytes(output), bech32::encode_segwit_address(hrp, 1, output).expect("p2tr"));
+            }
+        },
+        "scan" => {
+            let tx: Transaction = Transaction::from_hex(&args[1]).expect("transaction");
+            let scripts: Vec<Vec<u8>> = args[2].split(',').map(|s: &str| Script::from_hex(s).expect("script").bytes).collect();
+            assert!(scripts.len() == tx.inputs.len(), "spent-scripts");
+            let spend_key: PublicKey = PublicKey::from_slice(&base16::decode_string(&args[4])).expect("spend-key");
+            let mut receiver: Receiver = Receiver::new(&read_private_key(&args[3]).secret_key, &spend_key, network).expect("scan-key");
+            labels.iter().for_each(|m: &u32| receiver.add_label(*m).expect("label"));
+
+            // the x-only keys of the taproot outputs
+            let outputs: Vec<[u8; 32]> = tx.outputs.iter()
+                .filter(|o: &&TxOut| o.script_pubkey.bytes.len() == 34 && o.script_pubkey.bytes[..2] == [0x51, 0x20])
+                .map(|o: &TxOut| o.script_pubkey.bytes[2..].try_into().expect("output-key"))
+                .collect();
+            println!("[Silent Payment Scan]\n");
+            println!("TXID: {}", transaction::hash_to_hex(&tx.txid()));
+            println!("Network: {}", network);
+            match silentpayments::transaction_tweak(&tx.inputs, &scripts) {
+                Ok(tweak) => {
+                    println!("Tweak: {}", base16::encode_bytes(&tweak.serialize()));
+                    let found: Vec<FoundOutput> = receiver.scan(&tweak, &outputs);
+                    println!("Found: {}", found.len());
+                    for f in found.iter() {
+                        let label: String = f.label.map(|m: u32| format!(" (label {})", m)).unwrap_or_default();
+                        let address: String = bech32::encode_segwit_address(network.bitcoin_params().expect("network").bech32_hrp, 1, &f.output_key).expect("p2tr");
+                        println!("{} {} tweak {}{}", base16::encode_bytes(&f.output_key), address, base16::encode_bytes(&f.tweak), label);
+                    }
+                },
+                Err(e) => println!("Eligible: no ({:?})", e)
+            };
+        },
+        _ => panic!("silentpayments-command")
+    }
+}
+
+fn script_asm(script: &Script) -> String {
+    return script.to_asm().unwrap_or_else(|_: ScriptError| script.to_hex());
+}
+
+fn print_input(i: usize, input: &TxIn, coinbase: bool) {
+    if coinbase {
+        println!("  #{} coinbase", i);
+        println!("     Coinbase Data: {}", input.script_sig.to_hex());
+    }
+    else {
+        println!("  #{} {}", i, input.previous_output);
+        println!("     ScriptSig: {}", script_asm(&input.script_sig));
+    }
+    if !input.witness.is_empty() {
+        let items: Vec<String> = input.witness.iter().map(|w: &Vec<u8>| base16::encode_bytes(w)).collect();
+        println!("     Witness: {}", items.join(" "));
+    }
+    println!("     Sequence: 0x{:08x}", input.sequence);
+}
+
+fn print_output(i: usize, output: &TxOut, network: Network) {
+    let address: String = match output.script_pubkey.to_address(network) {
+        Ok(a) => format!(" {}", a),
+        Err(_) => String::new()
+    };
+    println!("  #{} {} BTC {}{}", i, transaction::format_amount(output.value), output.script_pubkey.script_type(), address);
+    println!("     ScriptPubKey: {}", script_asm(&output.script_pubkey));
+}
+
+// discover <network> <mnemonic> <used-addresses-file> [--passphrase <p>] [--gap-limit <n>] [--purposes 44,49,84,86]
+fn discover(args: &[String]) {
+    let network: Network = Network::from_str(args[0].as_str()).expect("network");
+    let mnemonic: &String = &args[1];
+    let used_path: &String = &args[2];
+
+    let passphrase: String = option_value(args, "--passphrase").unwrap_or_default();
+    let gap_limit: u32 = match option_value(args, "--gap-limit") {
+        Some(v) => v.parse::<u32>().expect("gap-limit"),
+        None => discovery::DEFAULT_GAP_LIMIT
+    };
+    let purposes: Vec<Purpose> = match option_value(args, "--purposes") {
+        Some(v) => v.split(',').map(|p: &str| Purpose::from_str(p).expect("purpose")).collect::<Vec<Purpose>>(),
+        None => discovery::supported_purposes(network)
+    };
+
+    bip39::mnemonic_to_entropy(mnemonic).expect("mnemonic");
+    let master: ExtendedPrivateKey = ExtendedPrivateKey::from_seed(&bip39::mnemonic_to_seed(mnemonic, &passphrase));
+
+    let used = discovery::load_used_addresses(used_path).expect("used-addresses");
+    let reports: Vec<AccountReport> = discovery::discover(&master, network, &purposes, gap_limit, &used).expect("discovery");
+
+    println!("[Gap-Limit Address Discovery]\n");
+    println!("Network: {}", network);
+    println!("Gap Limit: {}", gap_limit);
+    println!("Purposes: {}", purposes.iter().map(|p: &Purpose| p.to_string()).collect::<Vec<String>>().join(", "));
+    println!("Known Addresses: {}", used.len());
+
+    if reports.is_empty() {
+        println!("\nNo used accounts found.");
+    }
+    for report in reports.iter() {
+        println!("\n{} account {} ({})", report.purpose, report.account, format_path(&report.path));
+        println!("Account Extended Public Key: {}", report.xpub);
+        println!("Next Receive Index: {}, Next Change Index: {}", report.next_index(0), report.next_index(1));
+        for u in report.used.iter() {
+            let mut path: Vec<u32> = report.path.clone();
+            path.extend_from_slice(&[u.change, u.index]);
+            println!("  {} {}", format_path(&path), u.address);
+        }
+    }
+}
+
+// bip85 <xprv> mnemonic <words> [--language english] [--index 0]
+// bip85 <xprv> hex <num-bytes> [--index 0]
+// bip85 <xprv> wif|xprv [--index 0]
+fn derive_bip85(args: &[String]) {
+    let master: ExtendedPrivateKey = ExtendedPrivateKey::from_str(&args[0]).expect("xprv");
+    let index: u32 = match option_value(args, "--index") {
+        Some(v) => v.parse::<u32>().expect("index"),
+        None => 0
+    };
+
+    let derived: String = match args[1].as_str() {
+        "mnemonic" => {
+            let words: u32 = args[2].parse::<u32>().expect("words");
+            let language: Language = match option_value(args, "--language") {
+                Some(v) => Language::from_str(&v).expect("language"),
+                None => Language::English
+            };
+            bip85::to_mnemonic(&master, language, words, index).expect("bip85")
+        },
+        "hex" => bip85::to_hex(&master, args[2].parse::<u32>().expect("num-bytes"), index).expect("bip85"),
+        "wif" => bip85::to_wif(&master, index).expect("bip85"),
+        "xprv" => bip85::to_xprv(&master, index).expect("bip85").to_xprv(),
+        _ => panic!("application")
+    };
+    println!("{}", derived);
+}
+
+// slip39 split <hex secret> <group-threshold> <member-threshold>of<member-count>,... [--passphrase <p>] [--iteration-exponent <e>] [--extendable]
+// slip39 combine <file with one share per line> [--passphrase <p>]
+fn shamir_slip39(args: &[String]) {
+    let passphrase: String = option_value(args, "--passphrase").unwrap_or_default();
+
+    match args[0].as_str() {
+        "split" => {
+            let secret: Vec<u8> = base16::decode_string(&args[1]);
+            let group_threshold: u8 = args[2].parse::<u8>().expect("group-threshold");
+            let groups: Vec<(u8, u8)> = args[3].split(',').map(|g: &str| {
+                let (t, n) = g.split_once("of").expect("group");
+                (t.parse::<u8>().expect("member-threshold"), n.parse::<u8>().expect("member-count"))
+            }).collect::<Vec<(u8, u8)>>();
+            let iteration_exponent: u8 = match option_value(args, "--iteration-exponent") {
+                Some(v) => v.parse::<u8>().expect("iteration-exponent"),
+                None => 1
+            };
+            let extendable: bool = args.iter().any(|a: &String| a == "--extendable");
+
+            let shares: Vec<Vec<String>> = slip39::split(&secret, &passphrase, group_threshold, &groups, iteration_exponent, extendable).expect("slip39");
+
+            println!("[SLIP-39 Shares]\n");
+            println!("Groups Required: {} of {}", group_threshold, groups.len());
+            for (i, (group, (t, n))) in shares.iter().zip(groups.iter()).enumerate() {
+                println!("\nGroup {} ({} of {} shares required):", i + 1, t, n);
+                group.iter().for_each(|m: &String| println!("{}", m));
+            }
+        },
+        "combine" => {
+            let content: String = fs::read_to_string(&args[1]).expect("shares-file");
+            let mnemonics: Vec<&str> = content.lines().map(|l: &str| l.trim()).filter(|l: &&str| !l.is_empty()).collect::<Vec<&str>>();
+
+            let secret: Vec<u8> = slip39::combine(&mnemonics, &passphrase).expect("slip39");
+            println!("{}", base16::encode_bytes(&secret));
+        },
+        _ => panic!("slip39-command")
+    }
+}
@@ -0,0 +1,379 @@
+use std::fmt;
+use crate::base16;
+use crate::block::{self, Block, BlockError, BlockHeader};
+use crate::bytes::{self, Reader, ReadError};
+use crate::crypto::hashing;
+
+// bitcoin core's bound: the most transactions of the smallest size a block can hold
+const MAX_TRANSACTIONS: u32 = 4000000 / 240;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum MerkleError {
+    Block(BlockError),
+    NoTransactions,
+    TooManyTransactions(u32),
+    // more hashes than transactions, or fewer flag bits than hashes
+    TooManyHashes(usize),
+    NotEnoughBits,
+    NotEnoughHashes,
+    UnusedBits,
+    UnusedHashes,
+    // two identical children under one node, the CVE-2012-2459 mutation
+    IdenticalChildren,
+    RootMismatch,
+    IndexOutOfRange(usize),
+    // one match flag per transaction: the flags given and the transactions
+    MatchCount(usize, usize)
+}
+
+impl From<BlockError> for MerkleError {
+    fn from(e: BlockError) -> Self {
+        return MerkleError::Block(e);
+    }
+}
+
+impl From<ReadError> for MerkleError {
+    fn from(e: ReadError) -> Self {
+        return MerkleError::Block(BlockError::Read(e));
+    }
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data: [u8; 64] = [0; 64];
+    data[..32].copy_from_slice(left);
+    data[32..].copy_from_slice(right);
+    return hashing::sha256d(&data);
+}
+
+// the root and whether two identical hashes were paired on some level, which lets [a, b, c] and [a, b, c, c]
+// share a root (CVE-2012-2459); an odd last hash is paired with itself
+pub fn compute_merkle_root(txids: &[[u8; 32]]) -> Option<([u8; 32], bool)> {
+    if txids.is_empty() {
+        return None;
+    }
+    let mut level: Vec<[u8; 32]> = txids.to_vec();
+    let mut mutated: bool = false;
+    while level.len() > 1 {
+        mutated |= level.chunks(2).any(|pair: &[[u8; 32]]| pair.len() == 2 && pair[0] == pair[1]);
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+        level = level.chunks(2).map(|pair: &[[u8; 32]]| hash_pair(&pair[0], &pair[1])).collect();
+    }
+    return Some((level[0], mutated));
+}
+
+pub fn merkle_root(txids: &[[u8; 32]]) -> Option<[u8; 32]> {
+    return compute_merkle_root(txids).map(|(root, _)| root);
+}
+
+// the sibling hashes from a transaction up to the root, as electrum servers return them
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct MerkleProof {
+    pub index: usize,
+    pub siblings: Vec<[u8; 32]>
+}
+
+impl MerkleProof {
+    pub fn root(&self, txid: &[u8; 32]) -> [u8; 32] {
+        let mut hash: [u8; 32] = *txid;
+        for (height, sibling) in self.siblings.iter().enumerate() {
+            hash = if (self.index >> height) & 1 == 1 { hash_pair(sibling, &hash) } else { hash_pair(&hash, sibling) };
+        }
+        return hash;
+    }
+
+    pub fn verify(&self, txid: &[u8; 32], root: &[u8; 32]) -> bool {
+        return self.root(txid) == *root;
+    }
+}
+
+pub fn merkle_proof(txids: &[[u8; 32]], index: usize) -> Result<MerkleProof, MerkleError> {
+    if index >= txids.len() {
+        return Err(MerkleError::IndexOutOfRange(index));
+    }
+    let mut level: Vec<[u8; 32]> = txids.to_vec();
+    let mut position: usize = index;
+    let mut siblings: Vec<[u8; 32]> = vec![];
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+        siblings.push(level[position ^ 1]);
+        level = level.chunks(2).map(|pair: &[[u8; 32]]| hash_pair(&pair[0], &pair[1])).collect();
+        position /= 2;
+    }
+    return Ok(MerkleProof { index, siblings });
+}
+
+// a matched transaction and its position in the block
+pub type Match = (usize, [u8; 32]);
+
+// BIP37: the hashes and depth-first flag bits of the pruned tree above the matched transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct PartialMerkleTree {
+    pub total_transactions: u32,
+    pub hashes: Vec<[u8; 32]>,
+    pub flags: Vec<bool>
+}
+
+impl PartialMerkleTree {
+    // nodes on a level, height 0 being the transactions
+    fn width(&self, height: u32) -> usize {
+        return (self.total_transactions as usize + (1 << height) - 1) >> height;
+    }
+
+    fn height(&self) -> u32 {
+        let mut height: u32 = 0;
+        while self.width(height) > 1 {
+            height += 1;
+        }
+        return height;
+    }
+
+    fn node_hash(&self, height: u32, position: usize, txids: &[[u8; 32]]) -> [u8; 32] {
+        if height == 0 {
+            return txids[position];
+        }
+        let left: [u8; 32] = self.node_hash(height - 1, position * 2, txids);
+        let right: [u8; 32] = if position * 2 + 1 < self.width(height - 1) { self.node_hash(height - 1, position * 2 + 1, txids) } else { left };
+        return hash_pair(&left, &right);
+    }
+
+    fn build(&mut self, height: u32, position: usize, txids: &[[u8; 32]], matches: &[bool]) {
+        let start: usize = position << height;
+        let end: usize = ((position + 1) << height).min(txids.len());
+        let parent_of_match: bool = matches[start..end].iter().any(|m: &bool| *m);
+        self.flags.push(parent_of_match);
+        if height == 0 || !parent_of_match {
+            self.hashes.push(self.node_hash(height, position, txids));
+            return;
+        }
+        self.build(height - 1, position * 2, txids, matches);
+        if position * 2 + 1 < self.width(height - 1) {
+            self.build(height - 1, position * 2 + 1, txids, matches);
+        }
+    }
+
+    pub fn from_txids(txids: &[[u8; 32]], matches: &[bool]) -> Result<Self, MerkleError> {
+        if matches.len() != txids.len() {
+            return Err(MerkleError::MatchCount(matches.len(), txids.len()));
+        }
+        let mut tree: PartialMerkleTree = Self { total_transactions: txids.len() as u32, hashes: vec![], flags: vec![] };
+        let height: u32 = tree.height();
+        tree.build(height, 0, txids, matches);
+        return Ok(tree);
+    }
+
+    fn extract(&self, height: u32, position: usize, bits_used: &mut usize, hashes_used: &mut usize, matches: &mut Vec<Match>) -> Result<[u8; 32], MerkleError> {
+        let parent_of_match: bool = *self.flags.get(*bits_used).ok_or(MerkleError::NotEnoughBits)?;
+        *bits_used += 1;
+        if height == 0 || !parent_of_match {
+            let hash: [u8; 32] = *self.hashes.get(*hashes_used).ok_or(MerkleError::NotEnoughHashes)?;
+            *hashes_used += 1;
+            if height == 0 && parent_of_match {
+                matches.push((position, hash));
+            }
+            return Ok(hash);
+        }
+        let left: [u8; 32] = self.extract(height - 1, position * 2, bits_used, hashes_used, matches)?;
+        let right: [u8; 32] = if position * 2 + 1 < self.width(height - 1) {
+            let right: [u8; 32] = self.extract(height - 1, position * 2 + 1, bits_used, hashes_used, matches)?;
+            if right == left {
+                return Err(MerkleError::IdenticalChildren);
+            }
+            right
+        }
+        else {
+            left
+        };
+        return Ok(hash_pair(&left, &right));
+    }
+
+    // the root and the matched transactions
+    pub fn extract_matches(&self) -> Result<([u8; 32], Vec<Match>), MerkleError> {
+        if self.total_transactions == 0 {
+            return Err(MerkleError::NoTransactions);
+        }
+        if self.total_transactions > MAX_TRANSACTIONS {
+            return Err(MerkleError::TooManyTransactions(self.total_transactions));
+        }
+        if self.hashes.len() > self.total_transactions as usize || self.flags.len() < self.hashes.len() {
+            return Err(MerkleError::TooManyHashes(self.hashes.len()));
+        }
+        let mut bits_used: usize = 0;
+        let mut hashes_used: usize = 0;
+        let mut matches: Vec<Match> = vec![];
+        let root: [u8; 32] = self.extract(self.height(), 0, &mut bits_used, &mut hashes_used, &mut matches)?;
+        // only the padding of the last flag byte may be left over
+        if bits_used.div_ceil(8) != self.flags.len().div_ceil(8) {
+            return Err(MerkleError::UnusedBits);
+        }
+        if hashes_used != self.hashes.len() {
+            return Err(MerkleError::UnusedHashes);
+        }
+        return Ok((root, matches));
+    }
+
+    pub fn read(reader: &mut Reader) -> Result<Self, ReadError> {
+        let total_transactions: u32 = reader.read_u32()?;
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for _ in 0..reader.read_compact_size()? {
+            hashes.push(reader.read_array::<32>()?);
+        }
+        // least significant bit first
+        let flags: Vec<bool> = reader.read_var_bytes()?.iter().flat_map(|b: &u8| (0..8).map(move |i: u8| (b >> i) & 1 == 1)).collect();
+        return Ok(Self { total_transactions, hashes, flags });
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut r: Vec<u8> = self.total_transactions.to_le_bytes().to_vec();
+        r.extend(bytes::compact_size(self.hashes.len() as u64));
+        for hash in self.hashes.iter() {
+            r.extend_from_slice(hash);
+        }
+        let mut flags: Vec<u8> = vec![0; self.flags.len().div_ceil(8)];
+        for (i, flag) in self.flags.iter().enumerate() {
+            if *flag {
+                flags[i / 8] |= 1 << (i % 8);
+            }
+        }
+        r.extend(bytes::compact_size(flags.len() as u64));
+        r.extend(flags);
+        return r;
+    }
+}
+
+// a header and the partial merkle tree of some of its transactions, the merkleblock message and what gettxoutproof returns
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct MerkleBlock {
+    pub header: BlockHeader,
+    pub tree: PartialMerkleTree
+}
+
+impl MerkleBlock {
+    pub fn from_header_txids(header: &BlockHeader, txids: &[[u8; 32]], matched: &[[u8; 32]]) -> Self {
+        let matches: Vec<bool> = txids.iter().map(|t: &[u8; 32]| matched.contains(t)).collect();
+        return Self { header: *header, tree: PartialMerkleTree::from_txids(txids, &matches).expect("matches") };
+    }
+
+    pub fn from_block(block: &Block, matched: &[[u8; 32]]) -> Self {
+        return MerkleBlock::from_header_txids(&block.header, &block.txids(), matched);
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, MerkleError> {
+        let mut reader: Reader = Reader::new(data);
+        let header: BlockHeader = BlockHeader::read(&mut reader)?;
+        let tree: PartialMerkleTree = PartialMerkleTree::read(&mut reader)?;
+        if reader.remaining() > 0 {
+            return Err(MerkleError::Block(BlockError::TrailingBytes(reader.remaining())));
+        }
+        return Ok(Self { header, tree });
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, MerkleError> {
+        return MerkleBlock::deserialize(&block::decode_hex(s)?);
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut r: Vec<u8> = self.header.serialize();
+        r.extend(self.tree.serialize());
+        return r;
+    }
+
+    pub fn to_hex(&self) -> String {
+        return base16::encode_bytes(&self.serialize());
+    }
+
+    // the matched transactions, once the tree is shown to commit to the header's merkle root
+    pub fn verify(&self) -> Result<Vec<Match>, MerkleError> {
+        let (root, matches) = self.tree.extract_matches()?;
+        if root != self.header.merkle_root {
+            return Err(MerkleError::RootMismatch);
+        }
+        return Ok(matches);
+    }
+}
+
+impl fmt::Display for MerkleBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.to_hex());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merkle::*;
+
+    fn txids(n: u8) -> Vec<[u8; 32]> {
+        return (0..n).map(|i: u8| hashing::sha256d(&[i])).collect();
+    }
+
+    #[test]
+    fn merkle_root_and_mutation() {
+        assert_eq!(compute_merkle_root(&[]), None);
+        let single: Vec<[u8; 32]> = txids(1);
+        assert_eq!(compute_merkle_root(&single), Some((single[0], false)));
+
+        // [a, b, c] is hashed as [a, b, c, c], so spelling out the duplicate gives the same root
+        let three: Vec<[u8; 32]> = txids(3);
+        let (root, mutated) = compute_merkle_root(&three).unwrap();
+        assert_eq!(root, hash_pair(&hash_pair(&three[0], &three[1]), &hash_pair(&three[2], &three[2])));
+        assert!(!mutated);
+        let mut four: Vec<[u8; 32]> = three.clone();
+        four.push(three[2]);
+        assert_eq!(compute_merkle_root(&four), Some((root, true)));
+
+        // the same on a higher level: six transactions are hashed as if the last pair were repeated
+        let six: Vec<[u8; 32]> = txids(6);
+        let mut eight: Vec<[u8; 32]> = six.clone();
+        eight.extend_from_slice(&six[4..6]);
+        assert_eq!(compute_merkle_root(&eight), Some((merkle_root(&six).unwrap(), true)));
+    }
+
+    #[test]
+    fn merkle_proofs_and_partial_trees() {
+        for n in 1..=17 {
+            let ids: Vec<[u8; 32]> = txids(n);
+            let root: [u8; 32] = merkle_root(&ids).unwrap();
+            for i in 0..ids.len() {
+                let proof: MerkleProof = merkle_proof(&ids, i).unwrap();
+                assert!(proof.verify(&ids[i], &root));
+                assert!(!proof.verify(&ids[(i + 1) % ids.len()], &root) || ids.len() == 1);
+            }
+            assert_eq!(merkle_proof(&ids, ids.len()), Err(MerkleError::IndexOutOfRange(ids.len())));
+
+            // every third transaction matched
+            let matches: Vec<bool> = (0..ids.len()).map(|i: usize| i % 3 == 1).collect();
+            let tree: PartialMerkleTree = PartialMerkleTree::from_txids(&ids, &matches).unwrap();
+            let expected: Vec<Match> = (0..ids.len()).filter(|i: &usize| matches[*i]).map(|i: usize| (i, ids[i])).collect();
+            assert_eq!(tree.extract_matches(), Ok((root, expected)));
+            let data: Vec<u8> = tree.serialize();
+            let mut reader: Reader = Reader::new(&data);
+            let read: PartialMerkleTree = PartialMerkleTree::read(&mut reader).unwrap();
+            assert_eq!(read.extract_matches().unwrap().0, root);
+        }
+
+        // a tree over a mutated list is refused
+        let mut ids: Vec<[u8; 32]> = txids(11);
+        ids.push(ids[10]);
+        let matches: Vec<bool> = (0..12).map(|i: usize| i >= 10).collect();
+        assert_eq!(PartialMerkleTree::from_txids(&ids, &matches).unwrap().extract_matches(), Err(MerkleError::IdenticalChildren));
+
+        // every transaction needs its match flag, and none more
+        assert_eq!(PartialMerkleTree::from_txids(&txids(5), &[false, true, false, false]), Err(MerkleError::MatchCount(4, 5)));
+        assert_eq!(PartialMerkleTree::from_txids(&txids(5), &[false; 6]), Err(MerkleError::MatchCount(6, 5)));
+
+        let mut tree: PartialMerkleTree = PartialMerkleTree::from_txids(&txids(5), &[false, true, false, false, false]).unwrap();
+        tree.hashes.push([0; 32]);
+        assert_eq!(tree.extract_matches(), Err(MerkleError::UnusedHashes));
+        tree.hashes.truncate(1);
+        assert_eq!(tree.extract_matches(), Err(MerkleError::NotEnoughHashes));
+        tree.total_transactions = 0;
+        assert_eq!(tree.extract_matches(), Err(MerkleError::NoTransactions));
+    }
+}
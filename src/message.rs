@@ -0,0 +1,496 @@
+use rand::Rng;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use crate::base64;
+use crate::bytes::{self, Reader, ReadError};
+use crate::crypto::hashing;
+use crate::inspect::{self, InspectError};
+use crate::script::{Builder, Instruction, Opcode, Script, ScriptType};
+use crate::sighash::{self, SighashType};
+use crate::sign::{self, SignError};
+use crate::transaction::{OutPoint, Transaction, TransactionError, TxIn, TxOut};
+
+const MESSAGE_MAGIC: &str = "Bitcoin Signed Message:\n";
+const BIP322_TAG: &str = "BIP0322-signed-message";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum SignatureFormat {
+    // Bitcoin Core signmessage, extended to segwit addresses by BIP137
+    Legacy,
+    // BIP322 witness stack
+    Simple,
+    // BIP322 to_sign transaction
+    Full
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum MessageError {
+    Address(InspectError),
+    // the address has no script pubkey or one that cannot be proven
+    UnsupportedAddress,
+    InvalidEncoding,
+    InvalidHeader(u8),
+    InvalidSignature,
+    Read(ReadError),
+    Transaction(TransactionError),
+    // a full signature that does not spend to_spend into the OP_RETURN output
+    InvalidToSign,
+    InvalidKey,
+    KeyMismatch,
+    Sign(SignError)
+}
+
+impl From<InspectError> for MessageError {
+    fn from(e: InspectError) -> Self {
+        return MessageError::Address(e);
+    }
+}
+
+impl From<ReadError> for MessageError {
+    fn from(e: ReadError) -> Self {
+        return MessageError::Read(e);
+    }
+}
+
+impl From<TransactionError> for MessageError {
+    fn from(e: TransactionError) -> Self {
+        return MessageError::Transaction(e);
+    }
+}
+
+impl From<SignError> for MessageError {
+    fn from(e: SignError) -> Self {
+        return MessageError::Sign(e);
+    }
+}
+
+fn script_pubkey(address: &str) -> Result<Script, MessageError> {
+    return inspect::inspect(address)?.script_pubkey.ok_or(MessageError::UnsupportedAddress);
+}
+
+// P2PKH addresses keep the legacy format, everything else gets a simple BIP322 signature
+pub fn default_format(address: &str) -> Result<SignatureFormat, MessageError> {
+    match script_pubkey(address)?.script_type() {
+        ScriptType::P2pkh(_) => return Ok(SignatureFormat::Legacy),
+        _ => return Ok(SignatureFormat::Simple),
+    };
+}
+
+// double sha256 of the magic and the message, each with a compact size prefix
+pub fn message_hash(message: &str) -> [u8; 32] {
+    let mut data: Vec<u8> = bytes::compact_size(MESSAGE_MAGIC.len() as u64);
+    data.extend_from_slice(MESSAGE_MAGIC.as_bytes());
+    data.extend(bytes::compact_size(message.len() as u64));
+    data.extend_from_slice(message.as_bytes());
+    return hashing::sha256d(&data);
+}
+
+// 65 bytes in base64: header 27 + recovery id, plus 4 for a compressed key, then r and s
+pub fn sign_message(secret_key: &[u8; 32], compressed: bool, message: &str) -> Result<String, MessageError> {
+    let secp = Secp256k1::signing_only();
+    let key: SecretKey = SecretKey::from_slice(secret_key).map_err(|_| MessageError::InvalidKey)?;
+    let signature: RecoverableSignature = secp.sign_ecdsa_recoverable(&Message::from_slice(&message_hash(message)).expect("message-hash"), &key);
+    let (recovery_id, compact) = signature.serialize_compact();
+
+    let mut r: Vec<u8> = vec![27 + recovery_id.to_i32() as u8 + if compressed { 4 } else { 0 }];
+    r.extend_from_slice(&compact);
+    return Ok(base64::encode_bytes(&r));
+}
+
+// the SEC1 key that produced a legacy signature, compressed when the header says so
+pub fn recover_public_key(signature: &str, message: &str) -> Result<Vec<u8>, MessageError> {
+    let data: Vec<u8> = base64::decode_string(signature).map_err(|_| MessageError::InvalidEncoding)?;
+    if data.len() != 65 {
+        return Err(MessageError::InvalidSignature);
+    }
+    // BIP137 headers: 27-30 uncompressed, 31-34 compressed P2PKH, 35-38 P2SH-P2WPKH, 39-42 P2WPKH
+    if !(27..=42).contains(&data[0]) {
+        return Err(MessageError::InvalidHeader(data[0]));
+    }
+    let recovery_id: RecoveryId = RecoveryId::from_i32(((data[0] - 27) & 3) as i32).expect("recovery-id");
+    let signature: RecoverableSignature = RecoverableSignature::from_compact(&data[1..], recovery_id).map_err(|_| MessageError::InvalidSignature)?;
+
+    let secp = Secp256k1::verification_only();
+    let key: PublicKey = secp.recover_ecdsa(&Message::from_slice(&message_hash(message)).expect("message-hash"), &signature).map_err(|_| MessageError::InvalidSignature)?;
+    if data[0] >= 31 {
+        return Ok(key.serialize().to_vec());
+    }
+    return Ok(key.serialize_uncompressed().to_vec());
+}
+
+// P2PKH as in Bitcoin Core, P2WPKH and P2SH-P2WPKH as in BIP137
+pub fn verify_message(address: &str, signature: &str, message: &str) -> Result<bool, MessageError> {
+    let script: Script = script_pubkey(address)?;
+    let key: Vec<u8> = recover_public_key(signature, message)?;
+    let compressed: bool = key.len() == 33;
+
+    match script.script_type() {
+        ScriptType::P2pkh(h) => return Ok(h[..] == hashing::hash160(&key)),
+        ScriptType::P2wpkh(h) => return Ok(compressed && h[..] == hashing::hash160(&key)),
+        ScriptType::P2sh(h) => {
+            let redeem_script: Script = Builder::new().push_int(0).push_slice(&hashing::hash160(&key)).into_script();
+            return Ok(compressed && h[..] == hashing::hash160(&redeem_script.bytes));
+        },
+        _ => return Err(MessageError::UnsupportedAddress),
+    };
+}
+
+pub fn bip322_message_hash(message: &str) -> [u8; 32] {
+    return hashing::tagged_hash(BIP322_TAG, message.as_bytes());
+}
+
+// the virtual transaction paying to the address; its only output is what gets spent
+pub fn to_spend(script_pubkey: &Script, message: &str) -> Transaction {
+    let mut tx: Transaction = Transaction::new(0, 0);
+    let mut input: TxIn = TxIn::new(OutPoint::null());
+    input.sequence = 0;
+    input.script_sig = Builder::new().push_int(0).push_slice(&bip322_message_hash(message)).into_script();
+    tx.inputs.push(input);
+    tx.outputs.push(TxOut { value: 0, script_pubkey: script_pubkey.clone() });
+    return tx;
+}
+
+// unsigned; spends to_spend into a single empty OP_RETURN
+pub fn to_sign(to_spend: &Transaction) -> Transaction {
+    let mut tx: Transaction = Transaction::new(0, 0);
+    let mut input: TxIn = TxIn::new(OutPoint::new(to_spend.txid(), 0));
+    input.sequence = 0;
+    tx.inputs.push(input);
+    tx.outputs.push(TxOut { value: 0, script_pubkey: Builder::new().push_opcode(Opcode::Return).into_script() });
+    return tx;
+}
+
+fn encode_witness(witness: &[Vec<u8>]) -> Vec<u8> {
+    let mut r: Vec<u8> = bytes::compact_size(witness.len() as u64);
+    for item in witness.iter() {
+        r.extend(bytes::compact_size(item.len() as u64));
+        r.extend_from_slice(item);
+    }
+    return r;
+}
+
+fn decode_witness(data: &[u8]) -> Result<Vec<Vec<u8>>, ReadError> {
+    let mut reader: Reader = Reader::new(data);
+    let mut r: Vec<Vec<u8>> = vec![];
+    for _ in 0..reader.read_compact_size()? {
+        r.push(reader.read_var_bytes()?.to_vec());
+    }
+    if reader.remaining() > 0 {
+        return Err(ReadError::UnexpectedEnd(reader.position));
+    }
+    return Ok(r);
+}
+
+pub fn sign(secret_key: &[u8; 32], address: &str, message: &str, format: SignatureFormat) -> Result<String, MessageError> {
+    let mut aux_rand: [u8; 32] = [0; 32];
+    rand::thread_rng().fill(&mut aux_rand[..]);
+    return sign_with_aux_rand(secret_key, address, message, format, &aux_rand);
+}
+
+// legacy signatures need a P2PKH address of either key form; BIP322 signs the
+// single-key P2PKH (full only), P2WPKH, P2SH-P2WPKH and BIP86 P2TR outputs
+pub fn sign_with_aux_rand(secret_key: &[u8; 32], address: &str, message: &str, format: SignatureFormat, aux_rand: &[u8; 32]) -> Result<String, MessageError> {
+    let script: Script = script_pubkey(address)?;
+    if format == SignatureFormat::Legacy {
+        let hash: Vec<u8> = match script.script_type() {
+            ScriptType::P2pkh(h) => h,
+            _ => return Err(MessageError::UnsupportedAddress)
+        };
+        let secp = Secp256k1::signing_only();
+        let key: SecretKey = SecretKey::from_slice(secret_key).map_err(|_| MessageError::InvalidKey)?;
+        let public_key: PublicKey = PublicKey::from_secret_key(&secp, &key);
+        if hash == hashing::hash160(&public_key.serialize()) {
+            return sign_message(secret_key, true, message);
+        }
+        if hash == hashing::hash160(&public_key.serialize_uncompressed()) {
+            return sign_message(secret_key, false, message);
+        }
+        return Err(MessageError::KeyMismatch);
+    }
+
+    let to_spend: Transaction = to_spend(&script, message);
+    let mut tx: Transaction = to_sign(&to_spend);
+    let sighash_type: SighashType = match script.script_type() {
+        ScriptType::P2tr(_) => SighashType::Default,
+        ScriptType::P2pkh(_) if format == SignatureFormat::Simple => return Err(MessageError::UnsupportedAddress),
+        _ => SighashType::All
+    };
+    match sign::sign_input_with_aux_rand(&mut tx, 0, secret_key, &to_spend.outputs, sighash_type, aux_rand) {
+        Ok(()) => {},
+        Err(SignError::KeyMismatch(_)) => return Err(MessageError::KeyMismatch),
+        Err(SignError::UnsupportedScript(_)) => return Err(MessageError::UnsupportedAddress),
+        Err(e) => return Err(MessageError::Sign(e))
+    };
+
+    if format == SignatureFormat::Simple {
+        return Ok(base64::encode_bytes(&encode_witness(&tx.inputs[0].witness)));
+    }
+    return Ok(base64::encode_bytes(&tx.serialize()));
+}
+
+// a simple signature is tried first: a full one never decodes as a witness stack
+// without leftover bytes
+pub fn verify_bip322(address: &str, signature: &str, message: &str) -> Result<bool, MessageError> {
+    let script: Script = script_pubkey(address)?;
+    let data: Vec<u8> = base64::decode_string(signature).map_err(|_| MessageError::InvalidEncoding)?;
+    let to_spend: Transaction = to_spend(&script, message);
+
+    let tx: Transaction = match decode_witness(&data) {
+        Ok(witness) => {
+            let mut tx: Transaction = to_sign(&to_spend);
+            // the scriptSig of a nested segwit input is implied by its witness
+            if let (ScriptType::P2sh(h), Some(last)) = (script.script_type(), witness.last()) {
+                let p2wpkh: Script = Builder::new().push_int(0).push_slice(&hashing::hash160(last)).into_script();
+                let p2wsh: Script = Builder::new().push_int(0).push_slice(&hashing::sha256(last)).into_script();
+                if let Some(redeem_script) = [p2wpkh, p2wsh].iter().find(|s: &&Script| h[..] == hashing::hash160(&s.bytes)) {
+                    tx.inputs[0].script_sig = Builder::new().push_slice(&redeem_script.bytes).into_script();
+                }
+            }
+            tx.inputs[0].witness = witness;
+            tx
+        },
+        Err(_) => {
+            let tx: Transaction = Transaction::deserialize(&data)?;
+            let expected: Transaction = to_sign(&to_spend);
+            if tx.inputs.len() != 1 || tx.outputs != expected.outputs {
+                return Err(MessageError::InvalidToSign);
+            }
+            // signed for another message or address
+            if tx.inputs[0].previous_output != expected.inputs[0].previous_output {
+                return Ok(false);
+            }
+            tx
+        }
+    };
+    return verify_input(&tx, &to_spend.outputs);
+}
+
+// legacy or BIP322, told apart by the 65-byte length and BIP137 header
+pub fn verify(address: &str, signature: &str, message: &str) -> Result<(SignatureFormat, bool), MessageError> {
+    let data: Vec<u8> = base64::decode_string(signature).map_err(|_| MessageError::InvalidEncoding)?;
+    if data.len() == 65 && (27..=42).contains(&data[0]) {
+        return Ok((SignatureFormat::Legacy, verify_message(address, signature, message)?));
+    }
+    let format: SignatureFormat = if decode_witness(&data).is_ok() { SignatureFormat::Simple } else { SignatureFormat::Full };
+    return Ok((format, verify_bip322(address, signature, message)?));
+}
+
+fn pushes(script: &Script) -> Option<Vec<Vec<u8>>> {
+    if !script.is_push_only() {
+        return None;
+    }
+    return script.instructions().ok().map(|instructions: Vec<Instruction>| instructions.iter().map(|i: &Instruction| match i {
+        Instruction::Push(_, d) => d.clone(),
+        Instruction::Op(Opcode::Num(n)) => vec![*n],
+        Instruction::Op(_) => vec![]
+    }).collect::<Vec<Vec<u8>>>());
+}
+
+// the trailing type byte picks the sighash the signature commits to
+fn check_ecdsa(signature: &[u8], key: &[u8], sighash: &dyn Fn(u8) -> Option<[u8; 32]>) -> bool {
+    return match signature.last().and_then(|t: &u8| sighash(*t)) {
+        Some(h) => sign::verify_ecdsa_signature(&h, signature, key),
+        None => false
+    };
+}
+
+// OP_CHECKMULTISIG: signatures in key order, each key used at most once
+fn check_multisig(signatures: &[Vec<u8>], keys: &[Vec<u8>], sighash: &dyn Fn(u8) -> Option<[u8; 32]>) -> bool {
+    let mut keys = keys.iter();
+    return signatures.iter().all(|s: &Vec<u8>| keys.any(|k: &Vec<u8>| check_ecdsa(s, k, sighash)));
+}
+
+// runs the spend of the to_spend output for the script types a wallet signs with
+fn verify_input(tx: &Transaction, prevouts: &[TxOut]) -> Result<bool, MessageError> {
+    let input: &TxIn = &tx.inputs[0];
+    let spent: &TxOut = &prevouts[0];
+    let legacy = |code: Script| move |t: u8| sighash::legacy_sighash(tx, 0, &code, t as u32).ok();
+
+    match spent.script_pubkey.script_type() {
+        ScriptType::P2pkh(h) => {
+            return match pushes(&input.script_sig).as_deref() {
+                Some([signature, key]) if input.witness.is_empty() => Ok(h[..] == hashing::hash160(key) && check_ecdsa(signature, key, &legacy(spent.script_pubkey.clone()))),
+                _ => Ok(false)
+            };
+        },
+        ScriptType::P2sh(h) => {
+            let items: Vec<Vec<u8>> = match pushes(&input.script_sig) {
+                Some(items) if !items.is_empty() => items,
+                _ => return Ok(false)
+            };
+            let redeem_script: Script = Script::from_bytes(&items[items.len() - 1]);
+            if h[..] != hashing::hash160(&redeem_script.bytes) {
+                return Ok(false);
+            }
+            if redeem_script.witness_program().is_some() {
+                return Ok(items.len() == 1 && verify_witness(tx, &redeem_script, prevouts));
+            }
+            return match redeem_script.script_type() {
+                // OP_CHECKMULTISIG pops one extra item, which must be empty
+                ScriptType::Multisig(m, keys) => Ok(input.witness.is_empty() && items.len() == m + 2 && items[0].is_empty()
+                    && check_multisig(&items[1..=m], &keys, &legacy(redeem_script.clone()))),
+                _ => Err(MessageError::UnsupportedAddress)
+            };
+        },
+        ScriptType::P2wpkh(_) | ScriptType::P2wsh(_) | ScriptType::P2tr(_) => {
+            return Ok(input.script_sig.bytes.is_empty() && verify_witness(tx, &spent.script_pubkey, prevouts));
+        },
+        _ => return Err(MessageError::UnsupportedAddress),
+    };
+}
+
+fn verify_witness(tx: &Transaction, program: &Script, prevouts: &[TxOut]) -> bool {
+    let witness: &[Vec<u8>] = &tx.inputs[0].witness;
+    let value: u64 = prevouts[0].value;
+    let segwit = |code: Script| move |t: u8| SighashType::from_u8(t).and_then(|t: SighashType| sighash::segwit_v0_sighash(tx, 0, &code, value, t).ok());
+
+    match program.script_type() {
+        ScriptType::P2wpkh(h) => {
+            return match witness {
+                [signature, key] => key.len() == 33 && h[..] == hashing::hash160(key) && check_ecdsa(signature, key, &segwit(sighash::p2wpkh_script_code(&h))),
+                _ => false
+            };
+        },
+        ScriptType::P2wsh(h) => {
+            let (witness_script, items) = match witness.split_last() {
+                Some((s, items)) if hashing::sha256(s)[..] == h[..] => (Script::from_bytes(s), items),
+                _ => return false
+            };
+            return match witness_script.script_type() {
+                ScriptType::Multisig(m, keys) => items.len() == m + 1 && items[0].is_empty() && check_multisig(&items[1..], &keys, &segwit(witness_script.clone())),
+                _ => false
+            };
+        },
+        ScriptType::P2tr(k) => {
+            // key path only, without an annex
+            let signature: &Vec<u8> = match witness {
+                [signature] => signature,
+                _ => return false
+            };
+            let sighash_type: Option<SighashType> = match signature.len() {
+                64 => Some(SighashType::Default),
+                65 => SighashType::from_u8(signature[64]).filter(|t: &SighashType| *t != SighashType::Default),
+                _ => None
+            };
+            let mut key: [u8; 32] = [0; 32];
+            key.copy_from_slice(&k);
+            return match sighash_type.map(|t: SighashType| sighash::taproot_sighash(tx, 0, prevouts, None, None, t)) {
+                Some(Ok(h)) => sign::verify_schnorr_signature(&h, signature, &key),
+                _ => false
+            };
+        },
+        _ => return false,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message::*;
+    use crate::base16;
+    use crate::base58;
+    use crate::transaction;
+
+    const SEGWIT_ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const TAPROOT_ADDRESS: &str = "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3";
+    const LEGACY_ADDRESS: &str = "14vV3aCHBeStb5bkenkNHbe2YAFinYdXgc";
+    const NESTED_SEGWIT_ADDRESS: &str = "3HSVzEhCFuH9Z3wvoWTexy7BMVVp3PjS6f";
+
+    fn wif(s: &str) -> [u8; 32] {
+        let mut r: [u8; 32] = [0; 32];
+        r.copy_from_slice(&base58::decode_check(s).unwrap()[1..33]);
+        return r;
+    }
+
+    #[test]
+    fn message_legacy_sign_and_verify() {
+        assert_eq!(transaction::hash_to_hex(&message_hash("test")), "a6f87fe6d58a032c320ff8d1541656f0282c2c7bfcc69d61af4c8e8ed528e49c");
+
+        // rust-bitcoin's signed message vector; RFC6979 reproduces it
+        let key: Vec<u8> = base64::decode_string("UuOGDsfLPr4HIMKQX0ipjJeRaj1geCq3yPUF2COP5ME=").unwrap();
+        let signature: String = sign_message(&key.try_into().unwrap(), true, "rust-bitcoin MessageSignature test").unwrap();
+        assert_eq!(signature, "IAM2qX24tYx/bdBTIgVLhD8QEAjrPlJpmjB4nZHdRYGIBa4DmVulAcwjPnWe6Q5iEwXH6F0pUCJP/ZeHPWS1h1o=");
+        assert_eq!(base64::encode_bytes(&recover_public_key(&signature, "rust-bitcoin MessageSignature test").unwrap()), "A1FTfMEntPpAty3qkEo0q2Dc1FEycI10a3jmwEFy+Qr6");
+
+        let key: [u8; 32] = wif("L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k");
+        let signature: String = sign(&key, LEGACY_ADDRESS, "Hello World", SignatureFormat::Legacy).unwrap();
+        assert_eq!(verify(LEGACY_ADDRESS, &signature, "Hello World"), Ok((SignatureFormat::Legacy, true)));
+        assert_eq!(verify(LEGACY_ADDRESS, &signature, "Hello World!"), Ok((SignatureFormat::Legacy, false)));
+        // BIP137 lets the same compressed key prove its P2WPKH address
+        assert_eq!(verify_message(SEGWIT_ADDRESS, &signature, "Hello World"), Ok(true));
+        assert_eq!(sign(&key, SEGWIT_ADDRESS, "Hello World", SignatureFormat::Legacy), Err(MessageError::UnsupportedAddress));
+        assert_eq!(sign(&[1; 32], LEGACY_ADDRESS, "Hello World", SignatureFormat::Legacy), Err(MessageError::KeyMismatch));
+
+        let mut data: Vec<u8> = base64::decode_string(&signature).unwrap();
+        data[0] = 43;
+        assert_eq!(verify_message(LEGACY_ADDRESS, &base64::encode_bytes(&data), "Hello World"), Err(MessageError::InvalidHeader(43)));
+    }
+
+    // vectors from BIP322
+    #[test]
+    fn message_bip322_transactions() {
+        assert_eq!(base16::encode_bytes(&bip322_message_hash("")), "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1");
+        assert_eq!(base16::encode_bytes(&bip322_message_hash("Hello World")), "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a");
+
+        let script: Script = inspect::inspect(SEGWIT_ADDRESS).unwrap().script_pubkey.unwrap();
+        let empty: Transaction = to_spend(&script, "");
+        let hello: Transaction = to_spend(&script, "Hello World");
+        assert_eq!(transaction::hash_to_hex(&empty.txid()), "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7");
+        assert_eq!(transaction::hash_to_hex(&hello.txid()), "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b");
+        assert_eq!(transaction::hash_to_hex(&to_sign(&empty).txid()), "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6");
+        assert_eq!(transaction::hash_to_hex(&to_sign(&hello).txid()), "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf");
+    }
+
+    #[test]
+    fn message_bip322_sign_and_verify() {
+        let key: [u8; 32] = wif("L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k");
+        assert_eq!(sign(&key, SEGWIT_ADDRESS, "Hello World", SignatureFormat::Simple).unwrap(),
+            "AkgwRQIhAOzyynlqt93lOKJr+wmmxIens//zPzl9tqIOua93wO6MAiBi5n5EyAcPScOjf1lAqIUIQtr3zKNeavYabHyR8eGhowEhAsfxIAMZZEKUPYWI4BruhAQjzFT8FSFSajuFwrDL1Yhy");
+        assert_eq!(sign(&key, SEGWIT_ADDRESS, "", SignatureFormat::Simple).unwrap(),
+            "AkgwRQIhAPkJ1Q4oYS0htvyuSFHLxRQpFAY56b70UvE7Dxazen0ZAiAtZfFz1S6T6I23MWI2lK/pcNTWncuyL8UL+oMdydVgzAEhAsfxIAMZZEKUPYWI4BruhAQjzFT8FSFSajuFwrDL1Yhy");
+
+        // signatures from the BIP, made with other nonces
+        let empty: &str = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        let hello: &str = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        assert_eq!(verify(SEGWIT_ADDRESS, empty, ""), Ok((SignatureFormat::Simple, true)));
+        assert_eq!(verify(SEGWIT_ADDRESS, hello, "Hello World"), Ok((SignatureFormat::Simple, true)));
+        assert_eq!(verify(SEGWIT_ADDRESS, empty, "Hello World"), Ok((SignatureFormat::Simple, false)));
+        assert_eq!(verify(SEGWIT_ADDRESS, hello, ""), Ok((SignatureFormat::Simple, false)));
+
+        let taproot: &str = "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==";
+        assert_eq!(verify_bip322(TAPROOT_ADDRESS, taproot, "Hello World"), Ok(true));
+        assert_eq!(verify_bip322(TAPROOT_ADDRESS, taproot, "Hello World!"), Ok(false));
+
+        let nested: [u8; 32] = wif("KwTbAxmBXjoZM3bzbXixEr9nxLhyYSM4vp2swet58i19bw9sqk5z");
+        let signature: String = sign(&nested, NESTED_SEGWIT_ADDRESS, "Hello World", SignatureFormat::Simple).unwrap();
+        assert_eq!(signature, "AkgwRQIhAMd2wZSY3x0V9Kr/NClochoTXcgDaGl3OObOR17yx3QQAiBVWxqNSS+CKen7bmJTG6YfJjsggQ4Fa2RHKgBKrdQQ+gEhAxa5UDdQCHSQHfKQv14ybcYm1C9y6b12xAuukWzSnS+w");
+        assert_eq!(verify_bip322(NESTED_SEGWIT_ADDRESS, &signature, "Hello World"), Ok(true));
+        assert_eq!(verify_bip322(NESTED_SEGWIT_ADDRESS, &signature, "Hello World - this should fail"), Ok(false));
+    }
+
+    #[test]
+    fn message_bip322_round_trips() {
+        let key: [u8; 32] = wif("L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k");
+        let nested: [u8; 32] = wif("KwTbAxmBXjoZM3bzbXixEr9nxLhyYSM4vp2swet58i19bw9sqk5z");
+        for (key, address) in [(key, SEGWIT_ADDRESS), (key, TAPROOT_ADDRESS), (nested, NESTED_SEGWIT_ADDRESS), (key, LEGACY_ADDRESS)] {
+            for format in [SignatureFormat::Simple, SignatureFormat::Full] {
+                if address == LEGACY_ADDRESS && format == SignatureFormat::Simple {
+                    assert_eq!(sign(&key, address, "proof", format), Err(MessageError::UnsupportedAddress));
+                    continue;
+                }
+                let signature: String = sign_with_aux_rand(&key, address, "proof", format, &[3; 32]).unwrap();
+                assert_eq!(verify(address, &signature, "proof"), Ok((format, true)));
+                assert_eq!(verify(address, &signature, "other"), Ok((format, false)));
+            }
+        }
+        assert_eq!(sign(&key, NESTED_SEGWIT_ADDRESS, "proof", SignatureFormat::Simple), Err(MessageError::KeyMismatch));
+
+        // a full signature must spend to_spend into the OP_RETURN output
+        let signature: String = sign(&key, SEGWIT_ADDRESS, "proof", SignatureFormat::Full).unwrap();
+        let mut tx: Transaction = Transaction::deserialize(&base64::decode_string(&signature).unwrap()).unwrap();
+        tx.outputs[0].value = 1;
+        assert_eq!(verify_bip322(SEGWIT_ADDRESS, &base64::encode_bytes(&tx.serialize()), "proof"), Err(MessageError::InvalidToSign));
+        assert_eq!(verify_bip322("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed", &signature, "proof"), Err(MessageError::UnsupportedAddress));
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::str::FromStr;
+use secp256k1::PublicKey;
+use crate::app::Network;
+use crate::base16;
+use crate::bip32::{self, ExtendedPublicKey};
+use crate::crypto::bitcoin;
+
+pub const OP_CHECKMULTISIG: u8 = 0xae;
+
+// OP_1 .. OP_16 push the numbers used for m and n
+pub const MAX_KEYS: usize = 16;
+
+// the consensus limit on the redeem script pushed in a p2sh input
+pub const MAX_REDEEM_SCRIPT_SIZE: usize = 520;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum MultisigType {
+    P2sh,
+    P2shP2wsh,
+    P2wsh
+}
+
+impl MultisigType {
+    pub fn is_segwit(&self) -> bool {
+        return *self != MultisigType::P2sh;
+    }
+}
+
+impl FromStr for MultisigType {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<MultisigType, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "p2sh" => Ok(MultisigType::P2sh),
+            "p2sh-p2wsh" | "p2sh_p2wsh" => Ok(MultisigType::P2shP2wsh),
+            "p2wsh" => Ok(MultisigType::P2wsh),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for MultisigType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultisigType::P2sh => return write!(f, "P2SH"),
+            MultisigType::P2shP2wsh => return write!(f, "P2SH-P2WSH"),
+            MultisigType::P2wsh => return write!(f, "P2WSH"),
+        };
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum MultisigError {
+    InvalidKey(String),
+    InvalidThreshold(usize, usize),
+    TooManyKeys(usize),
+    UncompressedKey(String),
+    ScriptTooLarge(usize)
+}
+
+// a hex SEC1 key, compressed or uncompressed, or an xpub/tpub followed by a
+// normal derivation path such as xpub.../0/1
+pub fn parse_key(key: &str) -> Result<Vec<u8>, MultisigError> {
+    let key: &str = key.trim();
+    let error = || MultisigError::InvalidKey(String::from(key));
+
+    let head: &str = key.split('/').next().unwrap_or_default();
+    if let Ok(xpub) = ExtendedPublicKey::from_str(head) {
+        let path: Vec<u32> = bip32::parse_path(&format!("m{}", &key[head.len()..])).map_err(|_| error())?;
+        return xpub.derive_path(&path).map(|k: ExtendedPublicKey| k.public_key.to_vec()).map_err(|_| error());
+    }
+
+    let bytes: Vec<u8> = hex::decode(key).map_err(|_| error())?;
+    if PublicKey::from_slice(&bytes).is_err() {
+        return Err(error());
+    }
+    return Ok(bytes);
+}
+
+// BIP67: keys ordered lexicographically by their serialized form
+pub fn sort_keys(keys: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut r: Vec<Vec<u8>> = keys.to_vec();
+    r.sort();
+    return r;
+}
+
+// OP_m <key 1> ... <key n> OP_n OP_CHECKMULTISIG
+pub fn multisig_script(threshold: usize, keys: &[Vec<u8>], sorted: bool) -> Result<Vec<u8>, MultisigError> {
+    if keys.len() > MAX_KEYS {
+        return Err(MultisigError::TooManyKeys(keys.len()));
+    }
+    if threshold == 0 || threshold > keys.len() {
+        return Err(MultisigError::InvalidThreshold(threshold, keys.len()));
+    }
+    let keys: Vec<Vec<u8>> = if sorted { sort_keys(keys) } else { keys.to_vec() };
+
+    let mut script: Vec<u8> = vec![0x50 + threshold as u8];
+    for key in keys.iter() {
+        script.push(key.len() as u8);
+        script.extend_from_slice(key);
+    }
+    script.push(0x50 + keys.len() as u8);
+    script.push(OP_CHECKMULTISIG);
+    return Ok(script);
+}
+
+// segwit scripts only relay with compressed keys
+pub fn derive_address(threshold: usize, keys: &[Vec<u8>], sorted: bool, multisig_type: MultisigType, network: Network) -> Result<String, MultisigError> {
+    if multisig_type.is_segwit() {
+        if let Some(key) = keys.iter().find(|k: &&Vec<u8>| k.len() != 33) {
+            return Err(MultisigError::UncompressedKey(base16::encode_bytes(key)));
+        }
+    }
+
+    // with compressed keys even 16 of them stay far below any witness script limit
+    let script: Vec<u8> = multisig_script(threshold, keys, sorted)?;
+    if !multisig_type.is_segwit() && script.len() > MAX_REDEEM_SCRIPT_SIZE {
+        return Err(MultisigError::ScriptTooLarge(script.len()));
+    }
+
+    match multisig_type {
+        MultisigType::P2sh => return Ok(bitcoin::derive_p2sh_address(&script, network)),
+        MultisigType::P2shP2wsh => return Ok(bitcoin::derive_p2sh_p2wsh_address(&script, network)),
+        MultisigType::P2wsh => return Ok(bitcoin::derive_p2wsh_address(&script, network)),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::multisig::*;
+
+    fn keys(hex_keys: &[&str]) -> Vec<Vec<u8>> {
+        return hex_keys.iter().map(|k: &&str| base16::decode_string(k)).collect::<Vec<Vec<u8>>>();
+    }
+
+    // BIP67 test vectors
+    #[test]
+    fn multisig_bip67_vectors() {
+        let vectors: [(&[&str], &str, &str); 4] = [
+            (&["02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8", "02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f"],
+                "522102fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f2102ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f852ae",
+                "39bgKC7RFbpoCRbtD5KEdkYKtNyhpsNa3Z"),
+            (&["02632b12f4ac5b1d1b72b2a3b508c19172de44f6f46bcee50ba33f3f9291e47ed0", "027735a29bae7780a9755fae7a1c4374c656ac6a69ea9f3697fda61bb99a4f3e77", "02e2cc6bd5f45edd43bebe7cb9b675f0ce9ed3efe613b177588290ad188d11b404"],
+                "522102632b12f4ac5b1d1b72b2a3b508c19172de44f6f46bcee50ba33f3f9291e47ed021027735a29bae7780a9755fae7a1c4374c656ac6a69ea9f3697fda61bb99a4f3e772102e2cc6bd5f45edd43bebe7cb9b675f0ce9ed3efe613b177588290ad188d11b40453ae",
+                "3CKHTjBKxCARLzwABMu9yD85kvtm7WnMfH"),
+            (&["030000000000000000000000000000000000004141414141414141414141414141", "020000000000000000000000000000000000004141414141414141414141414141", "020000000000000000000000000000000000004141414141414141414141414140", "030000000000000000000000000000000000004141414141414141414141414140"],
+                "522102000000000000000000000000000000000000414141414141414141414141414021020000000000000000000000000000000000004141414141414141414141414141210300000000000000000000000000000000000041414141414141414141414141402103000000000000000000000000000000000000414141414141414141414141414154ae",
+                "32V85igBri9zcfBRVupVvwK18NFtS37FuD"),
+            (&["022df8750480ad5b26950b25c7ba79d3e37d75f640f8e5d9bcd5b150a0f85014da", "03e3818b65bcc73a7d64064106a859cc1a5a728c4345ff0b641209fba0d90de6e9", "021f2f6e1e50cb6a953935c3601284925decd3fd21bc445712576873fb8c6ebc18"],
+                "5221021f2f6e1e50cb6a953935c3601284925decd3fd21bc445712576873fb8c6ebc1821022df8750480ad5b26950b25c7ba79d3e37d75f640f8e5d9bcd5b150a0f85014da2103e3818b65bcc73a7d64064106a859cc1a5a728c4345ff0b641209fba0d90de6e953ae",
+                "3Q4sF6tv9wsdqu2NtARzNCpQgwifm2rAba")
+        ];
+
+        for (hex_keys, script, address) in vectors.iter() {
+            assert_eq!(base16::encode_bytes(&multisig_script(2, &keys(hex_keys), true).unwrap()), *script);
+            assert_eq!(derive_address(2, &keys(hex_keys), true, MultisigType::P2sh, Network::Bitcoin).unwrap(), *address);
+        }
+    }
+
+    #[test]
+    fn multisig_segwit_addresses() {
+        let k = keys(&["022df8750480ad5b26950b25c7ba79d3e37d75f640f8e5d9bcd5b150a0f85014da", "03e3818b65bcc73a7d64064106a859cc1a5a728c4345ff0b641209fba0d90de6e9", "021f2f6e1e50cb6a953935c3601284925decd3fd21bc445712576873fb8c6ebc18"]);
+
+        assert_eq!(derive_address(2, &k, true, MultisigType::P2wsh, Network::Bitcoin).unwrap(), "bc1q0uyls9kc4acv9ntqw6u096t53jlld4frp4rscrf8fruddhu62p6sy9507s");
+        assert_eq!(derive_address(2, &k, true, MultisigType::P2wsh, Network::Testnet).unwrap(), "tb1q0uyls9kc4acv9ntqw6u096t53jlld4frp4rscrf8fruddhu62p6sndzqyl");
+        assert_eq!(derive_address(2, &k, true, MultisigType::P2shP2wsh, Network::Bitcoin).unwrap(), "38Yre4Cmxe9EfAr71nGSQjRS5GJ6BikhoU");
+        assert_eq!(derive_address(2, &k, true, MultisigType::P2shP2wsh, Network::Testnet).unwrap(), "2Mz74ho8oa6earxUegutK2gQhHcWG4jGA4p");
+        assert_ne!(derive_address(2, &k, false, MultisigType::P2wsh, Network::Bitcoin), derive_address(2, &k, true, MultisigType::P2wsh, Network::Bitcoin));
+    }
+
+    #[test]
+    fn multisig_unsorted_and_limits() {
+        let k = keys(&["02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8", "02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f"]);
+        let script = multisig_script(1, &k, false).unwrap();
+
+        assert_eq!(&script[2..35], k[0].as_slice());
+        assert_eq!(multisig_script(3, &k, true), Err(MultisigError::InvalidThreshold(3, 2)));
+        assert_eq!(multisig_script(0, &k, true), Err(MultisigError::InvalidThreshold(0, 2)));
+        assert_eq!(multisig_script(1, &vec![k[0].clone(); 17], true), Err(MultisigError::TooManyKeys(17)));
+
+        // fifteen uncompressed keys no longer fit a p2sh redeem script
+        let uncompressed = base16::decode_string(&crate::crypto::secp256k1::get_public_key("0000000000000000000000000000000000000000000000000000000000000001"));
+        assert_eq!(derive_address(1, &vec![uncompressed.clone(); 15], true, MultisigType::P2sh, Network::Bitcoin), Err(MultisigError::ScriptTooLarge(993)));
+        assert_eq!(derive_address(1, std::slice::from_ref(&uncompressed), true, MultisigType::P2wsh, Network::Bitcoin), Err(MultisigError::UncompressedKey(base16::encode_bytes(&uncompressed))));
+    }
+
+    #[test]
+    fn multisig_parse_key() {
+        let xpub = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+        let child = ExtendedPublicKey::from_str(xpub).unwrap().derive_path(&[1, 2]).unwrap();
+
+        assert_eq!(parse_key(&format!("{}/1/2", xpub)).unwrap(), child.public_key.to_vec());
+        assert_eq!(parse_key(xpub).unwrap().len(), 33);
+        assert_eq!(parse_key(&format!("{}/1'", xpub)), Err(MultisigError::InvalidKey(format!("{}/1'", xpub))));
+        assert_eq!(parse_key("02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8").unwrap().len(), 33);
+        assert!(parse_key("020000000000000000000000000000000000000000000000000000000000000000").is_err());
+        assert!(parse_key("zz").is_err());
+    }
+}
@@ -0,0 +1,1422 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use rand::Rng;
+use secp256k1::{ecdsa, PublicKey, XOnlyPublicKey};
+use crate::base16;
+use crate::base64;
+use crate::bip32::ExtendedPrivateKey;
+use crate::bytes::{self, Reader, ReadError};
+use crate::crypto::hashing;
+use crate::script::{Builder, Instruction, Opcode, Script, ScriptType};
+use crate::sighash::{self, SighashError, SighashType};
+use crate::sign::{self, SignError};
+use crate::taproot;
+use crate::transaction::{OutPoint, Transaction, TxIn, TxOut, SEQUENCE_FINAL};
+
+// "psbt" followed by 0xff
+pub const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_GLOBAL_XPUB: u8 = 0x01;
+const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const PSBT_GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+const PSBT_GLOBAL_VERSION: u8 = 0xfb;
+
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+const PSBT_IN_POR_COMMITMENT: u8 = 0x09;
+const PSBT_IN_RIPEMD160: u8 = 0x0a;
+const PSBT_IN_SHA256: u8 = 0x0b;
+const PSBT_IN_HASH160: u8 = 0x0c;
+const PSBT_IN_HASH256: u8 = 0x0d;
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+const PSBT_IN_TAP_KEY_SIG: u8 = 0x13;
+const PSBT_IN_TAP_SCRIPT_SIG: u8 = 0x14;
+const PSBT_IN_TAP_LEAF_SCRIPT: u8 = 0x15;
+const PSBT_IN_TAP_BIP32_DERIVATION: u8 = 0x16;
+const PSBT_IN_TAP_INTERNAL_KEY: u8 = 0x17;
+const PSBT_IN_TAP_MERKLE_ROOT: u8 = 0x18;
+
+// PSBT_IN_PREVIOUS_TXID to PSBT_IN_REQUIRED_HEIGHT_LOCKTIME
+const INPUT_V2_FIELDS: [&str; 5] = ["PSBT_IN_PREVIOUS_TXID", "PSBT_IN_OUTPUT_INDEX", "PSBT_IN_SEQUENCE", "PSBT_IN_REQUIRED_TIME_LOCKTIME", "PSBT_IN_REQUIRED_HEIGHT_LOCKTIME"];
+
+const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
+const PSBT_OUT_WITNESS_SCRIPT: u8 = 0x01;
+const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+const PSBT_OUT_SCRIPT: u8 = 0x04;
+const PSBT_OUT_TAP_INTERNAL_KEY: u8 = 0x05;
+const PSBT_OUT_TAP_TREE: u8 = 0x06;
+const PSBT_OUT_TAP_BIP32_DERIVATION: u8 = 0x07;
+
+// lock times below this are block heights
+const LOCKTIME_THRESHOLD: u32 = 500000000;
+
+// a key and its value, the key starting with its type
+type Pair = (Vec<u8>, Vec<u8>);
+// x-only key, the leaves it signs for and its secret key
+type TapSigningKey = ([u8; 32], Vec<[u8; 32]>, [u8; 32]);
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum PsbtError {
+    // neither base64 nor hex
+    InvalidEncoding,
+    InvalidMagic,
+    Read(ReadError),
+    DuplicateKey(Vec<u8>),
+    // the key of the offending pair
+    InvalidKey(Vec<u8>),
+    InvalidValue(Vec<u8>),
+    UnsupportedVersion(u32),
+    MissingUnsignedTx,
+    UnsignedTxHasScriptSigs,
+    // a field the PSBT version requires or forbids
+    MissingField(&'static str),
+    FieldNotAllowed(&'static str),
+    // inputs with height and time lock times
+    LockTimeConflict,
+    DifferentTransactions,
+    MissingUtxo(usize),
+    // amounts whose sum does not fit in 64 bits
+    ValueOverflow,
+    // the outputs spend more than the inputs
+    NegativeFee,
+    // the non-witness utxo is not the transaction being spent
+    UtxoMismatch(usize),
+    InvalidSighashType(usize),
+    MissingScript(usize),
+    ScriptMismatch(usize),
+    Sighash(SighashError),
+    Sign(SignError),
+    CannotFinalize(usize),
+    NotFinalized(usize)
+}
+
+impl From<ReadError> for PsbtError {
+    fn from(e: ReadError) -> Self {
+        return PsbtError::Read(e);
+    }
+}
+
+impl From<SighashError> for PsbtError {
+    fn from(e: SighashError) -> Self {
+        return PsbtError::Sighash(e);
+    }
+}
+
+impl From<SignError> for PsbtError {
+    fn from(e: SignError) -> Self {
+        return PsbtError::Sign(e);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct KeySource {
+    pub fingerprint: [u8; 4],
+    pub path: Vec<u32>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct TapKeySource {
+    // the leaves the key appears in, empty for the internal key
+    pub leaf_hashes: Vec<[u8; 32]>,
+    pub source: KeySource
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct TapLeaf {
+    pub depth: u8,
+    pub leaf_version: u8,
+    pub script: Script
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+
+pub struct Input {
+    pub non_witness_utxo: Option<Transaction>,
+    pub witness_utxo: Option<TxOut>,
+    // keyed by the serialized public key
+    pub partial_sigs: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub sighash_type: Option<u32>,
+    pub redeem_script: Option<Script>,
+    pub witness_script: Option<Script>,
+    pub bip32_derivations: BTreeMap<Vec<u8>, KeySource>,
+    pub final_script_sig: Option<Script>,
+    pub final_script_witness: Option<Vec<Vec<u8>>>,
+    pub por_commitment: Option<Vec<u8>>,
+    // hash preimages, keyed by the hash
+    pub ripemd160_preimages: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub sha256_preimages: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub hash160_preimages: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub hash256_preimages: BTreeMap<Vec<u8>, Vec<u8>>,
+    // version 2 only
+    pub previous_txid: Option<[u8; 32]>,
+    pub output_index: Option<u32>,
+    pub sequence: Option<u32>,
+    pub required_time_locktime: Option<u32>,
+    pub required_height_locktime: Option<u32>,
+    pub tap_key_sig: Option<Vec<u8>>,
+    // keyed by the x-only key and the leaf hash
+    pub tap_script_sigs: BTreeMap<([u8; 32], [u8; 32]), Vec<u8>>,
+    // script and leaf version, keyed by the control block
+    pub tap_leaf_scripts: BTreeMap<Vec<u8>, (Script, u8)>,
+    pub tap_bip32_derivations: BTreeMap<[u8; 32], TapKeySource>,
+    pub tap_internal_key: Option<[u8; 32]>,
+    pub tap_merkle_root: Option<[u8; 32]>,
+    // unknown and proprietary pairs, with the whole key
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+
+pub struct Output {
+    pub redeem_script: Option<Script>,
+    pub witness_script: Option<Script>,
+    pub bip32_derivations: BTreeMap<Vec<u8>, KeySource>,
+    // version 2 only
+    pub amount: Option<u64>,
+    pub script: Option<Script>,
+    pub tap_internal_key: Option<[u8; 32]>,
+    pub tap_tree: Option<Vec<TapLeaf>>,
+    pub tap_bip32_derivations: BTreeMap<[u8; 32], TapKeySource>,
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct Psbt {
+    pub version: u32,
+    // version 0 only
+    pub unsigned_tx: Option<Transaction>,
+    // version 2 only
+    pub tx_version: Option<i32>,
+    pub fallback_locktime: Option<u32>,
+    pub tx_modifiable: Option<u8>,
+    // keyed by the 78-byte serialized xpub
+    pub xpubs: BTreeMap<Vec<u8>, KeySource>,
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub inputs: Vec<Input>,
+    pub outputs: Vec<Output>
+}
+
+fn read_map(reader: &mut Reader) -> Result<Vec<Pair>, PsbtError> {
+    let mut r: Vec<Pair> = vec![];
+    loop {
+        // an empty key ends the map
+        let key: &[u8] = reader.read_var_bytes()?;
+        if key.is_empty() {
+            return Ok(r);
+        }
+        if r.iter().any(|(k, _): &Pair| k == key) {
+            return Err(PsbtError::DuplicateKey(key.to_vec()));
+        }
+        let value: &[u8] = reader.read_var_bytes()?;
+        r.push((key.to_vec(), value.to_vec()));
+    }
+}
+
+fn no_key_data(key: &[u8]) -> Result<(), PsbtError> {
+    if key.len() != 1 {
+        return Err(PsbtError::InvalidKey(key.to_vec()));
+    }
+    return Ok(());
+}
+
+fn fixed_value<const N: usize>(key: &[u8], value: &[u8]) -> Result<[u8; N], PsbtError> {
+    no_key_data(key)?;
+    return value.try_into().map_err(|_| PsbtError::InvalidValue(key.to_vec()));
+}
+
+fn public_key_data(key: &[u8]) -> Result<Vec<u8>, PsbtError> {
+    let data: &[u8] = &key[1..];
+    if (data.len() != 33 && data.len() != 65) || PublicKey::from_slice(data).is_err() {
+        return Err(PsbtError::InvalidKey(key.to_vec()));
+    }
+    return Ok(data.to_vec());
+}
+
+fn x_only_key(data: &[u8]) -> Option<[u8; 32]> {
+    let r: [u8; 32] = data.try_into().ok()?;
+    return XOnlyPublicKey::from_slice(&r).ok().map(|_| r);
+}
+
+fn script_value(key: &[u8], value: &[u8]) -> Result<Script, PsbtError> {
+    no_key_data(key)?;
+    return Ok(Script::from_bytes(value));
+}
+
+fn read_key_source(reader: &mut Reader) -> Result<KeySource, ReadError> {
+    let fingerprint: [u8; 4] = reader.read_array::<4>()?;
+    let mut path: Vec<u32> = vec![];
+    while reader.remaining() > 0 {
+        path.push(reader.read_u32()?);
+    }
+    return Ok(KeySource { fingerprint, path });
+}
+
+fn key_source_value(key: &[u8], value: &[u8]) -> Result<KeySource, PsbtError> {
+    if value.len() < 4 || !value.len().is_multiple_of(4) {
+        return Err(PsbtError::InvalidValue(key.to_vec()));
+    }
+    return Ok(read_key_source(&mut Reader::new(value))?);
+}
+
+fn tap_key_source_value(key: &[u8], value: &[u8]) -> Result<TapKeySource, PsbtError> {
+    let mut reader: Reader = Reader::new(value);
+    let count: u64 = reader.read_compact_size().map_err(|_| PsbtError::InvalidValue(key.to_vec()))?;
+    let mut leaf_hashes: Vec<[u8; 32]> = vec![];
+    for _ in 0..count {
+        leaf_hashes.push(reader.read_array::<32>().map_err(|_| PsbtError::InvalidValue(key.to_vec()))?);
+    }
+    return Ok(TapKeySource { leaf_hashes, source: key_source_value(key, &value[reader.position..])? });
+}
+
+// 64 bytes, or 65 with an explicit sighash type other than DEFAULT
+fn schnorr_signature_value(key: &[u8], value: &[u8]) -> Result<Vec<u8>, PsbtError> {
+    if value.len() != 64 && (value.len() != 65 || value[64] == 0x00) {
+        return Err(PsbtError::InvalidValue(key.to_vec()));
+    }
+    return Ok(value.to_vec());
+}
+
+fn transaction_value(key: &[u8], value: &[u8]) -> Result<Transaction, PsbtError> {
+    no_key_data(key)?;
+    return Transaction::deserialize(value).map_err(|_| PsbtError::InvalidValue(key.to_vec()));
+}
+
+fn witness_value(key: &[u8], value: &[u8]) -> Result<Vec<Vec<u8>>, PsbtError> {
+    no_key_data(key)?;
+    let mut reader: Reader = Reader::new(value);
+    let mut r: Vec<Vec<u8>> = vec![];
+    for _ in 0..reader.read_compact_size()? {
+        r.push(reader.read_var_bytes()?.to_vec());
+    }
+    if reader.remaining() > 0 {
+        return Err(PsbtError::InvalidValue(key.to_vec()));
+    }
+    return Ok(r);
+}
+
+// the key holds the hash of the preimage in the value
+fn preimage_value(key: &[u8], value: &[u8], hash: &[u8]) -> Result<(Vec<u8>, Vec<u8>), PsbtError> {
+    if key.len() != 1 + hash.len() {
+        return Err(PsbtError::InvalidKey(key.to_vec()));
+    }
+    if hash != &key[1..] {
+        return Err(PsbtError::InvalidValue(key.to_vec()));
+    }
+    return Ok((key[1..].to_vec(), value.to_vec()));
+}
+
+fn tap_tree_value(key: &[u8], value: &[u8]) -> Result<Vec<TapLeaf>, PsbtError> {
+    no_key_data(key)?;
+    let mut reader: Reader = Reader::new(value);
+    let mut r: Vec<TapLeaf> = vec![];
+    while reader.remaining() > 0 {
+        let depth: u8 = reader.read_u8()?;
+        let leaf_version: u8 = reader.read_u8()?;
+        let script: Script = Script::from_bytes(reader.read_var_bytes()?);
+        if depth > 128 || leaf_version & 0x01 != 0 {
+            return Err(PsbtError::InvalidValue(key.to_vec()));
+        }
+        r.push(TapLeaf { depth, leaf_version, script });
+    }
+    if r.is_empty() {
+        return Err(PsbtError::InvalidValue(key.to_vec()));
+    }
+    return Ok(r);
+}
+
+fn write_pair(r: &mut Vec<u8>, key_type: u8, key_data: &[u8], value: &[u8]) {
+    r.extend(bytes::compact_size(key_data.len() as u64 + 1));
+    r.push(key_type);
+    r.extend_from_slice(key_data);
+    r.extend(bytes::compact_size(value.len() as u64));
+    r.extend_from_slice(value);
+}
+
+fn write_unknown(r: &mut Vec<u8>, unknown: &BTreeMap<Vec<u8>, Vec<u8>>) {
+    for (key, value) in unknown.iter() {
+        write_pair(r, key[0], &key[1..], value);
+    }
+}
+
+fn key_source_bytes(source: &KeySource) -> Vec<u8> {
+    let mut r: Vec<u8> = source.fingerprint.to_vec();
+    source.path.iter().for_each(|i: &u32| r.extend_from_slice(&i.to_le_bytes()));
+    return r;
+}
+
+fn tap_key_source_bytes(source: &TapKeySource) -> Vec<u8> {
+    let mut r: Vec<u8> = bytes::compact_size(source.leaf_hashes.len() as u64);
+    source.leaf_hashes.iter().for_each(|h: &[u8; 32]| r.extend_from_slice(h));
+    r.extend(key_source_bytes(&source.source));
+    return r;
+}
+
+fn merge<K: Ord + Clone, V: Clone>(a: &mut BTreeMap<K, V>, b: &BTreeMap<K, V>) {
+    for (key, value) in b.iter() {
+        a.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+fn merge_option<T: Clone>(a: &mut Option<T>, b: &Option<T>) {
+    if a.is_none() {
+        *a = b.clone();
+    }
+}
+
+impl Input {
+    fn parse(pairs: Vec<Pair>, version: u32) -> Result<Self, PsbtError> {
+        let mut r: Input = Input::default();
+        for (key, value) in pairs.into_iter() {
+            // the version 2 fields come from the unsigned transaction in version 0; with key data
+            // these types are some other, unknown field
+            if version == 0 && (PSBT_IN_PREVIOUS_TXID..=PSBT_IN_REQUIRED_HEIGHT_LOCKTIME).contains(&key[0]) {
+                if key.len() == 1 {
+                    return Err(PsbtError::FieldNotAllowed(INPUT_V2_FIELDS[(key[0] - PSBT_IN_PREVIOUS_TXID) as usize]));
+                }
+                r.unknown.insert(key, value);
+                continue;
+            }
+            match key[0] {
+                PSBT_IN_NON_WITNESS_UTXO => r.non_witness_utxo = Some(transaction_value(&key, &value)?),
+                PSBT_IN_WITNESS_UTXO => {
+                    no_key_data(&key)?;
+                    let mut reader: Reader = Reader::new(&value);
+                    let output: TxOut = TxOut::deserialize(&mut reader).map_err(|_| PsbtError::InvalidValue(key.clone()))?;
+                    if reader.remaining() > 0 {
+                        return Err(PsbtError::InvalidValue(key));
+                    }
+                    r.witness_utxo = Some(output);
+                },
+                PSBT_IN_PARTIAL_SIG => {
+                    let public_key: Vec<u8> = public_key_data(&key)?;
+                    if value.is_empty() || ecdsa::Signature::from_der(&value[..value.len() - 1]).is_err() {
+                        return Err(PsbtError::InvalidValue(key));
+                    }
+                    r.partial_sigs.insert(public_key, value);
+                },
+                PSBT_IN_SIGHASH_TYPE => r.sighash_type = Some(u32::from_le_bytes(fixed_value::<4>(&key, &value)?)),
+                PSBT_IN_REDEEM_SCRIPT => r.redeem_script = Some(script_value(&key, &value)?),
+                PSBT_IN_WITNESS_SCRIPT => r.witness_script = Some(script_value(&key, &value)?),
+                PSBT_IN_BIP32_DERIVATION => {
+                    let public_key: Vec<u8> = public_key_data(&key)?;
+                    r.bip32_derivations.insert(public_key, key_source_value(&key, &value)?);
+                },
+                PSBT_IN_FINAL_SCRIPTSIG => r.final_script_sig = Some(script_value(&key, &value)?),
+                PSBT_IN_FINAL_SCRIPTWITNESS => r.final_script_witness = Some(witness_value(&key, &value)?),
+                PSBT_IN_POR_COMMITMENT => {
+                    no_key_data(&key)?;
+                    r.por_commitment = Some(value);
+                },
+                PSBT_IN_RIPEMD160 => {
+                    let (hash, preimage) = preimage_value(&key, &value, &hashing::ripemd160(&value))?;
+                    r.ripemd160_preimages.insert(hash, preimage);
+                },
+                PSBT_IN_SHA256 => {
+                    let (hash, preimage) = preimage_value(&key, &value, &hashing::sha256(&value))?;
+                    r.sha256_preimages.insert(hash, preimage);
+                },
+                PSBT_IN_HASH160 => {
+                    let (hash, preimage) = preimage_value(&key, &value, &hashing::hash160(&value))?;
+                    r.hash160_preimages.insert(hash, preimage);
+                },
+                PSBT_IN_HASH256 => {
+                    let (hash, preimage) = preimage_value(&key, &value, &hashing::sha256d(&value))?;
+                    r.hash256_preimages.insert(hash, preimage);
+                },
+                PSBT_IN_PREVIOUS_TXID => r.previous_txid = Some(fixed_value::<32>(&key, &value)?),
+                PSBT_IN_OUTPUT_INDEX => r.output_index = Some(u32::from_le_bytes(fixed_value::<4>(&key, &value)?)),
+                PSBT_IN_SEQUENCE => r.sequence = Some(u32::from_le_bytes(fixed_value::<4>(&key, &value)?)),
+                PSBT_IN_REQUIRED_TIME_LOCKTIME => {
+                    let time: u32 = u32::from_le_bytes(fixed_value::<4>(&key, &value)?);
+                    if time < LOCKTIME_THRESHOLD {
+                        return Err(PsbtError::InvalidValue(key));
+                    }
+                    r.required_time_locktime = Some(time);
+                },
+                PSBT_IN_REQUIRED_HEIGHT_LOCKTIME => {
+                    let height: u32 = u32::from_le_bytes(fixed_value::<4>(&key, &value)?);
+                    if height == 0 || height >= LOCKTIME_THRESHOLD {
+                        return Err(PsbtError::InvalidValue(key));
+                    }
+                    r.required_height_locktime = Some(height);
+                },
+                PSBT_IN_TAP_KEY_SIG => {
+                    no_key_data(&key)?;
+                    r.tap_key_sig = Some(schnorr_signature_value(&key, &value)?);
+                },
+                PSBT_IN_TAP_SCRIPT_SIG => {
+                    if key.len() != 65 {
+                        return Err(PsbtError::InvalidKey(key));
+                    }
+                    let x_only: [u8; 32] = x_only_key(&key[1..33]).ok_or(PsbtError::InvalidKey(key.clone()))?;
+                    let leaf_hash: [u8; 32] = key[33..].try_into().expect("leaf-hash");
+                    r.tap_script_sigs.insert((x_only, leaf_hash), schnorr_signature_value(&key, &value)?);
+                },
+                PSBT_IN_TAP_LEAF_SCRIPT => {
+                    // leaf version and internal key, then up to 128 hashes of the path
+                    let control_block: &[u8] = &key[1..];
+                    if control_block.len() < 33 || !(control_block.len() - 33).is_multiple_of(32) || control_block.len() > 33 + 128 * 32
+                        || x_only_key(&control_block[1..33]).is_none() {
+                        return Err(PsbtError::InvalidKey(key));
+                    }
+                    let (leaf_version, script) = match value.split_last() {
+                        Some((v, s)) => (*v, Script::from_bytes(s)),
+                        None => return Err(PsbtError::InvalidValue(key))
+                    };
+                    r.tap_leaf_scripts.insert(control_block.to_vec(), (script, leaf_version));
+                },
+                PSBT_IN_TAP_BIP32_DERIVATION => {
+                    let x_only: [u8; 32] = x_only_key(&key[1..]).ok_or(PsbtError::InvalidKey(key.clone()))?;
+                    r.tap_bip32_derivations.insert(x_only, tap_key_source_value(&key, &value)?);
+                },
+                PSBT_IN_TAP_INTERNAL_KEY => {
+                    no_key_data(&key)?;
+                    r.tap_internal_key = Some(x_only_key(&value).ok_or(PsbtError::InvalidValue(key.clone()))?);
+                },
+                PSBT_IN_TAP_MERKLE_ROOT => r.tap_merkle_root = Some(fixed_value::<32>(&key, &value)?),
+                _ => {
+                    r.unknown.insert(key, value);
+                }
+            };
+        }
+        return Ok(r);
+    }
+
+    fn serialize(&self, r: &mut Vec<u8>) {
+        if let Some(tx) = &self.non_witness_utxo {
+            write_pair(r, PSBT_IN_NON_WITNESS_UTXO, &[], &tx.serialize());
+        }
+        if let Some(output) = &self.witness_utxo {
+            write_pair(r, PSBT_IN_WITNESS_UTXO, &[], &output.serialize());
+        }
+        for (key, signature) in self.partial_sigs.iter() {
+            write_pair(r, PSBT_IN_PARTIAL_SIG, key, signature);
+        }
+        if let Some(t) = self.sighash_type {
+            write_pair(r, PSBT_IN_SIGHASH_TYPE, &[], &t.to_le_bytes());
+        }
+        if let Some(script) = &self.redeem_script {
+            write_pair(r, PSBT_IN_REDEEM_SCRIPT, &[], &script.bytes);
+        }
+        if let Some(script) = &self.witness_script {
+            write_pair(r, PSBT_IN_WITNESS_SCRIPT, &[], &script.bytes);
+        }
+        for (key, source) in self.bip32_derivations.iter() {
+            write_pair(r, PSBT_IN_BIP32_DERIVATION, key, &key_source_bytes(source));
+        }
+        if let Some(script) = &self.final_script_sig {
+            write_pair(r, PSBT_IN_FINAL_SCRIPTSIG, &[], &script.bytes);
+        }
+        if let Some(witness) = &self.final_script_witness {
+            let mut value: Vec<u8> = bytes::compact_size(witness.len() as u64);
+            for item in witness.iter() {
+                value.extend(bytes::compact_size(item.len() as u64));
+                value.extend_from_slice(item);
+            }
+            write_pair(r, PSBT_IN_FINAL_SCRIPTWITNESS, &[], &value);
+        }
+        if let Some(commitment) = &self.por_commitment {
+            write_pair(r, PSBT_IN_POR_COMMITMENT, &[], commitment);
+        }
+        for (key_type, preimages) in [
+            (PSBT_IN_RIPEMD160, &self.ripemd160_preimages),
+            (PSBT_IN_SHA256, &self.sha256_preimages),
+            (PSBT_IN_HASH160, &self.hash160_preimages),
+            (PSBT_IN_HASH256, &self.hash256_preimages)
+        ] {
+            for (hash, preimage) in preimages.iter() {
+                write_pair(r, key_type, hash, preimage);
+            }
+        }
+        if let Some(txid) = &self.previous_txid {
+            write_pair(r, PSBT_IN_PREVIOUS_TXID, &[], txid);
+        }
+        for (key_type, field) in [
+            (PSBT_IN_OUTPUT_INDEX, self.output_index),
+            (PSBT_IN_SEQUENCE, self.sequence),
+            (PSBT_IN_REQUIRED_TIME_LOCKTIME, self.required_time_locktime),
+            (PSBT_IN_REQUIRED_HEIGHT_LOCKTIME, self.required_height_locktime)
+        ] {
+            if let Some(n) = field {
+                write_pair(r, key_type, &[], &n.to_le_bytes());
+            }
+        }
+        if let Some(signature) = &self.tap_key_sig {
+            write_pair(r, PSBT_IN_TAP_KEY_SIG, &[], signature);
+        }
+        for ((x_only, leaf_hash), signature) in self.tap_script_sigs.iter() {
+            write_pair(r, PSBT_IN_TAP_SCRIPT_SIG, &[&x_only[..], &leaf_hash[..]].concat(), signature);
+        }
+        for (control_block, (script, leaf_version)) in self.tap_leaf_scripts.iter() {
+            write_pair(r, PSBT_IN_TAP_LEAF_SCRIPT, control_block, &[&script.bytes[..], &[*leaf_version]].concat());
+        }
+        for (x_only, source) in self.tap_bip32_derivations.iter() {
+            write_pair(r, PSBT_IN_TAP_BIP32_DERIVATION, x_only, &tap_key_source_bytes(source));
+        }
+        if let Some(key) = &self.tap_internal_key {
+            write_pair(r, PSBT_IN_TAP_INTERNAL_KEY, &[], key);
+        }
+        if let Some(root) = &self.tap_merkle_root {
+            write_pair(r, PSBT_IN_TAP_MERKLE_ROOT, &[], root);
+        }
+        write_unknown(r, &self.unknown);
+        r.push(0x00);
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        return self.final_script_sig.is_some() || self.final_script_witness.is_some();
+    }
+
+    fn combine(&mut self, other: &Input) {
+        merge_option(&mut self.non_witness_utxo, &other.non_witness_utxo);
+        merge_option(&mut self.witness_utxo, &other.witness_utxo);
+        merge(&mut self.partial_sigs, &other.partial_sigs);
+        merge_option(&mut self.sighash_type, &other.sighash_type);
+        merge_option(&mut self.redeem_script, &other.redeem_script);
+        merge_option(&mut self.witness_script, &other.witness_script);
+        merge(&mut self.bip32_derivations, &other.bip32_derivations);
+        merge_option(&mut self.final_script_sig, &other.final_script_sig);
+        merge_option(&mut self.final_script_witness, &other.final_script_witness);
+        merge_option(&mut self.por_commitment, &other.por_commitment);
+        merge(&mut self.ripemd160_preimages, &other.ripemd160_preimages);
+        merge(&mut self.sha256_preimages, &other.sha256_preimages);
+        merge(&mut self.hash160_preimages, &other.hash160_preimages);
+        merge(&mut self.hash256_preimages, &other.hash256_preimages);
+        merge_option(&mut self.required_time_locktime, &other.required_time_locktime);
+        merge_option(&mut self.required_height_locktime, &other.required_height_locktime);
+        merge_option(&mut self.tap_key_sig, &other.tap_key_sig);
+        merge(&mut self.tap_script_sigs, &other.tap_script_sigs);
+        merge(&mut self.tap_leaf_scripts, &other.tap_leaf_scripts);
+        merge(&mut self.tap_bip32_derivations, &other.tap_bip32_derivations);
+        merge_option(&mut self.tap_internal_key, &other.tap_internal_key);
+        merge_option(&mut self.tap_merkle_root, &other.tap_merkle_root);
+        merge(&mut self.unknown, &other.unknown);
+    }
+}
+
+impl Output {
+    fn parse(pairs: Vec<Pair>, version: u32) -> Result<Self, PsbtError> {
+        let mut r: Output = Output::default();
+        for (key, value) in pairs.into_iter() {
+            if version == 0 && (key[0] == PSBT_OUT_AMOUNT || key[0] == PSBT_OUT_SCRIPT) {
+                if key.len() == 1 {
+                    return Err(PsbtError::FieldNotAllowed(if key[0] == PSBT_OUT_AMOUNT { "PSBT_OUT_AMOUNT" } else { "PSBT_OUT_SCRIPT" }));
+                }
+                r.unknown.insert(key, value);
+                continue;
+            }
+            match key[0] {
+                PSBT_OUT_REDEEM_SCRIPT => r.redeem_script = Some(script_value(&key, &value)?),
+                PSBT_OUT_WITNESS_SCRIPT => r.witness_script = Some(script_value(&key, &value)?),
+                PSBT_OUT_BIP32_DERIVATION => {
+                    let public_key: Vec<u8> = public_key_data(&key)?;
+                    r.bip32_derivations.insert(public_key, key_source_value(&key, &value)?);
+                },
+                PSBT_OUT_AMOUNT => r.amount = Some(u64::from_le_bytes(fixed_value::<8>(&key, &value)?)),
+                PSBT_OUT_SCRIPT => r.script = Some(script_value(&key, &value)?),
+                PSBT_OUT_TAP_INTERNAL_KEY => {
+                    no_key_data(&key)?;
+                    r.tap_internal_key = Some(x_only_key(&value).ok_or(PsbtError::InvalidValue(key.clone()))?);
+                },
+                PSBT_OUT_TAP_TREE => r.tap_tree = Some(tap_tree_value(&key, &value)?),
+                PSBT_OUT_TAP_BIP32_DERIVATION => {
+                    let x_only: [u8; 32] = x_only_key(&key[1..]).ok_or(PsbtError::InvalidKey(key.clone()))?;
+                    r.tap_bip32_derivations.insert(x_only, tap_key_source_value(&key, &value)?);
+                },
+                _ => {
+                    r.unknown.insert(key, value);
+                }
+            };
+        }
+        return Ok(r);
+    }
+
+    fn serialize(&self, r: &mut Vec<u8>) {
+        if let Some(script) = &self.redeem_script {
+            write_pair(r, PSBT_OUT_REDEEM_SCRIPT, &[], &script.bytes);
+        }
+        if let Some(script) = &self.witness_script {
+            write_pair(r, PSBT_OUT_WITNESS_SCRIPT, &[], &script.bytes);
+        }
+        for (key, source) in self.bip32_derivations.iter() {
+            write_pair(r, PSBT_OUT_BIP32_DERIVATION, key, &key_source_bytes(source));
+        }
+        if let Some(amount) = self.amount {
+            write_pair(r, PSBT_OUT_AMOUNT, &[], &amount.to_le_bytes());
+        }
+        if let Some(script) = &self.script {
+            write_pair(r, PSBT_OUT_SCRIPT, &[], &script.bytes);
+        }
+        if let Some(key) = &self.tap_internal_key {
+            write_pair(r, PSBT_OUT_TAP_INTERNAL_KEY, &[], key);
+        }
+        if let Some(leaves) = &self.tap_tree {
+            let mut value: Vec<u8> = vec![];
+            for leaf in leaves.iter() {
+                value.extend_from_slice(&[leaf.depth, leaf.leaf_version]);
+                value.extend(bytes::compact_size(leaf.script.bytes.len() as u64));
+                value.extend_from_slice(&leaf.script.bytes);
+            }
+            write_pair(r, PSBT_OUT_TAP_TREE, &[], &value);
+        }
+        for (x_only, source) in self.tap_bip32_derivations.iter() {
+            write_pair(r, PSBT_OUT_TAP_BIP32_DERIVATION, x_only, &tap_key_source_bytes(source));
+        }
+        write_unknown(r, &self.unknown);
+        r.push(0x00);
+    }
+
+    fn combine(&mut self, other: &Output) {
+        merge_option(&mut self.redeem_script, &other.redeem_script);
+        merge_option(&mut self.witness_script, &other.witness_script);
+        merge(&mut self.bip32_derivations, &other.bip32_derivations);
+        merge_option(&mut self.tap_internal_key, &other.tap_internal_key);
+        merge_option(&mut self.tap_tree, &other.tap_tree);
+        merge(&mut self.tap_bip32_derivations, &other.tap_bip32_derivations);
+        merge(&mut self.unknown, &other.unknown);
+    }
+}
+
+// the stack satisfying a P2PK, P2PKH or multisig script from the partial signatures
+fn satisfy(input: &Input, script: &Script) -> Option<Vec<Vec<u8>>> {
+    match script.script_type() {
+        ScriptType::P2pk(key) => return Some(vec![input.partial_sigs.get(&key)?.clone()]),
+        ScriptType::P2pkh(hash) => {
+            let (key, signature) = input.partial_sigs.iter().find(|(k, _): &(&Vec<u8>, &Vec<u8>)| hash[..] == hashing::hash160(k))?;
+            return Some(vec![signature.clone(), key.clone()]);
+        },
+        ScriptType::Multisig(m, keys) => {
+            // CHECKMULTISIG pops one element too many, signatures follow in key order
+            let mut r: Vec<Vec<u8>> = vec![vec![]];
+            r.extend(keys.iter().filter_map(|k: &Vec<u8>| input.partial_sigs.get(k).cloned()).take(m));
+            return if r.len() == m + 1 { Some(r) } else { None };
+        },
+        _ => return None
+    };
+}
+
+// the witness spending a P2WPKH or P2WSH program
+fn witness_stack(input: &Input, program: &Script) -> Option<Vec<Vec<u8>>> {
+    match program.script_type() {
+        ScriptType::P2wpkh(hash) => {
+            let (key, signature) = input.partial_sigs.iter().find(|(k, _): &(&Vec<u8>, &Vec<u8>)| k.len() == 33 && hash[..] == hashing::hash160(k))?;
+            return Some(vec![signature.clone(), key.clone()]);
+        },
+        ScriptType::P2wsh(hash) => {
+            let witness_script: &Script = input.witness_script.as_ref()?;
+            if hash[..] != hashing::sha256(&witness_script.bytes) {
+                return None;
+            }
+            let mut r: Vec<Vec<u8>> = satisfy(input, witness_script)?;
+            r.push(witness_script.bytes.clone());
+            return Some(r);
+        },
+        _ => return None
+    };
+}
+
+// a key-path signature, or a script path through a single-key leaf
+fn taproot_witness(input: &Input) -> Option<Vec<Vec<u8>>> {
+    if let Some(signature) = &input.tap_key_sig {
+        return Some(vec![signature.clone()]);
+    }
+    for (control_block, (script, leaf_version)) in input.tap_leaf_scripts.iter() {
+        if let Ok([Instruction::Push(_, key), Instruction::Op(Opcode::CheckSig)]) = script.instructions().as_deref() {
+            let x_only: [u8; 32] = match key.as_slice().try_into() {
+                Ok(k) => k,
+                Err(_) => continue
+            };
+            let leaf_hash: [u8; 32] = taproot::tap_leaf_hash(&script.bytes, *leaf_version);
+            if let Some(signature) = input.tap_script_sigs.get(&(x_only, leaf_hash)) {
+                return Some(vec![signature.clone(), script.bytes.clone(), control_block.clone()]);
+            }
+        }
+    }
+    return None;
+}
+
+fn push_stack(stack: &[Vec<u8>]) -> Builder {
+    return stack.iter().fold(Builder::new(), |b: Builder, item: &Vec<u8>| b.push_slice(item));
+}
+
+impl Psbt {
+    // a version 0 PSBT around a transaction without signatures
+    pub fn from_unsigned_tx(tx: Transaction) -> Result<Self, PsbtError> {
+        if tx.inputs.iter().any(|i: &TxIn| !i.script_sig.bytes.is_empty() || !i.witness.is_empty()) {
+            return Err(PsbtError::UnsignedTxHasScriptSigs);
+        }
+        return Ok(Self {
+            version: 0,
+            inputs: vec![Input::default(); tx.inputs.len()],
+            outputs: vec![Output::default(); tx.outputs.len()],
+            unsigned_tx: Some(tx),
+            tx_version: None,
+            fallback_locktime: None,
+            tx_modifiable: None,
+            xpubs: BTreeMap::new(),
+            unknown: BTreeMap::new()
+        });
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, PsbtError> {
+        if !data.starts_with(&PSBT_MAGIC) {
+            return Err(PsbtError::InvalidMagic);
+        }
+        let mut reader: Reader = Reader::new(&data[PSBT_MAGIC.len()..]);
+
+        let mut psbt: Psbt = Psbt {
+            version: 0,
+            unsigned_tx: None,
+            tx_version: None,
+            fallback_locktime: None,
+            tx_modifiable: None,
+            xpubs: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+            inputs: vec![],
+            outputs: vec![]
+        };
+        let mut input_count: Option<u64> = None;
+        let mut output_count: Option<u64> = None;
+        for (key, value) in read_map(&mut reader)?.into_iter() {
+            match key[0] {
+                PSBT_GLOBAL_UNSIGNED_TX => psbt.unsigned_tx = Some(transaction_value(&key, &value)?),
+                PSBT_GLOBAL_XPUB => {
+                    if key.len() != 79 {
+                        return Err(PsbtError::InvalidKey(key));
+                    }
+                    psbt.xpubs.insert(key[1..].to_vec(), key_source_value(&key, &value)?);
+                },
+                PSBT_GLOBAL_TX_VERSION => psbt.tx_version = Some(i32::from_le_bytes(fixed_value::<4>(&key, &value)?)),
+                PSBT_GLOBAL_FALLBACK_LOCKTIME => psbt.fallback_locktime = Some(u32::from_le_bytes(fixed_value::<4>(&key, &value)?)),
+                PSBT_GLOBAL_INPUT_COUNT | PSBT_GLOBAL_OUTPUT_COUNT => {
+                    no_key_data(&key)?;
+                    let mut count_reader: Reader = Reader::new(&value);
+                    let count: u64 = count_reader.read_compact_size().map_err(|_| PsbtError::InvalidValue(key.clone()))?;
+                    if count_reader.remaining() > 0 {
+                        return Err(PsbtError::InvalidValue(key));
+                    }
+                    if key[0] == PSBT_GLOBAL_INPUT_COUNT {
+                        input_count = Some(count);
+                    }
+                    else {
+                        output_count = Some(count);
+                    }
+                },
+                PSBT_GLOBAL_TX_MODIFIABLE => psbt.tx_modifiable = Some(fixed_value::<1>(&key, &value)?[0]),
+                PSBT_GLOBAL_VERSION => psbt.version = u32::from_le_bytes(fixed_value::<4>(&key, &value)?),
+                _ => {
+                    psbt.unknown.insert(key, value);
+                }
+            };
+        }
+
+        // the unsigned transaction of version 0 is replaced by per-input and per-output fields in version 2
+        let (input_count, output_count): (u64, u64) = match psbt.version {
+            0 => {
+                let tx: &Transaction = psbt.unsigned_tx.as_ref().ok_or(PsbtError::MissingUnsignedTx)?;
+                if tx.inputs.iter().any(|i: &TxIn| !i.script_sig.bytes.is_empty() || !i.witness.is_empty()) {
+                    return Err(PsbtError::UnsignedTxHasScriptSigs);
+                }
+                for (field, name) in [
+                    (psbt.tx_version.is_some(), "PSBT_GLOBAL_TX_VERSION"),
+                    (psbt.fallback_locktime.is_some(), "PSBT_GLOBAL_FALLBACK_LOCKTIME"),
+                    (input_count.is_some(), "PSBT_GLOBAL_INPUT_COUNT"),
+                    (output_count.is_some(), "PSBT_GLOBAL_OUTPUT_COUNT"),
+                    (psbt.tx_modifiable.is_some(), "PSBT_GLOBAL_TX_MODIFIABLE")
+                ] {
+                    if field {
+                        return Err(PsbtError::FieldNotAllowed(name));
+                    }
+                }
+                (tx.inputs.len() as u64, tx.outputs.len() as u64)
+            },
+            2 => {
+                if psbt.unsigned_tx.is_some() {
+                    return Err(PsbtError::FieldNotAllowed("PSBT_GLOBAL_UNSIGNED_TX"));
+                }
+                if psbt.tx_version.is_none() {
+                    return Err(PsbtError::MissingField("PSBT_GLOBAL_TX_VERSION"));
+                }
+                (input_count.ok_or(PsbtError::MissingField("PSBT_GLOBAL_INPUT_COUNT"))?,
+                    output_count.ok_or(PsbtError::MissingField("PSBT_GLOBAL_OUTPUT_COUNT"))?)
+            },
+            v => return Err(PsbtError::UnsupportedVersion(v))
+        };
+
+        for _ in 0..input_count {
+            let input: Input = Input::parse(read_map(&mut reader)?, psbt.version)?;
+            if psbt.version == 2 && input.previous_txid.is_none() {
+                return Err(PsbtError::MissingField("PSBT_IN_PREVIOUS_TXID"));
+            }
+            if psbt.version == 2 && input.output_index.is_none() {
+                return Err(PsbtError::MissingField("PSBT_IN_OUTPUT_INDEX"));
+            }
+            psbt.inputs.push(input);
+        }
+        for _ in 0..output_count {
+            let output: Output = Output::parse(read_map(&mut reader)?, psbt.version)?;
+            if psbt.version == 2 && output.amount.is_none() {
+                return Err(PsbtError::MissingField("PSBT_OUT_AMOUNT"));
+            }
+            if psbt.version == 2 && output.script.is_none() {
+                return Err(PsbtError::MissingField("PSBT_OUT_SCRIPT"));
+            }
+            psbt.outputs.push(output);
+        }
+
+        if reader.remaining() > 0 {
+            return Err(PsbtError::Read(ReadError::UnexpectedEnd(reader.position + PSBT_MAGIC.len())));
+        }
+        return Ok(psbt);
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut r: Vec<u8> = PSBT_MAGIC.to_vec();
+        if let Some(tx) = &self.unsigned_tx {
+            write_pair(&mut r, PSBT_GLOBAL_UNSIGNED_TX, &[], &tx.serialize_without_witness());
+        }
+        for (xpub, source) in self.xpubs.iter() {
+            write_pair(&mut r, PSBT_GLOBAL_XPUB, xpub, &key_source_bytes(source));
+        }
+        if let Some(version) = self.tx_version {
+            write_pair(&mut r, PSBT_GLOBAL_TX_VERSION, &[], &version.to_le_bytes());
+        }
+        if let Some(lock_time) = self.fallback_locktime {
+            write_pair(&mut r, PSBT_GLOBAL_FALLBACK_LOCKTIME, &[], &lock_time.to_le_bytes());
+        }
+        if self.version >= 2 {
+            write_pair(&mut r, PSBT_GLOBAL_INPUT_COUNT, &[], &bytes::compact_size(self.inputs.len() as u64));
+            write_pair(&mut r, PSBT_GLOBAL_OUTPUT_COUNT, &[], &bytes::compact_size(self.outputs.len() as u64));
+        }
+        if let Some(flags) = self.tx_modifiable {
+            write_pair(&mut r, PSBT_GLOBAL_TX_MODIFIABLE, &[], &[flags]);
+        }
+        // version 0 is implied
+        if self.version > 0 {
+            write_pair(&mut r, PSBT_GLOBAL_VERSION, &[], &self.version.to_le_bytes());
+        }
+        write_unknown(&mut r, &self.unknown);
+        r.push(0x00);
+
+        self.inputs.iter().for_each(|i: &Input| i.serialize(&mut r));
+        self.outputs.iter().for_each(|o: &Output| o.serialize(&mut r));
+        return r;
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, PsbtError> {
+        let s: String = s.trim().to_lowercase();
+        if !s.len().is_multiple_of(2) || !s.chars().all(|c: char| c.is_ascii_hexdigit()) {
+            return Err(PsbtError::InvalidEncoding);
+        }
+        return Psbt::deserialize(&base16::decode_string(&s));
+    }
+
+    pub fn to_hex(&self) -> String {
+        return base16::encode_bytes(&self.serialize());
+    }
+
+    pub fn from_base64(s: &str) -> Result<Self, PsbtError> {
+        let data: Vec<u8> = base64::decode_string(s.trim()).map_err(|_| PsbtError::InvalidEncoding)?;
+        return Psbt::deserialize(&data);
+    }
+
+    pub fn to_base64(&self) -> String {
+        return base64::encode_bytes(&self.serialize());
+    }
+
+    // the lock time of BIP370: the fallback, unless inputs require a height or a time
+    fn lock_time(&self) -> Result<u32, PsbtError> {
+        let required: Vec<&Input> = self.inputs.iter()
+            .filter(|i: &&Input| i.required_time_locktime.is_some() || i.required_height_locktime.is_some())
+            .collect();
+        if required.is_empty() {
+            return Ok(self.fallback_locktime.unwrap_or(0));
+        }
+        // a height is preferred when every input accepts one
+        if required.iter().all(|i: &&Input| i.required_height_locktime.is_some()) {
+            return Ok(required.iter().filter_map(|i: &&Input| i.required_height_locktime).max().expect("height"));
+        }
+        if required.iter().all(|i: &&Input| i.required_time_locktime.is_some()) {
+            return Ok(required.iter().filter_map(|i: &&Input| i.required_time_locktime).max().expect("time"));
+        }
+        return Err(PsbtError::LockTimeConflict);
+    }
+
+    // the transaction being signed, rebuilt from the input and output fields in version 2
+    pub fn unsigned_tx(&self) -> Result<Transaction, PsbtError> {
+        if let Some(tx) = &self.unsigned_tx {
+            return Ok(tx.clone());
+        }
+        let mut tx: Transaction = Transaction::new(self.tx_version.ok_or(PsbtError::MissingField("PSBT_GLOBAL_TX_VERSION"))?, self.lock_time()?);
+        for input in self.inputs.iter() {
+            let txid: [u8; 32] = input.previous_txid.ok_or(PsbtError::MissingField("PSBT_IN_PREVIOUS_TXID"))?;
+            let vout: u32 = input.output_index.ok_or(PsbtError::MissingField("PSBT_IN_OUTPUT_INDEX"))?;
+            let mut tx_in: TxIn = TxIn::new(OutPoint::new(txid, vout));
+            tx_in.sequence = input.sequence.unwrap_or(SEQUENCE_FINAL);
+            tx.inputs.push(tx_in);
+        }
+        for output in self.outputs.iter() {
+            tx.outputs.push(TxOut {
+                value: output.amount.ok_or(PsbtError::MissingField("PSBT_OUT_AMOUNT"))?,
+                script_pubkey: output.script.clone().ok_or(PsbtError::MissingField("PSBT_OUT_SCRIPT"))?
+            });
+        }
+        return Ok(tx);
+    }
+
+    pub fn to_v2(&self) -> Result<Self, PsbtError> {
+        let tx: Transaction = self.unsigned_tx()?;
+        let mut r: Psbt = self.clone();
+        r.version = 2;
+        r.unsigned_tx = None;
+        r.tx_version = Some(tx.version);
+        if self.version == 0 {
+            r.fallback_locktime = Some(tx.lock_time);
+        }
+        for (input, tx_in) in r.inputs.iter_mut().zip(tx.inputs.iter()) {
+            input.previous_txid = Some(tx_in.previous_output.txid);
+            input.output_index = Some(tx_in.previous_output.vout);
+            input.sequence = Some(tx_in.sequence);
+        }
+        for (output, tx_out) in r.outputs.iter_mut().zip(tx.outputs.iter()) {
+            output.amount = Some(tx_out.value);
+            output.script = Some(tx_out.script_pubkey.clone());
+        }
+        return Ok(r);
+    }
+
+    pub fn to_v0(&self) -> Result<Self, PsbtError> {
+        let tx: Transaction = self.unsigned_tx()?;
+        let mut r: Psbt = self.clone();
+        r.version = 0;
+        r.unsigned_tx = Some(tx);
+        r.tx_version = None;
+        r.fallback_locktime = None;
+        r.tx_modifiable = None;
+        for input in r.inputs.iter_mut() {
+            input.previous_txid = None;
+            input.output_index = None;
+            input.sequence = None;
+            input.required_time_locktime = None;
+            input.required_height_locktime = None;
+        }
+        for output in r.outputs.iter_mut() {
+            output.amount = None;
+            output.script = None;
+        }
+        return Ok(r);
+    }
+
+    // the output spent by an input, checked against the outpoint when the whole transaction is given
+    pub fn spent_output(&self, input_index: usize) -> Result<TxOut, PsbtError> {
+        let tx: Transaction = self.unsigned_tx()?;
+        let input: &Input = self.inputs.get(input_index).ok_or(PsbtError::Sighash(SighashError::InputIndex(input_index)))?;
+        let outpoint: OutPoint = tx.inputs[input_index].previous_output;
+        if let Some(previous) = &input.non_witness_utxo {
+            if previous.txid() != outpoint.txid {
+                return Err(PsbtError::UtxoMismatch(input_index));
+            }
+            return previous.outputs.get(outpoint.vout as usize).cloned().ok_or(PsbtError::UtxoMismatch(input_index));
+        }
+        // an amount in a witness utxo is only signed for by segwit inputs, so legacy inputs need the whole transaction
+        let spent: TxOut = input.witness_utxo.clone().ok_or(PsbtError::MissingUtxo(input_index))?;
+        let segwit: bool = match spent.script_pubkey.script_type() {
+            ScriptType::P2sh(hash) => match &input.redeem_script {
+                Some(redeem_script) => hashing::hash160(&redeem_script.bytes).to_vec() == hash && redeem_script.witness_program().is_some(),
+                None => false
+            },
+            _ => spent.script_pubkey.witness_program().is_some()
+        };
+        if !segwit {
+            return Err(PsbtError::MissingUtxo(input_index));
+        }
+        return Ok(spent);
+    }
+
+    pub fn fee(&self) -> Result<u64, PsbtError> {
+        let mut total: u64 = 0;
+        for i in 0..self.inputs.len() {
+            total = total.checked_add(self.spent_output(i)?.value).ok_or(PsbtError::ValueOverflow)?;
+        }
+        let mut output_value: u64 = 0;
+        for output in self.unsigned_tx()?.outputs.iter() {
+            output_value = output_value.checked_add(output.value).ok_or(PsbtError::ValueOverflow)?;
+        }
+        return total.checked_sub(output_value).ok_or(PsbtError::NegativeFee);
+    }
+
+    fn sighash_type(&self, input_index: usize, default: SighashType) -> Result<SighashType, PsbtError> {
+        return match self.inputs[input_index].sighash_type {
+            None => Ok(default),
+            Some(t) if t <= 0xff => SighashType::from_u8(t as u8).ok_or(PsbtError::InvalidSighashType(input_index)),
+            Some(_) => Err(PsbtError::InvalidSighashType(input_index))
+        };
+    }
+
+    fn ecdsa_sighash(&self, tx: &Transaction, input_index: usize, spent: &TxOut, sighash_type: SighashType) -> Result<[u8; 32], PsbtError> {
+        let input: &Input = &self.inputs[input_index];
+        let script: Script = match spent.script_pubkey.script_type() {
+            ScriptType::P2sh(hash) => {
+                let redeem_script: &Script = input.redeem_script.as_ref().ok_or(PsbtError::MissingScript(input_index))?;
+                if hash[..] != hashing::hash160(&redeem_script.bytes) {
+                    return Err(PsbtError::ScriptMismatch(input_index));
+                }
+                redeem_script.clone()
+            },
+            _ => spent.script_pubkey.clone()
+        };
+
+        match script.script_type() {
+            ScriptType::P2wpkh(hash) => {
+                return Ok(sighash::segwit_v0_sighash(tx, input_index, &sighash::p2wpkh_script_code(&hash), spent.value, sighash_type)?);
+            },
+            ScriptType::P2wsh(hash) => {
+                let witness_script: &Script = input.witness_script.as_ref().ok_or(PsbtError::MissingScript(input_index))?;
+                if hash[..] != hashing::sha256(&witness_script.bytes) {
+                    return Err(PsbtError::ScriptMismatch(input_index));
+                }
+                return Ok(sighash::segwit_v0_sighash(tx, input_index, witness_script, spent.value, sighash_type)?);
+            },
+            _ => return Ok(sighash::legacy_sighash(tx, input_index, &script, sighash_type.to_u8() as u32)?)
+        };
+    }
+
+    // signs every input with a BIP32 derivation from this master key, returning the inputs signed
+    pub fn sign(&mut self, master: &ExtendedPrivateKey) -> Result<Vec<usize>, PsbtError> {
+        let mut aux_rand: [u8; 32] = [0; 32];
+        rand::thread_rng().fill(&mut aux_rand[..]);
+        return self.sign_with_aux_rand(master, &aux_rand);
+    }
+
+    pub fn sign_with_aux_rand(&mut self, master: &ExtendedPrivateKey, aux_rand: &[u8; 32]) -> Result<Vec<usize>, PsbtError> {
+        let tx: Transaction = self.unsigned_tx()?;
+        let fingerprint: [u8; 4] = master.fingerprint();
+        let mut signed: Vec<usize> = vec![];
+
+        for index in 0..self.inputs.len() {
+            let input: &Input = &self.inputs[index];
+            if input.is_finalized() {
+                continue;
+            }
+
+            let ecdsa_keys: Vec<(Vec<u8>, [u8; 32])> = input.bip32_derivations.iter()
+                .filter(|(_, s): &(&Vec<u8>, &KeySource)| s.fingerprint == fingerprint)
+                .filter_map(|(key, s): (&Vec<u8>, &KeySource)| {
+                    let child: ExtendedPrivateKey = master.derive_path(&s.path);
+                    let owned: bool = *key == child.compressed_public_key() || *key == base16::decode_string(&child.public_key());
+                    return if owned { Some((key.clone(), child.private_key)) } else { None };
+                })
+                .collect();
+            let tap_keys: Vec<TapSigningKey> = input.tap_bip32_derivations.iter()
+                .filter(|(_, s): &(&[u8; 32], &TapKeySource)| s.source.fingerprint == fingerprint)
+                .filter_map(|(key, s): (&[u8; 32], &TapKeySource)| {
+                    let child: ExtendedPrivateKey = master.derive_path(&s.source.path);
+                    return if child.compressed_public_key()[1..] == key[..] { Some((*key, s.leaf_hashes.clone(), child.private_key)) } else { None };
+                })
+                .collect();
+            if ecdsa_keys.is_empty() && tap_keys.is_empty() {
+                continue;
+            }
+
+            let spent: TxOut = self.spent_output(index)?;
+            let mut count: usize = 0;
+            if let ScriptType::P2tr(output_key) = spent.script_pubkey.script_type() {
+                let prevouts: Vec<TxOut> = (0..self.inputs.len()).map(|i: usize| self.spent_output(i)).collect::<Result<Vec<TxOut>, PsbtError>>()?;
+                let sighash_type: SighashType = self.sighash_type(index, SighashType::Default)?;
+                let input: &mut Input = &mut self.inputs[index];
+                for (x_only, leaf_hashes, secret_key) in tap_keys.iter() {
+                    if leaf_hashes.is_empty() && input.tap_internal_key == Some(*x_only) {
+                        let merkle_root: Option<&[u8; 32]> = input.tap_merkle_root.as_ref();
+                        let (expected, _) = taproot::tweak_public_key(x_only, merkle_root).map_err(|_| SignError::InvalidKey)?;
+                        if output_key != expected {
+                            return Err(PsbtError::ScriptMismatch(index));
+                        }
+                        let tweaked: [u8; 32] = taproot::tweak_private_key(secret_key, merkle_root).map_err(|_| SignError::InvalidKey)?;
+                        let sighash: [u8; 32] = sighash::taproot_sighash(&tx, index, &prevouts, None, None, sighash_type)?;
+                        input.tap_key_sig = Some(sign::schnorr_signature(&sighash, &tweaked, sighash_type, aux_rand)?);
+                        count += 1;
+                    }
+                    for leaf_hash in leaf_hashes.iter() {
+                        let sighash: [u8; 32] = sighash::taproot_sighash(&tx, index, &prevouts, None, Some(*leaf_hash), sighash_type)?;
+                        input.tap_script_sigs.insert((*x_only, *leaf_hash), sign::schnorr_signature(&sighash, secret_key, sighash_type, aux_rand)?);
+                        count += 1;
+                    }
+                }
+            }
+            else {
+                let sighash_type: SighashType = self.sighash_type(index, SighashType::All)?;
+                let sighash: [u8; 32] = self.ecdsa_sighash(&tx, index, &spent, sighash_type)?;
+                for (key, secret_key) in ecdsa_keys.iter() {
+                    self.inputs[index].partial_sigs.insert(key.clone(), sign::ecdsa_signature(&sighash, secret_key, sighash_type)?);
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                signed.push(index);
+            }
+        }
+        return Ok(signed);
+    }
+
+    // merges the fields of another PSBT for the same transaction
+    pub fn combine(&mut self, other: &Psbt) -> Result<(), PsbtError> {
+        if self.version != other.version || self.unsigned_tx()?.txid() != other.unsigned_tx()?.txid()
+            || self.inputs.len() != other.inputs.len() || self.outputs.len() != other.outputs.len() {
+            return Err(PsbtError::DifferentTransactions);
+        }
+        merge(&mut self.xpubs, &other.xpubs);
+        merge(&mut self.unknown, &other.unknown);
+        self.inputs.iter_mut().zip(other.inputs.iter()).for_each(|(a, b): (&mut Input, &Input)| a.combine(b));
+        self.outputs.iter_mut().zip(other.outputs.iter()).for_each(|(a, b): (&mut Output, &Output)| a.combine(b));
+        return Ok(());
+    }
+
+    pub fn finalize_input(&mut self, input_index: usize) -> Result<(), PsbtError> {
+        let spent: TxOut = self.spent_output(input_index)?;
+        let input: &Input = &self.inputs[input_index];
+        if input.is_finalized() {
+            return Ok(());
+        }
+
+        let (script_sig, witness): (Option<Script>, Option<Vec<Vec<u8>>>) = match spent.script_pubkey.script_type() {
+            ScriptType::P2sh(_) => {
+                let redeem_script: &Script = input.redeem_script.as_ref().ok_or(PsbtError::CannotFinalize(input_index))?;
+                match witness_stack(input, redeem_script) {
+                    Some(w) => (Some(Builder::new().push_slice(&redeem_script.bytes).into_script()), Some(w)),
+                    None => {
+                        let stack: Vec<Vec<u8>> = satisfy(input, redeem_script).ok_or(PsbtError::CannotFinalize(input_index))?;
+                        (Some(push_stack(&stack).push_slice(&redeem_script.bytes).into_script()), None)
+                    }
+                }
+            },
+            ScriptType::P2tr(_) => (None, Some(taproot_witness(input).ok_or(PsbtError::CannotFinalize(input_index))?)),
+            _ => match witness_stack(input, &spent.script_pubkey) {
+                Some(w) => (None, Some(w)),
+                None => (Some(push_stack(&satisfy(input, &spent.script_pubkey).ok_or(PsbtError::CannotFinalize(input_index))?).into_script()), None)
+            }
+        };
+
+        // only the utxos, the final scripts and the fields nothing else reads are kept
+        self.inputs[input_index] = Input {
+            non_witness_utxo: input.non_witness_utxo.clone(),
+            witness_utxo: input.witness_utxo.clone(),
+            final_script_sig: script_sig,
+            final_script_witness: witness,
+            por_commitment: input.por_commitment.clone(),
+            previous_txid: input.previous_txid,
+            output_index: input.output_index,
+            sequence: input.sequence,
+            required_time_locktime: input.required_time_locktime,
+            required_height_locktime: input.required_height_locktime,
+            unknown: input.unknown.clone(),
+            ..Input::default()
+        };
+        return Ok(());
+    }
+
+    // finalizes every input it can, failing on the first that cannot be
+    pub fn finalize(&mut self) -> Result<(), PsbtError> {
+        let mut r: Result<(), PsbtError> = Ok(());
+        for i in 0..self.inputs.len() {
+            if let Err(e) = self.finalize_input(i) {
+                if r.is_ok() {
+                    r = Err(e);
+                }
+            }
+        }
+        return r;
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        return self.inputs.iter().all(|i: &Input| i.is_finalized());
+    }
+
+    // the network transaction of a finalized PSBT
+    pub fn extract(&self) -> Result<Transaction, PsbtError> {
+        let mut tx: Transaction = self.unsigned_tx()?;
+        for (i, input) in self.inputs.iter().enumerate() {
+            if !input.is_finalized() {
+                return Err(PsbtError::NotFinalized(i));
+            }
+            tx.inputs[i].script_sig = input.final_script_sig.clone().unwrap_or_default();
+            tx.inputs[i].witness = input.final_script_witness.clone().unwrap_or_default();
+        }
+        return Ok(tx);
+    }
+}
+
+// base64, or hex starting with the magic bytes
+impl FromStr for Psbt {
+    type Err = PsbtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().to_lowercase().starts_with(&base16::encode_bytes(&PSBT_MAGIC)) {
+            return Psbt::from_hex(s);
+        }
+        return Psbt::from_base64(s);
+    }
+}
+
+impl fmt::Display for Psbt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.to_base64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::psbt::*;
+
+    // BIP174: one P2PKH input with its previous transaction
+    const PSBT_HEX: &str = "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300000100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab300000000000000";
+
+    #[test]
+    fn psbt_round_trip() {
+        let psbt = Psbt::from_hex(PSBT_HEX).unwrap();
+        assert_eq!(psbt.version, 0);
+        assert_eq!(psbt.inputs.len(), 1);
+        assert_eq!(psbt.outputs.len(), 2);
+        assert_eq!(psbt.to_hex(), PSBT_HEX);
+
+        let base64 = psbt.to_base64();
+        assert!(base64.starts_with("cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////"));
+        assert_eq!(base64.parse::<Psbt>().unwrap(), psbt);
+        assert_eq!(PSBT_HEX.parse::<Psbt>().unwrap(), psbt);
+
+        let tx = psbt.unsigned_tx().unwrap();
+        assert_eq!(psbt.spent_output(0).unwrap().script_pubkey.to_hex(), "76a91485cff1097fd9e008bb34af709c62197b38978a4888ac");
+        assert_eq!(tx.lock_time, 1257139);
+    }
+
+    #[test]
+    fn psbt_version_2() {
+        let psbt = Psbt::from_hex(PSBT_HEX).unwrap();
+        let v2 = psbt.to_v2().unwrap();
+        assert_eq!(v2.unsigned_tx, None);
+        assert_eq!(v2.inputs[0].output_index, Some(0));
+        assert_eq!(v2.outputs[1].amount, Some(100000000));
+
+        let parsed = Psbt::deserialize(&v2.serialize()).unwrap();
+        assert_eq!(parsed, v2);
+        assert_eq!(parsed.unsigned_tx().unwrap(), psbt.unsigned_tx().unwrap());
+        assert_eq!(parsed.to_v0().unwrap(), psbt);
+
+        // a required height lock time overrides the fallback
+        let mut locked = v2.clone();
+        locked.inputs[0].required_height_locktime = Some(1300000);
+        assert_eq!(locked.unsigned_tx().unwrap().lock_time, 1300000);
+        locked.inputs.push(Input { required_time_locktime: Some(1700000000), ..locked.inputs[0].clone() });
+        locked.inputs[1].required_height_locktime = None;
+        assert_eq!(locked.unsigned_tx(), Err(PsbtError::LockTimeConflict));
+
+        // version 2 fields are not allowed in version 0 and required in version 2
+        let mut mixed = psbt.clone();
+        mixed.fallback_locktime = Some(0);
+        assert_eq!(Psbt::deserialize(&mixed.serialize()), Err(PsbtError::FieldNotAllowed("PSBT_GLOBAL_FALLBACK_LOCKTIME")));
+        let mut missing = v2.clone();
+        missing.outputs[0].script = None;
+        assert_eq!(Psbt::deserialize(&missing.serialize()), Err(PsbtError::MissingField("PSBT_OUT_SCRIPT")));
+    }
+
+    #[test]
+    fn psbt_sign_finalize_extract() {
+        let master = ExtendedPrivateKey::from_seed(&[7; 32]);
+        let fingerprint = master.fingerprint();
+        let segwit_path: Vec<u32> = vec![0x80000054, 0x80000001, 0x80000000, 0, 0];
+        let taproot_path: Vec<u32> = vec![0x80000056, 0x80000001, 0x80000000, 0, 0];
+        let segwit_key = master.derive_path(&segwit_path).compressed_public_key();
+        let internal_key: [u8; 32] = master.derive_path(&taproot_path).compressed_public_key()[1..].try_into().unwrap();
+        let (output_key, _) = taproot::tweak_public_key(&internal_key, None).unwrap();
+
+        let mut tx = Transaction::new(2, 0);
+        tx.inputs.push(TxIn::new(OutPoint::new([1; 32], 0)));
+        tx.inputs.push(TxIn::new(OutPoint::new([2; 32], 1)));
+        tx.outputs.push(TxOut { value: 150000, script_pubkey: Script::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap() });
+        let mut psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+
+        let prevouts = vec![
+            TxOut { value: 100000, script_pubkey: Builder::new().push_int(0).push_slice(&hashing::hash160(&segwit_key)).into_script() },
+            TxOut { value: 60000, script_pubkey: Builder::new().push_int(1).push_slice(&output_key).into_script() }
+        ];
+        psbt.inputs[0].witness_utxo = Some(prevouts[0].clone());
+        psbt.inputs[0].bip32_derivations.insert(segwit_key.to_vec(), KeySource { fingerprint, path: segwit_path });
+        psbt.inputs[1].witness_utxo = Some(prevouts[1].clone());
+        psbt.inputs[1].tap_internal_key = Some(internal_key);
+        psbt.inputs[1].tap_bip32_derivations.insert(internal_key, TapKeySource { leaf_hashes: vec![], source: KeySource { fingerprint, path: taproot_path } });
+        assert_eq!(psbt.fee(), Ok(10000));
+        let mut overspent = psbt.clone();
+        overspent.inputs[1].witness_utxo = Some(TxOut { value: 40000, ..prevouts[1].clone() });
+        assert_eq!(overspent.fee(), Err(PsbtError::NegativeFee));
+        overspent.inputs[1].witness_utxo = Some(TxOut { value: u64::MAX, ..prevouts[1].clone() });
+        assert_eq!(overspent.fee(), Err(PsbtError::ValueOverflow));
+
+        // keys of another wallet sign nothing
+        assert_eq!(psbt.clone().sign(&ExtendedPrivateKey::from_seed(&[8; 32])), Ok(vec![]));
+        assert_eq!(psbt.sign_with_aux_rand(&master, &[0; 32]), Ok(vec![0, 1]));
+        let signed = Psbt::from_base64(&psbt.to_base64()).unwrap();
+        assert_eq!(signed, psbt);
+
+        psbt.finalize().unwrap();
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 0);
+        assert_eq!(psbt.inputs[1].tap_internal_key, None);
+        let signed_tx = psbt.extract().unwrap();
+
+        let sighash = sighash::segwit_v0_sighash(&tx, 0, &sighash::p2wpkh_script_code(&hashing::hash160(&segwit_key)), 100000, SighashType::All).unwrap();
+        assert_eq!(signed_tx.inputs[0].witness[1], segwit_key.to_vec());
+        assert!(sign::verify_ecdsa_signature(&sighash, &signed_tx.inputs[0].witness[0], &segwit_key));
+        let sighash = sighash::taproot_sighash(&tx, 1, &prevouts, None, None, SighashType::Default).unwrap();
+        assert_eq!(signed_tx.inputs[1].witness.len(), 1);
+        assert!(sign::verify_schnorr_signature(&sighash, &signed_tx.inputs[1].witness[0], &output_key));
+    }
+
+    // BIP174: legacy signatures do not commit to the amount, so a witness utxo is not enough for them
+    #[test]
+    fn psbt_legacy_witness_utxo() {
+        let master = ExtendedPrivateKey::from_seed(&[7; 32]);
+        let path: Vec<u32> = vec![0x8000002c, 0x80000001, 0x80000000, 0, 0];
+        let key = master.derive_path(&path).compressed_public_key();
+        let p2pkh: Script = Script::from_bytes(&[&[0x76, 0xa9, 0x14][..], &hashing::hash160(&key), &[0x88, 0xac]].concat());
+
+        let mut tx = Transaction::new(2, 0);
+        tx.inputs.push(TxIn::new(OutPoint::new([1; 32], 0)));
+        tx.outputs.push(TxOut { value: 90000, script_pubkey: p2pkh.clone() });
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut { value: 100000, script_pubkey: p2pkh });
+        psbt.inputs[0].bip32_derivations.insert(key.to_vec(), KeySource { fingerprint: master.fingerprint(), path });
+
+        assert_eq!(psbt.spent_output(0), Err(PsbtError::MissingUtxo(0)));
+        assert_eq!(psbt.fee(), Err(PsbtError::MissingUtxo(0)));
+        assert_eq!(psbt.sign_with_aux_rand(&master, &[0; 32]), Err(PsbtError::MissingUtxo(0)));
+        assert!(psbt.inputs[0].partial_sigs.is_empty());
+
+        // P2SH only with a segwit redeem script that matches
+        let redeem_script: Script = Builder::new().push_int(0).push_slice(&hashing::hash160(&key)).into_script();
+        let p2sh: Script = Script::from_bytes(&[&[0xa9, 0x14][..], &hashing::hash160(&redeem_script.bytes), &[0x87]].concat());
+        psbt.inputs[0].witness_utxo = Some(TxOut { value: 100000, script_pubkey: p2sh });
+        assert_eq!(psbt.spent_output(0), Err(PsbtError::MissingUtxo(0)));
+        psbt.inputs[0].redeem_script = Some(redeem_script);
+        assert_eq!(psbt.fee(), Ok(10000));
+    }
+}
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::str::FromStr;
 use primitive_types::U256;
 use hex;
@@ -22,11 +23,11 @@ impl FromStr for RD256 {
         }
     }
 }
-impl ToString for RD256 {
-    fn to_string(&self) -> String {
+impl fmt::Display for RD256 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut bytes: [u8; 32] = [0; 32];
         self.v.to_big_endian(&mut bytes);
-        return hex::encode(bytes);
+        return write!(f, "{}", hex::encode(bytes));
     }
 }
 
@@ -0,0 +1,231 @@
+use std::collections::HashSet;
+use crate::app::Network;
+use crate::bip32::{ExtendedPrivateKey, HARDENED};
+use crate::bip39::{self, Language};
+use crate::discovery::{self, Purpose, DiscoveryError};
+
+// placeholder for a word whose position is known but whose value is not
+pub const MISSING_WORD: &str = "?";
+pub const DEFAULT_MAX_DISTANCE: usize = 2;
+
+// upper bound on the phrases tried before giving up, about two unknown words
+const MAX_COMBINATIONS: usize = 1 << 22;
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum RepairError {
+    WordCount(usize),
+    NoCandidates(String),
+    TooManyCombinations(usize),
+    Discovery(DiscoveryError)
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect::<Vec<char>>();
+    let b: Vec<char> = b.chars().collect::<Vec<char>>();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, x) in a.iter().enumerate() {
+        let mut current: Vec<usize> = vec![i + 1];
+        for (j, y) in b.iter().enumerate() {
+            let substitution: usize = previous[j] + if x == y { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    return previous[b.len()];
+}
+
+// wordlist indexes within max_distance of the word, nearest first
+pub fn closest_words(word: &str, language: Language, max_distance: usize) -> Vec<usize> {
+    let word: String = bip39::normalize(word);
+    let mut r: Vec<(usize, usize)> = language.wordlist().iter().enumerate()
+        .map(|(i, w): (usize, &&str)| (levenshtein(&word, w), i))
+        .filter(|(d, _): &(usize, usize)| *d <= max_distance)
+        .collect::<Vec<(usize, usize)>>();
+    r.sort();
+    return r.iter().map(|(_, i): &(usize, usize)| *i).collect::<Vec<usize>>();
+}
+
+// the language with the most known words, ties going to the earlier one
+pub fn guess_language(words: &[String]) -> Language {
+    let mut best: (usize, Language) = (0, Language::English);
+    for language in Language::all() {
+        let list: Vec<&str> = language.wordlist();
+        let known: usize = words.iter().filter(|w: &&String| list.contains(&w.as_str())).count();
+        if known > best.0 {
+            best = (known, language);
+        }
+    }
+    return best.1;
+}
+
+fn valid_count(count: usize) -> bool {
+    return (12..=24).contains(&count) && count.is_multiple_of(3);
+}
+
+// every checksum-valid choice of one index per slot, in slot order; seen drops
+// the phrases an earlier call already found
+fn enumerate(slots: &[Vec<usize>], found: &mut Vec<Vec<usize>>, seen: &mut HashSet<Vec<usize>>) -> Result<(), RepairError> {
+    let total: usize = slots.iter().try_fold(1usize, |acc: usize, s: &Vec<usize>| acc.checked_mul(s.len())).unwrap_or(usize::MAX);
+    if total > MAX_COMBINATIONS {
+        return Err(RepairError::TooManyCombinations(total));
+    }
+    if total == 0 {
+        return Ok(());
+    }
+
+    let mut position: Vec<usize> = vec![0; slots.len()];
+    loop {
+        let indexes: Vec<usize> = slots.iter().zip(position.iter()).map(|(s, p): (&Vec<usize>, &usize)| s[*p]).collect::<Vec<usize>>();
+        if bip39::indexes_to_entropy(&indexes).is_ok() && seen.insert(indexes.clone()) {
+            found.push(indexes);
+        }
+
+        // odometer step, the last slot turning fastest
+        let mut i: usize = slots.len();
+        loop {
+            if i == 0 {
+                return Ok(());
+            }
+            i -= 1;
+            position[i] += 1;
+            if position[i] < slots[i].len() {
+                break;
+            }
+            position[i] = 0;
+        }
+    }
+}
+
+// a phrase may contain MISSING_WORD placeholders, misspelled words (matched
+// against the wordlist by edit distance) or lack one word at an unknown position;
+// a phrase of known words with a bad checksum is treated as one mistyped word
+pub fn repair(mnemonic: &str, language: Option<Language>, max_distance: usize) -> Result<Vec<String>, RepairError> {
+    let words: Vec<String> = bip39::split_words(mnemonic);
+    let language: Language = language.unwrap_or_else(|| guess_language(&words));
+    let list: Vec<&str> = language.wordlist();
+    let everything: Vec<usize> = (0..list.len()).collect::<Vec<usize>>();
+
+    let mut slots: Vec<Vec<usize>> = vec![];
+    for word in words.iter() {
+        if word == MISSING_WORD {
+            slots.push(everything.clone());
+            continue;
+        }
+        match list.iter().position(|w: &&str| w == word) {
+            Some(i) => slots.push(vec![i]),
+            None => {
+                let close: Vec<usize> = closest_words(word, language, max_distance);
+                if close.is_empty() {
+                    return Err(RepairError::NoCandidates(word.clone()));
+                }
+                slots.push(close);
+            }
+        };
+    }
+
+    let mut found: Vec<Vec<usize>> = vec![];
+    let mut seen: HashSet<Vec<usize>> = HashSet::new();
+    if valid_count(slots.len()) {
+        enumerate(&slots, &mut found, &mut seen)?;
+
+        if found.is_empty() && slots.iter().all(|s: &Vec<usize>| s.len() == 1) {
+            for i in 0..slots.len() {
+                let mut alternative: Vec<Vec<usize>> = slots.clone();
+                alternative[i] = closest_words(list[slots[i][0]], language, max_distance);
+                alternative[i].retain(|w: &usize| *w != slots[i][0]);
+                enumerate(&alternative, &mut found, &mut seen)?;
+            }
+        }
+    }
+    else if valid_count(slots.len() + 1) && !words.iter().any(|w: &String| w == MISSING_WORD) {
+        for i in 0..=slots.len() {
+            let mut inserted: Vec<Vec<usize>> = slots.clone();
+            inserted.insert(i, everything.clone());
+            enumerate(&inserted, &mut found, &mut seen)?;
+        }
+    }
+    else {
+        return Err(RepairError::WordCount(slots.len()));
+    }
+
+    return Ok(found.iter()
+        .map(|indexes: &Vec<usize>| bip39::join_words(&indexes.iter().map(|i: &usize| list[*i]).collect::<Vec<&str>>(), language))
+        .collect::<Vec<String>>());
+}
+
+// the first receive address m/purpose'/coin'/0'/0/0 of a candidate phrase
+pub fn first_address(mnemonic: &str, passphrase: &str, network: Network, purpose: Purpose) -> Result<String, RepairError> {
+    let master: ExtendedPrivateKey = ExtendedPrivateKey::from_seed(&bip39::mnemonic_to_seed(mnemonic, passphrase));
+    let path: Vec<u32> = vec![purpose.index() | HARDENED, discovery::coin_type(network) | HARDENED, HARDENED, 0, 0];
+
+    return discovery::derive_address(purpose, network, &master.derive_path(&path)).map_err(RepairError::Discovery);
+}
+
+pub fn filter_by_address(candidates: &[String], passphrase: &str, network: Network, purpose: Purpose, address: &str) -> Result<Vec<String>, RepairError> {
+    let expected: String = discovery::normalize_address(address);
+    let mut r: Vec<String> = vec![];
+
+    for candidate in candidates.iter() {
+        if discovery::normalize_address(&first_address(candidate, passphrase, network, purpose)?) == expected {
+            r.push(candidate.clone());
+        }
+    }
+    return Ok(r);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repair::*;
+
+    const PHRASE: &str = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+    #[test]
+    fn repair_levenshtein() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("abaut", "about"), 1);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("é", "e"), 1);
+    }
+
+    #[test]
+    fn repair_missing_word() {
+        let candidates = repair("legal winner thank year wave sausage worth useful legal winner thank ?", None, DEFAULT_MAX_DISTANCE).unwrap();
+
+        // 7 free bits in the last word of a 12-word phrase, 4 checksum bits
+        assert_eq!(candidates.len(), 128);
+        assert!(candidates.contains(&String::from(PHRASE)));
+        assert!(candidates.iter().all(|c| bip39::validate_mnemonic(c)));
+    }
+
+    #[test]
+    fn repair_misspelled_word() {
+        let candidates = repair("legal winner thank year wave sausage wrth useful legal winner thank yellow", None, DEFAULT_MAX_DISTANCE).unwrap();
+        assert!(candidates.contains(&String::from(PHRASE)));
+
+        let candidates = repair("legal winner thank year wave sausage worth useful legal winner thank yellow", None, 1).unwrap();
+        assert_eq!(candidates, vec![String::from(PHRASE)]);
+
+        // a valid but wrong word is only caught through the checksum
+        let candidates = repair("legal winner thank year wave sausage worth useful legal winner thank yellow".replace("useful", "use").as_str(), None, 3).unwrap();
+        assert!(candidates.contains(&String::from(PHRASE)));
+        assert_eq!(repair("legal winner thank year wave qqqqqqqqqq worth useful legal winner thank yellow", None, 2), Err(RepairError::NoCandidates(String::from("qqqqqqqqqq"))));
+    }
+
+    #[test]
+    fn repair_unknown_position() {
+        let candidates = repair("legal winner thank year wave worth useful legal winner thank yellow", None, DEFAULT_MAX_DISTANCE).unwrap();
+        assert!(candidates.contains(&String::from(PHRASE)));
+        assert_eq!(repair("legal winner thank year wave useful legal winner thank yellow", None, DEFAULT_MAX_DISTANCE), Err(RepairError::WordCount(10)));
+    }
+
+    #[test]
+    fn repair_filter_by_address() {
+        let candidates = repair("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon ?", None, DEFAULT_MAX_DISTANCE).unwrap();
+        assert!(candidates.len() > 1);
+
+        let matched = filter_by_address(&candidates, "", Network::Bitcoin, Purpose::Bip44, "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA").unwrap();
+        assert_eq!(matched, vec![String::from("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about")]);
+    }
+}
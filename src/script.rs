@@ -0,0 +1,686 @@
+use std::fmt;
+use crate::app::Network;
+use crate::base16;
+use crate::bech32;
+use crate::crypto::bitcoin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum Opcode {
+    // OP_0 pushes the empty array
+    Op0,
+    // 0x01 .. 0x4b push that many following bytes
+    PushBytes(u8),
+    PushData1,
+    PushData2,
+    PushData4,
+    Op1Negate,
+    Reserved,
+    // OP_1 .. OP_16
+    Num(u8),
+    Nop,
+    Ver,
+    If,
+    NotIf,
+    VerIf,
+    VerNotIf,
+    Else,
+    EndIf,
+    Verify,
+    Return,
+    ToAltStack,
+    FromAltStack,
+    Drop2,
+    Dup2,
+    Dup3,
+    Over2,
+    Rot2,
+    Swap2,
+    IfDup,
+    Depth,
+    Drop,
+    Dup,
+    Nip,
+    Over,
+    Pick,
+    Roll,
+    Rot,
+    Swap,
+    Tuck,
+    Cat,
+    Substr,
+    Left,
+    Right,
+    Size,
+    Invert,
+    And,
+    Or,
+    Xor,
+    Equal,
+    EqualVerify,
+    Reserved1,
+    Reserved2,
+    Add1,
+    Sub1,
+    Mul2,
+    Div2,
+    Negate,
+    Abs,
+    Not,
+    NotEqual0,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    LShift,
+    RShift,
+    BoolAnd,
+    BoolOr,
+    NumEqual,
+    NumEqualVerify,
+    NumNotEqual,
+    LessThan,
+    GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
+    Min,
+    Max,
+    Within,
+    Ripemd160,
+    Sha1,
+    Sha256,
+    Hash160,
+    Hash256,
+    CodeSeparator,
+    CheckSig,
+    CheckSigVerify,
+    CheckMultisig,
+    CheckMultisigVerify,
+    Nop1,
+    CheckLockTimeVerify,
+    CheckSequenceVerify,
+    Nop4,
+    Nop5,
+    Nop6,
+    Nop7,
+    Nop8,
+    Nop9,
+    Nop10,
+    CheckSigAdd,
+    // anything not assigned, up to OP_INVALIDOPCODE
+    Invalid(u8)
+}
+
+// the opcodes that stand for a single byte, with their assembly names
+const OPCODES: [(u8, Opcode, &str); 96] = [
+    (0x00, Opcode::Op0, "OP_0"),
+    (0x4c, Opcode::PushData1, "OP_PUSHDATA1"),
+    (0x4d, Opcode::PushData2, "OP_PUSHDATA2"),
+    (0x4e, Opcode::PushData4, "OP_PUSHDATA4"),
+    (0x4f, Opcode::Op1Negate, "OP_1NEGATE"),
+    (0x50, Opcode::Reserved, "OP_RESERVED"),
+    (0x61, Opcode::Nop, "OP_NOP"),
+    (0x62, Opcode::Ver, "OP_VER"),
+    (0x63, Opcode::If, "OP_IF"),
+    (0x64, Opcode::NotIf, "OP_NOTIF"),
+    (0x65, Opcode::VerIf, "OP_VERIF"),
+    (0x66, Opcode::VerNotIf, "OP_VERNOTIF"),
+    (0x67, Opcode::Else, "OP_ELSE"),
+    (0x68, Opcode::EndIf, "OP_ENDIF"),
+    (0x69, Opcode::Verify, "OP_VERIFY"),
+    (0x6a, Opcode::Return, "OP_RETURN"),
+    (0x6b, Opcode::ToAltStack, "OP_TOALTSTACK"),
+    (0x6c, Opcode::FromAltStack, "OP_FROMALTSTACK"),
+    (0x6d, Opcode::Drop2, "OP_2DROP"),
+    (0x6e, Opcode::Dup2, "OP_2DUP"),
+    (0x6f, Opcode::Dup3, "OP_3DUP"),
+    (0x70, Opcode::Over2, "OP_2OVER"),
+    (0x71, Opcode::Rot2, "OP_2ROT"),
+    (0x72, Opcode::Swap2, "OP_2SWAP"),
+    (0x73, Opcode::IfDup, "OP_IFDUP"),
+    (0x74, Opcode::Depth, "OP_DEPTH"),
+    (0x75, Opcode::Drop, "OP_DROP"),
+    (0x76, Opcode::Dup, "OP_DUP"),
+    (0x77, Opcode::Nip, "OP_NIP"),
+    (0x78, Opcode::Over, "OP_OVER"),
+    (0x79, Opcode::Pick, "OP_PICK"),
+    (0x7a, Opcode::Roll, "OP_ROLL"),
+    (0x7b, Opcode::Rot, "OP_ROT"),
+    (0x7c, Opcode::Swap, "OP_SWAP"),
+    (0x7d, Opcode::Tuck, "OP_TUCK"),
+    (0x7e, Opcode::Cat, "OP_CAT"),
+    (0x7f, Opcode::Substr, "OP_SUBSTR"),
+    (0x80, Opcode::Left, "OP_LEFT"),
+    (0x81, Opcode::Right, "OP_RIGHT"),
+    (0x82, Opcode::Size, "OP_SIZE"),
+    (0x83, Opcode::Invert, "OP_INVERT"),
+    (0x84, Opcode::And, "OP_AND"),
+    (0x85, Opcode::Or, "OP_OR"),
+    (0x86, Opcode::Xor, "OP_XOR"),
+    (0x87, Opcode::Equal, "OP_EQUAL"),
+    (0x88, Opcode::EqualVerify, "OP_EQUALVERIFY"),
+    (0x89, Opcode::Reserved1, "OP_RESERVED1"),
+    (0x8a, Opcode::Reserved2, "OP_RESERVED2"),
+    (0x8b, Opcode::Add1, "OP_1ADD"),
+    (0x8c, Opcode::Sub1, "OP_1SUB"),
+    (0x8d, Opcode::Mul2, "OP_2MUL"),
+    (0x8e, Opcode::Div2, "OP_2DIV"),
+    (0x8f, Opcode::Negate, "OP_NEGATE"),
+    (0x90, Opcode::Abs, "OP_ABS"),
+    (0x91, Opcode::Not, "OP_NOT"),
+    (0x92, Opcode::NotEqual0, "OP_0NOTEQUAL"),
+    (0x93, Opcode::Add, "OP_ADD"),
+    (0x94, Opcode::Sub, "OP_SUB"),
+    (0x95, Opcode::Mul, "OP_MUL"),
+    (0x96, Opcode::Div, "OP_DIV"),
+    (0x97, Opcode::Mod, "OP_MOD"),
+    (0x98, Opcode::LShift, "OP_LSHIFT"),
+    (0x99, Opcode::RShift, "OP_RSHIFT"),
+    (0x9a, Opcode::BoolAnd, "OP_BOOLAND"),
+    (0x9b, Opcode::BoolOr, "OP_BOOLOR"),
+    (0x9c, Opcode::NumEqual, "OP_NUMEQUAL"),
+    (0x9d, Opcode::NumEqualVerify, "OP_NUMEQUALVERIFY"),
+    (0x9e, Opcode::NumNotEqual, "OP_NUMNOTEQUAL"),
+    (0x9f, Opcode::LessThan, "OP_LESSTHAN"),
+    (0xa0, Opcode::GreaterThan, "OP_GREATERTHAN"),
+    (0xa1, Opcode::LessThanOrEqual, "OP_LESSTHANOREQUAL"),
+    (0xa2, Opcode::GreaterThanOrEqual, "OP_GREATERTHANOREQUAL"),
+    (0xa3, Opcode::Min, "OP_MIN"),
+    (0xa4, Opcode::Max, "OP_MAX"),
+    (0xa5, Opcode::Within, "OP_WITHIN"),
+    (0xa6, Opcode::Ripemd160, "OP_RIPEMD160"),
+    (0xa7, Opcode::Sha1, "OP_SHA1"),
+    (0xa8, Opcode::Sha256, "OP_SHA256"),
+    (0xa9, Opcode::Hash160, "OP_HASH160"),
+    (0xaa, Opcode::Hash256, "OP_HASH256"),
+    (0xab, Opcode::CodeSeparator, "OP_CODESEPARATOR"),
+    (0xac, Opcode::CheckSig, "OP_CHECKSIG"),
+    (0xad, Opcode::CheckSigVerify, "OP_CHECKSIGVERIFY"),
+    (0xae, Opcode::CheckMultisig, "OP_CHECKMULTISIG"),
+    (0xaf, Opcode::CheckMultisigVerify, "OP_CHECKMULTISIGVERIFY"),
+    (0xb0, Opcode::Nop1, "OP_NOP1"),
+    (0xb1, Opcode::CheckLockTimeVerify, "OP_CHECKLOCKTIMEVERIFY"),
+    (0xb2, Opcode::CheckSequenceVerify, "OP_CHECKSEQUENCEVERIFY"),
+    (0xb3, Opcode::Nop4, "OP_NOP4"),
+    (0xb4, Opcode::Nop5, "OP_NOP5"),
+    (0xb5, Opcode::Nop6, "OP_NOP6"),
+    (0xb6, Opcode::Nop7, "OP_NOP7"),
+    (0xb7, Opcode::Nop8, "OP_NOP8"),
+    (0xb8, Opcode::Nop9, "OP_NOP9"),
+    (0xb9, Opcode::Nop10, "OP_NOP10"),
+    (0xba, Opcode::CheckSigAdd, "OP_CHECKSIGADD")
+];
+
+// names accepted by the assembly parser besides the canonical ones
+const ALIASES: [(&str, Opcode); 4] = [
+    ("OP_FALSE", Opcode::Op0),
+    ("OP_TRUE", Opcode::Num(1)),
+    ("OP_NOP2", Opcode::CheckLockTimeVerify),
+    ("OP_NOP3", Opcode::CheckSequenceVerify)
+];
+
+impl Opcode {
+    pub fn from_byte(b: u8) -> Opcode {
+        match b {
+            0x01..=0x4b => return Opcode::PushBytes(b),
+            0x51..=0x60 => return Opcode::Num(b - 0x50),
+            _ => {}
+        };
+        match OPCODES.iter().find(|(byte, _, _): &&(u8, Opcode, &str)| *byte == b) {
+            Some((_, op, _)) => return *op,
+            None => return Opcode::Invalid(b)
+        };
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            Opcode::PushBytes(n) => return *n,
+            Opcode::Num(n) => return 0x50 + n,
+            Opcode::Invalid(b) => return *b,
+            _ => return OPCODES.iter().find(|(_, op, _): &&(u8, Opcode, &str)| op == self).expect("opcode").0,
+        };
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Opcode::PushBytes(n) => return format!("OP_PUSHBYTES_{}", n),
+            Opcode::Num(n) => return format!("OP_{}", n),
+            Opcode::Invalid(0xff) => return String::from("OP_INVALIDOPCODE"),
+            Opcode::Invalid(b) => return format!("OP_UNKNOWN_{:02x}", b),
+            _ => return String::from(OPCODES.iter().find(|(_, op, _): &&(u8, Opcode, &str)| op == self).expect("opcode").2),
+        };
+    }
+
+    // names may leave out the OP_ prefix, except numbers, which would read the same as one-byte hex pushes
+    pub fn from_name(name: &str) -> Option<Opcode> {
+        let name: String = name.to_uppercase();
+        if name.bytes().all(|b: u8| b.is_ascii_digit()) {
+            return None;
+        }
+        let name: String = if name.starts_with("OP_") { name } else { format!("OP_{}", name) };
+
+        if let Some(n) = name.strip_prefix("OP_").and_then(|n: &str| n.parse::<u8>().ok()) {
+            if (1..=16).contains(&n) && name == format!("OP_{}", n) {
+                return Some(Opcode::Num(n));
+            }
+        }
+        if let Some((_, op)) = ALIASES.iter().find(|(alias, _): &&(&str, Opcode)| *alias == name) {
+            return Some(*op);
+        }
+        return OPCODES.iter().find(|(_, _, n): &&(u8, Opcode, &str)| *n == name).map(|(_, op, _): &(u8, Opcode, &str)| *op);
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.name());
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum ScriptError {
+    // a push running past the end of the script, at the offset of its opcode
+    TruncatedPush(usize),
+    InvalidToken(String),
+    NoAddress,
+    UnsupportedNetwork(Network)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum Instruction {
+    // the pushing opcode is kept so that non-minimal pushes disassemble faithfully
+    Push(Opcode, Vec<u8>),
+    Op(Opcode)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum ScriptType {
+    P2pk(Vec<u8>),
+    P2pkh(Vec<u8>),
+    P2sh(Vec<u8>),
+    P2wpkh(Vec<u8>),
+    P2wsh(Vec<u8>),
+    P2tr(Vec<u8>),
+    // future witness versions and program sizes
+    WitnessUnknown(u8, Vec<u8>),
+    Multisig(usize, Vec<Vec<u8>>),
+    NullData(Vec<u8>),
+    NonStandard
+}
+
+impl fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptType::P2pk(_) => return write!(f, "P2PK"),
+            ScriptType::P2pkh(_) => return write!(f, "P2PKH"),
+            ScriptType::P2sh(_) => return write!(f, "P2SH"),
+            ScriptType::P2wpkh(_) => return write!(f, "P2WPKH"),
+            ScriptType::P2wsh(_) => return write!(f, "P2WSH"),
+            ScriptType::P2tr(_) => return write!(f, "P2TR"),
+            ScriptType::WitnessUnknown(v, _) => return write!(f, "Witness v{}", v),
+            ScriptType::Multisig(m, keys) => return write!(f, "Multisig {} of {}", m, keys.len()),
+            ScriptType::NullData(_) => return write!(f, "OP_RETURN"),
+            ScriptType::NonStandard => return write!(f, "Non-standard"),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+
+pub struct Script {
+    pub bytes: Vec<u8>
+}
+
+#[derive(Debug, Clone, Default)]
+
+pub struct Builder {
+    bytes: Vec<u8>
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        return Self { bytes: vec![] };
+    }
+
+    pub fn push_opcode(mut self, op: Opcode) -> Self {
+        self.bytes.push(op.to_byte());
+        return self;
+    }
+
+    // the shortest push for the length, OP_0 for empty data
+    pub fn push_slice(mut self, data: &[u8]) -> Self {
+        match data.len() {
+            0 => self.bytes.push(Opcode::Op0.to_byte()),
+            n if n <= 0x4b => self.bytes.push(n as u8),
+            n if n <= 0xff => self.bytes.extend([Opcode::PushData1.to_byte(), n as u8]),
+            n if n <= 0xffff => {
+                self.bytes.push(Opcode::PushData2.to_byte());
+                self.bytes.extend((n as u16).to_le_bytes());
+            },
+            n => {
+                self.bytes.push(Opcode::PushData4.to_byte());
+                self.bytes.extend((n as u32).to_le_bytes());
+            }
+        };
+        self.bytes.extend_from_slice(data);
+        return self;
+    }
+
+    // small numbers get their own opcodes, anything else a minimal script number
+    pub fn push_int(self, n: i64) -> Self {
+        match n {
+            0 => return self.push_opcode(Opcode::Op0),
+            -1 => return self.push_opcode(Opcode::Op1Negate),
+            1..=16 => return self.push_opcode(Opcode::Num(n as u8)),
+            _ => return self.push_slice(&script_number(n)),
+        };
+    }
+
+    pub fn push_instruction(self, instruction: &Instruction) -> Self {
+        match instruction {
+            Instruction::Op(op) => return self.push_opcode(*op),
+            Instruction::Push(op, data) => {
+                let mut builder: Builder = self.push_opcode(*op);
+                match op {
+                    Opcode::PushData1 => builder.bytes.push(data.len() as u8),
+                    Opcode::PushData2 => builder.bytes.extend((data.len() as u16).to_le_bytes()),
+                    Opcode::PushData4 => builder.bytes.extend((data.len() as u32).to_le_bytes()),
+                    _ => {}
+                };
+                builder.bytes.extend_from_slice(data);
+                return builder;
+            }
+        };
+    }
+
+    pub fn into_script(self) -> Script {
+        return Script { bytes: self.bytes };
+    }
+}
+
+// little-endian magnitude with the sign in the top bit of the last byte
+pub fn script_number(n: i64) -> Vec<u8> {
+    let mut r: Vec<u8> = vec![];
+    let negative: bool = n < 0;
+    let mut magnitude: u64 = n.unsigned_abs();
+    while magnitude > 0 {
+        r.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    if let Some(last) = r.last_mut() {
+        if *last & 0x80 != 0 {
+            r.push(if negative { 0x80 } else { 0 });
+        }
+        else if negative {
+            *last |= 0x80;
+        }
+    }
+    return r;
+}
+
+impl Script {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        return Self { bytes: bytes.to_vec() };
+    }
+
+    pub fn from_hex(s: &str) -> Option<Self> {
+        return hex::decode(s.trim()).ok().map(|b: Vec<u8>| Self { bytes: b });
+    }
+
+    pub fn to_hex(&self) -> String {
+        return base16::encode_bytes(&self.bytes);
+    }
+
+    pub fn instructions(&self) -> Result<Vec<Instruction>, ScriptError> {
+        let mut r: Vec<Instruction> = vec![];
+        let mut i: usize = 0;
+        while i < self.bytes.len() {
+            let start: usize = i;
+            let op: Opcode = Opcode::from_byte(self.bytes[i]);
+            i += 1;
+
+            let (size_length, length): (usize, usize) = match op {
+                Opcode::PushBytes(n) => (0, n as usize),
+                Opcode::PushData1 => (1, *self.bytes.get(i).ok_or(ScriptError::TruncatedPush(start))? as usize),
+                Opcode::PushData2 => match self.bytes.get(i..i + 2) {
+                    Some(b) => (2, u16::from_le_bytes([b[0], b[1]]) as usize),
+                    None => return Err(ScriptError::TruncatedPush(start))
+                },
+                Opcode::PushData4 => match self.bytes.get(i..i + 4) {
+                    Some(b) => (4, u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize),
+                    None => return Err(ScriptError::TruncatedPush(start))
+                },
+                _ => {
+                    r.push(Instruction::Op(op));
+                    continue;
+                }
+            };
+            i += size_length;
+            match self.bytes.get(i..i + length) {
+                Some(data) => r.push(Instruction::Push(op, data.to_vec())),
+                None => return Err(ScriptError::TruncatedPush(start))
+            };
+            i += length;
+        }
+        return Ok(r);
+    }
+
+    // direct pushes are written as bare hex, longer pushes keep their OP_PUSHDATA opcode
+    pub fn to_asm(&self) -> Result<String, ScriptError> {
+        let tokens: Vec<String> = self.instructions()?.iter().map(|instruction: &Instruction| match instruction {
+            Instruction::Push(Opcode::PushBytes(_), data) => base16::encode_bytes(data),
+            Instruction::Push(op, data) => format!("{} {}", op, base16::encode_bytes(data)),
+            Instruction::Op(op) => op.name()
+        }).collect::<Vec<String>>();
+        return Ok(tokens.join(" "));
+    }
+
+    pub fn from_asm(asm: &str) -> Result<Self, ScriptError> {
+        let mut builder: Builder = Builder::new();
+        let mut tokens = asm.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match Opcode::from_name(token) {
+                Some(op) if op == Opcode::PushData1 || op == Opcode::PushData2 || op == Opcode::PushData4 => {
+                    let data: &str = tokens.next().ok_or_else(|| ScriptError::InvalidToken(String::from(token)))?;
+                    let data: Vec<u8> = hex::decode(data).map_err(|_| ScriptError::InvalidToken(String::from(data)))?;
+                    builder = builder.push_instruction(&Instruction::Push(op, data));
+                },
+                Some(op) => builder = builder.push_opcode(op),
+                None => match hex::decode(token) {
+                    Ok(data) if !data.is_empty() => builder = builder.push_slice(&data),
+                    _ => return Err(ScriptError::InvalidToken(String::from(token)))
+                }
+            };
+        }
+        return Ok(builder.into_script());
+    }
+
+    pub fn is_push_only(&self) -> bool {
+        match self.instructions() {
+            Ok(instructions) => return instructions.iter().all(|i: &Instruction| match i {
+                Instruction::Push(_, _) => true,
+                Instruction::Op(op) => matches!(op, Opcode::Op0 | Opcode::Op1Negate | Opcode::Num(_))
+            }),
+            Err(_) => return false
+        };
+    }
+
+    // version opcode and a single direct push of 2 to 40 bytes, per BIP141
+    pub fn witness_program(&self) -> Option<(u8, Vec<u8>)> {
+        let b: &[u8] = &self.bytes;
+        if b.len() < 4 || b.len() > 42 || b[1] as usize != b.len() - 2 {
+            return None;
+        }
+        match Opcode::from_byte(b[0]) {
+            Opcode::Op0 => return Some((0, b[2..].to_vec())),
+            Opcode::Num(v) => return Some((v, b[2..].to_vec())),
+            _ => return None
+        };
+    }
+
+    pub fn script_type(&self) -> ScriptType {
+        let b: &[u8] = &self.bytes;
+        let instructions: Vec<Instruction> = match self.instructions() {
+            Ok(i) => i,
+            Err(_) => return ScriptType::NonStandard
+        };
+
+        if b.len() == 25 && b[0] == 0x76 && b[1] == 0xa9 && b[2] == 20 && b[23] == 0x88 && b[24] == 0xac {
+            return ScriptType::P2pkh(b[3..23].to_vec());
+        }
+        if b.len() == 23 && b[0] == 0xa9 && b[1] == 20 && b[22] == 0x87 {
+            return ScriptType::P2sh(b[2..22].to_vec());
+        }
+        if let Some((version, program)) = self.witness_program() {
+            match (version, program.len()) {
+                (0, 20) => return ScriptType::P2wpkh(program),
+                (0, 32) => return ScriptType::P2wsh(program),
+                (0, _) => return ScriptType::NonStandard,
+                (1, 32) => return ScriptType::P2tr(program),
+                _ => return ScriptType::WitnessUnknown(version, program),
+            };
+        }
+        if b.first() == Some(&0x6a) {
+            let rest: Script = Script::from_bytes(&b[1..]);
+            if !rest.is_push_only() {
+                return ScriptType::NonStandard;
+            }
+            let data: Vec<u8> = rest.instructions().unwrap_or_default().iter().flat_map(|i: &Instruction| match i {
+                Instruction::Push(_, d) => d.clone(),
+                Instruction::Op(_) => vec![]
+            }).collect::<Vec<u8>>();
+            return ScriptType::NullData(data);
+        }
+        if let [Instruction::Push(_, key), Instruction::Op(Opcode::CheckSig)] = instructions.as_slice() {
+            if key.len() == 33 || key.len() == 65 {
+                return ScriptType::P2pk(key.clone());
+            }
+        }
+        if let [Instruction::Op(Opcode::Num(m)), keys @ .., Instruction::Op(Opcode::Num(n)), Instruction::Op(Opcode::CheckMultisig)] = instructions.as_slice() {
+            // nothing but the n keys between the counts
+            let keys: Option<Vec<Vec<u8>>> = keys.iter().map(|i: &Instruction| match i {
+                Instruction::Push(_, k) if k.len() == 33 || k.len() == 65 => Some(k.clone()),
+                _ => None
+            }).collect::<Option<Vec<Vec<u8>>>>();
+            if let Some(keys) = keys {
+                if keys.len() == *n as usize && m <= n {
+                    return ScriptType::Multisig(*m as usize, keys);
+                }
+            }
+        }
+        return ScriptType::NonStandard;
+    }
+
+    // P2PK, multisig and OP_RETURN outputs have no address
+    pub fn to_address(&self, network: Network) -> Result<String, ScriptError> {
+        let params = network.bitcoin_params().ok_or(ScriptError::UnsupportedNetwork(network))?;
+        match self.script_type() {
+            ScriptType::P2pkh(h) => return Ok(bitcoin::encode_public_key_hash_address(&h, network)),
+            ScriptType::P2sh(h) => return Ok(bitcoin::encode_script_hash_address(&h, network)),
+            ScriptType::P2wpkh(p) | ScriptType::P2wsh(p) => return Ok(bech32::encode_segwit_address(params.bech32_hrp, 0, &p).expect("segwit")),
+            ScriptType::P2tr(p) => return Ok(bech32::encode_segwit_address(params.bech32_hrp, 1, &p).expect("segwit")),
+            ScriptType::WitnessUnknown(v, p) => return bech32::encode_segwit_address(params.bech32_hrp, v, &p).map_err(|_| ScriptError::NoAddress),
+            _ => return Err(ScriptError::NoAddress),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::*;
+
+    fn script(s: &str) -> Script {
+        return Script::from_hex(s).unwrap();
+    }
+
+    #[test]
+    fn script_opcodes() {
+        for b in 0..=255u8 {
+            let op = Opcode::from_byte(b);
+            assert_eq!(op.to_byte(), b);
+            if !matches!(op, Opcode::PushBytes(_) | Opcode::Invalid(_)) {
+                assert_eq!(Opcode::from_name(&op.name()), Some(op));
+            }
+        }
+        assert_eq!(Opcode::from_name("OP_TRUE"), Some(Opcode::Num(1)));
+        assert_eq!(Opcode::from_name("checksig"), Some(Opcode::CheckSig));
+        assert_eq!(Opcode::from_name("10"), None);
+        assert_eq!(Opcode::from_name("OP_05"), None);
+        assert_eq!(Opcode::from_byte(0xff).name(), "OP_INVALIDOPCODE");
+    }
+
+    #[test]
+    fn script_builder_and_asm() {
+        let p2pkh = Builder::new()
+            .push_opcode(Opcode::Dup)
+            .push_opcode(Opcode::Hash160)
+            .push_slice(&base16::decode_string("751e76e8199196d454941c45d1b3a323f1433bd6"))
+            .push_opcode(Opcode::EqualVerify)
+            .push_opcode(Opcode::CheckSig)
+            .into_script();
+        assert_eq!(p2pkh.to_hex(), "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac");
+        assert_eq!(p2pkh.to_asm().unwrap(), "OP_DUP OP_HASH160 751e76e8199196d454941c45d1b3a323f1433bd6 OP_EQUALVERIFY OP_CHECKSIG");
+        assert_eq!(Script::from_asm(&p2pkh.to_asm().unwrap()).unwrap(), p2pkh);
+
+        let numbers = Builder::new().push_int(0).push_int(-1).push_int(16).push_int(17).push_int(-255).push_int(128).into_script();
+        assert_eq!(numbers.to_hex(), "004f60011102ff80028000");
+
+        let pushdata = script("4c0201024d0100ff");
+        assert_eq!(pushdata.to_asm().unwrap(), "OP_PUSHDATA1 0102 OP_PUSHDATA2 ff");
+        assert_eq!(Script::from_asm("OP_PUSHDATA1 0102 OP_PUSHDATA2 ff").unwrap(), pushdata);
+
+        assert_eq!(script("4c05ab").instructions(), Err(ScriptError::TruncatedPush(0)));
+        assert_eq!(script("76a915ab").instructions(), Err(ScriptError::TruncatedPush(2)));
+        assert_eq!(Script::from_asm("OP_DUP OP_FOO"), Err(ScriptError::InvalidToken(String::from("OP_FOO"))));
+
+        // one-byte pushes stay pushes and do not turn into OP_0 .. OP_16
+        for b in 0..=0x10u8 {
+            let push = Builder::new().push_slice(&[b]).into_script();
+            assert_eq!(push.to_asm().unwrap(), format!("{:02x}", b));
+            assert_eq!(Script::from_asm(&push.to_asm().unwrap()).unwrap(), push);
+        }
+        assert_eq!(Script::from_asm("OP_10 OP_0").unwrap().to_hex(), "5a00");
+    }
+
+    #[test]
+    fn script_templates() {
+        let key = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+        assert_eq!(script(&format!("21{}ac", key)).script_type(), ScriptType::P2pk(base16::decode_string(key)));
+        assert_eq!(script("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").script_type().to_string(), "P2PKH");
+        assert_eq!(script("a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87").script_type().to_string(), "P2SH");
+        assert_eq!(script("0014751e76e8199196d454941c45d1b3a323f1433bd6").script_type().to_string(), "P2WPKH");
+        assert_eq!(script("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262").script_type().to_string(), "P2WSH");
+        assert_eq!(script("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").script_type().to_string(), "P2TR");
+        assert_eq!(script("5210751e76e8199196d454941c45d1b3a323").script_type(), ScriptType::WitnessUnknown(2, base16::decode_string("751e76e8199196d454941c45d1b3a323")));
+        assert_eq!(script(&format!("5121{}21{}52ae", key, key)).script_type().to_string(), "Multisig 1 of 2");
+        // junk between the keys
+        assert_eq!(script(&format!("5221{}21{}0301020352ae", key, key)).script_type(), ScriptType::NonStandard);
+        assert_eq!(script(&format!("5221{}21{}7552ae", key, key)).script_type(), ScriptType::NonStandard);
+        assert_eq!(script(&format!("5321{}21{}52ae", key, key)).script_type(), ScriptType::NonStandard);
+        assert_eq!(script("6a0b68656c6c6f20776f726c64").script_type(), ScriptType::NullData(b"hello world".to_vec()));
+        assert_eq!(script("6a76").script_type(), ScriptType::NonStandard);
+        assert_eq!(script("0015751e76e8199196d454941c45d1b3a323f1433bd6ff").script_type(), ScriptType::NonStandard);
+    }
+
+    // BIP173 and BIP350 examples
+    #[test]
+    fn script_addresses() {
+        let address = |hex: &str, network: Network| script(hex).to_address(network).unwrap();
+
+        assert_eq!(address("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", Network::Bitcoin), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+        assert_eq!(address("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", Network::Testnet), "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r");
+        assert_eq!(address("0014751e76e8199196d454941c45d1b3a323f1433bd6", Network::Bitcoin), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        assert_eq!(address("0014751e76e8199196d454941c45d1b3a323f1433bd6", Network::Regtest), "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080");
+        assert_eq!(address("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262", Network::Testnet), "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7");
+        assert_eq!(address("5210751e76e8199196d454941c45d1b3a323", Network::Bitcoin), "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs");
+        assert_eq!(address("a914f815b036d9bbbce5e9f2a00abd1bf3dc91e9551087", Network::Bitcoin), "3QJmV3qfvL9SuYo34YihAf3sRCW3qSinyC");
+
+        assert_eq!(script("6a0b68656c6c6f20776f726c64").to_address(Network::Bitcoin), Err(ScriptError::NoAddress));
+        assert_eq!(script("0014751e76e8199196d454941c45d1b3a323f1433bd6").to_address(Network::Ethereum), Err(ScriptError::UnsupportedNetwork(Network::Ethereum)));
+    }
+}
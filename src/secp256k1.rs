@@ -17,7 +17,7 @@ impl Point {
         };
     }
     pub fn to_hex_string(&self) -> String {
-        return format!("04{}{}", self.x.to_string(), self.y.to_string());
+        return format!("04{}{}", self.x, self.y);
     }
     pub fn is_zero_point(&self) -> bool {
         return self.x == RD256::from_str("0x0").unwrap() && self.y == RD256::from_str("0x0").unwrap();
@@ -64,7 +64,7 @@ impl SECP256K1 {
         //slope calculation
         let y_diff: &RD256 = &pt1.y.sub_mod(&pt2.y, p);
         let x_diff: &RD256 = &pt1.x.sub_mod(&pt2.x, p);
-        let lambda: &RD256 = &y_diff.div_mod(&x_diff, p);
+        let lambda: &RD256 = &y_diff.div_mod(x_diff, p);
 
         //x3 calculation
         let x3: &RD256 = &lambda.mul_mod(lambda, p).sub_mod(&pt1.x, p).sub_mod(&pt2.x, p);
@@ -94,7 +94,7 @@ impl SECP256K1 {
         //slope calculation
         let two_y: &RD256 = &pt.y.mul_mod(const_2, p);
         let x1_2_3: &RD256 = &pt.x.mul_mod(&pt.x, p).mul_mod(const_3, p);
-        let lambda: &RD256 = &x1_2_3.div_mod(&two_y, p);
+        let lambda: &RD256 = &x1_2_3.div_mod(two_y, p);
 
         //x3 calculation
         let x3: &RD256 = &lambda.mul_mod(lambda, p).sub_mod(&pt.x, p).sub_mod(&pt.x, p);
@@ -133,6 +133,7 @@ impl SECP256K1 {
     }
 }
 
+#[cfg(test)]
 mod tests {
     use crate::secp256k1::*;
 
@@ -0,0 +1,442 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::crypto::hashing;
+use crate::bytes;
+use crate::script::{Builder, Instruction, Opcode, Script};
+use crate::transaction::{Transaction, TxIn, TxOut};
+
+pub const SIGHASH_ANYONECANPAY: u8 = 0x80;
+// BIP341 annexes start with this byte
+pub const ANNEX_TAG: u8 = 0x50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub enum SighashType {
+    // taproot only: ALL, without the trailing byte in the signature
+    Default,
+    All,
+    None,
+    Single,
+    AllPlusAnyoneCanPay,
+    NonePlusAnyoneCanPay,
+    SinglePlusAnyoneCanPay
+}
+
+impl SighashType {
+    pub fn from_u8(b: u8) -> Option<Self> {
+        match b {
+            0x00 => return Some(SighashType::Default),
+            0x01 => return Some(SighashType::All),
+            0x02 => return Some(SighashType::None),
+            0x03 => return Some(SighashType::Single),
+            0x81 => return Some(SighashType::AllPlusAnyoneCanPay),
+            0x82 => return Some(SighashType::NonePlusAnyoneCanPay),
+            0x83 => return Some(SighashType::SinglePlusAnyoneCanPay),
+            _ => return None,
+        };
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            SighashType::Default => return 0x00,
+            SighashType::All => return 0x01,
+            SighashType::None => return 0x02,
+            SighashType::Single => return 0x03,
+            SighashType::AllPlusAnyoneCanPay => return 0x81,
+            SighashType::NonePlusAnyoneCanPay => return 0x82,
+            SighashType::SinglePlusAnyoneCanPay => return 0x83,
+        };
+    }
+
+    pub fn is_anyone_can_pay(&self) -> bool {
+        return self.to_u8() & SIGHASH_ANYONECANPAY != 0;
+    }
+
+    fn is_none(&self) -> bool {
+        return self.to_u8() & 0x1f == 0x02;
+    }
+
+    fn is_single(&self) -> bool {
+        return self.to_u8() & 0x1f == 0x03;
+    }
+}
+
+impl FromStr for SighashType {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<SighashType, Self::Err> {
+        match input.to_uppercase().replace("SIGHASH_", "").as_str() {
+            "DEFAULT" => Ok(SighashType::Default),
+            "ALL" => Ok(SighashType::All),
+            "NONE" => Ok(SighashType::None),
+            "SINGLE" => Ok(SighashType::Single),
+            "ALL|ANYONECANPAY" => Ok(SighashType::AllPlusAnyoneCanPay),
+            "NONE|ANYONECANPAY" => Ok(SighashType::NonePlusAnyoneCanPay),
+            "SINGLE|ANYONECANPAY" => Ok(SighashType::SinglePlusAnyoneCanPay),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for SighashType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SighashType::Default => return write!(f, "DEFAULT"),
+            SighashType::All => return write!(f, "ALL"),
+            SighashType::None => return write!(f, "NONE"),
+            SighashType::Single => return write!(f, "SINGLE"),
+            SighashType::AllPlusAnyoneCanPay => return write!(f, "ALL|ANYONECANPAY"),
+            SighashType::NonePlusAnyoneCanPay => return write!(f, "NONE|ANYONECANPAY"),
+            SighashType::SinglePlusAnyoneCanPay => return write!(f, "SINGLE|ANYONECANPAY"),
+        };
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum SighashError {
+    InputIndex(usize),
+    // the spent outputs given and the number of inputs
+    PrevoutsCount(usize, usize),
+    // taproot SIGHASH_SINGLE needs an output at the input's index
+    SingleWithoutOutput(usize),
+    DefaultNotAllowed,
+    InvalidAnnex
+}
+
+// pre-segwit signature hash; the type is the raw 4-byte value so that nonstandard
+// types hash as they do in consensus
+pub fn legacy_sighash(tx: &Transaction, input_index: usize, script_code: &Script, sighash_type: u32) -> Result<[u8; 32], SighashError> {
+    if input_index >= tx.inputs.len() {
+        return Err(SighashError::InputIndex(input_index));
+    }
+    let base: u32 = sighash_type & 0x1f;
+    let anyone_can_pay: bool = sighash_type & SIGHASH_ANYONECANPAY as u32 != 0;
+
+    // SIGHASH_SINGLE without a matching output signs the number one
+    if base == 0x03 && input_index >= tx.outputs.len() {
+        let mut one: [u8; 32] = [0; 32];
+        one[0] = 1;
+        return Ok(one);
+    }
+
+    let script_code: Script = remove_code_separators(script_code);
+    let mut copy: Transaction = Transaction::new(tx.version, tx.lock_time);
+    for (i, input) in tx.inputs.iter().enumerate() {
+        if anyone_can_pay && i != input_index {
+            continue;
+        }
+        let mut input: TxIn = TxIn::new(input.previous_output);
+        input.sequence = tx.inputs[i].sequence;
+        if i == input_index {
+            input.script_sig = script_code.clone();
+        }
+        // the other inputs' sequences are not signed with NONE and SINGLE
+        else if base == 0x02 || base == 0x03 {
+            input.sequence = 0;
+        }
+        copy.inputs.push(input);
+    }
+
+    copy.outputs = match base {
+        0x02 => vec![],
+        // earlier outputs are blanked to an empty script and value -1
+        0x03 => {
+            let mut outputs: Vec<TxOut> = vec![TxOut { value: u64::MAX, script_pubkey: Script::default() }; input_index];
+            outputs.push(tx.outputs[input_index].clone());
+            outputs
+        },
+        _ => tx.outputs.clone()
+    };
+
+    let mut data: Vec<u8> = copy.serialize_without_witness();
+    data.extend_from_slice(&sighash_type.to_le_bytes());
+    return Ok(hashing::sha256d(&data));
+}
+
+fn remove_code_separators(script: &Script) -> Script {
+    let instructions: Vec<Instruction> = match script.instructions() {
+        Ok(i) => i,
+        Err(_) => return script.clone()
+    };
+    if !instructions.contains(&Instruction::Op(Opcode::CodeSeparator)) {
+        return script.clone();
+    }
+    let mut builder: Builder = Builder::new();
+    for instruction in instructions.iter().filter(|i: &&Instruction| **i != Instruction::Op(Opcode::CodeSeparator)) {
+        builder = builder.push_instruction(instruction);
+    }
+    return builder.into_script();
+}
+
+// the script code BIP143 signs for a P2WPKH program: the matching P2PKH script
+pub fn p2wpkh_script_code(key_hash: &[u8]) -> Script {
+    return Builder::new()
+        .push_opcode(Opcode::Dup)
+        .push_opcode(Opcode::Hash160)
+        .push_slice(key_hash)
+        .push_opcode(Opcode::EqualVerify)
+        .push_opcode(Opcode::CheckSig)
+        .into_script();
+}
+
+fn hash_outpoints(inputs: &[TxIn]) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![];
+    for input in inputs.iter() {
+        data.extend_from_slice(&input.previous_output.txid);
+        data.extend_from_slice(&input.previous_output.vout.to_le_bytes());
+    }
+    return data;
+}
+
+fn hash_sequences(inputs: &[TxIn]) -> Vec<u8> {
+    return inputs.iter().flat_map(|i: &TxIn| i.sequence.to_le_bytes()).collect();
+}
+
+fn hash_outputs(outputs: &[TxOut]) -> Vec<u8> {
+    return outputs.iter().flat_map(|o: &TxOut| o.serialize()).collect();
+}
+
+fn script_with_length(script: &Script) -> Vec<u8> {
+    let mut r: Vec<u8> = bytes::compact_size(script.bytes.len() as u64);
+    r.extend_from_slice(&script.bytes);
+    return r;
+}
+
+// BIP143
+pub fn segwit_v0_sighash(tx: &Transaction, input_index: usize, script_code: &Script, value: u64, sighash_type: SighashType) -> Result<[u8; 32], SighashError> {
+    if sighash_type == SighashType::Default {
+        return Err(SighashError::DefaultNotAllowed);
+    }
+    return segwit_v0_sighash_raw(tx, input_index, script_code, value, sighash_type.to_u8() as u32);
+}
+
+// BIP143 with the raw 4-byte type, which consensus does not restrict to the named ones
+pub fn segwit_v0_sighash_raw(tx: &Transaction, input_index: usize, script_code: &Script, value: u64, sighash_type: u32) -> Result<[u8; 32], SighashError> {
+    if input_index >= tx.inputs.len() {
+        return Err(SighashError::InputIndex(input_index));
+    }
+    let input: &TxIn = &tx.inputs[input_index];
+    let anyone_can_pay: bool = sighash_type & SIGHASH_ANYONECANPAY as u32 != 0;
+    let none: bool = sighash_type & 0x1f == 0x02;
+    let single: bool = sighash_type & 0x1f == 0x03;
+
+    let zero: [u8; 32] = [0; 32];
+    let prevouts: [u8; 32] = if anyone_can_pay { zero } else { hashing::sha256d(&hash_outpoints(&tx.inputs)) };
+    let sequences: [u8; 32] = if anyone_can_pay || none || single { zero } else { hashing::sha256d(&hash_sequences(&tx.inputs)) };
+    let outputs: [u8; 32] = if single {
+        match tx.outputs.get(input_index) {
+            Some(o) => hashing::sha256d(&o.serialize()),
+            None => zero
+        }
+    }
+    else if none {
+        zero
+    }
+    else {
+        hashing::sha256d(&hash_outputs(&tx.outputs))
+    };
+
+    let mut data: Vec<u8> = tx.version.to_le_bytes().to_vec();
+    data.extend_from_slice(&prevouts);
+    data.extend_from_slice(&sequences);
+    data.extend_from_slice(&input.previous_output.txid);
+    data.extend_from_slice(&input.previous_output.vout.to_le_bytes());
+    data.extend(script_with_length(script_code));
+    data.extend_from_slice(&value.to_le_bytes());
+    data.extend_from_slice(&input.sequence.to_le_bytes());
+    data.extend_from_slice(&outputs);
+    data.extend_from_slice(&tx.lock_time.to_le_bytes());
+    data.extend_from_slice(&sighash_type.to_le_bytes());
+    return Ok(hashing::sha256d(&data));
+}
+
+// BIP341; prevouts are the outputs spent by every input, in order. a leaf hash
+// selects the script path of BIP342 with no OP_CODESEPARATOR executed
+pub fn taproot_sighash(tx: &Transaction, input_index: usize, prevouts: &[TxOut], annex: Option<&[u8]>, leaf_hash: Option<[u8; 32]>, sighash_type: SighashType) -> Result<[u8; 32], SighashError> {
+    return taproot_sighash_with_code_separator(tx, input_index, prevouts, annex, leaf_hash, 0xffffffff, sighash_type);
+}
+
+// the script path position is the opcode index of the last OP_CODESEPARATOR executed
+pub fn taproot_sighash_with_code_separator(tx: &Transaction, input_index: usize, prevouts: &[TxOut], annex: Option<&[u8]>, leaf_hash: Option<[u8; 32]>, code_separator_position: u32, sighash_type: SighashType) -> Result<[u8; 32], SighashError> {
+    if input_index >= tx.inputs.len() {
+        return Err(SighashError::InputIndex(input_index));
+    }
+    if prevouts.len() != tx.inputs.len() {
+        return Err(SighashError::PrevoutsCount(prevouts.len(), tx.inputs.len()));
+    }
+    if sighash_type.is_single() && input_index >= tx.outputs.len() {
+        return Err(SighashError::SingleWithoutOutput(input_index));
+    }
+    if annex.is_some_and(|a: &[u8]| a.first() != Some(&ANNEX_TAG)) {
+        return Err(SighashError::InvalidAnnex);
+    }
+    let input: &TxIn = &tx.inputs[input_index];
+    let anyone_can_pay: bool = sighash_type.is_anyone_can_pay();
+
+    // epoch 0
+    let mut data: Vec<u8> = vec![0x00, sighash_type.to_u8()];
+    data.extend_from_slice(&tx.version.to_le_bytes());
+    data.extend_from_slice(&tx.lock_time.to_le_bytes());
+
+    if !anyone_can_pay {
+        let amounts: Vec<u8> = prevouts.iter().flat_map(|o: &TxOut| o.value.to_le_bytes()).collect();
+        let scripts: Vec<u8> = prevouts.iter().flat_map(|o: &TxOut| script_with_length(&o.script_pubkey)).collect();
+        data.extend_from_slice(&hashing::sha256(&hash_outpoints(&tx.inputs)));
+        data.extend_from_slice(&hashing::sha256(&amounts));
+        data.extend_from_slice(&hashing::sha256(&scripts));
+        data.extend_from_slice(&hashing::sha256(&hash_sequences(&tx.inputs)));
+    }
+    if !sighash_type.is_none() && !sighash_type.is_single() {
+        data.extend_from_slice(&hashing::sha256(&hash_outputs(&tx.outputs)));
+    }
+
+    let spend_type: u8 = (leaf_hash.is_some() as u8) * 2 + annex.is_some() as u8;
+    data.push(spend_type);
+    if anyone_can_pay {
+        data.extend_from_slice(&input.previous_output.txid);
+        data.extend_from_slice(&input.previous_output.vout.to_le_bytes());
+        data.extend_from_slice(&prevouts[input_index].value.to_le_bytes());
+        data.extend(script_with_length(&prevouts[input_index].script_pubkey));
+        data.extend_from_slice(&input.sequence.to_le_bytes());
+    }
+    else {
+        data.extend_from_slice(&(input_index as u32).to_le_bytes());
+    }
+    if let Some(a) = annex {
+        let mut annex_data: Vec<u8> = bytes::compact_size(a.len() as u64);
+        annex_data.extend_from_slice(a);
+        data.extend_from_slice(&hashing::sha256(&annex_data));
+    }
+
+    if sighash_type.is_single() {
+        data.extend_from_slice(&hashing::sha256(&tx.outputs[input_index].serialize()));
+    }
+    if let Some(leaf) = leaf_hash {
+        data.extend_from_slice(&leaf);
+        // key version 0
+        data.push(0x00);
+        data.extend_from_slice(&code_separator_position.to_le_bytes());
+    }
+    return Ok(hashing::tagged_hash("TapSighash", &data));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sighash::*;
+    use crate::base16;
+    use crate::bytes::Reader;
+    use crate::taproot;
+
+    fn prevouts(s: &str) -> Vec<TxOut> {
+        let data: Vec<u8> = base16::decode_string(s);
+        let mut reader: Reader = Reader::new(&data);
+        let n: u64 = reader.read_compact_size().unwrap();
+        return (0..n).map(|_| TxOut::deserialize(&mut reader).unwrap()).collect();
+    }
+
+    #[test]
+    fn sighash_types() {
+        for b in [0x00, 0x01, 0x02, 0x03, 0x81, 0x82, 0x83] {
+            let t: SighashType = SighashType::from_u8(b).unwrap();
+            assert_eq!(t.to_u8(), b);
+            assert_eq!(SighashType::from_str(&t.to_string()), Ok(t));
+        }
+        assert_eq!(SighashType::from_u8(0x04), None);
+        assert_eq!(SighashType::from_str("sighash_single|anyonecanpay"), Ok(SighashType::SinglePlusAnyoneCanPay));
+    }
+
+    #[test]
+    fn sighash_legacy_single_bug() {
+        let mut tx: Transaction = Transaction::new(1, 0);
+        tx.inputs = vec![TxIn::new(Default::default()), TxIn::new(Default::default())];
+        tx.outputs = vec![TxOut { value: u64::MAX, script_pubkey: Script::default() }];
+
+        let mut one: [u8; 32] = [0; 32];
+        one[0] = 1;
+        assert_eq!(legacy_sighash(&tx, 1, &Script::default(), 3).unwrap(), one);
+        assert_eq!(legacy_sighash(&tx, 2, &Script::default(), 1), Err(SighashError::InputIndex(2)));
+    }
+
+    // BIP143 native P2WPKH and P2SH-P2WPKH examples
+    #[test]
+    fn sighash_bip143_p2wpkh() {
+        let tx: Transaction = Transaction::from_hex("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        let script_code: Script = p2wpkh_script_code(&base16::decode_string("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1"));
+        assert_eq!(base16::encode_bytes(&segwit_v0_sighash(&tx, 1, &script_code, 600_000_000, SighashType::All).unwrap()), "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+        assert_eq!(base16::encode_bytes(&hashing::sha256d(&hash_outpoints(&tx.inputs))), "96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37");
+        assert_eq!(base16::encode_bytes(&hashing::sha256d(&hash_sequences(&tx.inputs))), "52b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3b");
+        assert_eq!(base16::encode_bytes(&hashing::sha256d(&hash_outputs(&tx.outputs))), "863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e5");
+
+        let tx: Transaction = Transaction::from_hex("0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000").unwrap();
+        let script_code: Script = p2wpkh_script_code(&base16::decode_string("79091972186c449eb1ded22b78e40d009bdf0089"));
+        assert_eq!(base16::encode_bytes(&segwit_v0_sighash(&tx, 0, &script_code, 1_000_000_000, SighashType::All).unwrap()), "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6");
+        assert_eq!(segwit_v0_sighash(&tx, 0, &script_code, 1_000_000_000, SighashType::Default), Err(SighashError::DefaultNotAllowed));
+    }
+
+    // BIP143 P2SH-P2WSH 6-of-6 example, one signature per sighash type
+    #[test]
+    fn sighash_bip143_all_types() {
+        let tx: Transaction = Transaction::from_hex("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000").unwrap();
+        let witness_script: Script = Script::from_hex("56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae").unwrap();
+
+        for (sighash_type, expected) in [
+            (SighashType::All, "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c"),
+            (SighashType::None, "e9733bc60ea13c95c6527066bb975a2ff29a925e80aa14c213f686cbae5d2f36"),
+            (SighashType::Single, "1e1f1c303dc025bd664acb72e583e933fae4cff9148bf78c157d1e8f78530aea"),
+            (SighashType::AllPlusAnyoneCanPay, "2a67f03e63a6a422125878b40b82da593be8d4efaafe88ee528af6e5a9955c6e"),
+            (SighashType::NonePlusAnyoneCanPay, "781ba15f3779d5542ce8ecb5c18716733a5ee42a6f51488ec96154934e2c890a"),
+            (SighashType::SinglePlusAnyoneCanPay, "511e8e52ed574121fc1b654970395502128263f62662e076dc6baf05c2e6a99b")
+        ] {
+            assert_eq!(base16::encode_bytes(&segwit_v0_sighash(&tx, 0, &witness_script, 987_654_321, sighash_type).unwrap()), expected, "{}", sighash_type);
+        }
+    }
+
+    // key path vectors from the Bitcoin Core functional tests, one per sighash type
+    #[test]
+    fn sighash_taproot_key_path() {
+        for (tx, spent, index, sighash_type, expected) in [
+            ("020000000164eb050a5e3da0c2a65e4786f26d753b7bc69691fabccafb11f7acef36641f1846010000003101b2b404392a22000000000017a9147f2bde86fe78bf68a0544a4f290e12f0b7e0a08c87580200000000000017a91425d11723074ecfb96a0a83c3956bfaf362ae0c908758020000000000001600147e20f938993641de67bb0cdd71682aa34c4d29ad5802000000000000160014c64984dc8761acfa99418bd6bedc79b9287d652d72000000",
+             "01365724000000000023542156b39dab4f8f3508e0432cfb41fab110170acaa2d4c42539cb90a4dc7c093bc500", 0, SighashType::Default,
+             "33ca0ebfb4a945eeee9569fc0f5040221275f88690b7f8592ada88ce3bdf6703"),
+            ("0200000002fff49be59befe7566050737910f6ccdc5e749c7f8860ddc140386463d88c5ad0f3000000002cf68eb4a3d67f9d4c079249f7e4f27b8854815cb1ed13842d4fbf395f9e217fd605ee24090100000065235d9203f458520000000000160014b6d48333bb13b4c644e57c43a9a26df3a44b785e58020000000000001976a914eea9461a9e1e3f765d3af3e726162e0229fe3eb688ac58020000000000001976a9143a8869c9f2b5ea1d4ff3aeeb6a8fb2fffb1ad5fe88ac0ad7125c",
+             "02591f220000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece48fb310000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece", 1, SighashType::All,
+             "626ab955d58c9a8a600a0c580549d06dc7da4e802eb2a531f62a588e430967a8"),
+            ("0200000001350005f65aa830ced2079df348e2d8c2bdb4f10e2dde6a161d8a07b40d1ad87dae000000001611d0d603d9dc0e000000000017a914459b6d7d6bbb4d8837b4bf7e9a4556f952da2f5c8758020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88ac58020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88aca71c1f4f",
+             "01c4811000000000002251201bf9297d0a2968ae6693aadd0fa514717afefd218087a239afb7418e2d22e65c", 0, SighashType::AllPlusAnyoneCanPay,
+             "dfa9437f9c9a1d1f9af271f79f2f5482f287cdb0d2e03fa92c8a9b216cc6061c"),
+            ("020000000185bed1a6da2bffbd60ec681a1bfb71c5111d6395b99b3f8b2bf90167111bcb18f5010000007c83ace802ded24a00000000001600142c4698f9f7a773866879755aa78c516fb332af8e5802000000000000160014d38639dfbac4259323b98a472405db0c461b31fa61073747",
+             "0144c84d0000000000225120e3f2107989c88e67296ab2faca930efa2e3a5bd3ff0904835a11c9e807458621", 0, SighashType::None,
+             "3129de36a5d05fff97ffca31eb75fcccbbbc27b3147a7a36a9e4b45d8b625067"),
+            ("eb93dbb901028c8515589dac980b6e7f8e4088b77ed866ca0d6d210a7218b6fd0f6b22dd6d7300000000eb4740a9047efc0e0000000000160014913da2128d8fcf292b3691db0e187414aa1783825802000000000000160014913da2128d8fcf292b3691db0e187414aa178382580200000000000017a9143dd27f01c6f7ef9bb9159937b17f17065ed01a0c875802000000000000160014d7630e19df70ada9905ede1722b800c0005f246641000000",
+             "013fed110000000000225120eb536ae8c33580290630fc495046e998086a64f8f33b93b07967d9029b265c55", 0, SighashType::NonePlusAnyoneCanPay,
+             "2441e8b0e063a2083ee790f14f2045022f07258ddde5ee01de543c9e789d80ae"),
+            ("02000000017836b409a5fed32211407e44b971591f2032053f14701fb5b3a30c0ff382f2cc9c0100000061ac55f60288fb5600000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ac58020000000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ace4000000",
+             "01efa558000000000022512007071ea3dc7e331b0687d0193d1e6d6ed10e645ef36f10ef8831d5e522ac9e80", 0, SighashType::Single,
+             "30239345177cadd0e3ea413d49803580abb6cb27971b481b7788a78d35117a88"),
+            ("0100000001aa6deae89d5e0aaca58714fc76ef6f3c8284224888089232d4e663843ed3ab3eae010000008b6657a60450cb4c0000000000160014a3d42b5413ef0c0701c4702f3cd7d4df222c147058020000000000001976a91430b4ed8723a4ee8992aa2c8814cfe5c3ad0ab9d988ac5802000000000000160014365b1166a6ed0a5e8e9dff17a6d00bbb43454bc758020000000000001976a914bc98c51a84fe7fad5dc380eb8b39586eff47241688ac4f313247",
+             "0107af4e00000000002251202c36d243dfc06cb56a248e62df27ecba7417307511a81ae61aa41c597a929c69", 0, SighashType::SinglePlusAnyoneCanPay,
+             "bf9c83f26c6dd16449e4921f813f551c4218e86f2ec906ca8611175b41b566df")
+        ] {
+            let tx: Transaction = Transaction::from_hex(tx).unwrap();
+            assert_eq!(base16::encode_bytes(&taproot_sighash(&tx, index, &prevouts(spent), None, None, sighash_type).unwrap()), expected, "{}", sighash_type);
+        }
+    }
+
+    #[test]
+    fn sighash_taproot_script_path_and_annex() {
+        let tx: Transaction = Transaction::from_hex("020000000189fc651483f9296b906455dd939813bf086b1bbe7c77635e157c8e14ae29062195010000004445b5c7044561320000000000160014331414dbdada7fb578f700f38fb69995fc9b5ab958020000000000001976a914268db0a8104cc6d8afd91233cc8b3d1ace8ac3ef88ac580200000000000017a914ec00dcb368d6a693e11986d265f659d2f59e8be2875802000000000000160014c715799a49a0bae3956df9c17cb4440a673ac0df6f010000").unwrap();
+        let spent: Vec<TxOut> = prevouts("011bec34000000000022512028055142ea437db73382e991861446040b61dd2185c4891d7daf6893d79f7182");
+        let leaf: [u8; 32] = taproot::tap_leaf_hash(&base16::decode_string("20cc4e1107aea1d170c5ff5b6817e1303010049724fb3caa7941792ea9d29b3e2bacab"), taproot::TAPSCRIPT_LEAF_VERSION);
+        assert_eq!(base16::encode_bytes(&leaf), "15a2530514e399f8b5cf0b3d3112cf5b289eaa3e308ba2071b58392fdc6da68a");
+        assert_eq!(base16::encode_bytes(&taproot_sighash(&tx, 0, &spent, None, Some(leaf), SighashType::All).unwrap()), "d66de5274a60400c7b08c86ba6b7f198f40660079edf53aca89d2a9501317f2e");
+
+        let tx: Transaction = Transaction::from_hex("0200000001df8123752e8f37d132c4e9f1ff7e4f9b986ade9211267e9ebd5fd22a5e718dec6d01000000ce4023b903cb7b23000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787580200000000000017a914afd0d512a2c5c2b40e25669e9cc460303c325b8b87580200000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787f6020000").unwrap();
+        let spent: Vec<TxOut> = prevouts("01ea49260000000000225120ab5e9800806bf18cb246edcf5fe63441208fe955a4b5a35bbff65f5db622a010");
+        let annex: Vec<u8> = base16::decode_string("507b979802e62d397acb29f56743a791894b99372872fc5af06a4f6e8d242d0615cda53062bb20e6ec79756fe39183f0c128adfe85559a8fa042b042c018aa8010143799e44f0893c40e1e");
+        assert_eq!(base16::encode_bytes(&taproot_sighash(&tx, 0, &spent, Some(&annex), None, SighashType::SinglePlusAnyoneCanPay).unwrap()), "3b003000add359a364a156e73e02846782a59d0d95ca8c4638aaad99f2ef915c");
+
+        assert_eq!(taproot_sighash(&tx, 0, &spent, Some(&annex[1..]), None, SighashType::All), Err(SighashError::InvalidAnnex));
+        assert_eq!(taproot_sighash(&tx, 0, &[], None, None, SighashType::All), Err(SighashError::PrevoutsCount(0, 1)));
+    }
+}
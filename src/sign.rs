@@ -0,0 +1,224 @@
+use rand::Rng;
+use secp256k1::{ecdsa, schnorr, KeyPair, Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey};
+use crate::crypto::hashing;
+use crate::script::{Builder, Script, ScriptType};
+use crate::sighash::{self, SighashError, SighashType};
+use crate::taproot;
+use crate::transaction::{Transaction, TxOut};
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum SignError {
+    Sighash(SighashError),
+    InvalidKey,
+    // the key does not control the output spent by this input
+    KeyMismatch(usize),
+    UnsupportedScript(usize)
+}
+
+impl From<SighashError> for SignError {
+    fn from(e: SighashError) -> Self {
+        return SignError::Sighash(e);
+    }
+}
+
+// DER signature followed by the sighash type byte
+pub fn ecdsa_signature(sighash: &[u8; 32], secret_key: &[u8; 32], sighash_type: SighashType) -> Result<Vec<u8>, SignError> {
+    if sighash_type == SighashType::Default {
+        return Err(SignError::Sighash(SighashError::DefaultNotAllowed));
+    }
+    let secp = Secp256k1::signing_only();
+    let key: SecretKey = SecretKey::from_slice(secret_key).map_err(|_| SignError::InvalidKey)?;
+    let message: Message = Message::from_slice(sighash).expect("sighash");
+
+    // RFC6979 nonces, normalised to low S
+    let mut r: Vec<u8> = secp.sign_ecdsa(&message, &key).serialize_der().to_vec();
+    r.push(sighash_type.to_u8());
+    return Ok(r);
+}
+
+pub fn verify_ecdsa_signature(sighash: &[u8; 32], signature: &[u8], public_key: &[u8]) -> bool {
+    let secp = Secp256k1::verification_only();
+    let (der, _) = match signature.split_last() {
+        Some((t, der)) => (der, t),
+        None => return false
+    };
+    return match (ecdsa::Signature::from_der(der), PublicKey::from_slice(public_key)) {
+        (Ok(s), Ok(k)) => secp.verify_ecdsa(&Message::from_slice(sighash).expect("sighash"), &s, &k).is_ok(),
+        _ => false
+    };
+}
+
+// 64 bytes for SIGHASH_DEFAULT, otherwise the type byte is appended
+pub fn schnorr_signature(sighash: &[u8; 32], secret_key: &[u8; 32], sighash_type: SighashType, aux_rand: &[u8; 32]) -> Result<Vec<u8>, SignError> {
+    let secp = Secp256k1::signing_only();
+    let key_pair: KeyPair = KeyPair::from_seckey_slice(&secp, secret_key).map_err(|_| SignError::InvalidKey)?;
+    let message: Message = Message::from_slice(sighash).expect("sighash");
+
+    let mut r: Vec<u8> = secp.sign_schnorr_with_aux_rand(&message, &key_pair, aux_rand).as_ref().to_vec();
+    if sighash_type != SighashType::Default {
+        r.push(sighash_type.to_u8());
+    }
+    return Ok(r);
+}
+
+pub fn verify_schnorr_signature(sighash: &[u8; 32], signature: &[u8], x_only_key: &[u8; 32]) -> bool {
+    let secp = Secp256k1::verification_only();
+    return match (schnorr::Signature::from_slice(&signature[..signature.len().min(64)]), XOnlyPublicKey::from_slice(x_only_key)) {
+        (Ok(s), Ok(k)) => secp.verify_schnorr(&s, &Message::from_slice(sighash).expect("sighash"), &k).is_ok(),
+        _ => false
+    };
+}
+
+// signs the input spending prevouts[input_index], which must be P2PKH, P2WPKH,
+// P2SH-P2WPKH or a BIP86 key-path P2TR output of this key; prevouts lists the
+// output spent by every input, as taproot signs them all
+pub fn sign_input(tx: &mut Transaction, input_index: usize, secret_key: &[u8; 32], prevouts: &[TxOut], sighash_type: SighashType) -> Result<(), SignError> {
+    let mut aux_rand: [u8; 32] = [0; 32];
+    rand::thread_rng().fill(&mut aux_rand[..]);
+    return sign_input_with_aux_rand(tx, input_index, secret_key, prevouts, sighash_type, &aux_rand);
+}
+
+pub fn sign_input_with_aux_rand(tx: &mut Transaction, input_index: usize, secret_key: &[u8; 32], prevouts: &[TxOut], sighash_type: SighashType, aux_rand: &[u8; 32]) -> Result<(), SignError> {
+    if input_index >= tx.inputs.len() {
+        return Err(SignError::Sighash(SighashError::InputIndex(input_index)));
+    }
+    if prevouts.len() != tx.inputs.len() {
+        return Err(SignError::Sighash(SighashError::PrevoutsCount(prevouts.len(), tx.inputs.len())));
+    }
+    let secp = Secp256k1::signing_only();
+    let key: SecretKey = SecretKey::from_slice(secret_key).map_err(|_| SignError::InvalidKey)?;
+    let public_key: PublicKey = PublicKey::from_secret_key(&secp, &key);
+    let compressed: Vec<u8> = public_key.serialize().to_vec();
+    let uncompressed: Vec<u8> = public_key.serialize_uncompressed().to_vec();
+
+    let spent: &TxOut = &prevouts[input_index];
+    let (script_sig, witness): (Script, Vec<Vec<u8>>) = match spent.script_pubkey.script_type() {
+        ScriptType::P2pkh(hash) => {
+            let public: Vec<u8> = if hash == hashing::hash160(&compressed) {
+                compressed
+            }
+            else if hash == hashing::hash160(&uncompressed) {
+                uncompressed
+            }
+            else {
+                return Err(SignError::KeyMismatch(input_index));
+            };
+            let sighash: [u8; 32] = sighash::legacy_sighash(tx, input_index, &spent.script_pubkey, sighash_type.to_u8() as u32)?;
+            let signature: Vec<u8> = ecdsa_signature(&sighash, secret_key, sighash_type)?;
+            (Builder::new().push_slice(&signature).push_slice(&public).into_script(), vec![])
+        },
+        ScriptType::P2wpkh(hash) => {
+            if hash != hashing::hash160(&compressed) {
+                return Err(SignError::KeyMismatch(input_index));
+            }
+            let script_code: Script = sighash::p2wpkh_script_code(&hash);
+            let sighash: [u8; 32] = sighash::segwit_v0_sighash(tx, input_index, &script_code, spent.value, sighash_type)?;
+            (Script::default(), vec![ecdsa_signature(&sighash, secret_key, sighash_type)?, compressed])
+        },
+        ScriptType::P2sh(hash) => {
+            let key_hash: [u8; 20] = hashing::hash160(&compressed);
+            let redeem_script: Script = Builder::new().push_int(0).push_slice(&key_hash).into_script();
+            if hash != hashing::hash160(&redeem_script.bytes) {
+                return Err(SignError::KeyMismatch(input_index));
+            }
+            let script_code: Script = sighash::p2wpkh_script_code(&key_hash);
+            let sighash: [u8; 32] = sighash::segwit_v0_sighash(tx, input_index, &script_code, spent.value, sighash_type)?;
+            (Builder::new().push_slice(&redeem_script.bytes).into_script(), vec![ecdsa_signature(&sighash, secret_key, sighash_type)?, compressed])
+        },
+        ScriptType::P2tr(output_key) => {
+            let tweaked: [u8; 32] = taproot::tweak_private_key(secret_key, None).map_err(|_| SignError::InvalidKey)?;
+            let internal_key: [u8; 32] = public_key.x_only_public_key().0.serialize();
+            let (expected, _) = taproot::tweak_public_key(&internal_key, None).map_err(|_| SignError::InvalidKey)?;
+            if output_key != expected {
+                return Err(SignError::KeyMismatch(input_index));
+            }
+            let sighash: [u8; 32] = sighash::taproot_sighash(tx, input_index, prevouts, None, None, sighash_type)?;
+            (Script::default(), vec![schnorr_signature(&sighash, &tweaked, sighash_type, aux_rand)?])
+        },
+        _ => return Err(SignError::UnsupportedScript(input_index))
+    };
+
+    tx.inputs[input_index].script_sig = script_sig;
+    tx.inputs[input_index].witness = witness;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sign::*;
+    use crate::base16;
+    use crate::script::Instruction;
+    use crate::transaction::TxIn;
+
+    fn array(s: &str) -> [u8; 32] {
+        let mut r: [u8; 32] = [0; 32];
+        r.copy_from_slice(&base16::decode_string(s));
+        return r;
+    }
+
+    fn output(value: u64, script: &str) -> TxOut {
+        return TxOut { value, script_pubkey: Script::from_hex(script).unwrap() };
+    }
+
+    // BIP143 examples: RFC6979 signatures reproduce the published transactions
+    #[test]
+    fn sign_bip143_p2wpkh() {
+        let mut tx: Transaction = Transaction::from_hex("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        let prevouts: Vec<TxOut> = vec![
+            output(625_000_000, "2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac"),
+            output(600_000_000, "00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1")
+        ];
+        sign_input(&mut tx, 1, &array("619c335025c7f4012e556c2a58b2506e30b8511b53ade95ea316fd8c3286feb9"), &prevouts, SighashType::All).unwrap();
+        assert_eq!(base16::encode_bytes(&tx.inputs[1].witness[0]), "304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01");
+        assert_eq!(base16::encode_bytes(&tx.inputs[1].witness[1]), "025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357");
+
+        // the P2PK input is not a template this signer handles
+        assert_eq!(sign_input(&mut tx, 0, &array("bbc27228ddcb9209d7fd6f36b02f7dfa6252af40bb2f1cbc7a557da8027ff866"), &prevouts, SighashType::All), Err(SignError::UnsupportedScript(0)));
+        assert_eq!(sign_input(&mut tx, 1, &array("bbc27228ddcb9209d7fd6f36b02f7dfa6252af40bb2f1cbc7a557da8027ff866"), &prevouts, SighashType::All), Err(SignError::KeyMismatch(1)));
+    }
+
+    #[test]
+    fn sign_bip143_p2sh_p2wpkh() {
+        let mut tx: Transaction = Transaction::from_hex("0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000").unwrap();
+        let prevouts: Vec<TxOut> = vec![output(1_000_000_000, "a9144733f37cf4db86fbc2efed2500b4f4e49f31202387")];
+        sign_input(&mut tx, 0, &array("eb696a065ef48a2192da5b28b694f87544b30fae8327c4510137a922f32c6dcf"), &prevouts, SighashType::All).unwrap();
+        assert_eq!(tx.to_hex(), "01000000000101db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a5477010000001716001479091972186c449eb1ded22b78e40d009bdf0089feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac02473044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5d794d12d482f0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb012103ad1d8e89212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a2687392040000");
+    }
+
+    #[test]
+    fn sign_p2pkh_and_p2tr() {
+        let key: [u8; 32] = array("0000000000000000000000000000000000000000000000000000000000000001");
+        let (output_key, _) = taproot::tweak_public_key(&array("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"), None).unwrap();
+        let mut tx: Transaction = Transaction::new(2, 0);
+        tx.inputs = vec![TxIn::new(Default::default()), TxIn::new(Default::default())];
+        tx.outputs = vec![output(90_000, "0014751e76e8199196d454941c45d1b3a323f1433bd6")];
+        let prevouts: Vec<TxOut> = vec![
+            // 1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH
+            output(50_000, "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"),
+            // BIP86 output of the same key
+            output(50_000, &format!("5120{}", base16::encode_bytes(&output_key)))
+        ];
+
+        sign_input(&mut tx, 0, &key, &prevouts, SighashType::All).unwrap();
+        let pushes: Vec<Instruction> = tx.inputs[0].script_sig.instructions().unwrap();
+        let (signature, public_key) = match (&pushes[0], &pushes[1]) {
+            (Instruction::Push(_, s), Instruction::Push(_, k)) => (s.clone(), k.clone()),
+            _ => panic!("script-sig")
+        };
+        let sighash: [u8; 32] = sighash::legacy_sighash(&tx, 0, &prevouts[0].script_pubkey, 1).unwrap();
+        assert!(verify_ecdsa_signature(&sighash, &signature, &public_key));
+
+        sign_input_with_aux_rand(&mut tx, 1, &key, &prevouts, SighashType::Default, &[0; 32]).unwrap();
+        let signature: &Vec<u8> = &tx.inputs[1].witness[0];
+        assert_eq!(signature.len(), 64);
+        let sighash: [u8; 32] = sighash::taproot_sighash(&tx, 1, &prevouts, None, None, SighashType::Default).unwrap();
+        assert!(verify_schnorr_signature(&sighash, signature, &output_key));
+
+        // the taproot signature commits to every spent output
+        let mut other: Vec<TxOut> = prevouts.clone();
+        other[0].value += 1;
+        let sighash: [u8; 32] = sighash::taproot_sighash(&tx, 1, &other, None, None, SighashType::Default).unwrap();
+        assert!(!verify_schnorr_signature(&sighash, signature, &output_key));
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fmt;
+use secp256k1::{Parity, PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey};
+use crate::app::Network;
+use crate::bech32::{self, Bech32Error, Variant};
+use crate::crypto::hashing;
+use crate::transaction::{OutPoint, TxIn};
+
+// BIP352 limits the outputs to one scan key in a transaction, so that scanning stays bounded
+pub const MAX_OUTPUTS_PER_GROUP: u32 = 2323;
+const MAX_ADDRESS_LENGTH: usize = 1023;
+// the taproot internal key with no known private key; script-path spends from it are not eligible
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0
+];
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum SilentPaymentError {
+    Bech32(Bech32Error),
+    InvalidHrp,
+    InvalidVersion(u8),
+    InvalidLength(usize),
+    InvalidKey,
+    // none of the inputs carries a public key the receiver could find
+    NoEligibleInputs,
+    // the input keys cancel out
+    ZeroInputSum,
+    TooManyRecipients(usize)
+}
+
+impl From<Bech32Error> for SilentPaymentError {
+    fn from(e: Bech32Error) -> Self {
+        return SilentPaymentError::Bech32(e);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub struct SilentPaymentAddress {
+    pub scan_key: PublicKey,
+    // the spend key, with the label already added for a labelled address
+    pub spend_key: PublicKey,
+    pub network: Network
+}
+
+impl SilentPaymentAddress {
+    pub fn encode(&self) -> String {
+        let mut keys: Vec<u8> = self.scan_key.serialize().to_vec();
+        keys.extend_from_slice(&self.spend_key.serialize());
+        let mut data: Vec<u8> = vec![0];
+        data.extend(bech32::convert_bits(&keys, 8, 5, true).expect("keys"));
+        let hrp: &str = self.network.bitcoin_params().expect("network").silent_payment_hrp;
+        return bech32::encode_with_limit(hrp, &data, Variant::Bech32m, MAX_ADDRESS_LENGTH).expect("silent-payment-address");
+    }
+
+    pub fn decode(address: &str) -> Result<Self, SilentPaymentError> {
+        let (hrp, data, variant) = bech32::decode_with_limit(address, MAX_ADDRESS_LENGTH)?;
+        let network: Network = match hrp.as_str() {
+            "sp" => Network::Bitcoin,
+            "tsp" => Network::Testnet,
+            "sprt" => Network::Regtest,
+            _ => return Err(SilentPaymentError::InvalidHrp)
+        };
+        if data.is_empty() {
+            return Err(SilentPaymentError::InvalidLength(0));
+        }
+        if variant != Variant::Bech32m {
+            return Err(Bech32Error::InvalidVariant.into());
+        }
+        // later versions may append data, version 31 is reserved for an incompatible change
+        let keys: Vec<u8> = bech32::convert_bits(&data[1..], 5, 8, false)?;
+        if data[0] == 31 {
+            return Err(SilentPaymentError::InvalidVersion(data[0]));
+        }
+        if keys.len() < 66 || (data[0] == 0 && keys.len() != 66) {
+            return Err(SilentPaymentError::InvalidLength(keys.len()));
+        }
+        return Ok(Self {
+            scan_key: PublicKey::from_slice(&keys[..33]).map_err(|_| SilentPaymentError::InvalidKey)?,
+            spend_key: PublicKey::from_slice(&keys[33..66]).map_err(|_| SilentPaymentError::InvalidKey)?,
+            network
+        });
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.encode());
+    }
+}
+
+fn scalar(bytes: [u8; 32]) -> Result<Scalar, SilentPaymentError> {
+    return Scalar::from_be_bytes(bytes).map_err(|_| SilentPaymentError::InvalidKey);
+}
+
+fn secret_key(bytes: &[u8; 32]) -> Result<SecretKey, SilentPaymentError> {
+    return SecretKey::from_slice(bytes).map_err(|_| SilentPaymentError::InvalidKey);
+}
+
+// label m (0 is kept for change) adds hash(b_scan || m)·G to the spend key
+pub fn label_tweak(scan_secret: &[u8; 32], m: u32) -> [u8; 32] {
+    let mut data: Vec<u8> = scan_secret.to_vec();
+    data.extend_from_slice(&m.to_be_bytes());
+    return hashing::tagged_hash("BIP0352/Label", &data);
+}
+
+// the public key a receiver finds in an input, or None if the input does not count
+pub fn input_public_key(input: &TxIn, script_pubkey: &[u8]) -> Option<PublicKey> {
+    let compressed = |key: &[u8]| -> Option<PublicKey> {
+        if key.len() != 33 {
+            return None;
+        }
+        return PublicKey::from_slice(key).ok();
+    };
+    match script_pubkey {
+        // P2TR: the output key, unless a script path reveals the NUMS point as internal key
+        [0x51, 0x20, key @ ..] if key.len() == 32 => {
+            let mut witness: &[Vec<u8>] = &input.witness;
+            if witness.len() > 1 && witness[witness.len() - 1].first() == Some(&0x50) {
+                witness = &witness[..witness.len() - 1];
+            }
+            if witness.len() > 1 && witness[witness.len() - 1].get(1..33) == Some(&NUMS_H[..]) {
+                return None;
+            }
+            let x_only: XOnlyPublicKey = XOnlyPublicKey::from_slice(key).ok()?;
+            return Some(PublicKey::from_x_only_public_key(x_only, Parity::Even));
+        },
+        [0x00, 0x14, _hash @ ..] if script_pubkey.len() == 22 => return compressed(input.witness.last()?),
+        // only P2SH-P2WPKH among P2SH outputs
+        [0xa9, 0x14, .., 0x87] if script_pubkey.len() == 23 => {
+            let script_sig: &[u8] = &input.script_sig.bytes;
+            if script_sig.len() != 23 || script_sig[..3] != [0x16, 0x00, 0x14] {
+                return None;
+            }
+            return compressed(input.witness.last()?);
+        },
+        // P2PKH: the last 33 bytes of the scriptSig that hash to the key hash, however the script was malleated
+        [0x76, 0xa9, 0x14, hash @ .., 0x88, 0xac] if script_pubkey.len() == 25 => {
+            let script_sig: &[u8] = &input.script_sig.bytes;
+            return (33..=script_sig.len()).rev()
+                .map(|end: usize| &script_sig[end - 33..end])
+                .find(|key: &&[u8]| hashing::hash160(key)[..] == hash[..])
+                .and_then(compressed);
+        },
+        _ => return None,
+    };
+}
+
+// hash(outpoint_L || A), outpoint_L being the smallest serialized outpoint of all inputs
+fn input_hash(outpoints: &[OutPoint], sum: &PublicKey) -> Result<Scalar, SilentPaymentError> {
+    let smallest: Vec<u8> = outpoints.iter().map(|o: &OutPoint| {
+        let mut r: Vec<u8> = o.txid.to_vec();
+        r.extend_from_slice(&o.vout.to_le_bytes());
+        r
+    }).min().ok_or(SilentPaymentError::NoEligibleInputs)?;
+    let mut data: Vec<u8> = smallest;
+    data.extend_from_slice(&sum.serialize());
+    return scalar(hashing::tagged_hash("BIP0352/Inputs", &data));
+}
+
+fn shared_secret_tweak(shared_secret: &PublicKey, k: u32) -> [u8; 32] {
+    let mut data: Vec<u8> = shared_secret.serialize().to_vec();
+    data.extend_from_slice(&k.to_be_bytes());
+    return hashing::tagged_hash("BIP0352/SharedSecret", &data);
+}
+
+// a private key of an eligible input; taproot keys are used with the even y of the output key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+pub struct InputKey {
+    pub secret_key: [u8; 32],
+    pub taproot: bool
+}
+
+// the x-only output keys paying each recipient, in the order given; outpoints are those of every input
+pub fn sender_outputs(outpoints: &[OutPoint], keys: &[InputKey], recipients: &[SilentPaymentAddress]) -> Result<Vec<[u8; 32]>, SilentPaymentError> {
+    let secp = Secp256k1::new();
+    if keys.is_empty() {
+        return Err(SilentPaymentError::NoEligibleInputs);
+    }
+    // partial sums may cancel out, only the total has to be non-zero
+    let mut sum: Option<SecretKey> = None;
+    for key in keys.iter() {
+        let mut k: SecretKey = secret_key(&key.secret_key)?;
+        if key.taproot && k.public_key(&secp).x_only_public_key().1 == Parity::Odd {
+            k = k.negate();
+        }
+        sum = match sum {
+            Some(s) => s.add_tweak(&Scalar::from(k)).ok(),
+            None => Some(k)
+        };
+    }
+    let sum: SecretKey = sum.ok_or(SilentPaymentError::ZeroInputSum)?;
+    let tweaked: SecretKey = sum.mul_tweak(&input_hash(outpoints, &sum.public_key(&secp))?).expect("input-hash");
+
+    let mut counts: HashMap<[u8; 33], u32> = HashMap::new();
+    let mut outputs: Vec<[u8; 32]> = vec![];
+    for recipient in recipients.iter() {
+        let k: &mut u32 = counts.entry(recipient.scan_key.serialize()).or_insert(0);
+        if *k >= MAX_OUTPUTS_PER_GROUP {
+            return Err(SilentPaymentError::TooManyRecipients(*k as usize + 1));
+        }
+        let shared_secret: PublicKey = recipient.scan_key.mul_tweak(&secp, &Scalar::from(tweaked)).expect("shared-secret");
+        let t: Scalar = scalar(shared_secret_tweak(&shared_secret, *k))?;
+        let output: PublicKey = recipient.spend_key.add_exp_tweak(&secp, &t).map_err(|_| SilentPaymentError::InvalidKey)?;
+        outputs.push(output.x_only_public_key().0.serialize());
+        *k += 1;
+    }
+    return Ok(outputs);
+}
+
+// input_hash·A, all a receiver needs from the inputs; light clients may get it from a server instead
+pub fn public_tweak(outpoints: &[OutPoint], input_keys: &[PublicKey]) -> Result<PublicKey, SilentPaymentError> {
+    if input_keys.is_empty() {
+        return Err(SilentPaymentError::NoEligibleInputs);
+    }
+    let keys: Vec<&PublicKey> = input_keys.iter().collect();
+    let sum: PublicKey = PublicKey::combine_keys(&keys).map_err(|_| SilentPaymentError::ZeroInputSum)?;
+    return Ok(sum.mul_tweak(&Secp256k1::verification_only(), &input_hash(outpoints, &sum)?).expect("input-hash"));
+}
+
+// the same from a transaction's inputs and the scriptPubKeys they spend
+pub fn transaction_tweak(inputs: &[TxIn], prevout_scripts: &[Vec<u8>]) -> Result<PublicKey, SilentPaymentError> {
+    let keys: Vec<PublicKey> = inputs.iter().zip(prevout_scripts.iter())
+        .filter_map(|(input, script)| input_public_key(input, script))
+        .collect();
+    let outpoints: Vec<OutPoint> = inputs.iter().map(|i: &TxIn| i.previous_output).collect();
+    return public_tweak(&outpoints, &keys);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct FoundOutput {
+    pub output_key: [u8; 32],
+    // added to the spend private key to spend the output
+    pub tweak: [u8; 32],
+    pub label: Option<u32>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct Receiver {
+    pub scan_secret: [u8; 32],
+    pub spend_key: PublicKey,
+    pub network: Network,
+    // m, the label point and its tweak
+    labels: Vec<(u32, PublicKey, [u8; 32])>
+}
+
+impl Receiver {
+    pub fn new(scan_secret: &[u8; 32], spend_key: &PublicKey, network: Network) -> Result<Self, SilentPaymentError> {
+        secret_key(scan_secret)?;
+        return Ok(Self { scan_secret: *scan_secret, spend_key: *spend_key, network, labels: vec![] });
+    }
+
+    pub fn add_label(&mut self, m: u32) -> Result<(), SilentPaymentError> {
+        let tweak: [u8; 32] = label_tweak(&self.scan_secret, m);
+        let point: PublicKey = secret_key(&tweak)?.public_key(&Secp256k1::signing_only());
+        self.labels.push((m, point, tweak));
+        return Ok(());
+    }
+
+    pub fn address(&self) -> SilentPaymentAddress {
+        let scan_key: PublicKey = secret_key(&self.scan_secret).expect("scan-key").public_key(&Secp256k1::signing_only());
+        return SilentPaymentAddress { scan_key, spend_key: self.spend_key, network: self.network };
+    }
+
+    pub fn labelled_address(&self, m: u32) -> Result<SilentPaymentAddress, SilentPaymentError> {
+        let tweak: Scalar = scalar(label_tweak(&self.scan_secret, m))?;
+        let spend_key: PublicKey = self.spend_key.add_exp_tweak(&Secp256k1::verification_only(), &tweak).map_err(|_| SilentPaymentError::InvalidKey)?;
+        return Ok(SilentPaymentAddress { spend_key, ..self.address() });
+    }
+
+    pub fn shared_secret(&self, tweak: &PublicKey) -> PublicKey {
+        return tweak.mul_tweak(&Secp256k1::verification_only(), &scalar(self.scan_secret).expect("scan-key")).expect("shared-secret");
+    }
+
+    // finds the x-only taproot outputs paying this receiver, trying k = 0, 1, ... until one is missing
+    pub fn scan(&self, tweak: &PublicKey, outputs: &[[u8; 32]]) -> Vec<FoundOutput> {
+        let secp = Secp256k1::verification_only();
+        let shared_secret: PublicKey = self.shared_secret(tweak);
+        let mut remaining: Vec<[u8; 32]> = outputs.to_vec();
+        let mut found: Vec<FoundOutput> = vec![];
+        for k in 0..MAX_OUTPUTS_PER_GROUP {
+            let t: [u8; 32] = shared_secret_tweak(&shared_secret, k);
+            let base: PublicKey = match scalar(t).ok().and_then(|s: Scalar| self.spend_key.add_exp_tweak(&secp, &s).ok()) {
+                Some(p) => p,
+                None => break
+            };
+            // unlabelled first, then P_k plus each label point
+            let mut candidates: Vec<(PublicKey, [u8; 32], Option<u32>)> = vec![(base, t, None)];
+            for (m, point, label) in self.labels.iter() {
+                let sum: Option<SecretKey> = SecretKey::from_slice(&t).ok().and_then(|s: SecretKey| s.add_tweak(&scalar(*label).ok()?).ok());
+                if let (Ok(p), Some(s)) = (base.combine(point), sum) {
+                    candidates.push((p, s.secret_bytes(), Some(*m)));
+                }
+            }
+            let matched: Option<(usize, [u8; 32], Option<u32>)> = candidates.iter().find_map(|(p, tweak, label)| {
+                let x_only: [u8; 32] = p.x_only_public_key().0.serialize();
+                return remaining.iter().position(|o: &[u8; 32]| *o == x_only).map(|i: usize| (i, *tweak, *label));
+            });
+            match matched {
+                Some((i, tweak, label)) => found.push(FoundOutput { output_key: remaining.remove(i), tweak, label }),
+                None => break
+            };
+        }
+        return found;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::silentpayments::*;
+    use crate::base16;
+
+    #[test]
+    fn silentpayments_address_encoding() {
+        let address: &str = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+        let decoded: SilentPaymentAddress = SilentPaymentAddress::decode(address).unwrap();
+        assert_eq!(base16::encode_bytes(&decoded.scan_key.serialize()), "0220bcfac5b99e04ad1a06ddfb016ee13582609d60b6291e98d01a9bc9a16c96d4");
+        assert_eq!(base16::encode_bytes(&decoded.spend_key.serialize()), "025cc9856d6f8375350e123978daac200c260cb5b5ae83106cab90484dcd8fcf36");
+        assert_eq!(decoded.network, Network::Bitcoin);
+        assert_eq!(decoded.encode(), address);
+        assert_eq!(SilentPaymentAddress { network: Network::Regtest, ..decoded }.encode()[..5], *"sprt1");
+
+        // a later version may carry more data, version 31 may not be read at all
+        let mut keys: Vec<u8> = decoded.scan_key.serialize().to_vec();
+        keys.extend_from_slice(&decoded.spend_key.serialize());
+        keys.extend_from_slice(&[0xab; 10]);
+        let mut data: Vec<u8> = vec![1];
+        data.extend(bech32::convert_bits(&keys, 8, 5, true).unwrap());
+        let longer: String = bech32::encode_with_limit("sp", &data, Variant::Bech32m, 1023).unwrap();
+        assert_eq!(SilentPaymentAddress::decode(&longer), Ok(decoded));
+        data[0] = 0;
+        assert_eq!(SilentPaymentAddress::decode(&bech32::encode_with_limit("sp", &data, Variant::Bech32m, 1023).unwrap()), Err(SilentPaymentError::InvalidLength(76)));
+        data[0] = 31;
+        assert_eq!(SilentPaymentAddress::decode(&bech32::encode_with_limit("sp", &data, Variant::Bech32m, 1023).unwrap()), Err(SilentPaymentError::InvalidVersion(31)));
+        assert_eq!(SilentPaymentAddress::decode(&address.replace("sp1", "bc1")), Err(SilentPaymentError::Bech32(Bech32Error::InvalidChecksum)));
+    }
+
+    #[test]
+    fn silentpayments_recipient_limit() {
+        let receiver: Receiver = Receiver::new(&[1; 32], &SecretKey::from_slice(&[2; 32]).unwrap().public_key(&Secp256k1::signing_only()), Network::Bitcoin).unwrap();
+        let recipients: Vec<SilentPaymentAddress> = vec![receiver.address(); MAX_OUTPUTS_PER_GROUP as usize + 1];
+        let keys: [InputKey; 1] = [InputKey { secret_key: [3; 32], taproot: false }];
+        let outpoints: [OutPoint; 1] = [OutPoint::new([4; 32], 0)];
+        assert_eq!(sender_outputs(&outpoints, &keys, &recipients), Err(SilentPaymentError::TooManyRecipients(2324)));
+        assert_eq!(sender_outputs(&outpoints, &keys, &recipients[..3]).unwrap().len(), 3);
+        assert_eq!(sender_outputs(&outpoints, &[], &recipients[..1]), Err(SilentPaymentError::NoEligibleInputs));
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::BTreeMap;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use pbkdf2::pbkdf2_hmac;
+use rand::Rng;
+
+const WORDLIST: &str = include_str!("wordlists/slip39.txt");
+
+const RADIX_BITS: usize = 10;
+const ID_BITS: usize = 15;
+const CHECKSUM_WORDS: usize = 3;
+const METADATA_WORDS: usize = 7;
+const MIN_MNEMONIC_WORDS: usize = 20;
+const MIN_SECRET_BYTES: usize = 16;
+const MAX_SHARE_COUNT: u8 = 16;
+
+const BASE_ITERATION_COUNT: u32 = 10000;
+const ROUND_COUNT: u8 = 4;
+
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+const DIGEST_LENGTH: usize = 4;
+
+const CUSTOMIZATION: &[u8] = b"shamir";
+const CUSTOMIZATION_EXTENDABLE: &[u8] = b"shamir_extendable";
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum Slip39Error {
+    MnemonicLength(usize),
+    UnknownWord(String),
+    Checksum,
+    Padding,
+    SecretLength(usize),
+    Passphrase,
+    Threshold,
+    MismatchedShares,
+    DuplicateMemberIndex,
+    InsufficientShares,
+    Digest
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct Share {
+    pub identifier: u16,
+    pub extendable: bool,
+    pub iteration_exponent: u8,
+    pub group_index: u8,
+    pub group_threshold: u8,
+    pub group_count: u8,
+    pub member_index: u8,
+    pub member_threshold: u8,
+    pub value: Vec<u8>
+}
+
+pub fn wordlist() -> Vec<&'static str> {
+    return WORDLIST.lines().collect::<Vec<&str>>();
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    if extendable {
+        return CUSTOMIZATION_EXTENDABLE;
+    }
+    return CUSTOMIZATION;
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+    (0..count).rev().for_each(|i| bits.push((value >> i) & 1 == 1));
+}
+
+fn read_bits(bits: &[bool]) -> u32 {
+    return bits.iter().fold(0u32, |acc, bit| (acc << 1) | *bit as u32);
+}
+
+// reed-solomon code over GF(1024), the 30-bit checksum of every share
+fn rs1024_polymod(values: &[u32]) -> u32 {
+    const GEN: [u32; 10] = [
+        0xE0E040, 0x1C1C080, 0x3838100, 0x7070200, 0xE0E0009,
+        0x1C0C2412, 0x38086C24, 0x3090FC48, 0x21B1F890, 0x3F3F120
+    ];
+
+    let mut chk: u32 = 1;
+    for v in values.iter() {
+        let b: u32 = chk >> 20;
+        chk = ((chk & 0xFFFFF) << 10) ^ v;
+        for (i, g) in GEN.iter().enumerate() {
+            if (b >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    return chk;
+}
+
+fn rs1024_create_checksum(custom: &[u8], data: &[u32]) -> Vec<u32> {
+    let mut values: Vec<u32> = custom.iter().map(|c: &u8| *c as u32).collect::<Vec<u32>>();
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0; CHECKSUM_WORDS]);
+
+    let polymod: u32 = rs1024_polymod(&values) ^ 1;
+    return (0..CHECKSUM_WORDS).rev().map(|i: usize| (polymod >> (10 * i)) & 1023).collect::<Vec<u32>>();
+}
+
+fn rs1024_verify_checksum(custom: &[u8], data: &[u32]) -> bool {
+    let mut values: Vec<u32> = custom.iter().map(|c: &u8| *c as u32).collect::<Vec<u32>>();
+    values.extend_from_slice(data);
+    return rs1024_polymod(&values) == 1;
+}
+
+impl Share {
+    pub fn from_mnemonic(mnemonic: &str) -> Result<Share, Slip39Error> {
+        let words: Vec<&str> = wordlist();
+        let phrase: Vec<&str> = mnemonic.split_whitespace().collect::<Vec<&str>>();
+
+        let mut indices: Vec<u32> = vec![];
+        for word in phrase.iter() {
+            match words.binary_search(&word.to_lowercase().as_str()) {
+                Ok(i) => indices.push(i as u32),
+                Err(_) => return Err(Slip39Error::UnknownWord(word.to_string()))
+            };
+        }
+
+        // the share value is padded to a multiple of 10 bits with at most 8 zero bits
+        let padding: usize = (RADIX_BITS * (indices.len().max(METADATA_WORDS) - METADATA_WORDS)) % 16;
+        if indices.len() < MIN_MNEMONIC_WORDS || padding > 8 {
+            return Err(Slip39Error::MnemonicLength(indices.len()));
+        }
+
+        let extendable: bool = (indices[1] >> 4) & 1 == 1;
+        if !rs1024_verify_checksum(customization(extendable), &indices) {
+            return Err(Slip39Error::Checksum);
+        }
+
+        let mut bits: Vec<bool> = vec![];
+        indices[..indices.len() - CHECKSUM_WORDS].iter().for_each(|i: &u32| push_bits(&mut bits, *i, RADIX_BITS));
+
+        let share = Share {
+            identifier: read_bits(&bits[0..15]) as u16,
+            extendable,
+            iteration_exponent: read_bits(&bits[16..20]) as u8,
+            group_index: read_bits(&bits[20..24]) as u8,
+            group_threshold: read_bits(&bits[24..28]) as u8 + 1,
+            group_count: read_bits(&bits[28..32]) as u8 + 1,
+            member_index: read_bits(&bits[32..36]) as u8,
+            member_threshold: read_bits(&bits[36..40]) as u8 + 1,
+            value: bits[40 + padding..].chunks(8).map(|b: &[bool]| read_bits(b) as u8).collect::<Vec<u8>>()
+        };
+
+        if bits[40..40 + padding].iter().any(|b: &bool| *b) {
+            return Err(Slip39Error::Padding);
+        }
+        if share.value.len() < MIN_SECRET_BYTES || !share.value.len().is_multiple_of(2) {
+            return Err(Slip39Error::SecretLength(share.value.len()));
+        }
+        if share.group_count < share.group_threshold {
+            return Err(Slip39Error::Threshold);
+        }
+        return Ok(share);
+    }
+
+    pub fn to_mnemonic(&self) -> String {
+        let words: Vec<&str> = wordlist();
+
+        let mut bits: Vec<bool> = vec![];
+        push_bits(&mut bits, self.identifier as u32, ID_BITS);
+        push_bits(&mut bits, self.extendable as u32, 1);
+        push_bits(&mut bits, self.iteration_exponent as u32, 4);
+        push_bits(&mut bits, self.group_index as u32, 4);
+        push_bits(&mut bits, self.group_threshold as u32 - 1, 4);
+        push_bits(&mut bits, self.group_count as u32 - 1, 4);
+        push_bits(&mut bits, self.member_index as u32, 4);
+        push_bits(&mut bits, self.member_threshold as u32 - 1, 4);
+
+        let padding: usize = (RADIX_BITS - (self.value.len() * 8) % RADIX_BITS) % RADIX_BITS;
+        push_bits(&mut bits, 0, padding);
+        self.value.iter().for_each(|b: &u8| push_bits(&mut bits, *b as u32, 8));
+
+        let mut indices: Vec<u32> = bits.chunks(RADIX_BITS).map(read_bits).collect::<Vec<u32>>();
+        let check_sum: Vec<u32> = rs1024_create_checksum(customization(self.extendable), &indices);
+        indices.extend(check_sum);
+
+        return indices.iter().map(|i: &u32| words[*i as usize]).collect::<Vec<&str>>().join(" ");
+    }
+
+    fn common_parameters(&self) -> (u16, bool, u8, u8, u8) {
+        return (self.identifier, self.extendable, self.iteration_exponent, self.group_threshold, self.group_count);
+    }
+}
+
+// GF(256) with the Rijndael polynomial x^8 + x^4 + x^3 + x + 1, generator 3
+fn gf256_tables() -> ([u8; 255], [u8; 256]) {
+    let mut exp: [u8; 255] = [0; 255];
+    let mut log: [u8; 256] = [0; 256];
+
+    let mut poly: u16 = 1;
+    for (i, e) in exp.iter_mut().enumerate() {
+        *e = poly as u8;
+        log[poly as usize] = i as u8;
+
+        // multiply poly by the generator x + 1
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11B;
+        }
+    }
+    return (exp, log);
+}
+
+fn interpolate(shares: &[(u8, Vec<u8>)], x: u8) -> Vec<u8> {
+    if let Some((_, value)) = shares.iter().find(|(sx, _)| *sx == x) {
+        return value.clone();
+    }
+
+    let (exp, log) = gf256_tables();
+    let log_prod: u32 = shares.iter().map(|(sx, _)| log[(sx ^ x) as usize] as u32).sum::<u32>();
+
+    let mut r: Vec<u8> = vec![0; shares[0].1.len()];
+    for (sx, value) in shares.iter() {
+        let others: u32 = shares.iter().filter(|(ox, _)| ox != sx).map(|(ox, _)| log[(sx ^ ox) as usize] as u32).sum::<u32>();
+        let log_basis: u32 = (log_prod + 255 * 16 - log[(sx ^ x) as usize] as u32 - others % 255) % 255;
+
+        for (out, v) in r.iter_mut().zip(value.iter()) {
+            if *v != 0 {
+                *out ^= exp[((log[*v as usize] as u32 + log_basis) % 255) as usize];
+            }
+        }
+    }
+    return r;
+}
+
+fn share_digest(random_part: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(random_part).expect("hmac-key");
+    mac.update(secret);
+    return mac.finalize().into_bytes()[..DIGEST_LENGTH].to_vec();
+}
+
+fn random_bytes(length: usize) -> Vec<u8> {
+    let mut r: Vec<u8> = vec![0; length];
+    rand::thread_rng().fill(&mut r[..]);
+    return r;
+}
+
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Result<Vec<(u8, Vec<u8>)>, Slip39Error> {
+    if threshold == 0 || threshold > count || count > MAX_SHARE_COUNT {
+        return Err(Slip39Error::Threshold);
+    }
+    if threshold == 1 {
+        return Ok((0..count).map(|i: u8| (i, secret.to_vec())).collect::<Vec<(u8, Vec<u8>)>>());
+    }
+
+    // threshold - 2 random shares plus the digest and secret points fix the polynomial
+    let random_count: u8 = threshold - 2;
+    let mut shares: Vec<(u8, Vec<u8>)> = (0..random_count).map(|i: u8| (i, random_bytes(secret.len()))).collect::<Vec<(u8, Vec<u8>)>>();
+
+    let random_part: Vec<u8> = random_bytes(secret.len() - DIGEST_LENGTH);
+    let mut digest: Vec<u8> = share_digest(&random_part, secret);
+    digest.extend_from_slice(&random_part);
+
+    let mut base: Vec<(u8, Vec<u8>)> = shares.clone();
+    base.push((DIGEST_INDEX, digest));
+    base.push((SECRET_INDEX, secret.to_vec()));
+
+    for i in random_count..count {
+        shares.push((i, interpolate(&base, i)));
+    }
+    return Ok(shares);
+}
+
+fn recover_secret(threshold: u8, shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, Slip39Error> {
+    if threshold == 1 {
+        return Ok(shares[0].1.clone());
+    }
+
+    let secret: Vec<u8> = interpolate(shares, SECRET_INDEX);
+    let digest: Vec<u8> = interpolate(shares, DIGEST_INDEX);
+
+    if digest[..DIGEST_LENGTH] != share_digest(&digest[DIGEST_LENGTH..], &secret)[..] {
+        return Err(Slip39Error::Digest);
+    }
+    return Ok(secret);
+}
+
+// four-round feistel network keyed by pbkdf2-hmac-sha256 of the passphrase
+fn feistel(input: &[u8], passphrase: &str, iteration_exponent: u8, identifier: u16, extendable: bool, rounds: Vec<u8>) -> Result<Vec<u8>, Slip39Error> {
+    if passphrase.chars().any(|c: char| !(' '..='~').contains(&c)) {
+        return Err(Slip39Error::Passphrase);
+    }
+
+    let mut salt: Vec<u8> = vec![];
+    if !extendable {
+        salt.extend_from_slice(CUSTOMIZATION);
+        salt.extend_from_slice(&identifier.to_be_bytes());
+    }
+    let iterations: u32 = (BASE_ITERATION_COUNT / ROUND_COUNT as u32) << iteration_exponent;
+
+    let mut l: Vec<u8> = input[..input.len() / 2].to_vec();
+    let mut r: Vec<u8> = input[input.len() / 2..].to_vec();
+
+    for i in rounds {
+        let mut password: Vec<u8> = vec![i];
+        password.extend_from_slice(passphrase.as_bytes());
+        let mut round_salt: Vec<u8> = salt.clone();
+        round_salt.extend_from_slice(&r);
+
+        let mut f: Vec<u8> = vec![0; r.len()];
+        pbkdf2_hmac::<Sha256>(&password, &round_salt, iterations, &mut f);
+
+        let next: Vec<u8> = l.iter().zip(f.iter()).map(|(a, b)| a ^ b).collect::<Vec<u8>>();
+        l = r;
+        r = next;
+    }
+
+    r.extend_from_slice(&l);
+    return Ok(r);
+}
+
+pub fn encrypt(master_secret: &[u8], passphrase: &str, iteration_exponent: u8, identifier: u16, extendable: bool) -> Result<Vec<u8>, Slip39Error> {
+    return feistel(master_secret, passphrase, iteration_exponent, identifier, extendable, (0..ROUND_COUNT).collect::<Vec<u8>>());
+}
+
+pub fn decrypt(encrypted: &[u8], passphrase: &str, iteration_exponent: u8, identifier: u16, extendable: bool) -> Result<Vec<u8>, Slip39Error> {
+    return feistel(encrypted, passphrase, iteration_exponent, identifier, extendable, (0..ROUND_COUNT).rev().collect::<Vec<u8>>());
+}
+
+// groups are (member_threshold, member_count) pairs; returns the mnemonics of each group
+pub fn split(master_secret: &[u8], passphrase: &str, group_threshold: u8, groups: &[(u8, u8)], iteration_exponent: u8, extendable: bool) -> Result<Vec<Vec<String>>, Slip39Error> {
+    if master_secret.len() < MIN_SECRET_BYTES || !master_secret.len().is_multiple_of(2) {
+        return Err(Slip39Error::SecretLength(master_secret.len()));
+    }
+    if iteration_exponent > 15 || groups.is_empty() || groups.len() > MAX_SHARE_COUNT as usize {
+        return Err(Slip39Error::Threshold);
+    }
+    // a 1-of-n member split would hand out n copies of the same share
+    if groups.iter().any(|(t, n)| *t == 1 && *n > 1) {
+        return Err(Slip39Error::Threshold);
+    }
+
+    let identifier: u16 = rand::thread_rng().gen_range(0..(1 << ID_BITS));
+    let encrypted: Vec<u8> = encrypt(master_secret, passphrase, iteration_exponent, identifier, extendable)?;
+
+    let group_shares: Vec<(u8, Vec<u8>)> = split_secret(group_threshold, groups.len() as u8, &encrypted)?;
+
+    let mut r: Vec<Vec<String>> = vec![];
+    for ((group_index, group_secret), (member_threshold, member_count)) in group_shares.iter().zip(groups.iter()) {
+        let member_shares: Vec<(u8, Vec<u8>)> = split_secret(*member_threshold, *member_count, group_secret)?;
+
+        r.push(member_shares.into_iter().map(|(member_index, value)| Share {
+            identifier,
+            extendable,
+            iteration_exponent,
+            group_index: *group_index,
+            group_threshold,
+            group_count: groups.len() as u8,
+            member_index,
+            member_threshold: *member_threshold,
+            value
+        }.to_mnemonic()).collect::<Vec<String>>());
+    }
+    return Ok(r);
+}
+
+pub fn combine(mnemonics: &[&str], passphrase: &str) -> Result<Vec<u8>, Slip39Error> {
+    let mut shares: Vec<Share> = vec![];
+    for m in mnemonics.iter() {
+        let share: Share = Share::from_mnemonic(m)?;
+        if !shares.contains(&share) {
+            shares.push(share);
+        }
+    }
+    if shares.is_empty() {
+        return Err(Slip39Error::InsufficientShares);
+    }
+
+    let first: &Share = &shares[0];
+    if shares.iter().any(|s: &Share| s.common_parameters() != first.common_parameters() || s.value.len() != first.value.len()) {
+        return Err(Slip39Error::MismatchedShares);
+    }
+
+    let mut groups: BTreeMap<u8, Vec<&Share>> = BTreeMap::new();
+    shares.iter().for_each(|s: &Share| groups.entry(s.group_index).or_default().push(s));
+
+    let mut group_secrets: Vec<(u8, Vec<u8>)> = vec![];
+    for (group_index, members) in groups.iter() {
+        let threshold: u8 = members[0].member_threshold;
+        if members.iter().any(|s: &&Share| s.member_threshold != threshold) {
+            return Err(Slip39Error::MismatchedShares);
+        }
+        let mut indexes: Vec<u8> = members.iter().map(|s: &&Share| s.member_index).collect::<Vec<u8>>();
+        indexes.sort();
+        indexes.dedup();
+        if indexes.len() != members.len() {
+            return Err(Slip39Error::DuplicateMemberIndex);
+        }
+
+        // groups short of their member threshold are ignored
+        if members.len() >= threshold as usize && group_secrets.len() < first.group_threshold as usize {
+            let points: Vec<(u8, Vec<u8>)> = members[..threshold as usize].iter().map(|s: &&Share| (s.member_index, s.value.clone())).collect::<Vec<(u8, Vec<u8>)>>();
+            group_secrets.push((*group_index, recover_secret(threshold, &points)?));
+        }
+    }
+
+    if group_secrets.len() < first.group_threshold as usize {
+        return Err(Slip39Error::InsufficientShares);
+    }
+
+    let encrypted: Vec<u8> = recover_secret(first.group_threshold, &group_secrets)?;
+    return decrypt(&encrypted, passphrase, first.iteration_exponent, first.identifier, first.extendable);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::slip39::*;
+    use crate::base16;
+
+    #[test]
+    fn slip39_gf256_interpolation() {
+        let secret: Vec<u8> = base16::decode_string("bb54aac4b89dc868ba37d9cc21b2cece");
+        let shares = split_secret(3, 5, &secret).unwrap();
+
+        assert_eq!(recover_secret(3, &[shares[0].clone(), shares[2].clone(), shares[4].clone()]).unwrap(), secret);
+        assert_eq!(recover_secret(3, &[shares[1].clone(), shares[3].clone(), shares[4].clone()]).unwrap(), secret);
+        assert_eq!(recover_secret(3, &[shares[0].clone(), shares[1].clone(), (7, shares[2].1.clone())]), Err(Slip39Error::Digest));
+    }
+
+    #[test]
+    fn slip39_feistel_roundtrip() {
+        let secret: Vec<u8> = base16::decode_string("bb54aac4b89dc868ba37d9cc21b2cece");
+        for extendable in [false, true] {
+            let encrypted = encrypt(&secret, "TREZOR", 1, 7945, extendable).unwrap();
+            assert_ne!(encrypted, secret);
+            assert_eq!(decrypt(&encrypted, "TREZOR", 1, 7945, extendable).unwrap(), secret);
+        }
+        assert_eq!(encrypt(&secret, "pässphrase", 0, 1, false), Err(Slip39Error::Passphrase));
+    }
+
+    #[test]
+    fn slip39_split_and_combine() {
+        let secret: Vec<u8> = base16::decode_string("c938b319067687e990e05e0da0ecce1278f75ff58d9853f19dcaeed5de104aae");
+        let groups = split(&secret, "TREZOR", 2, &[(1, 1), (2, 3), (3, 5)], 0, true).unwrap();
+
+        assert_eq!(groups.iter().map(|g| g.len()).collect::<Vec<usize>>(), vec![1, 3, 5]);
+        assert!(groups.iter().flatten().all(|m| m.split(' ').count() == 33));
+
+        let picked: Vec<&str> = vec![&groups[0][0], &groups[2][4], &groups[2][0], &groups[2][2]];
+        assert_eq!(combine(&picked, "TREZOR").unwrap(), secret);
+        assert_ne!(combine(&picked, "").unwrap(), secret);
+
+        let short: Vec<&str> = vec![&groups[0][0], &groups[2][4], &groups[2][0]];
+        assert_eq!(combine(&short, "TREZOR"), Err(Slip39Error::InsufficientShares));
+
+        assert_eq!(split(&secret, "", 1, &[(1, 3)], 0, false), Err(Slip39Error::Threshold));
+        assert_eq!(split(&secret, "", 3, &[(2, 3), (2, 3)], 0, false), Err(Slip39Error::Threshold));
+    }
+}
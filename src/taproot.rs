@@ -0,0 +1,130 @@
+use secp256k1::{Secp256k1, PublicKey, XOnlyPublicKey, KeyPair, SecretKey, Scalar, Parity};
+use crate::base16;
+use crate::bytes;
+use crate::crypto::hashing;
+
+// leaf version of BIP342 tapscript
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+
+#[derive(Debug, PartialEq, Eq)]
+
+pub enum TaprootError {
+    InvalidKey,
+    InvalidTweak
+}
+
+// accepts the 04|x|y form used across crypto as well as a compressed key
+pub fn x_only_public_key(pub_key: &str) -> Result<[u8; 32], TaprootError> {
+    let key: PublicKey = match PublicKey::from_slice(&base16::decode_string(pub_key)) {
+        Ok(k) => k,
+        Err(_) => return Err(TaprootError::InvalidKey)
+    };
+    return Ok(key.x_only_public_key().0.serialize());
+}
+
+pub fn tap_leaf_hash(script: &[u8], leaf_version: u8) -> [u8; 32] {
+    let mut data: Vec<u8> = vec![leaf_version];
+    data.extend(bytes::compact_size(script.len() as u64));
+    data.extend_from_slice(script);
+    return hashing::tagged_hash("TapLeaf", &data);
+}
+
+// children are hashed in lexicographic order
+pub fn tap_branch_hash(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+
+    let mut data: Vec<u8> = left.to_vec();
+    data.extend_from_slice(right);
+    return hashing::tagged_hash("TapBranch", &data);
+}
+
+pub fn tap_tweak_hash(internal_key: &[u8; 32], merkle_root: Option<&[u8; 32]>) -> [u8; 32] {
+    let mut data: Vec<u8> = internal_key.to_vec();
+    if let Some(root) = merkle_root {
+        data.extend_from_slice(root);
+    }
+    return hashing::tagged_hash("TapTweak", &data);
+}
+
+// Q = P + tG; returns the x-only output key and whether its y is odd
+pub fn tweak_public_key(internal_key: &[u8; 32], merkle_root: Option<&[u8; 32]>) -> Result<([u8; 32], bool), TaprootError> {
+    let secp = Secp256k1::verification_only();
+    let key: XOnlyPublicKey = match XOnlyPublicKey::from_slice(internal_key) {
+        Ok(k) => k,
+        Err(_) => return Err(TaprootError::InvalidKey)
+    };
+    let tweak: Scalar = match Scalar::from_be_bytes(tap_tweak_hash(internal_key, merkle_root)) {
+        Ok(t) => t,
+        Err(_) => return Err(TaprootError::InvalidTweak)
+    };
+
+    match key.add_tweak(&secp, &tweak) {
+        Ok((output, parity)) => return Ok((output.serialize(), parity == Parity::Odd)),
+        Err(_) => return Err(TaprootError::InvalidTweak),
+    };
+}
+
+// the key spending the output: d is negated first when P has an odd y
+pub fn tweak_private_key(private_key: &[u8; 32], merkle_root: Option<&[u8; 32]>) -> Result<[u8; 32], TaprootError> {
+    let secp = Secp256k1::new();
+    let key_pair: KeyPair = match KeyPair::from_seckey_slice(&secp, private_key) {
+        Ok(k) => k,
+        Err(_) => return Err(TaprootError::InvalidKey)
+    };
+    let internal_key: [u8; 32] = key_pair.x_only_public_key().0.serialize();
+    let tweak: Scalar = match Scalar::from_be_bytes(tap_tweak_hash(&internal_key, merkle_root)) {
+        Ok(t) => t,
+        Err(_) => return Err(TaprootError::InvalidTweak)
+    };
+
+    match key_pair.add_xonly_tweak(&secp, &tweak) {
+        Ok(tweaked) => return Ok(SecretKey::from_keypair(&tweaked).secret_bytes()),
+        Err(_) => return Err(TaprootError::InvalidTweak),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::taproot::*;
+    use crate::base16;
+    use crate::bech32;
+
+    fn array(s: &str) -> [u8; 32] {
+        let mut r: [u8; 32] = [0; 32];
+        r.copy_from_slice(&base16::decode_string(s));
+        return r;
+    }
+
+    #[test]
+    fn taproot_branch_order() {
+        assert_eq!(tap_branch_hash(&[1; 32], &[2; 32]), tap_branch_hash(&[2; 32], &[1; 32]));
+        assert_ne!(tap_branch_hash(&[1; 32], &[2; 32]), tap_branch_hash(&[1; 32], &[3; 32]));
+    }
+
+    // BIP341 wallet vectors, scriptPubKey section
+    #[test]
+    fn taproot_output_keys() {
+        let internal_key = array("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        assert_eq!(base16::encode_bytes(&tap_tweak_hash(&internal_key, None)), "b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70");
+        assert_eq!(base16::encode_bytes(&tweak_public_key(&internal_key, None).unwrap().0), "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343");
+        assert_eq!(bech32::encode_segwit_address("bc", 1, &tweak_public_key(&internal_key, None).unwrap().0).unwrap(), "bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5");
+
+        let internal_key = array("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27");
+        let leaf = tap_leaf_hash(&base16::decode_string("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac"), TAPSCRIPT_LEAF_VERSION);
+        assert_eq!(base16::encode_bytes(&leaf), "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21");
+        assert_eq!(base16::encode_bytes(&tap_tweak_hash(&internal_key, Some(&leaf))), "cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001");
+        assert_eq!(base16::encode_bytes(&tweak_public_key(&internal_key, Some(&leaf)).unwrap().0), "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+        assert_eq!(bech32::encode_segwit_address("bc", 1, &tweak_public_key(&internal_key, Some(&leaf)).unwrap().0).unwrap(), "bc1pz37fc4cn9ah8anwm4xqqhvxygjf9rjf2resrw8h8w4tmvcs0863sa2e586");
+    }
+
+    // BIP341 wallet vectors, keyPathSpending section
+    #[test]
+    fn taproot_tweak_private_key() {
+        let private_key = array("6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa");
+        assert_eq!(base16::encode_bytes(&tweak_private_key(&private_key, None).unwrap()), "2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9");
+
+        let public_key = crate::crypto::secp256k1::get_public_key(&base16::encode_bytes(&private_key));
+        assert_eq!(base16::encode_bytes(&x_only_public_key(&public_key).unwrap()), "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        assert_eq!(x_only_public_key("05ab"), Err(TaprootError::InvalidKey));
+    }
+}
@@ -0,0 +1,331 @@
+use std::fmt;
+use crate::base16;
+use crate::bytes::{self, Reader, ReadError};
+use crate::crypto::hashing;
+use crate::script::Script;
+
+pub const SEQUENCE_FINAL: u32 = 0xffffffff;
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+// 21 million bitcoin in satoshis, the most any output or transaction may hold
+pub const MAX_MONEY: u64 = 2_100_000_000_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum TransactionError {
+    InvalidHex,
+    Read(ReadError),
+    // the byte after the segwit marker must be 0x01
+    InvalidFlag(u8),
+    // a segwit serialization whose witnesses are all empty
+    SuperfluousWitness,
+    TrailingBytes(usize)
+}
+
+impl From<ReadError> for TransactionError {
+    fn from(e: ReadError) -> Self {
+        return TransactionError::Read(e);
+    }
+}
+
+// txids and block hashes are shown byte-reversed
+pub fn hash_to_hex(hash: &[u8; 32]) -> String {
+    let mut r: [u8; 32] = *hash;
+    r.reverse();
+    return base16::encode_bytes(&r);
+}
+
+pub fn hash_from_hex(s: &str) -> Option<[u8; 32]> {
+    if s.len() != 64 || !s.chars().all(|c: char| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut r: [u8; 32] = [0; 32];
+    r.copy_from_slice(&base16::decode_string(&s.to_lowercase()));
+    r.reverse();
+    return Some(r);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+
+pub struct OutPoint {
+    // in internal byte order, i.e. as hashed
+    pub txid: [u8; 32],
+    pub vout: u32
+}
+
+impl OutPoint {
+    pub fn new(txid: [u8; 32], vout: u32) -> Self {
+        return Self { txid, vout };
+    }
+
+    // the outpoint spent by a coinbase input
+    pub fn null() -> Self {
+        return Self { txid: [0; 32], vout: 0xffffffff };
+    }
+
+    pub fn is_null(&self) -> bool {
+        return *self == OutPoint::null();
+    }
+}
+
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}:{}", hash_to_hex(&self.txid), self.vout);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct TxIn {
+    pub previous_output: OutPoint,
+    pub script_sig: Script,
+    pub sequence: u32,
+    pub witness: Vec<Vec<u8>>
+}
+
+impl TxIn {
+    pub fn new(previous_output: OutPoint) -> Self {
+        return Self { previous_output, script_sig: Script::default(), sequence: SEQUENCE_FINAL, witness: vec![] };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct TxOut {
+    pub value: u64,
+    pub script_pubkey: Script
+}
+
+impl TxOut {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut r: Vec<u8> = self.value.to_le_bytes().to_vec();
+        r.extend(bytes::compact_size(self.script_pubkey.bytes.len() as u64));
+        r.extend_from_slice(&self.script_pubkey.bytes);
+        return r;
+    }
+
+    pub fn deserialize(reader: &mut Reader) -> Result<Self, ReadError> {
+        let value: u64 = reader.read_u64()?;
+        let script_pubkey: Script = Script::from_bytes(reader.read_var_bytes()?);
+        return Ok(Self { value, script_pubkey });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub struct Transaction {
+    pub version: i32,
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+    pub lock_time: u32
+}
+
+impl Transaction {
+    pub fn new(version: i32, lock_time: u32) -> Self {
+        return Self { version, inputs: vec![], outputs: vec![], lock_time };
+    }
+
+    pub fn has_witness(&self) -> bool {
+        return self.inputs.iter().any(|i: &TxIn| !i.witness.is_empty());
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        return self.inputs.len() == 1 && self.inputs[0].previous_output.is_null();
+    }
+
+    fn encode(&self, with_witness: bool) -> Vec<u8> {
+        let mut r: Vec<u8> = self.version.to_le_bytes().to_vec();
+        // BIP144 marker and flag
+        if with_witness {
+            r.extend_from_slice(&[0x00, 0x01]);
+        }
+
+        r.extend(bytes::compact_size(self.inputs.len() as u64));
+        for input in self.inputs.iter() {
+            r.extend_from_slice(&input.previous_output.txid);
+            r.extend_from_slice(&input.previous_output.vout.to_le_bytes());
+            r.extend(bytes::compact_size(input.script_sig.bytes.len() as u64));
+            r.extend_from_slice(&input.script_sig.bytes);
+            r.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        r.extend(bytes::compact_size(self.outputs.len() as u64));
+        for output in self.outputs.iter() {
+            r.extend(output.serialize());
+        }
+
+        if with_witness {
+            for input in self.inputs.iter() {
+                r.extend(bytes::compact_size(input.witness.len() as u64));
+                for item in input.witness.iter() {
+                    r.extend(bytes::compact_size(item.len() as u64));
+                    r.extend_from_slice(item);
+                }
+            }
+        }
+        r.extend_from_slice(&self.lock_time.to_le_bytes());
+        return r;
+    }
+
+    // the witness serialization whenever any input has a witness
+    pub fn serialize(&self) -> Vec<u8> {
+        return self.encode(self.has_witness());
+    }
+
+    pub fn serialize_without_witness(&self) -> Vec<u8> {
+        return self.encode(false);
+    }
+
+    pub fn to_hex(&self) -> String {
+        return base16::encode_bytes(&self.serialize());
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, TransactionError> {
+        let mut reader: Reader = Reader::new(data);
+        let tx: Transaction = Transaction::read(&mut reader)?;
+        if reader.remaining() > 0 {
+            return Err(TransactionError::TrailingBytes(reader.remaining()));
+        }
+        return Ok(tx);
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, TransactionError> {
+        let s: String = s.trim().to_lowercase();
+        if !s.len().is_multiple_of(2) || !s.chars().all(|c: char| c.is_ascii_hexdigit()) {
+            return Err(TransactionError::InvalidHex);
+        }
+        return Transaction::deserialize(&base16::decode_string(&s));
+    }
+
+    // reads one transaction, e.g. from a block
+    pub fn read(reader: &mut Reader) -> Result<Self, TransactionError> {
+        let version: i32 = reader.read_u32()? as i32;
+
+        // a zero input count can only be the segwit marker
+        let segwit: bool = reader.peek_u8()? == 0x00;
+        if segwit {
+            reader.read_u8()?;
+            let flag: u8 = reader.read_u8()?;
+            if flag != 0x01 {
+                return Err(TransactionError::InvalidFlag(flag));
+            }
+        }
+
+        let mut inputs: Vec<TxIn> = vec![];
+        for _ in 0..reader.read_compact_size()? {
+            let txid: [u8; 32] = reader.read_array::<32>()?;
+            let vout: u32 = reader.read_u32()?;
+            let script_sig: Script = Script::from_bytes(reader.read_var_bytes()?);
+            let sequence: u32 = reader.read_u32()?;
+            inputs.push(TxIn { previous_output: OutPoint::new(txid, vout), script_sig, sequence, witness: vec![] });
+        }
+        let mut outputs: Vec<TxOut> = vec![];
+        for _ in 0..reader.read_compact_size()? {
+            outputs.push(TxOut::deserialize(reader)?);
+        }
+
+        if segwit {
+            for input in inputs.iter_mut() {
+                for _ in 0..reader.read_compact_size()? {
+                    input.witness.push(reader.read_var_bytes()?.to_vec());
+                }
+            }
+            if inputs.iter().all(|i: &TxIn| i.witness.is_empty()) {
+                return Err(TransactionError::SuperfluousWitness);
+            }
+        }
+        let lock_time: u32 = reader.read_u32()?;
+
+        return Ok(Self { version, inputs, outputs, lock_time });
+    }
+
+    // internal byte order; see hash_to_hex for the usual display
+    pub fn txid(&self) -> [u8; 32] {
+        return hashing::sha256d(&self.serialize_without_witness());
+    }
+
+    pub fn wtxid(&self) -> [u8; 32] {
+        return hashing::sha256d(&self.serialize());
+    }
+
+    pub fn size(&self) -> usize {
+        return self.serialize().len();
+    }
+
+    // BIP141: base size * 3 + total size
+    pub fn weight(&self) -> usize {
+        return self.serialize_without_witness().len() * (WITNESS_SCALE_FACTOR - 1) + self.size();
+    }
+
+    pub fn vsize(&self) -> usize {
+        return self.weight().div_ceil(WITNESS_SCALE_FACTOR);
+    }
+
+    // none when the values overflow, which only a transaction consensus rejects can do
+    pub fn output_value(&self) -> Option<u64> {
+        return self.outputs.iter().try_fold(0, |total: u64, o: &TxOut| total.checked_add(o.value));
+    }
+}
+
+// value in satoshis as whole coins
+pub fn format_amount(value: u64) -> String {
+    return format!("{}.{:08}", value / 100_000_000, value % 100_000_000);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transaction::*;
+    use crate::script::ScriptType;
+
+    // mainnet f5864806e3565c34d1b41e716f72609d00b55ea5eac5b924c9719a842ef42206
+    const SEGWIT_TX: &str = "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c0100000000ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd2710e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed8941055d3bcb8627d085e94553e62f057dcc00000000";
+
+    #[test]
+    fn transaction_segwit_round_trip() {
+        let tx: Transaction = Transaction::from_hex(SEGWIT_TX).unwrap();
+        assert_eq!(tx.version, 2);
+        assert_eq!(tx.lock_time, 0);
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.inputs[0].previous_output.to_string(), "7cac3cf9a112cf04901a51d605058615d56ffe6d04b45270e89d1720ea955859:1");
+        assert_eq!(tx.inputs[0].witness.len(), 2);
+        assert_eq!(tx.outputs[0].value, 506078);
+        assert!(matches!(tx.outputs[0].script_pubkey.script_type(), ScriptType::P2sh(_)));
+
+        assert_eq!(tx.to_hex(), SEGWIT_TX);
+        assert_eq!(hash_to_hex(&tx.txid()), "f5864806e3565c34d1b41e716f72609d00b55ea5eac5b924c9719a842ef42206");
+        assert_eq!(hash_to_hex(&tx.wtxid()), "80b7d8a82d5d5bf92905b06f2014dd699e03837ca172e3a59d51426ebbe3e7f5");
+        assert_eq!(tx.size(), 193);
+        assert_eq!(tx.weight(), 442);
+        assert_eq!(tx.vsize(), 111);
+        assert_eq!(hash_from_hex("f5864806e3565c34d1b41e716f72609d00b55ea5eac5b924c9719a842ef42206"), Some(tx.txid()));
+    }
+
+    #[test]
+    fn transaction_invalid() {
+        let mut truncated: String = String::from(SEGWIT_TX);
+        truncated.truncate(SEGWIT_TX.len() - 2);
+        assert_eq!(Transaction::from_hex(&truncated), Err(TransactionError::Read(ReadError::UnexpectedEnd(189))));
+        assert_eq!(Transaction::from_hex(&format!("{}00", SEGWIT_TX)), Err(TransactionError::TrailingBytes(1)));
+        assert_eq!(Transaction::from_hex("0200000000020000000000"), Err(TransactionError::InvalidFlag(2)));
+        assert_eq!(Transaction::from_hex("zz"), Err(TransactionError::InvalidHex));
+
+        // segwit marker with no witness at all
+        let mut tx: Transaction = Transaction::from_hex(SEGWIT_TX).unwrap();
+        tx.inputs[0].witness.clear();
+        let mut data: Vec<u8> = tx.serialize_without_witness();
+        data.splice(4..4, [0x00, 0x01]);
+        let lock_time: Vec<u8> = data.split_off(data.len() - 4);
+        data.push(0x00);
+        data.extend(lock_time);
+        assert_eq!(Transaction::deserialize(&data), Err(TransactionError::SuperfluousWitness));
+    }
+
+    #[test]
+    fn transaction_output_value_overflow() {
+        // two outputs of u64::MAX, which parse but no valid transaction can hold
+        let tx: Transaction = Transaction::from_hex("0200000001595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c0100000000ffffffff02ffffffffffffffff00ffffffffffffffff0000000000").unwrap();
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.output_value(), None);
+
+        let tx: Transaction = Transaction::from_hex(SEGWIT_TX).unwrap();
+        assert_eq!(tx.output_value(), Some(506078));
+    }
+}
@@ -0,0 +1,47 @@
+use walletcryptography::bip38::{self, Confirmation};
+use walletcryptography::base58;
+use walletcryptography::app::Network;
+use walletcryptography::crypto::{bitcoin, secp256k1};
+use serde_json::Value;
+use ::secp256k1::PublicKey;
+
+#[test]
+
+fn bip38_vectors() {
+    let vectors: Value = serde_json::from_str(include_str!("fixtures/bip38_vectors.json")).unwrap();
+
+    for vector in vectors.as_array().unwrap() {
+        let passphrase: &str = vector["passphrase"].as_str().unwrap();
+        let encrypted: &str = vector["encrypted"].as_str().unwrap();
+        let (key, compressed) = bip38::decrypt(encrypted, passphrase).unwrap();
+
+        let public_key: PublicKey = secp256k1::public_key(&key);
+        let (wif, address): (String, String) = if compressed {
+            (bitcoin::encode_compressed_pr_key(&key, Network::Bitcoin), bitcoin::derive_compressed_address(&public_key, Network::Bitcoin))
+        }
+        else {
+            (bitcoin::encode_uncompressed_pr_key(&key, Network::Bitcoin), bitcoin::derive_uncompressed_address(&public_key, Network::Bitcoin))
+        };
+        assert_eq!(wif, vector["wif"].as_str().unwrap(), "{}", vector["description"]);
+        assert_eq!(address, vector["address"].as_str().unwrap());
+
+        let intermediate: &str = match vector["intermediate"].as_str() {
+            Some(i) => i,
+            None => {
+                assert_eq!(bip38::encrypt(&key, compressed, passphrase).unwrap(), encrypted);
+                continue;
+            }
+        };
+        // the owner salt is carried in the intermediate code
+        let lot_sequence: Option<(u32, u32)> = vector["lot"].as_u64().map(|lot: u64| (lot as u32, vector["sequence"].as_u64().unwrap() as u32));
+        let mut salt: [u8; 8] = [0; 8];
+        let length: usize = if lot_sequence.is_some() { 4 } else { 8 };
+        salt[..length].copy_from_slice(&base58::decode_check(intermediate).unwrap()[8..8 + length]);
+        assert_eq!(bip38::intermediate_code_with_salt(passphrase, &salt, lot_sequence).unwrap(), intermediate);
+
+        if let Some(confirmation) = vector["confirmation"].as_str() {
+            let expected: Confirmation = Confirmation { address: address.clone(), compressed, lot_sequence };
+            assert_eq!(bip38::verify_confirmation(confirmation, passphrase), Ok(expected));
+        }
+    }
+}
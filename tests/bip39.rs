@@ -0,0 +1,47 @@
+use std::str::FromStr;
+use walletcryptography::bip39::{self, Language};
+use walletcryptography::bip32::ExtendedPrivateKey;
+use walletcryptography::base16;
+use serde_json::Value;
+
+#[test]
+
+fn bip39_japanese_vectors() {
+    let vectors: Value = serde_json::from_str(include_str!("fixtures/bip39_japanese_vectors.json")).unwrap();
+
+    for vector in vectors.as_array().unwrap() {
+        let entropy: &str = vector["entropy"].as_str().unwrap();
+        let mnemonic: &str = vector["mnemonic"].as_str().unwrap();
+        let passphrase: &str = vector["passphrase"].as_str().unwrap();
+        let seed: [u8; 64] = bip39::mnemonic_to_seed(mnemonic, passphrase);
+
+        // the fixtures are NFC, the generated phrase uses the NFKD words of the wordlist
+        let generated: String = bip39::entropy_to_mnemonic_in(&base16::decode_string(entropy), Language::Japanese).unwrap();
+        assert_eq!(bip39::normalize(&generated), bip39::normalize(mnemonic));
+        assert_eq!(generated.matches('\u{3000}').count(), bip39::split_words(mnemonic).len() - 1);
+        assert_eq!(bip39::detect_language(mnemonic), Ok(Language::Japanese));
+        assert_eq!(base16::encode_bytes(&bip39::mnemonic_to_entropy(mnemonic).unwrap()), entropy);
+        assert_eq!(base16::encode_bytes(&seed), vector["seed"].as_str().unwrap());
+        assert_eq!(ExtendedPrivateKey::from_seed(&seed).to_xprv(), vector["bip32_xprv"].as_str().unwrap());
+
+        // plain ascii spaces are accepted in place of the ideographic space
+        assert_eq!(bip39::mnemonic_to_seed(&mnemonic.replace('\u{3000}', " "), passphrase), seed);
+    }
+}
+
+#[test]
+
+fn bip39_detect_language() {
+    for language in Language::all() {
+        let mnemonic: String = bip39::entropy_to_mnemonic_in(&[0x5a; 16], language).unwrap();
+        let detected: Language = bip39::detect_language(&mnemonic).unwrap();
+
+        // shared characters map to the same index, so either chinese list decodes the phrase
+        assert_eq!(bip39::mnemonic_to_entropy_in(&mnemonic, detected).unwrap(), vec![0x5a; 16]);
+        assert_eq!(bip39::mnemonic_to_entropy(&mnemonic).unwrap(), vec![0x5a; 16]);
+        if language != Language::ChineseTraditional {
+            assert_eq!(detected, language);
+        }
+    }
+    assert_eq!(Language::from_str("chinese_traditional"), Ok(Language::ChineseTraditional));
+}
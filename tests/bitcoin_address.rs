@@ -0,0 +1,64 @@
+use walletcryptography::app::Network;
+use walletcryptography::crypto::{bitcoin, secp256k1};
+use walletcryptography::base16;
+use ::secp256k1::PublicKey;
+
+const PRIVATE_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+fn private_key() -> [u8; 32] {
+    return base16::decode_string(PRIVATE_KEY).try_into().unwrap();
+}
+
+#[test]
+
+fn bitcoin_compressed_and_uncompressed() {
+    let pub_key: PublicKey = secp256k1::public_key(&private_key());
+
+    assert_eq!(bitcoin::derive_compressed_address(&pub_key, Network::Bitcoin), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+    assert_eq!(bitcoin::encode_compressed_pr_key(&private_key(), Network::Bitcoin), "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn");
+    assert_eq!(bitcoin::derive_uncompressed_address(&pub_key, Network::Bitcoin), "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm");
+    assert_eq!(bitcoin::encode_uncompressed_pr_key(&private_key(), Network::Bitcoin), "5HpHagT65TZzG1PH3CSu63k8DbpvD8s5ip4nEB3kEsreAnchuDf");
+    assert_eq!(bitcoin::get_compressed_public_key(&pub_key), "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+    assert_eq!(base16::encode_bytes(&bitcoin::get_public_key_hash(&pub_key)), "751e76e8199196d454941c45d1b3a323f1433bd6");
+}
+
+#[test]
+
+fn bitcoin_segwit_and_taproot() {
+    let pub_key: PublicKey = secp256k1::public_key(&private_key());
+
+    assert_eq!(bitcoin::derive_p2sh_p2wpkh_address(&pub_key, Network::Bitcoin), "3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN");
+    assert_eq!(bitcoin::derive_p2wpkh_address(&pub_key, Network::Bitcoin), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+    assert_eq!(bitcoin::derive_p2tr_address(&pub_key, Network::Bitcoin), "bc1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5sspknck9");
+}
+
+#[test]
+
+fn bitcoin_test_networks() {
+    let pub_key: PublicKey = secp256k1::public_key(&private_key());
+
+    for network in [Network::Testnet, Network::Signet] {
+        assert_eq!(bitcoin::derive_compressed_address(&pub_key, network), "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r");
+        assert_eq!(bitcoin::derive_uncompressed_address(&pub_key, network), "mtoKs9V381UAhUia3d7Vb9GNak8Qvmcsme");
+        assert_eq!(bitcoin::encode_compressed_pr_key(&private_key(), network), "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN87JcbXMTcA");
+        assert_eq!(bitcoin::encode_uncompressed_pr_key(&private_key(), network), "91avARGdfge8E4tZfYLoxeJ5sGBdNJQH4kvjJoQFacbgwmaKkrx");
+        assert_eq!(bitcoin::derive_p2wpkh_address(&pub_key, network), "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx");
+        assert!(bitcoin::derive_p2sh_p2wpkh_address(&pub_key, network).starts_with('2'));
+        assert!(bitcoin::derive_p2tr_address(&pub_key, network).starts_with("tb1p"));
+    }
+
+    assert_eq!(bitcoin::derive_compressed_address(&pub_key, Network::Regtest), "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r");
+    assert_eq!(bitcoin::derive_p2wpkh_address(&pub_key, Network::Regtest), "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080");
+    assert!(bitcoin::derive_p2tr_address(&pub_key, Network::Regtest).starts_with("bcrt1p"));
+}
+
+// BIP173 example: P2WSH of a pay-to-pubkey script
+#[test]
+
+fn bitcoin_script_hash_addresses() {
+    let script: Vec<u8> = base16::decode_string("210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac");
+
+    assert_eq!(bitcoin::derive_p2wsh_address(&script, Network::Bitcoin), "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3");
+    assert_eq!(bitcoin::derive_p2wsh_address(&script, Network::Testnet), "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7");
+    assert_eq!(base16::encode_bytes(&bitcoin::get_p2wsh_redeem_script(&script)), "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262");
+}
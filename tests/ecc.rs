@@ -3,7 +3,7 @@ use walletcryptography::secp256k1::*;
 use walletcryptography::base16;
 use std::str::FromStr;
 
-use secp256k1::{Secp256k1, Message, SecretKey, PublicKey};
+use secp256k1::{Secp256k1, SecretKey, PublicKey};
 use rand::prelude::*;
 
 #[test]
@@ -0,0 +1,51 @@
+use walletcryptography::base16;
+use walletcryptography::crypto::hashing::{self, Hash160Hasher, Keccak256Hasher, Ripemd160Hasher, Sha256Hasher, Sha256dHasher, TaggedHasher};
+
+#[test]
+
+fn hashing_one_shot() {
+    assert_eq!(base16::encode_bytes(&hashing::sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(base16::encode_bytes(&hashing::sha256d(b"hello")), "9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50");
+    assert_eq!(base16::encode_bytes(&hashing::ripemd160(b"abc")), "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc");
+    // the compressed public key of private key 1
+    let key: Vec<u8> = base16::decode_string("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+    assert_eq!(base16::encode_bytes(&hashing::hash160(&key)), "751e76e8199196d454941c45d1b3a323f1433bd6");
+    assert_eq!(base16::encode_bytes(&hashing::keccak256(b"")), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+    assert_eq!(base16::encode_bytes(&hashing::tagged_hash("TapLeaf", b"abc")), "83a56308a9c56f467e8df293da5ae5fdbc85b871952a83c4bf0575ee948ec230");
+
+    // the hex forms keep their casing
+    assert_eq!(hashing::hash_ripemd160(b"abc"), "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc");
+    assert_eq!(hashing::hash_keccak256(b""), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+}
+
+#[test]
+
+fn hashing_streaming() {
+    // a million times 'a' from the sha256 test vectors, fed in pieces
+    let mut hasher: Sha256Hasher = Sha256Hasher::new();
+    for _ in 0..1000 {
+        hasher.update(&[b'a'; 1000]);
+    }
+    assert_eq!(base16::encode_bytes(&hasher.finalize()), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+
+    let data: &[u8] = b"The quick brown fox jumps over the lazy dog";
+    let (head, tail) = data.split_at(17);
+
+    let mut sha256d: Sha256dHasher = Sha256dHasher::new();
+    let mut ripemd160: Ripemd160Hasher = Ripemd160Hasher::new();
+    let mut hash160: Hash160Hasher = Hash160Hasher::new();
+    let mut keccak256: Keccak256Hasher = Keccak256Hasher::new();
+    let mut tagged: TaggedHasher = TaggedHasher::new("BIP0340/challenge");
+    for part in [head, tail] {
+        sha256d.update(part);
+        ripemd160.update(part);
+        hash160.update(part);
+        keccak256.update(part);
+        tagged.update(part);
+    }
+    assert_eq!(sha256d.finalize(), hashing::sha256d(data));
+    assert_eq!(ripemd160.finalize(), hashing::ripemd160(data));
+    assert_eq!(hash160.finalize(), hashing::hash160(data));
+    assert_eq!(base16::encode_bytes(&keccak256.finalize()), "4d741b6f1eb29cb2a9b9911c82f56fa8d73b04959d3d9d222895df6c0b28aa15");
+    assert_eq!(tagged.finalize(), hashing::tagged_hash("BIP0340/challenge", data));
+}
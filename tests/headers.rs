@@ -0,0 +1,137 @@
+use primitive_types::U256;
+use walletcryptography::block::BlockHeader;
+use walletcryptography::headers::{self, ChainParams, HeaderChain, HeaderError, MAINNET_CHAIN, RETARGET_INTERVAL, TARGET_SPACING};
+use walletcryptography::transaction;
+
+// mainnet rules with a target easy enough to mine in a test
+const EASY_CHAIN: ChainParams = ChainParams {
+    pow_limit: 0x207fffff,
+    allow_min_difficulty_blocks: false,
+    no_retargeting: false,
+    checkpoints: &[]
+};
+
+fn mine(prev: &BlockHeader, time: u32, bits: u32, params: &ChainParams) -> BlockHeader {
+    let mut header: BlockHeader = BlockHeader { version: 4, prev_blockhash: prev.block_hash(), merkle_root: [0x11; 32], time, bits, nonce: 0 };
+    while !headers::check_proof_of_work(&header, params) {
+        header.nonce += 1;
+    }
+    return header;
+}
+
+fn easy_anchor(bits: u32) -> BlockHeader {
+    return BlockHeader { version: 4, prev_blockhash: [0; 32], merkle_root: [0; 32], time: 1700000000, bits, nonce: 0 };
+}
+
+#[test]
+
+fn headers_mainnet_fixture() {
+    // the genesis block and the five blocks after it
+    let data: &[u8] = include_bytes!("fixtures/mainnet_headers.bin");
+    let mainnet: Vec<BlockHeader> = headers::read_headers(data).unwrap();
+    assert_eq!(mainnet.len(), 6);
+
+    let mut chain: HeaderChain = HeaderChain::new(&mainnet[0], 0, &MAINNET_CHAIN).unwrap();
+    chain.extend(&mainnet[1..]).unwrap();
+    assert_eq!(chain.height(), 5);
+    assert_eq!(transaction::hash_to_hex(&chain.tip_hash()), "000000009b7262315dbf071787ad3656097b892abffd1f95a1a022f896f533fc");
+    assert_eq!(chain.chain_work, U256::from(0x100010001u64) * 5);
+    assert_eq!(chain.median_time_past(), mainnet[3].time);
+
+    // the genesis block is not the block at a later checkpoint
+    assert_eq!(HeaderChain::new(&mainnet[0], 11111, &MAINNET_CHAIN), Err(HeaderError::CheckpointMismatch(11111)));
+    assert_eq!(HeaderChain::from_trusted(&mainnet[0], 11111, &MAINNET_CHAIN), Err(HeaderError::CheckpointMismatch(11111)));
+    // without a checkpoint the anchor has to be trusted explicitly
+    assert_eq!(HeaderChain::new(&mainnet[5], 5, &MAINNET_CHAIN), Err(HeaderError::NotACheckpoint(5)));
+    let mut chain: HeaderChain = HeaderChain::from_trusted(&mainnet[3], 3, &MAINNET_CHAIN).unwrap();
+    chain.extend(&mainnet[4..]).unwrap();
+    assert_eq!(chain.height(), 5);
+
+    let mut chain: HeaderChain = HeaderChain::new(&mainnet[0], 0, &MAINNET_CHAIN).unwrap();
+    assert_eq!(chain.push(&mainnet[2]), Err(HeaderError::PrevHashMismatch(1)));
+    let mut header: BlockHeader = mainnet[1];
+    header.nonce += 1;
+    assert_eq!(chain.push(&header), Err(HeaderError::HighHash(1)));
+    header.bits = 0x1c00ffff;
+    assert_eq!(chain.push(&header), Err(HeaderError::BadDifficulty(1, 0x1d00ffff)));
+    header.bits = 0x1d80ffff;
+    assert_eq!(chain.push(&header), Err(HeaderError::InvalidBits(1)));
+    assert_eq!(chain.height(), 0);
+
+    assert_eq!(headers::read_headers(&data[..479]), Err(HeaderError::InvalidLength(479)));
+    assert_eq!(headers::read_headers(&[]), Err(HeaderError::InvalidLength(0)));
+}
+
+#[test]
+
+fn headers_retarget_and_time() {
+    // a period mined in half the expected time doubles the difficulty
+    let mut chain: HeaderChain = HeaderChain::from_trusted(&easy_anchor(0x207fffff), 0, &EASY_CHAIN).unwrap();
+    for height in 1..RETARGET_INTERVAL {
+        let header: BlockHeader = mine(chain.tip(), 1700000000 + height * TARGET_SPACING / 2, 0x207fffff, &EASY_CHAIN);
+        chain.push(&header).unwrap();
+    }
+    let time: u32 = chain.tip().time + TARGET_SPACING / 2;
+    // 2015 intervals of five minutes: 0x7fffff * 604500 / 1209600
+    let expected: u32 = 0x203ff7de;
+    assert_eq!(chain.next_bits(time), Ok(expected));
+    let stale: BlockHeader = mine(chain.tip(), time, 0x207fffff, &EASY_CHAIN);
+    assert_eq!(chain.push(&stale), Err(HeaderError::BadDifficulty(RETARGET_INTERVAL, expected)));
+    let work: U256 = chain.chain_work;
+    chain.push(&mine(chain.tip(), time, expected, &EASY_CHAIN)).unwrap();
+    assert_eq!(chain.chain_work, work + headers::work_from_bits(expected));
+
+    // a header no later than the median of the last eleven
+    let median: u32 = chain.median_time_past();
+    assert_eq!(median, chain.tip().time - 5 * TARGET_SPACING / 2);
+    assert_eq!(chain.push(&mine(chain.tip(), median, expected, &EASY_CHAIN)), Err(HeaderError::TimeTooOld(RETARGET_INTERVAL + 1)));
+    chain.push(&mine(chain.tip(), median + 1, expected, &EASY_CHAIN)).unwrap();
+
+    // anchored inside a period, the retarget cannot be checked
+    let mut chain: HeaderChain = HeaderChain::from_trusted(&easy_anchor(0x207fffff), RETARGET_INTERVAL - 1, &EASY_CHAIN).unwrap();
+    let header: BlockHeader = mine(chain.tip(), 1700000600, 0x207fffff, &EASY_CHAIN);
+    assert_eq!(chain.push(&header), Err(HeaderError::MissingHistory(RETARGET_INTERVAL)));
+}
+
+#[test]
+
+fn headers_min_difficulty() {
+    let params: ChainParams = ChainParams { allow_min_difficulty_blocks: true, ..EASY_CHAIN };
+    let mut chain: HeaderChain = HeaderChain::from_trusted(&easy_anchor(0x1f7fffff), 100, &params).unwrap();
+    let start: u32 = chain.tip().time;
+
+    // more than twenty minutes after its parent a header may drop to the easiest target
+    assert_eq!(chain.next_bits(start + 2 * TARGET_SPACING), Ok(0x1f7fffff));
+    chain.push(&mine(chain.tip(), start + 2 * TARGET_SPACING + 1, 0x207fffff, &params)).unwrap();
+    // and the next one returns to the target before the easy blocks
+    let time: u32 = chain.tip().time + 60;
+    assert_eq!(chain.next_bits(time), Ok(0x1f7fffff));
+    assert_eq!(chain.push(&mine(chain.tip(), time, 0x207fffff, &params)), Err(HeaderError::BadDifficulty(102, 0x1f7fffff)));
+    chain.push(&mine(chain.tip(), time, 0x1f7fffff, &params)).unwrap();
+    assert_eq!(chain.height(), 102);
+}
+
+#[test]
+
+fn headers_mainnet_retarget() {
+    // the retarget at 32256 and the clamped ones at 46368 and 68544: the times and bits of the
+    // first and last header of each period and the bits bitcoin core's pow tests expect
+    for (height, first_time, last_time, bits, expected) in [
+        (32256u32, 1261130161u32, 1262152739u32, 0x1d00ffffu32, 0x1d00d86au32),
+        (46368, 1263163443, 1269211443, 0x1c387f6f, 0x1d00e1fd),
+        (68544, 1279008237, 1279297671, 0x1c05a3f4, 0x1c0168fd)
+    ] {
+        // the headers between them matter to the retarget only through their count
+        let first: BlockHeader = BlockHeader { version: 1, prev_blockhash: [0; 32], merkle_root: [0; 32], time: first_time, bits, nonce: 0 };
+        let mut chain: HeaderChain = HeaderChain::from_trusted(&first, height - RETARGET_INTERVAL, &MAINNET_CHAIN).unwrap();
+        chain.headers.extend((1..RETARGET_INTERVAL).map(|i: u32| BlockHeader { time: first_time + i, ..first }));
+        chain.headers.last_mut().unwrap().time = last_time;
+        assert_eq!(chain.height(), height - 1);
+        assert_eq!(chain.next_bits(last_time + TARGET_SPACING), Ok(expected));
+    }
+
+    // a hostile time near the end of the u32 range does not overflow the twenty-minute rule
+    let params: ChainParams = ChainParams { allow_min_difficulty_blocks: true, ..EASY_CHAIN };
+    let chain: HeaderChain = HeaderChain::from_trusted(&BlockHeader { time: u32::MAX - 60, ..easy_anchor(0x1f7fffff) }, 100, &params).unwrap();
+    assert_eq!(chain.next_bits(u32::MAX), Ok(0x1f7fffff));
+}
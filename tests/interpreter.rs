@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use walletcryptography::transaction::{self, OutPoint, Transaction, TxIn, TxOut};
+use walletcryptography::script::{Builder, Instruction, Opcode, Script, ScriptType};
+use walletcryptography::interpreter::{self, InterpreterError};
+use walletcryptography::taproot::{self, TAPSCRIPT_LEAF_VERSION};
+use walletcryptography::base16;
+use serde_json::Value;
+
+// the script verification flags that are consensus today, which the interpreter always applies
+const CONSENSUS_FLAGS: [&str; 8] = ["NONE", "P2SH", "DERSIG", "NULLDUMMY", "CHECKLOCKTIMEVERIFY", "CHECKSEQUENCEVERIFY", "WITNESS", "TAPROOT"];
+
+// Bitcoin Core's test notation: decimal numbers, raw 0x bytes, quoted strings and
+// opcode names with or without OP_
+fn parse_script(s: &str) -> Script {
+    let mut bytes: Vec<u8> = vec![];
+    for word in s.split_whitespace() {
+        if let Ok(n) = word.parse::<i64>() {
+            bytes.extend(Builder::new().push_int(n).into_script().bytes);
+        }
+        else if let Some(raw) = word.strip_prefix("0x") {
+            bytes.extend(base16::decode_string(raw));
+        }
+        else if word.len() >= 2 && word.starts_with('\'') && word.ends_with('\'') {
+            bytes.extend(Builder::new().push_slice(&word.as_bytes()[1..word.len() - 1]).into_script().bytes);
+        }
+        else {
+            bytes.push(Opcode::from_name(word).unwrap_or_else(|| panic!("opcode {}", word)).to_byte());
+        }
+    }
+    return Script::from_bytes(&bytes);
+}
+
+fn flags(s: &str) -> Vec<&str> {
+    return s.split(',').filter(|f: &&str| !f.is_empty()).collect();
+}
+
+fn expected_error(name: &str) -> InterpreterError {
+    match name {
+        "BAD_OPCODE" => return InterpreterError::BadOpcode,
+        "CLEANSTACK" => return InterpreterError::CleanStack,
+        "DISABLED_OPCODE" => return InterpreterError::DisabledOpcode,
+        "EQUALVERIFY" => return InterpreterError::Verify(Opcode::EqualVerify),
+        "NUMEQUALVERIFY" => return InterpreterError::Verify(Opcode::NumEqualVerify),
+        "CHECKSIGVERIFY" => return InterpreterError::Verify(Opcode::CheckSigVerify),
+        "CHECKMULTISIGVERIFY" => return InterpreterError::Verify(Opcode::CheckMultisigVerify),
+        "VERIFY" => return InterpreterError::Verify(Opcode::Verify),
+        "EVAL_FALSE" => return InterpreterError::EvalFalse,
+        "INVALID_ALTSTACK_OPERATION" => return InterpreterError::InvalidAltstackOperation,
+        "INVALID_STACK_OPERATION" => return InterpreterError::InvalidStackOperation,
+        "NEGATIVE_LOCKTIME" => return InterpreterError::NegativeLockTime,
+        "UNSATISFIED_LOCKTIME" => return InterpreterError::UnsatisfiedLockTime,
+        "OP_COUNT" => return InterpreterError::OpCount,
+        "OP_RETURN" => return InterpreterError::OpReturn,
+        "PUBKEY_COUNT" => return InterpreterError::PubkeyCount,
+        "PUSH_SIZE" => return InterpreterError::PushSize,
+        "SCRIPTNUM" => return InterpreterError::NumberOverflow,
+        "SCRIPT_SIZE" => return InterpreterError::ScriptSize,
+        "SIG_COUNT" => return InterpreterError::SigCount,
+        "SIG_DER" => return InterpreterError::SigDer,
+        "SIG_NULLDUMMY" => return InterpreterError::SigNullDummy,
+        "SIG_PUSHONLY" => return InterpreterError::SigPushOnly,
+        "STACK_SIZE" => return InterpreterError::StackSize,
+        "UNBALANCED_CONDITIONAL" => return InterpreterError::UnbalancedConditional,
+        "TAPSCRIPT_EMPTY_PUBKEY" => return InterpreterError::TapscriptEmptyPubkey,
+        "WITNESS_MALLEATED" => return InterpreterError::WitnessMalleated,
+        "WITNESS_MALLEATED_P2SH" => return InterpreterError::WitnessMalleatedP2sh,
+        "WITNESS_PROGRAM_MISMATCH" => return InterpreterError::WitnessProgramMismatch,
+        "WITNESS_PROGRAM_WITNESS_EMPTY" => return InterpreterError::WitnessProgramWitnessEmpty,
+        "WITNESS_PROGRAM_WRONG_LENGTH" => return InterpreterError::WitnessProgramWrongLength,
+        "WITNESS_UNEXPECTED" => return InterpreterError::WitnessUnexpected,
+        _ => panic!("error {}", name)
+    };
+}
+
+// a single-leaf tree under the key of private key 1, as Core builds for #SCRIPT# witnesses
+fn tapscript_spend(script: &[u8]) -> (Vec<u8>, Script) {
+    let internal_key: [u8; 32] = taproot::x_only_public_key("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+    let leaf_hash: [u8; 32] = taproot::tap_leaf_hash(script, TAPSCRIPT_LEAF_VERSION);
+    let (output_key, odd) = taproot::tweak_public_key(&internal_key, Some(&leaf_hash)).unwrap();
+    let mut control: Vec<u8> = vec![TAPSCRIPT_LEAF_VERSION | odd as u8];
+    control.extend_from_slice(&internal_key);
+    return (control, Builder::new().push_int(1).push_slice(&output_key).into_script());
+}
+
+// the spend of a coinbase paying the scriptPubKey, with every lock time 0 and every sequence final
+fn verify_script(script_sig: Script, script_pubkey: Script, witness: Vec<Vec<u8>>, value: u64) -> Result<(), InterpreterError> {
+    let mut credit: Transaction = Transaction::new(1, 0);
+    credit.inputs = vec![TxIn::new(OutPoint::null())];
+    credit.inputs[0].script_sig = Builder::new().push_int(0).push_int(0).into_script();
+    credit.outputs = vec![TxOut { value, script_pubkey: script_pubkey.clone() }];
+
+    let mut spend: Transaction = Transaction::new(1, 0);
+    spend.inputs = vec![TxIn::new(OutPoint::new(credit.txid(), 0))];
+    spend.inputs[0].script_sig = script_sig;
+    spend.inputs[0].witness = witness;
+    spend.outputs = vec![TxOut { value, script_pubkey: Script::default() }];
+    return interpreter::verify_input(&spend, 0, &credit.outputs);
+}
+
+// script_tests.json from Bitcoin Core. a failure under fewer flags than the consensus
+// ones is a failure here too; failures under policy flags are skipped
+#[test]
+
+fn interpreter_core_script_tests() {
+    let tests: Value = serde_json::from_str(include_str!("fixtures/core_script_tests.json")).unwrap();
+    let mut checked: usize = 0;
+
+    for test in tests.as_array().unwrap().iter().filter(|t: &&Value| t.as_array().unwrap().len() > 1) {
+        let mut fields: Vec<Value> = test.as_array().unwrap().clone();
+        let mut witness: Vec<Vec<u8>> = vec![];
+        let mut value: u64 = 0;
+        let mut taproot_output: Option<Script> = None;
+        if let Value::Array(items) = fields.remove(0) {
+            let (amount, items) = items.split_last().unwrap();
+            value = (amount.as_f64().unwrap() * 100_000_000.0).round() as u64;
+            for item in items.iter().map(|i: &Value| i.as_str().unwrap()) {
+                if let Some(script) = item.strip_prefix("#SCRIPT#") {
+                    witness.push(parse_script(script).bytes);
+                }
+                else if item == "#CONTROLBLOCK#" {
+                    let (control, output) = tapscript_spend(witness.last().unwrap());
+                    witness.push(control);
+                    taproot_output = Some(output);
+                }
+                else {
+                    witness.push(base16::decode_string(item));
+                }
+            }
+        }
+        else {
+            fields.insert(0, test[0].clone());
+        }
+        let script_sig: Script = parse_script(fields[0].as_str().unwrap());
+        let script_pubkey: Script = match fields[1].as_str().unwrap() {
+            "0x51 0x20 #TAPROOTOUTPUT#" => taproot_output.clone().unwrap(),
+            s => parse_script(s)
+        };
+        let test_flags: Vec<&str> = flags(fields[2].as_str().unwrap());
+        let expected: &str = fields[3].as_str().unwrap();
+        let consensus_only: bool = test_flags.iter().all(|f: &&str| CONSENSUS_FLAGS.contains(f));
+        let lock_time_opcode: bool = [&script_sig, &script_pubkey].iter().any(|s: &&Script| s.instructions().unwrap_or_default().iter().any(|i: &Instruction| {
+            return matches!(i, Instruction::Op(Opcode::CheckLockTimeVerify | Opcode::CheckSequenceVerify));
+        }));
+        let p2sh: bool = matches!(script_pubkey.script_type(), ScriptType::P2sh(_));
+        let redeem_script: Option<Script> = match script_sig.instructions().unwrap_or_default().last() {
+            Some(Instruction::Push(_, data)) => Some(Script::from_bytes(data)),
+            _ => None
+        };
+        let segwit: bool = !witness.is_empty() || script_pubkey.witness_program().is_some() || (p2sh && redeem_script.is_some_and(|s: Script| s.witness_program().is_some()));
+
+        let result: Result<(), InterpreterError> = verify_script(script_sig, script_pubkey, witness, value);
+        // the test may leave out a consensus flag that fails it, or fails it earlier, here
+        let missing = |flag: &str| !test_flags.contains(&flag);
+        let excused: bool = match result {
+            Err(InterpreterError::SigDer) => missing("DERSIG"),
+            Err(InterpreterError::SigNullDummy) => missing("NULLDUMMY"),
+            Err(_) => (lock_time_opcode && (missing("CHECKLOCKTIMEVERIFY") || missing("CHECKSEQUENCEVERIFY"))) || (p2sh && missing("P2SH")) || (segwit && missing("WITNESS")),
+            Ok(()) => false
+        };
+        if expected == "OK" {
+            if excused {
+                continue;
+            }
+            assert_eq!(result, Ok(()), "{}", test);
+        }
+        else if consensus_only {
+            if excused {
+                assert!(result.is_err(), "{}", test);
+            }
+            else {
+                assert_eq!(result, Err(expected_error(expected)), "{}", test);
+            }
+        }
+        else {
+            continue;
+        }
+        checked += 1;
+    }
+    assert!(checked > 700, "{}", checked);
+}
+
+fn prevouts(test: &Value, tx: &Transaction) -> Vec<TxOut> {
+    let mut spent: HashMap<OutPoint, TxOut> = HashMap::new();
+    for prevout in test[0].as_array().unwrap() {
+        let outpoint: OutPoint = OutPoint::new(transaction::hash_from_hex(prevout[0].as_str().unwrap()).unwrap(), prevout[1].as_i64().unwrap() as u32);
+        let value: u64 = prevout.get(3).map(|v: &Value| v.as_u64().unwrap()).unwrap_or(0);
+        spent.insert(outpoint, TxOut { value, script_pubkey: parse_script(prevout[2].as_str().unwrap()) });
+    }
+    return tx.inputs.iter().map(|i: &TxIn| spent[&i.previous_output].clone()).collect();
+}
+
+// tx_valid.json from Bitcoin Core: valid with every flag but the excluded ones
+#[test]
+
+fn interpreter_core_tx_valid() {
+    let tests: Value = serde_json::from_str(include_str!("fixtures/core_tx_valid.json")).unwrap();
+
+    for test in tests.as_array().unwrap().iter().filter(|t: &&Value| t[0].is_array()) {
+        if flags(test[2].as_str().unwrap()).iter().any(|f: &&str| CONSENSUS_FLAGS[1..].contains(f)) {
+            continue;
+        }
+        let tx: Transaction = Transaction::from_hex(test[1].as_str().unwrap()).unwrap();
+        let prevouts: Vec<TxOut> = prevouts(test, &tx);
+        for i in 0..tx.inputs.len() {
+            assert_eq!(interpreter::verify_input(&tx, i, &prevouts), Ok(()), "input {} of {}", i, test);
+        }
+    }
+}
+
+// tx_invalid.json from Bitcoin Core: some input fails under the given flags
+#[test]
+
+fn interpreter_core_tx_invalid() {
+    let tests: Value = serde_json::from_str(include_str!("fixtures/core_tx_invalid.json")).unwrap();
+
+    for test in tests.as_array().unwrap().iter().filter(|t: &&Value| t[0].is_array()) {
+        // BADTX ones fail the transaction checks, before any script runs
+        if !flags(test[2].as_str().unwrap()).iter().all(|f: &&str| CONSENSUS_FLAGS.contains(f)) {
+            continue;
+        }
+        let tx: Transaction = Transaction::from_hex(test[1].as_str().unwrap()).unwrap();
+        let prevouts: Vec<TxOut> = prevouts(test, &tx);
+        assert!((0..tx.inputs.len()).any(|i: usize| interpreter::verify_input(&tx, i, &prevouts).is_err()), "{}", test);
+    }
+}
+
+// the key path spends of the BIP341 example, under every sighash type
+#[test]
+
+fn interpreter_bip341_key_path() {
+    let vectors: Value = serde_json::from_str(include_str!("fixtures/bip341_vectors.json")).unwrap();
+    let vector: &Value = &vectors["keyPathSpending"][0];
+    let mut tx: Transaction = Transaction::from_hex(vector["auxiliary"]["fullySignedTx"].as_str().unwrap()).unwrap();
+    let prevouts: Vec<TxOut> = vector["given"]["utxosSpent"].as_array().unwrap().iter().map(|u: &Value| TxOut {
+        value: u["amountSats"].as_u64().unwrap(),
+        script_pubkey: Script::from_hex(u["scriptPubKey"].as_str().unwrap()).unwrap()
+    }).collect();
+
+    let indices: Vec<usize> = vector["inputSpending"].as_array().unwrap().iter().map(|i: &Value| i["given"]["txinIndex"].as_u64().unwrap() as usize).collect();
+    for index in indices.iter() {
+        assert_eq!(interpreter::verify_input(&tx, *index, &prevouts), Ok(()), "input {}", index);
+    }
+
+    // the amounts are signed
+    let mut other: Vec<TxOut> = prevouts.clone();
+    other[0].value += 1;
+    assert_eq!(interpreter::verify_input(&tx, indices[0], &other), Err(InterpreterError::SchnorrSig));
+    assert_eq!(interpreter::verify_input(&tx, indices[0], &prevouts[1..]), Err(InterpreterError::PrevoutsCount(prevouts.len() - 1, prevouts.len())));
+
+    tx.inputs[indices[0]].witness[0][10] ^= 1;
+    assert_eq!(interpreter::verify_input(&tx, indices[0], &prevouts), Err(InterpreterError::SchnorrSig));
+    // SIGHASH_DEFAULT must not be written out
+    tx.inputs[indices[0]].witness[0].truncate(64);
+    tx.inputs[indices[0]].witness[0].push(0x00);
+    assert_eq!(interpreter::verify_input(&tx, indices[0], &prevouts), Err(InterpreterError::SchnorrSigHashType));
+    tx.inputs[indices[0]].witness.clear();
+    assert_eq!(interpreter::verify_input(&tx, indices[0], &prevouts), Err(InterpreterError::WitnessProgramWitnessEmpty));
+}
@@ -0,0 +1,74 @@
+use walletcryptography::block::Block;
+use walletcryptography::merkle::{self, MerkleBlock, MerkleError, MerkleProof};
+use walletcryptography::transaction;
+use serde_json::Value;
+
+fn hashes(values: &Value) -> Vec<[u8; 32]> {
+    return values.as_array().unwrap().iter().map(|v: &Value| transaction::hash_from_hex(v.as_str().unwrap()).unwrap()).collect();
+}
+
+#[test]
+
+fn merkle_block_fixtures() {
+    let fixtures: Value = serde_json::from_str(include_str!("fixtures/merkle_blocks.json")).unwrap();
+
+    for fixture in fixtures.as_array().unwrap() {
+        let description: &str = fixture["description"].as_str().unwrap();
+        let block: Block = Block::from_hex(fixture["block"].as_str().unwrap()).unwrap();
+        let txids: Vec<[u8; 32]> = hashes(&fixture["txids"]);
+        let root: [u8; 32] = transaction::hash_from_hex(fixture["merkle_root"].as_str().unwrap()).unwrap();
+
+        assert_eq!(transaction::hash_to_hex(&block.block_hash()), fixture["block_hash"].as_str().unwrap(), "{}", description);
+        assert_eq!(block.txids(), txids, "{}", description);
+        assert_eq!(block.header.merkle_root, root, "{}", description);
+        assert_eq!(merkle::compute_merkle_root(&txids), Some((root, false)), "{}", description);
+        for i in 0..txids.len() {
+            let proof: MerkleProof = merkle::merkle_proof(&txids, i).unwrap();
+            assert!(proof.verify(&txids[i], &root), "{}", description);
+        }
+
+        // what bitcoin core's gettxoutproof returned, and the same proof built here
+        let matched: Vec<(usize, [u8; 32])> = fixture["matched"].as_array().unwrap().iter().map(|m: &Value| (
+            m["index"].as_u64().unwrap() as usize,
+            transaction::hash_from_hex(m["txid"].as_str().unwrap()).unwrap()
+        )).collect();
+        let hex: &str = fixture["txoutproof"].as_str().unwrap();
+        let merkle_block: MerkleBlock = MerkleBlock::from_hex(hex).unwrap();
+        assert_eq!(merkle_block.header, block.header, "{}", description);
+        assert_eq!(merkle_block.verify(), Ok(matched.clone()), "{}", description);
+        assert_eq!(merkle_block.to_hex(), hex, "{}", description);
+        let ids: Vec<[u8; 32]> = matched.iter().map(|(_, txid)| *txid).collect();
+        assert_eq!(MerkleBlock::from_block(&block, &ids).to_hex(), hex, "{}", description);
+    }
+}
+
+#[test]
+
+fn merkle_block_invalid() {
+    let fixtures: Value = serde_json::from_str(include_str!("fixtures/merkle_blocks.json")).unwrap();
+    let hex: &str = fixtures[0]["txoutproof"].as_str().unwrap();
+    let merkle_block: MerkleBlock = MerkleBlock::from_hex(hex).unwrap();
+
+    // a header that does not commit to the tree
+    let mut changed: MerkleBlock = merkle_block.clone();
+    changed.header.merkle_root[0] ^= 1;
+    assert_eq!(changed.verify(), Err(MerkleError::RootMismatch));
+
+    // a changed hash changes the root
+    let mut changed: MerkleBlock = merkle_block.clone();
+    changed.tree.hashes[0][0] ^= 1;
+    assert_eq!(changed.verify(), Err(MerkleError::RootMismatch));
+
+    // a whole extra flag byte is more than padding
+    let mut changed: MerkleBlock = merkle_block.clone();
+    changed.tree.flags.extend([false; 8]);
+    assert_eq!(changed.verify(), Err(MerkleError::UnusedBits));
+
+    let mut changed: MerkleBlock = merkle_block.clone();
+    changed.tree.total_transactions = 20000;
+    assert_eq!(changed.verify(), Err(MerkleError::TooManyTransactions(20000)));
+
+    assert!(MerkleBlock::from_hex(&hex[..hex.len() - 2]).is_err());
+    assert!(MerkleBlock::from_hex(&format!("{}00", hex)).is_err());
+    assert!(MerkleBlock::from_hex("zz").is_err());
+}
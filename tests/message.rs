@@ -0,0 +1,26 @@
+use walletcryptography::message::{self, MessageError, SignatureFormat};
+use serde_json::Value;
+
+fn vectors() -> Value {
+    return serde_json::from_str(include_str!("fixtures/bip322_vectors.json")).unwrap();
+}
+
+fn verify(vector: &Value) -> Result<(SignatureFormat, bool), MessageError> {
+    return message::verify(vector["address"].as_str().unwrap(), vector["signature"].as_str().unwrap(), vector["message"].as_str().unwrap());
+}
+
+#[test]
+
+fn message_bip322_valid_vectors() {
+    for vector in vectors()["valid"].as_array().unwrap() {
+        assert!(matches!(verify(vector), Ok((_, true))), "{}", vector["address"]);
+    }
+}
+
+#[test]
+
+fn message_bip322_invalid_vectors() {
+    for vector in vectors()["invalid"].as_array().unwrap() {
+        assert!(!matches!(verify(vector), Ok((_, true))), "{}", vector["description"]);
+    }
+}
@@ -0,0 +1,97 @@
+use walletcryptography::psbt::{Input, Psbt};
+use walletcryptography::bip32::ExtendedPrivateKey;
+use walletcryptography::base16;
+use serde_json::Value;
+
+fn vectors() -> Value {
+    return serde_json::from_str(include_str!("fixtures/psbt_vectors.json")).unwrap();
+}
+
+#[test]
+
+fn psbt_bip174_valid_vectors() {
+    for vector in vectors()["valid"].as_array().unwrap() {
+        let hex: &str = vector["hex"].as_str().unwrap();
+        let psbt: Psbt = Psbt::from_hex(hex).unwrap();
+        assert_eq!(psbt.to_hex(), hex, "{}", vector["description"]);
+        if let Some(base64) = vector["base64"].as_str() {
+            assert_eq!(psbt.to_base64(), base64);
+            assert_eq!(base64.parse::<Psbt>().unwrap(), psbt);
+        }
+    }
+}
+
+#[test]
+
+fn psbt_bip174_invalid_vectors() {
+    for vector in vectors()["invalid"].as_array().unwrap() {
+        let error: String = format!("{:?}", Psbt::from_hex(vector["hex"].as_str().unwrap()).unwrap_err());
+        assert!(error.starts_with(vector["error"].as_str().unwrap()), "{}: {}", vector["description"], error);
+    }
+}
+
+#[test]
+
+fn psbt_bip370_valid_vectors() {
+    for vector in vectors()["valid_v2"].as_array().unwrap() {
+        let hex: &str = vector["hex"].as_str().unwrap();
+        let psbt: Psbt = Psbt::from_hex(hex).unwrap();
+        assert_eq!(psbt.version, 2, "{}", vector["description"]);
+        assert_eq!(psbt.to_hex(), hex, "{}", vector["description"]);
+        assert_eq!(psbt.to_base64(), vector["base64"].as_str().unwrap());
+
+        // the same transaction as a version 0 PSBT
+        let v0: Psbt = psbt.to_v0().unwrap();
+        assert_eq!(v0.unsigned_tx().unwrap(), psbt.unsigned_tx().unwrap(), "{}", vector["description"]);
+        assert_eq!(Psbt::from_hex(&v0.to_hex()).unwrap(), v0);
+    }
+}
+
+#[test]
+
+fn psbt_bip370_invalid_vectors() {
+    for vector in vectors()["invalid_v2"].as_array().unwrap() {
+        let error: String = format!("{:?}", Psbt::from_hex(vector["hex"].as_str().unwrap()).unwrap_err());
+        assert!(error.starts_with(vector["error"].as_str().unwrap()), "{}: {}", vector["description"], error);
+    }
+}
+
+#[test]
+
+fn psbt_bip174_combiner() {
+    let vector: Value = vectors()["combiner"].clone();
+    let mut psbt: Psbt = Psbt::from_hex(vector["psbts"][0].as_str().unwrap()).unwrap();
+    psbt.combine(&Psbt::from_hex(vector["psbts"][1].as_str().unwrap()).unwrap()).unwrap();
+    assert_eq!(psbt, Psbt::from_hex(vector["combined"].as_str().unwrap()).unwrap());
+
+    let other: Psbt = Psbt::from_hex(vectors()["valid"][0]["hex"].as_str().unwrap()).unwrap();
+    assert!(psbt.combine(&other).is_err());
+}
+
+#[test]
+
+fn psbt_bip174_signer() {
+    let vectors: Value = vectors();
+    let master: ExtendedPrivateKey = vectors["signer"]["xprv"].as_str().unwrap().parse().unwrap();
+    assert_eq!(base16::encode_bytes(&master.fingerprint()), vectors["signer"]["fingerprint"].as_str().unwrap());
+
+    // the published signatures are RFC6979, so signing the unsigned PSBT again gives the same ones
+    let expected: Psbt = Psbt::from_hex(vectors["finalizer"]["psbt"].as_str().unwrap()).unwrap();
+    let mut psbt: Psbt = expected.clone();
+    psbt.inputs.iter_mut().for_each(|i: &mut Input| i.partial_sigs.clear());
+    assert_eq!(psbt.sign(&master).unwrap(), vec![0, 1]);
+    assert_eq!(psbt, expected);
+}
+
+#[test]
+
+fn psbt_bip174_finalizer_and_extractor() {
+    let vector: Value = vectors()["finalizer"].clone();
+    let mut psbt: Psbt = Psbt::from_hex(vector["psbt"].as_str().unwrap()).unwrap();
+    assert!(psbt.extract().is_err());
+
+    psbt.finalize().unwrap();
+    assert!(psbt.is_finalized());
+    assert_eq!(psbt.to_hex(), vector["finalized"].as_str().unwrap());
+    assert_eq!(psbt.extract().unwrap().to_hex(), vector["transaction"].as_str().unwrap());
+}
@@ -0,0 +1,40 @@
+use walletcryptography::transaction::{Transaction, TxOut};
+use walletcryptography::script::Script;
+use walletcryptography::sighash::{self, SighashType};
+use walletcryptography::{base16, sign, taproot};
+use serde_json::Value;
+
+fn array(s: &str) -> [u8; 32] {
+    let mut r: [u8; 32] = [0; 32];
+    r.copy_from_slice(&base16::decode_string(s));
+    return r;
+}
+
+#[test]
+
+fn sighash_bip341_key_path_vectors() {
+    let vectors: Value = serde_json::from_str(include_str!("fixtures/bip341_vectors.json")).unwrap();
+
+    for vector in vectors["keyPathSpending"].as_array().unwrap() {
+        let tx: Transaction = Transaction::from_hex(vector["given"]["rawUnsignedTx"].as_str().unwrap()).unwrap();
+        let prevouts: Vec<TxOut> = vector["given"]["utxosSpent"].as_array().unwrap().iter().map(|u: &Value| TxOut {
+            value: u["amountSats"].as_u64().unwrap(),
+            script_pubkey: Script::from_hex(u["scriptPubKey"].as_str().unwrap()).unwrap()
+        }).collect();
+
+        for input in vector["inputSpending"].as_array().unwrap() {
+            let index: usize = input["given"]["txinIndex"].as_u64().unwrap() as usize;
+            let sighash_type: SighashType = SighashType::from_u8(input["given"]["hashType"].as_u64().unwrap() as u8).unwrap();
+            let merkle_root: Option<[u8; 32]> = input["given"]["merkleRoot"].as_str().map(array);
+
+            let sighash: [u8; 32] = sighash::taproot_sighash(&tx, index, &prevouts, None, None, sighash_type).unwrap();
+            assert_eq!(base16::encode_bytes(&sighash), input["intermediary"]["sigHash"].as_str().unwrap(), "input {}", index);
+
+            // the published signatures use all-zero auxiliary randomness
+            let tweaked: [u8; 32] = taproot::tweak_private_key(&array(input["given"]["internalPrivkey"].as_str().unwrap()), merkle_root.as_ref()).unwrap();
+            assert_eq!(base16::encode_bytes(&tweaked), input["intermediary"]["tweakedPrivkey"].as_str().unwrap(), "input {}", index);
+            let signature: Vec<u8> = sign::schnorr_signature(&sighash, &tweaked, sighash_type, &[0; 32]).unwrap();
+            assert_eq!(base16::encode_bytes(&signature), input["expected"]["witness"][0].as_str().unwrap(), "input {}", index);
+        }
+    }
+}
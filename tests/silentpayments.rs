@@ -0,0 +1,148 @@
+use secp256k1::{PublicKey, Secp256k1, SecretKey, Scalar};
+use walletcryptography::silentpayments::{self, FoundOutput, InputKey, Receiver, SilentPaymentAddress, SilentPaymentError};
+use walletcryptography::transaction::{self, OutPoint, TxIn};
+use walletcryptography::script::Script;
+use walletcryptography::bytes::Reader;
+use walletcryptography::crypto::hashing;
+use walletcryptography::sighash::SighashType;
+use walletcryptography::{base16, sign};
+use walletcryptography::app::Network;
+use serde_json::Value;
+
+fn array(s: &str) -> [u8; 32] {
+    return base16::decode_string(s).try_into().unwrap();
+}
+
+// the inputs of a vector and the scriptPubKeys they spend
+fn inputs(vin: &Value) -> (Vec<TxIn>, Vec<Vec<u8>>) {
+    let mut txins: Vec<TxIn> = vec![];
+    let mut scripts: Vec<Vec<u8>> = vec![];
+    for input in vin.as_array().unwrap() {
+        let outpoint: OutPoint = OutPoint::new(transaction::hash_from_hex(input["txid"].as_str().unwrap()).unwrap(), input["vout"].as_u64().unwrap() as u32);
+        let mut txin: TxIn = TxIn::new(outpoint);
+        txin.script_sig = Script::from_bytes(&base16::decode_string(input["scriptSig"].as_str().unwrap()));
+        // the witness stack as serialized in a transaction
+        let witness: Vec<u8> = base16::decode_string(input["txinwitness"].as_str().unwrap());
+        let mut reader: Reader = Reader::new(&witness);
+        if !witness.is_empty() {
+            for _ in 0..reader.read_compact_size().unwrap() {
+                txin.witness.push(reader.read_var_bytes().unwrap().to_vec());
+            }
+        }
+        txins.push(txin);
+        scripts.push(base16::decode_string(input["prevout"]["scriptPubKey"]["hex"].as_str().unwrap()));
+    }
+    return (txins, scripts);
+}
+
+fn sorted(mut values: Vec<String>) -> Vec<String> {
+    values.sort();
+    return values;
+}
+
+#[test]
+
+fn silentpayments_bip352_sending() {
+    let vectors: Value = serde_json::from_str(include_str!("fixtures/bip352_vectors.json")).unwrap();
+
+    for vector in vectors.as_array().unwrap() {
+        let comment: &str = vector["comment"].as_str().unwrap();
+        for sending in vector["sending"].as_array().unwrap() {
+            let (txins, scripts) = inputs(&sending["given"]["vin"]);
+            let mut keys: Vec<InputKey> = vec![];
+            let mut input_pub_keys: Vec<String> = vec![];
+            for ((txin, script), input) in txins.iter().zip(scripts.iter()).zip(sending["given"]["vin"].as_array().unwrap()) {
+                if let Some(key) = silentpayments::input_public_key(txin, script) {
+                    input_pub_keys.push(base16::encode_bytes(&key.serialize()));
+                    keys.push(InputKey { secret_key: array(input["private_key"].as_str().unwrap()), taproot: script[0] == 0x51 });
+                }
+            }
+            let expected: Vec<String> = sending["expected"]["input_pub_keys"].as_array().unwrap().iter().map(|k: &Value| k.as_str().unwrap().to_string()).collect();
+            assert_eq!(input_pub_keys, expected, "{}", comment);
+
+            // a recipient may be paid count times
+            let recipients: Vec<SilentPaymentAddress> = sending["given"]["recipients"].as_array().unwrap().iter().flat_map(|r: &Value| {
+                let address: SilentPaymentAddress = SilentPaymentAddress::decode(r["address"].as_str().unwrap()).unwrap();
+                assert_eq!(base16::encode_bytes(&address.scan_key.serialize()), r["scan_pub_key"].as_str().unwrap(), "{}", comment);
+                assert_eq!(base16::encode_bytes(&address.spend_key.serialize()), r["spend_pub_key"].as_str().unwrap(), "{}", comment);
+                vec![address; r["count"].as_u64().unwrap_or(1) as usize]
+            }).collect();
+            let outpoints: Vec<OutPoint> = txins.iter().map(|i: &TxIn| i.previous_output).collect();
+
+            // any of the expected sets, as recipients sharing a scan key may be paid in any order
+            let sets: Vec<Vec<String>> = sending["expected"]["outputs"].as_array().unwrap().iter()
+                .map(|set: &Value| sorted(set.as_array().unwrap().iter().map(|o: &Value| o.as_str().unwrap().to_string()).collect()))
+                .collect();
+            match silentpayments::sender_outputs(&outpoints, &keys, &recipients) {
+                Ok(outputs) => assert!(sets.contains(&sorted(outputs.iter().map(|o: &[u8; 32]| base16::encode_bytes(o)).collect())), "{}", comment),
+                Err(e) => {
+                    assert_eq!(sets, vec![Vec::<String>::new()], "{}", comment);
+                    assert!(matches!(e, SilentPaymentError::NoEligibleInputs | SilentPaymentError::ZeroInputSum | SilentPaymentError::TooManyRecipients(_)), "{}", comment);
+                }
+            };
+        }
+    }
+}
+
+#[test]
+
+fn silentpayments_bip352_receiving() {
+    let vectors: Value = serde_json::from_str(include_str!("fixtures/bip352_vectors.json")).unwrap();
+    let secp = Secp256k1::new();
+    // the message and auxiliary randomness the vectors sign with
+    let message: [u8; 32] = hashing::sha256(b"message");
+    let aux_rand: [u8; 32] = hashing::sha256(b"random auxiliary data");
+
+    for vector in vectors.as_array().unwrap() {
+        let comment: &str = vector["comment"].as_str().unwrap();
+        for receiving in vector["receiving"].as_array().unwrap() {
+            let given: &Value = &receiving["given"];
+            let expected: &Value = &receiving["expected"];
+            let scan_secret: [u8; 32] = array(given["key_material"]["scan_priv_key"].as_str().unwrap());
+            let spend_secret: SecretKey = SecretKey::from_slice(&array(given["key_material"]["spend_priv_key"].as_str().unwrap())).unwrap();
+            let mut receiver: Receiver = Receiver::new(&scan_secret, &spend_secret.public_key(&secp), Network::Bitcoin).unwrap();
+
+            let mut addresses: Vec<String> = vec![receiver.address().encode()];
+            for m in given["labels"].as_array().unwrap() {
+                let m: u32 = m.as_u64().unwrap() as u32;
+                receiver.add_label(m).unwrap();
+                addresses.push(receiver.labelled_address(m).unwrap().encode());
+            }
+            let expected_addresses: Vec<String> = expected["addresses"].as_array().unwrap().iter().map(|a: &Value| a.as_str().unwrap().to_string()).collect();
+            assert_eq!(sorted(addresses), sorted(expected_addresses), "{}", comment);
+
+            let (txins, scripts) = inputs(&given["vin"]);
+            let tweak: PublicKey = match silentpayments::transaction_tweak(&txins, &scripts) {
+                Ok(t) => t,
+                Err(_) => {
+                    assert!(expected["tweak"].is_null(), "{}", comment);
+                    assert_eq!(expected["outputs"].as_array().unwrap().len(), 0, "{}", comment);
+                    continue;
+                }
+            };
+            assert_eq!(base16::encode_bytes(&tweak.serialize()), expected["tweak"].as_str().unwrap(), "{}", comment);
+            assert_eq!(base16::encode_bytes(&receiver.shared_secret(&tweak).serialize()), expected["shared_secret"].as_str().unwrap(), "{}", comment);
+
+            let outputs: Vec<[u8; 32]> = given["outputs"].as_array().unwrap().iter().map(|o: &Value| array(o.as_str().unwrap())).collect();
+            let found: Vec<FoundOutput> = receiver.scan(&tweak, &outputs);
+            // past the recipient limit only the number of outputs found is given
+            if let Some(n) = expected["n_outputs"].as_u64() {
+                assert_eq!(found.len() as u64, n, "{}", comment);
+                continue;
+            }
+            let expected_outputs: &Vec<Value> = expected["outputs"].as_array().unwrap();
+            assert_eq!(found.len(), expected_outputs.len(), "{}", comment);
+            for output in expected_outputs.iter() {
+                let pub_key: [u8; 32] = array(output["pub_key"].as_str().unwrap());
+                let f: &FoundOutput = found.iter().find(|f: &&FoundOutput| f.output_key == pub_key).expect(comment);
+                assert_eq!(base16::encode_bytes(&f.tweak), output["priv_key_tweak"].as_str().unwrap(), "{}", comment);
+
+                // the receiver can spend it with b_spend + tweak
+                let key: [u8; 32] = spend_secret.add_tweak(&Scalar::from_be_bytes(f.tweak).unwrap()).unwrap().secret_bytes();
+                let signature: Vec<u8> = sign::schnorr_signature(&message, &key, SighashType::Default, &aux_rand).unwrap();
+                assert_eq!(base16::encode_bytes(&signature), output["signature"].as_str().unwrap(), "{}", comment);
+                assert!(sign::verify_schnorr_signature(&message, &signature, &pub_key), "{}", comment);
+            }
+        }
+    }
+}
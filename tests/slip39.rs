@@ -0,0 +1,41 @@
+use walletcryptography::slip39;
+use walletcryptography::base16;
+use walletcryptography::bip32::ExtendedPrivateKey;
+use serde_json::Value;
+
+#[test]
+
+fn slip39_reference_vectors() {
+    let vectors: Value = serde_json::from_str(include_str!("fixtures/slip39_vectors.json")).unwrap();
+
+    for vector in vectors.as_array().unwrap() {
+        let description: &str = vector[0].as_str().unwrap();
+        let mnemonics: Vec<&str> = vector[1].as_array().unwrap().iter().map(|m| m.as_str().unwrap()).collect();
+        let expected: &str = vector[2].as_str().unwrap();
+        let xprv: &str = vector[3].as_str().unwrap();
+
+        let r = slip39::combine(&mnemonics, "TREZOR");
+        if expected.is_empty() {
+            assert!(r.is_err(), "{}", description);
+        }
+        else {
+            let secret: Vec<u8> = r.unwrap();
+            assert_eq!(base16::encode_bytes(&secret), expected, "{}", description);
+            assert_eq!(ExtendedPrivateKey::from_seed(&secret).to_xprv(), xprv, "{}", description);
+        }
+    }
+}
+
+#[test]
+
+fn slip39_extendable_vectors() {
+    let vectors: Value = serde_json::from_str(include_str!("fixtures/slip39_vectors.json")).unwrap();
+
+    // vector 42 is an extendable backup with iteration exponent 3, 43 to 45 are extendable too
+    let share = slip39::Share::from_mnemonic(vectors[41][1][0].as_str().unwrap()).unwrap();
+    assert_eq!((share.extendable, share.iteration_exponent), (true, 3));
+    for vector in vectors.as_array().unwrap()[42..].iter() {
+        let share = slip39::Share::from_mnemonic(vector[1][0].as_str().unwrap()).unwrap();
+        assert!(share.extendable, "{}", vector[0]);
+    }
+}
@@ -0,0 +1,54 @@
+use walletcryptography::transaction::{self, Transaction};
+use walletcryptography::script::{Instruction, Script};
+use walletcryptography::crypto::bitcoin;
+use walletcryptography::{base16, sighash, sign};
+use serde_json::Value;
+
+#[test]
+
+fn transaction_mainnet_fixtures() {
+    let fixtures: Value = serde_json::from_str(include_str!("fixtures/mainnet_transactions.json")).unwrap();
+
+    for fixture in fixtures.as_array().unwrap() {
+        let description: &str = fixture["description"].as_str().unwrap();
+        let hex: &str = fixture["hex"].as_str().unwrap();
+
+        let tx: Transaction = Transaction::from_hex(hex).unwrap();
+        assert_eq!(tx.to_hex(), hex, "{}", description);
+        assert_eq!(transaction::hash_to_hex(&tx.txid()), fixture["txid"].as_str().unwrap(), "{}", description);
+        assert_eq!(transaction::hash_to_hex(&tx.wtxid()), fixture["wtxid"].as_str().unwrap(), "{}", description);
+        assert_eq!(tx.version as i64, fixture["version"].as_i64().unwrap(), "{}", description);
+        assert_eq!(tx.lock_time as u64, fixture["lock_time"].as_u64().unwrap(), "{}", description);
+        assert_eq!(tx.inputs.len() as u64, fixture["inputs"].as_u64().unwrap(), "{}", description);
+        assert_eq!(tx.outputs.len() as u64, fixture["outputs"].as_u64().unwrap(), "{}", description);
+        assert_eq!(tx.output_value(), fixture["value"].as_u64(), "{}", description);
+        assert_eq!(tx.is_coinbase(), description.contains("coinbase"), "{}", description);
+        // legacy transactions hash the same either way
+        assert_eq!(tx.txid() == tx.wtxid(), !tx.has_witness(), "{}", description);
+    }
+}
+
+#[test]
+
+fn transaction_mainnet_legacy_signatures() {
+    let fixtures: Value = serde_json::from_str(include_str!("fixtures/mainnet_transactions.json")).unwrap();
+
+    for fixture in fixtures.as_array().unwrap() {
+        let tx: Transaction = Transaction::from_hex(fixture["hex"].as_str().unwrap()).unwrap();
+        if tx.has_witness() {
+            continue;
+        }
+        // every legacy fixture spends P2PKH outputs: <signature> <public key>
+        for (i, input) in tx.inputs.iter().enumerate() {
+            let (signature, public_key) = match &input.script_sig.instructions().unwrap()[..] {
+                [Instruction::Push(_, s), Instruction::Push(_, k)] => (s.clone(), k.clone()),
+                _ => panic!("script-sig")
+            };
+            let key_hash: [u8; 20] = bitcoin::get_script_hash(&public_key);
+            let script_code: Script = Script::from_hex(&format!("76a914{}88ac", base16::encode_bytes(&key_hash))).unwrap();
+
+            let sighash: [u8; 32] = sighash::legacy_sighash(&tx, i, &script_code, *signature.last().unwrap() as u32).unwrap();
+            assert!(sign::verify_ecdsa_signature(&sighash, &signature, &public_key), "{}", fixture["description"]);
+        }
+    }
+}